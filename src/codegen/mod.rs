@@ -0,0 +1,110 @@
+//! Turns the module tree into a Cargo package. Each file module becomes a file at the
+//! same place in `src/` as rustc expects it : `mod a;` in the root is `src/a.rs`,
+//! `mod b;` in it is `src/a/b.rs`.
+
+pub mod rust;
+
+use std::path::PathBuf;
+
+use crate::semantic;
+use crate::syntax::ast::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    /// Relative to the root of the package.
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// The files of the package `name` for the crate rooted at `root`. The crate is a
+/// binary when the root has a `main` function, a library otherwise.
+pub fn generate_crate(name: &str, root: &Module) -> Vec<GeneratedFile> {
+    let has_main = root.items.iter().any(|item| match &item.kind {
+        ItemKind::Function(function) => function.name.name == "main",
+        _ => false,
+    });
+    let root_file = if has_main { "main.rs" } else { "lib.rs" };
+    let mut files = vec![
+        GeneratedFile {
+            path: PathBuf::from("Cargo.toml"),
+            text: format!(
+                "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n"
+            ),
+        },
+        GeneratedFile {
+            path: PathBuf::from("src").join(root_file),
+            // The unused and unreachable code is reported against Oxydant, rustc would
+            // report it again.
+            text: format!(
+                "#![allow({})]\n\n{}",
+                (semantic::unused::LINTS.iter())
+                    .chain(semantic::flow::LINTS)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                rust::module_source(root)
+            ),
+        },
+    ];
+    file_modules(root, PathBuf::from("src"), &mut files);
+    files
+}
+
+/// The files of the modules declared in `module`, whose children live in `dir`.
+fn file_modules(module: &Module, dir: PathBuf, files: &mut Vec<GeneratedFile>) {
+    for item in &module.items {
+        let ItemKind::Mod(item) = &item.kind else {
+            continue;
+        };
+        let Some(content) = &item.content else {
+            continue;
+        };
+        let dir = dir.join(&item.name.name);
+        if !item.inline {
+            files.push(GeneratedFile {
+                path: dir.with_extension("rs"),
+                text: rust::module_source(content),
+            });
+        }
+        file_modules(content, dir, files);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::generate_crate;
+    use crate::syntax::{ast::*, parse};
+
+    #[test]
+    fn mirrors_file_modules() {
+        let (mut root, _) = parse("mod shapes; mod util { mod text; } fn main() {}");
+        let (circle, _) = parse("pub fn area() -> f64 { 3.14 }");
+        let ItemKind::Mod(shapes) = &mut root.items[0].kind else {
+            panic!()
+        };
+        shapes.content = Some(circle.clone());
+        let ItemKind::Mod(util) = &mut root.items[1].kind else {
+            panic!()
+        };
+        let ItemKind::Mod(text) = &mut util.content.as_mut().unwrap().items[0].kind else {
+            panic!()
+        };
+        text.content = Some(circle);
+        let paths: Vec<_> = generate_crate("demo", &root)
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "Cargo.toml",
+                "src/main.rs",
+                "src/shapes.rs",
+                "src/util/text.rs"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+}
@@ -0,0 +1,823 @@
+//! Prints the tree as Rust source. Keyword aliases come out as their Rust spelling,
+//! names that are keywords in Rust only are written as raw identifiers, and
+//! parentheses are added wherever the shape of the tree needs them.
+
+use std::fmt::Write;
+
+use crate::semantic::attributes;
+use crate::syntax::ast::*;
+
+/// Words that cannot be plain identifiers in Rust 2021.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Binding power of the expressions that are not binary operations. Ranges bind
+/// less tightly than `||`, their bounds are written at the binding power of `||`.
+const PREC_ASSIGN: u8 = 0;
+const PREC_RANGE: u8 = 0;
+const PREC_RANGE_BOUND: u8 = 1;
+const PREC_UNARY: u8 = 6;
+const PREC_POSTFIX: u8 = 7;
+const PREC_ATOM: u8 = 8;
+
+/// The Rust source of the items of `module`. File modules are left as `mod name;`,
+/// their content is written to its own file.
+pub fn module_source(module: &Module) -> String {
+    let mut writer = RustWriter::default();
+    writer.items(&module.items);
+    writer.out
+}
+
+/// `name`, or `r#name` when `name` is reserved in Rust.
+pub fn ident(name: &str) -> String {
+    if RUST_KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+#[derive(Default)]
+struct RustWriter {
+    out: String,
+    indent: usize,
+}
+
+impl RustWriter {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn items(&mut self, items: &[Item]) {
+        // Macros are expanded already, Rust does not need them.
+        let items = items
+            .iter()
+            .filter(|item| !matches!(item.kind, ItemKind::MacroDef(_)));
+        for (i, item) in items.enumerate() {
+            if i > 0 && !matches!(item.kind, ItemKind::Use(_)) {
+                self.out.push('\n');
+            }
+            self.item(item);
+        }
+    }
+
+    /// The attributes left to rustc, one per line.
+    fn attributes(&mut self, attrs: &[Attribute]) {
+        for attr in attrs.iter().filter(|attr| !attributes::is_known(attr)) {
+            self.line(&format!("#[{}]", attr.meta.to_text()));
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        self.attributes(&item.attrs);
+        let vis = visibility(item.vis);
+        match &item.kind {
+            ItemKind::Function(function) => {
+                let params = function
+                    .params
+                    .iter()
+                    .map(param)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let output = function
+                    .output
+                    .as_ref()
+                    .map(|output| format!(" -> {}", ty(output)))
+                    .unwrap_or_default();
+                let name = ident(&function.name.name);
+                let generics = generic_params(&function.generics);
+                let where_ = where_clause(&function.generics);
+                let constness = if function.is_const { "const " } else { "" };
+                let head = format!("{vis}{constness}fn {name}{generics}({params}){output}{where_}");
+                match &function.body {
+                    Some(body) => {
+                        let body = self.block(body);
+                        self.line(&format!("{head} {body}"));
+                    }
+                    None => self.line(&format!("{head};")),
+                }
+            }
+            ItemKind::Struct(item) => {
+                let name = ident(&item.name.name);
+                let generics = generic_params(&item.generics);
+                let where_ = where_clause(&item.generics);
+                if item.fields.is_empty() {
+                    self.line(&format!("{vis}struct {name}{generics}{where_};"));
+                } else {
+                    self.line(&format!("{vis}struct {name}{generics}{where_} {{"));
+                    self.fields(&item.fields);
+                    self.line("}");
+                }
+            }
+            ItemKind::Enum(item) => {
+                self.line(&format!(
+                    "{vis}enum {}{}{} {{",
+                    ident(&item.name.name),
+                    generic_params(&item.generics),
+                    where_clause(&item.generics)
+                ));
+                self.indent += 1;
+                for variant in &item.variants {
+                    self.attributes(&variant.attrs);
+                    let name = ident(&variant.name.name);
+                    match &variant.data {
+                        VariantData::Unit => self.line(&format!("{name},")),
+                        VariantData::Tuple(types) => {
+                            let types = types.iter().map(ty).collect::<Vec<_>>().join(", ");
+                            self.line(&format!("{name}({types}),"));
+                        }
+                        VariantData::Struct(fields) => {
+                            self.line(&format!("{name} {{"));
+                            self.fields(fields);
+                            self.line("},");
+                        }
+                    }
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            ItemKind::Mod(item) => {
+                let name = ident(&item.name.name);
+                match &item.content {
+                    Some(content) if item.inline => {
+                        self.line(&format!("{vis}mod {name} {{"));
+                        self.indent += 1;
+                        self.items(&content.items);
+                        self.indent -= 1;
+                        self.line("}");
+                    }
+                    _ => self.line(&format!("{vis}mod {name};")),
+                }
+            }
+            ItemKind::Use(tree) => self.line(&format!("{vis}use {};", use_tree(tree))),
+            ItemKind::Trait(item) => {
+                let supertraits = if item.supertraits.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", bounds(&item.supertraits))
+                };
+                self.line(&format!(
+                    "{vis}trait {}{}{supertraits}{} {{",
+                    ident(&item.name.name),
+                    generic_params(&item.generics),
+                    where_clause(&item.generics)
+                ));
+                self.indent += 1;
+                self.items(&item.items);
+                self.indent -= 1;
+                self.line("}");
+            }
+            ItemKind::Impl(item) => {
+                let of_trait = item
+                    .of_trait
+                    .as_ref()
+                    .map(|path| format!("{} for ", ty_path(path)))
+                    .unwrap_or_default();
+                self.line(&format!(
+                    "impl{} {of_trait}{}{} {{",
+                    generic_params(&item.generics),
+                    ty(&item.self_ty),
+                    where_clause(&item.generics)
+                ));
+                self.indent += 1;
+                self.items(&item.items);
+                self.indent -= 1;
+                self.line("}");
+            }
+            ItemKind::TyAlias(alias) => {
+                let name = ident(&alias.name.name);
+                match &alias.ty {
+                    Some(ty_) => self.line(&format!("{vis}type {name} = {};", ty(ty_))),
+                    None => self.line(&format!("{vis}type {name};")),
+                }
+            }
+            ItemKind::Const(constant) | ItemKind::Static(constant) => {
+                let keyword = match item.kind {
+                    ItemKind::Static(_) => "static",
+                    _ => "const",
+                };
+                let value = self.expr(&constant.expr, 0);
+                self.line(&format!(
+                    "{vis}{keyword} {}: {} = {value};",
+                    ident(&constant.name.name),
+                    ty(&constant.ty)
+                ));
+            }
+            // Only trees without errors are given to the writer.
+            ItemKind::MacroDef(_) | ItemKind::Error => {}
+        }
+    }
+
+    fn fields(&mut self, fields: &[FieldDef]) {
+        self.indent += 1;
+        for field in fields {
+            self.attributes(&field.attrs);
+            let vis = visibility(field.vis);
+            self.line(&format!(
+                "{vis}{}: {},",
+                ident(&field.name.name),
+                ty(&field.ty)
+            ));
+        }
+        self.indent -= 1;
+    }
+
+    /// A block starting on the current line, its `}` indented at the current level.
+    fn block(&mut self, block: &Block) -> String {
+        if block.stmts.is_empty() {
+            return "{}".to_string();
+        }
+        let mut out = String::from("{\n");
+        self.indent += 1;
+        for stmt in &block.stmts {
+            let text = match &stmt.kind {
+                StmtKind::Let(local) => {
+                    let mut text = format!("let {}", pattern(&local.pat));
+                    if let Some(ty_) = &local.ty {
+                        let _ = write!(text, ": {}", ty(ty_));
+                    }
+                    if let Some(init) = &local.init {
+                        let _ = write!(text, " = {}", self.expr(init, PREC_ASSIGN));
+                    }
+                    if let Some(els) = &local.els {
+                        let _ = write!(text, " else {}", self.block(els));
+                    }
+                    text + ";"
+                }
+                StmtKind::Expr(expr) => self.expr(expr, PREC_ASSIGN),
+                StmtKind::Semi(expr) => self.expr(expr, PREC_ASSIGN) + ";",
+            };
+            let indent = "    ".repeat(self.indent);
+            for attr in stmt.attrs.iter().filter(|attr| !attributes::is_known(attr)) {
+                let _ = writeln!(out, "{indent}#[{}]", attr.meta.to_text());
+            }
+            let _ = writeln!(out, "{indent}{text}");
+        }
+        self.indent -= 1;
+        let _ = write!(out, "{}}}", "    ".repeat(self.indent));
+        out
+    }
+
+    /// `expr`, in parentheses when it binds less tightly than `min`.
+    fn expr(&mut self, expr: &Expr, min: u8) -> String {
+        let text = match &expr.kind {
+            ExprKind::Lit(lit) => lit.to_text(),
+            ExprKind::Path(path) => path_text(path),
+            ExprKind::Unary(op, operand) => {
+                let op = match op {
+                    UnOp::Neg => "-",
+                    UnOp::Not => "!",
+                    UnOp::Deref => "*",
+                };
+                format!("{op}{}", self.expr(operand, PREC_UNARY))
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let prec = op.precedence();
+                // Comparisons do not chain in Rust : `a < b < c` needs parentheses.
+                let lhs_min = if op.is_comparison() { prec + 1 } else { prec };
+                format!(
+                    "{} {} {}",
+                    self.expr(lhs, lhs_min),
+                    op.as_str(),
+                    self.expr(rhs, prec + 1)
+                )
+            }
+            ExprKind::AddrOf { mutable, expr } => {
+                let mutable = if *mutable { "mut " } else { "" };
+                format!("&{mutable}{}", self.expr(expr, PREC_UNARY))
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                format!("{} = {}", self.expr(lhs, 1), self.expr(rhs, PREC_ASSIGN))
+            }
+            ExprKind::Call(callee, args) => {
+                format!("{}({})", self.expr(callee, PREC_POSTFIX), self.args(args))
+            }
+            // Called through its trait, the receiver gets the adjustments method call
+            // syntax makes on its own.
+            ExprKind::MethodCall {
+                receiver,
+                generic_args,
+                args,
+                callee: Some(callee),
+                ..
+            } if callee.qualified => {
+                // The parentheses of `(&s).len()` are not needed as an argument.
+                let mut receiver = &**receiver;
+                while let ExprKind::Paren(inner) = &receiver.kind {
+                    receiver = inner;
+                }
+                let receiver = match callee.autoref {
+                    None if callee.derefs == 0 => self.expr(receiver, PREC_ASSIGN),
+                    autoref => format!(
+                        "{}{}{}",
+                        match autoref {
+                            Some(true) => "&mut ",
+                            Some(false) => "&",
+                            None => "",
+                        },
+                        "*".repeat(callee.derefs),
+                        self.expr(receiver, PREC_UNARY)
+                    ),
+                };
+                let path: Vec<_> = callee.path.split("::").map(ident).collect();
+                let args = std::iter::once(receiver)
+                    .chain(args.iter().map(|arg| self.expr(arg, PREC_ASSIGN)));
+                format!(
+                    "{}{}({})",
+                    path.join("::"),
+                    turbofish(generic_args),
+                    args.collect::<Vec<_>>().join(", ")
+                )
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                generic_args,
+                args,
+                ..
+            } => format!(
+                "{}.{}{}({})",
+                self.expr(receiver, PREC_POSTFIX),
+                ident(&method.name),
+                turbofish(generic_args),
+                self.args(args)
+            ),
+            ExprKind::Field(base, name) => {
+                format!("{}.{}", self.expr(base, PREC_POSTFIX), ident(&name.name))
+            }
+            ExprKind::Index(base, index) => format!(
+                "{}[{}]",
+                self.expr(base, PREC_POSTFIX),
+                self.expr(index, PREC_ASSIGN)
+            ),
+            ExprKind::Tuple(elements) => tuple_text(
+                elements
+                    .iter()
+                    .map(|element| self.expr(element, PREC_ASSIGN))
+                    .collect(),
+            ),
+            ExprKind::Array(elements) => format!("[{}]", self.args(elements)),
+            ExprKind::Repeat(element, len) => format!(
+                "[{}; {}]",
+                self.expr(element, PREC_ASSIGN),
+                self.expr(len, PREC_ASSIGN)
+            ),
+            ExprKind::Range { lo, hi, inclusive } => {
+                let mut bound = |bound: &Option<Box<Expr>>| {
+                    bound
+                        .as_ref()
+                        .map(|bound| self.expr(bound, PREC_RANGE_BOUND))
+                        .unwrap_or_default()
+                };
+                let lo = bound(lo);
+                let hi = bound(hi);
+                let op = if *inclusive { "..=" } else { ".." };
+                format!("{lo}{op}{hi}")
+            }
+            ExprKind::StructLit { path, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let name = ident(&field.name.name);
+                        if field.shorthand {
+                            name
+                        } else {
+                            format!("{name}: {}", self.expr(&field.expr, PREC_ASSIGN))
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if fields.is_empty() {
+                    format!("{} {{}}", path_text(path))
+                } else {
+                    format!("{} {{ {} }}", path_text(path), fields.join(", "))
+                }
+            }
+            ExprKind::Paren(inner) => format!("({})", self.expr(inner, PREC_ASSIGN)),
+            ExprKind::Try(inner) => format!("{}?", self.expr(inner, PREC_POSTFIX)),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If(cond, then, otherwise) => {
+                let mut text = format!("if {} {}", self.expr(cond, PREC_ASSIGN), self.block(then));
+                if let Some(otherwise) = otherwise {
+                    let _ = write!(text, " else {}", self.expr(otherwise, PREC_ASSIGN));
+                }
+                text
+            }
+            ExprKind::While(cond, body) => format!(
+                "while {} {}",
+                self.expr(cond, PREC_ASSIGN),
+                self.block(body)
+            ),
+            ExprKind::Loop(body) => format!("loop {}", self.block(body)),
+            ExprKind::ForLoop { pat, iter, body } => format!(
+                "for {} in {} {}",
+                pattern(pat),
+                self.expr(iter, PREC_ASSIGN),
+                self.block(body)
+            ),
+            ExprKind::Match(scrutinee, arms) => {
+                let mut text = format!("match {} {{\n", self.expr(scrutinee, PREC_ASSIGN));
+                self.indent += 1;
+                for arm in arms {
+                    let mut head = pattern(&arm.pat);
+                    if let Some(guard) = &arm.guard {
+                        let _ = write!(head, " if {}", self.expr(guard, PREC_ASSIGN));
+                    }
+                    let body = self.expr(&arm.body, PREC_ASSIGN);
+                    let _ = writeln!(text, "{}{head} => {body},", "    ".repeat(self.indent));
+                }
+                self.indent -= 1;
+                let _ = write!(text, "{}}}", "    ".repeat(self.indent));
+                text
+            }
+            ExprKind::Closure(closure) => {
+                let capture = match closure.capture {
+                    CaptureBy::Value => "move ",
+                    CaptureBy::Ref => "",
+                };
+                let params = closure
+                    .params
+                    .iter()
+                    .map(|param| match &param.ty {
+                        Some(ty_) => format!("{}: {}", pattern(&param.pat), ty(ty_)),
+                        None => pattern(&param.pat),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let output = closure
+                    .output
+                    .as_ref()
+                    .map(|output| format!(" -> {}", ty(output)))
+                    .unwrap_or_default();
+                let body = self.expr(&closure.body, PREC_ASSIGN);
+                format!("{capture}|{params}|{output} {body}")
+            }
+            ExprKind::Return(value) => self.jump("return", value),
+            ExprKind::Break(value) => self.jump("break", value),
+            ExprKind::Continue => "continue".to_string(),
+            ExprKind::Error => String::new(),
+        };
+        if precedence(expr) < min {
+            format!("({text})")
+        } else {
+            text
+        }
+    }
+
+    fn jump(&mut self, keyword: &str, value: &Option<Box<Expr>>) -> String {
+        match value {
+            Some(value) => format!("{keyword} {}", self.expr(value, PREC_ASSIGN)),
+            None => keyword.to_string(),
+        }
+    }
+
+    fn args(&mut self, args: &[Expr]) -> String {
+        args.iter()
+            .map(|arg| self.expr(arg, PREC_ASSIGN))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Assign(..) | ExprKind::Closure(_) | ExprKind::Return(_) | ExprKind::Break(_) => {
+            PREC_ASSIGN
+        }
+        ExprKind::Binary(op, ..) => op.precedence(),
+        ExprKind::Range { .. } => PREC_RANGE,
+        ExprKind::Unary(..) | ExprKind::AddrOf { .. } => PREC_UNARY,
+        ExprKind::Call(..)
+        | ExprKind::MethodCall { .. }
+        | ExprKind::Field(..)
+        | ExprKind::Index(..)
+        | ExprKind::Try(_) => PREC_POSTFIX,
+        _ => PREC_ATOM,
+    }
+}
+
+/// Receivers keep their short form : `&self` rather than `self: &Self`.
+fn param(param: &Param) -> String {
+    if param.is_self() {
+        let mutable = match param.pat.kind {
+            PatKind::Binding { mutable: true, .. } => "mut ",
+            _ => "",
+        };
+        if let TyKind::Ref { mutable: true, .. } = param.ty.kind {
+            return "&mut self".to_string();
+        }
+        if let TyKind::Ref { .. } = param.ty.kind {
+            return "&self".to_string();
+        }
+        return format!("{mutable}self");
+    }
+    format!("{}: {}", pattern(&param.pat), ty(&param.ty))
+}
+
+fn visibility(vis: Visibility) -> &'static str {
+    match vis {
+        Visibility::Public => "pub ",
+        Visibility::Private => "",
+    }
+}
+
+/// The path of an expression or a pattern, with `::<T>` before type arguments.
+fn path_text(path: &Path) -> String {
+    path_with(path, "::<")
+}
+
+/// The path of a type or a bound, with `<T>` directly after the name.
+fn ty_path(path: &Path) -> String {
+    path_with(path, "<")
+}
+
+/// `self`, `super` and `crate` stay as they are : they cannot be raw identifiers.
+fn path_with(path: &Path, open_args: &str) -> String {
+    path.segments
+        .iter()
+        .map(|segment| {
+            let mut text = match segment.ident.name.as_str() {
+                name @ ("self" | "super" | "crate" | "Self") => name.to_string(),
+                name => ident(name),
+            };
+            let args: Vec<_> = segment.args.iter().map(ty).collect();
+            if segment.parenthesized {
+                let _ = write!(text, "({})", args.join(", "));
+                if let Some(output) = &segment.output {
+                    let _ = write!(text, " -> {}", ty(output));
+                }
+            } else if !args.is_empty() {
+                let _ = write!(text, "{open_args}{}>", args.join(", "));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// `::<T>` after the name of a method, or nothing.
+fn turbofish(args: &[Ty]) -> String {
+    if args.is_empty() {
+        return String::new();
+    }
+    let args: Vec<_> = args.iter().map(ty).collect();
+    format!("::<{}>", args.join(", "))
+}
+
+fn bounds(bounds: &[Path]) -> String {
+    bounds.iter().map(ty_path).collect::<Vec<_>>().join(" + ")
+}
+
+/// `<T: Ord, U>`, or nothing.
+fn generic_params(generics: &Generics) -> String {
+    if generics.params.is_empty() {
+        return String::new();
+    }
+    let params: Vec<_> = generics
+        .params
+        .iter()
+        .map(|param| {
+            let name = ident(&param.name.name);
+            if param.bounds.is_empty() {
+                name
+            } else {
+                format!("{name}: {}", bounds(&param.bounds))
+            }
+        })
+        .collect();
+    format!("<{}>", params.join(", "))
+}
+
+/// ` where T: Ord, U: Clone`, or nothing.
+fn where_clause(generics: &Generics) -> String {
+    if generics.where_clause.is_empty() {
+        return String::new();
+    }
+    let predicates: Vec<_> = generics
+        .where_clause
+        .iter()
+        .map(|predicate| format!("{}: {}", ty(&predicate.ty), bounds(&predicate.bounds)))
+        .collect();
+    format!(" where {}", predicates.join(", "))
+}
+
+fn use_tree(tree: &UseTree) -> String {
+    let prefix = path_text(&tree.prefix);
+    let sep = if prefix.is_empty() { "" } else { "::" };
+    match &tree.kind {
+        UseTreeKind::Simple(None) => prefix,
+        UseTreeKind::Simple(Some(rename)) => format!("{prefix} as {}", ident(&rename.name)),
+        UseTreeKind::Nested(trees) => {
+            let trees = trees.iter().map(use_tree).collect::<Vec<_>>().join(", ");
+            format!("{prefix}{sep}{{{trees}}}")
+        }
+        UseTreeKind::Glob => format!("{prefix}{sep}*"),
+    }
+}
+
+fn ty(ty_: &Ty) -> String {
+    match &ty_.kind {
+        TyKind::Path(path) => ty_path(path),
+        TyKind::Ref { mutable, inner } => {
+            let mutable = if *mutable { "mut " } else { "" };
+            format!("&{mutable}{}", ty(inner))
+        }
+        TyKind::Tuple(tys) => tuple_text(tys.iter().map(ty).collect()),
+        TyKind::Array(inner, len) => {
+            format!(
+                "[{}; {}]",
+                ty(inner),
+                RustWriter::default().expr(len, PREC_ASSIGN)
+            )
+        }
+        TyKind::Slice(inner) => format!("[{}]", ty(inner)),
+        TyKind::Fn { inputs, output } => {
+            fn_text(inputs.iter().map(ty).collect(), output.as_deref().map(ty))
+        }
+    }
+}
+
+fn pattern(pat: &Pattern) -> String {
+    let list = |patterns: &[Pattern]| patterns.iter().map(pattern).collect::<Vec<_>>().join(", ");
+    match &pat.kind {
+        PatKind::Wild => "_".to_string(),
+        PatKind::Binding { name, mutable, sub } => {
+            let mutable = if *mutable { "mut " } else { "" };
+            let sub = sub
+                .as_ref()
+                .map(|sub| format!(" @ {}", pattern(sub)))
+                .unwrap_or_default();
+            format!("{mutable}{}{sub}", ident(&name.name))
+        }
+        PatKind::Lit(expr) => pattern_expr(expr),
+        PatKind::Range { lo, hi, inclusive } => {
+            let bound =
+                |bound: &Option<Box<Expr>>| bound.as_deref().map(pattern_expr).unwrap_or_default();
+            let op = if *inclusive { "..=" } else { ".." };
+            format!("{}{op}{}", bound(lo), bound(hi))
+        }
+        PatKind::Tuple(patterns) => tuple_text(patterns.iter().map(pattern).collect()),
+        PatKind::Paren(inner) => format!("({})", pattern(inner)),
+        PatKind::TupleStruct(path, patterns) => {
+            format!("{}({})", path_text(path), list(patterns))
+        }
+        PatKind::Struct { path, fields, rest } => {
+            let mut fields = fields
+                .iter()
+                .map(|field| {
+                    if field.shorthand {
+                        pattern(&field.pat)
+                    } else {
+                        format!("{}: {}", ident(&field.name.name), pattern(&field.pat))
+                    }
+                })
+                .collect::<Vec<_>>();
+            if *rest {
+                fields.push("..".to_string());
+            }
+            if fields.is_empty() {
+                format!("{} {{}}", path_text(path))
+            } else {
+                format!("{} {{ {} }}", path_text(path), fields.join(", "))
+            }
+        }
+        PatKind::Path(path) => path_text(path),
+        PatKind::Or(patterns) => patterns.iter().map(pattern).collect::<Vec<_>>().join(" | "),
+        PatKind::Rest => "..".to_string(),
+        PatKind::Error => "_".to_string(),
+    }
+}
+
+/// Literals and negated literals, the only expressions found in patterns.
+fn pattern_expr(expr: &Expr) -> String {
+    RustWriter::default().expr(expr, PREC_ASSIGN)
+}
+
+#[cfg(test)]
+mod test {
+    use super::module_source;
+    use crate::semantic::{resolve, typeck};
+    use crate::syntax::parse;
+
+    #[test]
+    fn normalizes_and_parenthesizes() {
+        let (module, errors) = parse(
+            "#[derive(Debug)] pub st Point { pub x: i32, #[doc = \"Up\"] yield: i32 }
+            type Units = u32;
+            pub const MAX: Units = (1 + 2) * 3;
+            const function twice(n: Units) -> Units { n * 2 }
+            fn apply<F: FnMut(Units) -> Units>(mut f: F) -> Units { f(1) }
+            #[deprecated]
+            function main() {
+                let p = Point { x: 1, yield: 2 };
+                let mut n = -(p.x + 1) * 2;
+                if n < 0 { n = 0; }
+                let k = s.parse::<Units>();
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = module_source(&module);
+        assert!(source.contains(
+            "#[derive(Debug)]\npub struct Point {\n    pub x: i32,\n    #[doc = \"Up\"]\n    r#yield: i32,\n}"
+        ));
+        assert!(source.contains("type Units = u32;\n\npub const MAX: Units = (1 + 2) * 3;"));
+        assert!(source.contains("const fn twice(n: Units) -> Units {"));
+        assert!(source.contains("fn apply<F: FnMut(Units) -> Units>(mut f: F) -> Units {"));
+        // `deprecated` is handled by Oxydant, rustc does not see it.
+        assert!(source.contains("\n\nfn main() {"));
+        assert!(source.contains("let p = Point { x: 1, r#yield: 2 };"));
+        assert!(source.contains("let mut n = -(p.x + 1) * 2;"));
+        assert!(source.contains("let k = s.parse::<Units>();"));
+    }
+
+    #[test]
+    fn lowers_tuples_arrays_and_ranges() {
+        let (module, errors) = parse(
+            "fn main() {
+                let pair: (i32, (bool,)) = (1, (true,));
+                let grid: [[u8; 2]; 3] = [[0; 2]; 3];
+                let tail: &[u8] = &grid[0][1..];
+                for i in 0..=pair.0 + 1 { }
+                let r = (..2, a..b);
+                let f: fn(i32, &str) -> bool = check;
+                let g: fn() = main;
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = module_source(&module);
+        assert!(
+            source.contains("let pair: (i32, (bool,)) = (1, (true,));"),
+            "{source}"
+        );
+        assert!(
+            source.contains("let grid: [[u8; 2]; 3] = [[0; 2]; 3];"),
+            "{source}"
+        );
+        assert!(
+            source.contains("let tail: &[u8] = &grid[0][1..];"),
+            "{source}"
+        );
+        assert!(source.contains("for i in 0..=pair.0 + 1 {}"), "{source}");
+        assert!(source.contains("let r = (..2, a..b);"), "{source}");
+        assert!(
+            source.contains("let f: fn(i32, &str) -> bool = check;"),
+            "{source}"
+        );
+        assert!(source.contains("let g: fn() = main;"), "{source}");
+    }
+
+    #[test]
+    fn lowers_destructuring_patterns() {
+        let (module, errors) = parse(
+            "fn f((a, mut b): (i32, i32), Point { x, .. }: Point) {
+                let Point { x: px, y } = p;
+                let Some(n) = a.checked_add(b) else { return; };
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = module_source(&module);
+        assert!(
+            source.contains("fn f((a, mut b): (i32, i32), Point { x, .. }: Point) {"),
+            "{source}"
+        );
+        assert!(source.contains("let Point { x: px, y } = p;"), "{source}");
+        assert!(
+            source.contains("let Some(n) = a.checked_add(b) else {\n        return;\n    };"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn calls_methods_of_traits_on_other_types_through_their_path() {
+        let (mut module, errors) = parse(
+            "trait Count { fn len(&self) -> usize; }
+            impl Count for String { fn len(&self) -> usize { 7 } }
+            struct Bag { n: usize }
+            impl Count for Bag { fn len(&self) -> usize { self.n } }
+            fn f(s: String, r: & &String, bag: Bag) -> usize {
+                s.len() + (&s).len() + r.len() + bag.len()
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        let resolutions = resolve::resolve_names(&module, &mut diagnostics);
+        let types = typeck::check_types(&module, &resolutions, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        typeck::record_callees(&mut module, &types);
+        let source = module_source(&module);
+        assert!(
+            source.contains(
+                "crate::Count::len(&s) + crate::Count::len(&s) + crate::Count::len(*r) + bag.len()"
+            ),
+            "{source}"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::Write;
+
+use crate::span::{SourceMap, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Something to tell the user about their code, pointing at the code in question.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// Other places worth looking at, with a word about each.
+    pub labels: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+    /// Boxed, since few diagnostics have one.
+    pub suggestion: Option<Box<Suggestion>>,
+}
+
+/// A fix to apply : the text replacing a span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    /// What the fix does.
+    pub message: String,
+    pub replacement: String,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Error, message.into(), span)
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Warning, message.into(), span)
+    }
+
+    fn new(severity: Severity, message: String, span: Span) -> Self {
+        Self {
+            severity,
+            message,
+            span,
+            labels: vec![],
+            notes: vec![],
+            suggestion: None,
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push((span, message.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        message: impl Into<String>,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.suggestion = Some(Box::new(Suggestion {
+            span,
+            message: message.into(),
+            replacement: replacement.into(),
+        }));
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Formats the diagnostic the way rustc does :
+    ///
+    /// ```text
+    /// error: expected `;` or `}`, found `let`
+    ///  --> main.oxy:3:5
+    ///   |
+    /// 3 |     let y = 2;
+    ///   |     ^^^
+    /// ```
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = String::new();
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = writeln!(out, "{severity}: {}", self.message);
+        render_span(&mut out, sources, self.span, '^', "");
+        // Code written by a macro is shown where it is defined, then each call. A
+        // macro calling itself shows its call once.
+        let backtrace = sources.expansions.backtrace(self.span.ctxt);
+        let mut frames = backtrace.iter().peekable();
+        while let Some(expansion) = frames.next() {
+            let call = expansion.call_site;
+            let mut repeated = 1;
+            while frames
+                .next_if(|next| {
+                    (next.call_site.start, next.call_site.end) == (call.start, call.end)
+                })
+                .is_some()
+            {
+                repeated += 1;
+            }
+            let label = match repeated {
+                1 => format!("in this expansion of `{}!`", expansion.name),
+                _ => format!("in {repeated} nested expansions of `{}!`", expansion.name),
+            };
+            render_span(&mut out, sources, call, '-', &label);
+        }
+        for (span, label) in &self.labels {
+            render_span(&mut out, sources, *span, '-', label);
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "  = note: {note}");
+        }
+        if let Some(suggestion) = &self.suggestion {
+            let _ = writeln!(out, "help: {}", suggestion.message);
+            render_suggestion(&mut out, sources, suggestion.span, &suggestion.replacement);
+        }
+        out
+    }
+}
+
+fn render_span(out: &mut String, sources: &SourceMap, span: Span, marker: char, label: &str) {
+    let Some(file) = sources.file(span.start) else {
+        let _ = writeln!(out, " --> {}..{}", span.start, span.end);
+        return;
+    };
+    let (line, column, text) = file.line_col(span.start);
+    let gutter = " ".repeat(line.to_string().len());
+    let _ = writeln!(out, "{gutter}--> {}:{line}:{column}", file.name);
+    let _ = writeln!(out, "{gutter} |");
+    let _ = writeln!(out, "{line} | {text}");
+    // Only the part of the span on its first line is underlined.
+    let width = text
+        .chars()
+        .skip(column - 1)
+        .take(span.end.saturating_sub(span.start))
+        .count()
+        .max(1);
+    let mut underline = format!(
+        "{}{}",
+        " ".repeat(column - 1),
+        marker.to_string().repeat(width)
+    );
+    if !label.is_empty() {
+        underline = format!("{underline} {label}");
+    }
+    let _ = writeln!(out, "{gutter} | {underline}");
+}
+
+/// The line of `span` with the replacement applied, the new text marked with `+` :
+///
+/// ```text
+///   |
+/// 3 |     let mut x = 1;
+///   |         ++++
+/// ```
+///
+/// Text only removed has nothing to mark : the line is shown before and after.
+///
+/// ```text
+///   |
+/// 3 -     let mut x = 1;
+/// 3 +     let x = 1;
+/// ```
+fn render_suggestion(out: &mut String, sources: &SourceMap, span: Span, replacement: &str) {
+    let Some(file) = sources.file(span.start) else {
+        let _ = writeln!(out, "  = help: `{replacement}`");
+        return;
+    };
+    let (line, column, text) = file.line_col(span.start);
+    let start = text
+        .char_indices()
+        .nth(column - 1)
+        .map_or(text.len(), |(i, _)| i);
+    let end = (start + span.end.saturating_sub(span.start)).min(text.len());
+    let gutter = " ".repeat(line.to_string().len());
+    let _ = writeln!(out, "{gutter} |");
+    if replacement.is_empty() {
+        let _ = writeln!(out, "{line} - {text}");
+        let _ = writeln!(out, "{line} + {}{}", &text[..start], &text[end..]);
+        return;
+    }
+    let _ = writeln!(
+        out,
+        "{line} | {}{replacement}{}",
+        &text[..start],
+        &text[end..]
+    );
+    let marker = "+".repeat(replacement.chars().count().max(1));
+    let _ = writeln!(out, "{gutter} | {}{marker}", " ".repeat(column - 1));
+}
@@ -0,0 +1,352 @@
+use std::{fs, io, path::Path};
+
+use log::debug;
+
+use crate::codegen;
+use crate::diagnostics::Diagnostic;
+use crate::semantic;
+use crate::semantic::resolve::Resolutions;
+use crate::semantic::typeck::TypeckResults;
+use crate::span::SourceMap;
+use crate::syntax::ast::{ItemKind, Module};
+use crate::syntax::parser::Parser;
+
+/// Everything that lives for a whole compilation : the sources and what was found in them.
+#[derive(Debug, Default)]
+pub struct Session {
+    pub sources: SourceMap,
+    pub diagnostics: Vec<Diagnostic>,
+    pub resolutions: Resolutions,
+    pub types: TypeckResults,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse_file(&mut self, path: &Path) -> io::Result<Module> {
+        let text = fs::read_to_string(path)?;
+        Ok(self.parse_source(path.display().to_string(), text))
+    }
+
+    pub fn parse_source(&mut self, name: String, text: String) -> Module {
+        debug!("parsing {name}");
+        let base = self.sources.add(name, text);
+        let expansions = std::mem::take(&mut self.sources.expansions);
+        let file = &self.sources.files()[self.sources.files().len() - 1];
+        let mut parser = Parser::new(&file.text, base).with_expansions(expansions);
+        let module = parser.parse_module();
+        let expansions = parser.take_expansions();
+        self.diagnostics.extend(parser.into_errors());
+        self.sources.expansions = expansions;
+        module
+    }
+
+    /// Parses the root file and every file module reachable from it. The modules
+    /// declared in a file are looked up in the directory of the file for the root and
+    /// for `mod.oxy` files, and next to it in a directory of the module's name otherwise.
+    pub fn load_crate(&mut self, root: &Path) -> io::Result<Module> {
+        let mut module = self.parse_file(root)?;
+        let dir = root.parent().unwrap_or(Path::new(""));
+        self.load_modules(&mut module, dir);
+        Ok(module)
+    }
+
+    /// Fills the `mod name;` of `module`, whose children live in `dir`.
+    fn load_modules(&mut self, module: &mut Module, dir: &Path) {
+        for item in &mut module.items {
+            let span = item.span;
+            let ItemKind::Mod(item) = &mut item.kind else {
+                continue;
+            };
+            let name = &item.name.name;
+            let dir = dir.join(name);
+            if item.inline {
+                if let Some(content) = &mut item.content {
+                    self.load_modules(content, &dir);
+                }
+                continue;
+            }
+            let file = dir.with_extension("oxy");
+            let mod_file = dir.join("mod.oxy");
+            let path = match (file.is_file(), mod_file.is_file()) {
+                (true, false) => file,
+                (false, true) => mod_file,
+                (false, false) => {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("file not found for module `{name}`"), span)
+                            .with_note(format!(
+                                "to create the module `{name}`, create file `{}` or `{}`",
+                                file.display(),
+                                mod_file.display()
+                            )),
+                    );
+                    continue;
+                }
+                (true, true) => {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "file for module `{name}` found at both `{}` and `{}`",
+                                file.display(),
+                                mod_file.display()
+                            ),
+                            span,
+                        )
+                        .with_note("delete or rename one of them to remove the ambiguity"),
+                    );
+                    continue;
+                }
+            };
+            match self.parse_file(&path) {
+                Ok(mut content) => {
+                    self.load_modules(&mut content, &dir);
+                    item.content = Some(content);
+                }
+                Err(err) => self.diagnostics.push(Diagnostic::error(
+                    format!("could not read `{}`: {err}", path.display()),
+                    span,
+                )),
+            }
+        }
+    }
+
+    /// Runs the semantic passes over the whole crate.
+    pub fn analyze(&mut self, module: &mut Module) {
+        semantic::captures::infer_captures(module);
+        semantic::operators::lower_operators(module);
+        self.resolutions = semantic::resolve::resolve_names(module, &mut self.diagnostics);
+        semantic::traits::check_impls(module, &self.resolutions, &mut self.diagnostics);
+        semantic::consts::check_consts(module, &self.resolutions, &mut self.diagnostics);
+        semantic::operators::check_operators(module, &mut self.diagnostics);
+        self.types =
+            semantic::typeck::check_types(module, &self.resolutions, &mut self.diagnostics);
+        semantic::typeck::record_callees(module, &self.types);
+        semantic::attributes::check_attributes(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::bounds::check_bounds(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::mutability::check_mutability(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::ownership::check_ownership(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::flow::check_flow(module, &self.resolutions, &mut self.diagnostics);
+        semantic::exhaustiveness::check_exhaustiveness(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::propagation::check_propagation(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::refutability::check_refutability(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+        semantic::unused::check_unused(
+            module,
+            &self.resolutions,
+            &self.types,
+            &mut self.diagnostics,
+        );
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    /// Every diagnostic, in the order of the source.
+    pub fn report(&self) -> String {
+        let mut diagnostics: Vec<_> = self.diagnostics.iter().collect();
+        let expansions = &self.sources.expansions;
+        diagnostics.sort_by_key(|diagnostic| expansions.source_callsite(diagnostic.span).start);
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.render(&self.sources))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// `oxydant check <file>`
+pub fn check(path: &Path) -> io::Result<Session> {
+    let mut session = Session::new();
+    let mut module = session.load_crate(path)?;
+    session.analyze(&mut module);
+    Ok(session)
+}
+
+/// `oxydant parse <file>`, the tree is returned even when there are errors.
+pub fn parse(path: &Path) -> io::Result<(Session, Module)> {
+    let mut session = Session::new();
+    let module = session.load_crate(path)?;
+    Ok((session, module))
+}
+
+/// `oxydant build <file> --out <dir>`, writes the Rust package to `out` when the
+/// crate has no errors. The package is named after the root file.
+pub fn build(path: &Path, out: &Path) -> io::Result<Session> {
+    let mut session = Session::new();
+    let mut module = session.load_crate(path)?;
+    session.analyze(&mut module);
+    if session.has_errors() {
+        return Ok(session);
+    }
+    let name = package_name(path);
+    for file in codegen::generate_crate(&name, &module) {
+        let path = out.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        debug!("writing {}", path.display());
+        fs::write(path, file.text)?;
+    }
+    Ok(session)
+}
+
+/// `my-game.oxy` gives `my_game`, Cargo names start with a letter.
+fn package_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("oxy_{name}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, path::PathBuf, process::Command};
+
+    use super::Session;
+    use crate::syntax::ast::ItemKind;
+
+    /// A fresh directory under the system temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("oxydant-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_file_modules() {
+        let dir = temp_dir("modules");
+        fs::write(dir.join("main.oxy"), "mod shapes; mod util; fn main() {}").unwrap();
+        fs::create_dir_all(dir.join("shapes")).unwrap();
+        fs::write(dir.join("shapes/mod.oxy"), "pub mod circle;").unwrap();
+        fs::write(dir.join("shapes/circle.oxy"), "pub fn area() {}").unwrap();
+        fs::write(dir.join("util.oxy"), "mod missing;").unwrap();
+
+        let mut session = Session::new();
+        let module = session.load_crate(&dir.join("main.oxy")).unwrap();
+        let ItemKind::Mod(shapes) = &module.items[0].kind else {
+            panic!()
+        };
+        let ItemKind::Mod(circle) = &shapes.content.as_ref().unwrap().items[0].kind else {
+            panic!()
+        };
+        assert_eq!(circle.content.as_ref().unwrap().items.len(), 1);
+
+        // `util.oxy` looks for its children in `util/`.
+        assert_eq!(session.diagnostics.len(), 1);
+        assert_eq!(
+            session.diagnostics[0].message,
+            "file not found for module `missing`"
+        );
+        assert!(session.diagnostics[0].notes[0].contains("util/missing.oxy"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn builds_threads_moving_their_captures() {
+        let dir = temp_dir("threads");
+        fs::write(
+            dir.join("main.oxy"),
+            "use std::thread;
+            fn main() {
+                let s = String::from(\"oxy\");
+                let handle = thread::spawn(|| s.len());
+                let n = handle.join().unwrap();
+            }",
+        )
+        .unwrap();
+        let out = dir.join("out");
+        let session = super::build(&dir.join("main.oxy"), &out).unwrap();
+        assert!(!session.has_errors(), "{}", session.report());
+        let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let status = Command::new(cargo)
+            .args(["build", "--quiet", "--offline"])
+            .current_dir(&out)
+            .env("CARGO_TARGET_DIR", dir.join("target"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shows_each_expansion_once() {
+        let mut session = Session::new();
+        let source =
+            "macro count { () => { missing }, ($x:tt $($rest:tt)*) => { count!($($rest)*) } }
+            fn main() { let n = count!(a b c); }";
+        let mut first = session.parse_source("a.oxy".to_string(), source.to_string());
+        let mut second = session.parse_source("b.oxy".to_string(), source.to_string());
+        session.analyze(&mut first);
+        session.analyze(&mut second);
+        let report = session.report();
+        // The files number their expansions apart.
+        assert_eq!(
+            report.matches("in 3 nested expansions of `count!`").count(),
+            2
+        );
+        assert_eq!(report.matches("in this expansion of `count!`").count(), 2);
+        assert!(report.contains("--> b.oxy:2:33"), "{report}");
+    }
+
+    #[test]
+    fn shows_removals_before_and_after() {
+        let mut session = Session::new();
+        let source = "fn main() {\n    let mut x = 1;\n    let y = x;\n    let _ = y;\n}";
+        let mut module = session.parse_source("a.oxy".to_string(), source.to_string());
+        session.analyze(&mut module);
+        let report = session.report();
+        assert!(
+            report.contains(
+                "help: remove this `mut`\n  |\n2 -     let mut x = 1;\n2 +     let x = 1;\n"
+            ),
+            "{report}"
+        );
+    }
+}
@@ -4,14 +4,15 @@ mod token;
 //
 //
 /// Re-exports :
-pub use token::{Token, Tokens};
+pub use token::{Keyword, Literal, Operator, Punctuation, Scope, Token, Tokens};
 //
 //
 //
 //
 //
 //
-use token::{parse_token, Length};
+use token::parse_token;
+pub use token::Length;
 pub struct Cursor<'input_lifetime> {
     input: &'input_lifetime str,
     input_len: usize,
@@ -29,7 +30,7 @@ impl<'input_lifetime> Cursor<'input_lifetime> {
         }
     }
 
-    pub fn read(&mut self) -> Vec<Token> {
+    pub fn read(&mut self) -> Vec<Token<'input_lifetime>> {
         // let mut cursor: Cursor = Cursor::new(input);
         let cursor = self;
         let mut tokens: Vec<Token> = vec![];
@@ -44,6 +45,11 @@ impl<'input_lifetime> Cursor<'input_lifetime> {
     pub fn position(&self) -> usize {
         self.pos
     }
+
+    /// What is left to read, starting at the cursor's position.
+    pub fn remaining(&self) -> &'input_lifetime str {
+        self.input.get(self.pos..).unwrap_or("")
+    }
     // pub fn next_whitespace(&self) -> bool {
     //     " " == self.extract_substring(self.pos, self.pos + 1)
     // }
@@ -69,10 +75,8 @@ impl<'input_lifetime> Cursor<'input_lifetime> {
     /// On the other hand, `start=x, end = x+1` will always return the char at position x.
     pub fn extract_substring(&self, start: usize, end: usize) -> &'input_lifetime str {
         //NOTE we could return an error here instead.
-        if start > self.input_len || end > self.input_len {
-            return "";
-        }
-        &self.input[start..end]
+        // `get` also refuses to cut through a multi-byte character.
+        self.input.get(start..end).unwrap_or("")
     }
 }
 
@@ -126,6 +130,32 @@ mod test {
             "The tokens were not properly parsed."
         );
     }
+    #[test]
+    fn read_longest_operators() {
+        use super::token::{Keyword, Literal, Operator, Punctuation, Token};
+        use crate::lexical::cursor::Cursor;
+        let input = "a==b=>fna 1..2.5";
+        let mut crs = Cursor::new(input);
+        let tokens = crs.read();
+        let tokens_verif = vec![
+            Token::Identifier(0, 1),
+            Token::Operator(Operator::Equality(1, 3)),
+            Token::Identifier(3, 4),
+            Token::Punctuation(Punctuation::FatArrow(4, 6)),
+            Token::Identifier(6, 9),
+            Token::Whitespace(9, 10),
+            Token::Literal(Literal::Integer(10, 11)),
+            Token::Punctuation(Punctuation::DotDot(11, 13)),
+            Token::Literal(Literal::Float(13, 16)),
+        ];
+        assert!(
+            tokens == tokens_verif,
+            "The tokens were not properly parsed."
+        );
+        let tokens = Cursor::new("enum\nmatch").read();
+        assert!(tokens[0] == Token::Keyword(Keyword::Enum(0, 4)));
+        assert!(tokens[2] == Token::Keyword(Keyword::Match(5, 10)));
+    }
 }
 // #[test]
 // fn test_tokens_functions() {
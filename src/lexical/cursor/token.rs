@@ -8,7 +8,7 @@ type TransformatorTuple<'a> = (&'static [&'static str], Transformator<'a>, Marke
 
 pub struct Tokens<'input>(Vec<Token<'input>>);
 impl<'input> Tokens<'input> {
-    pub fn new(cursor: &'input mut Cursor<'input>) -> Self {
+    pub fn new(cursor: &mut Cursor<'input>) -> Self {
         Self(cursor.read())
     }
     pub fn scopes(&self) -> Vec<(usize, &Scope)> {
@@ -68,7 +68,15 @@ pub enum Token<'input_lifetime> {
     Operator(Operator),
     /// Will contain all of {}, (), []
     Scope(Scope),
-    /// Any leftover string (for now)
+    /// Will contain all of :, ::, ;, ,, ., ->, =>
+    Punctuation(Punctuation),
+    /// Numbers, strings and characters.
+    Literal(Literal),
+    /// Names that are not keywords.
+    Identifier(usize, usize),
+    /// A `//` comment, up to the end of the line.
+    Comment(usize, usize),
+    /// Any leftover character.
     Unknown(&'input_lifetime str),
     /// Whitespace.
     Whitespace(usize, usize),
@@ -83,6 +91,10 @@ impl Length for Token<'_> {
             Token::Keyword(keyword) => keyword.length(),
             Token::Operator(operator) => operator.length(),
             Token::Scope(_) => 1,
+            Token::Punctuation(punctuation) => punctuation.length(),
+            Token::Literal(literal) => literal.length(),
+            Token::Identifier(start, end) => end - start,
+            Token::Comment(start, end) => end - start,
             Token::Unknown(str) => str.len(),
             Token::Whitespace(start, end) => end - start,
         }
@@ -95,6 +107,32 @@ impl Length for Keyword {
             Keyword::Function(a, b) => b - a,
             Keyword::Struct(a, b) => b - a,
             Keyword::Let(a, b) => b - a,
+            Keyword::Enum(a, b) => b - a,
+            Keyword::Match(a, b) => b - a,
+            Keyword::If(a, b) => b - a,
+            Keyword::Else(a, b) => b - a,
+            Keyword::While(a, b) => b - a,
+            Keyword::Loop(a, b) => b - a,
+            Keyword::Break(a, b) => b - a,
+            Keyword::Continue(a, b) => b - a,
+            Keyword::Return(a, b) => b - a,
+            Keyword::Mut(a, b) => b - a,
+            Keyword::True(a, b) => b - a,
+            Keyword::False(a, b) => b - a,
+            Keyword::Mod(a, b) => b - a,
+            Keyword::Use(a, b) => b - a,
+            Keyword::Pub(a, b) => b - a,
+            Keyword::As(a, b) => b - a,
+            Keyword::Move(a, b) => b - a,
+            Keyword::Impl(a, b) => b - a,
+            Keyword::Trait(a, b) => b - a,
+            Keyword::For(a, b) => b - a,
+            Keyword::Type(a, b) => b - a,
+            Keyword::Where(a, b) => b - a,
+            Keyword::Macro(a, b) => b - a,
+            Keyword::Const(a, b) => b - a,
+            Keyword::Static(a, b) => b - a,
+            Keyword::In(a, b) => b - a,
         }
     }
 }
@@ -109,6 +147,45 @@ impl Length for Operator {
             Operator::Or(a, b) => b - a,
             Operator::Add(a, b) => b - a,
             Operator::Substract(a, b) => b - a,
+            Operator::Multiply(a, b) => b - a,
+            Operator::Divide(a, b) => b - a,
+            Operator::Modulo(a, b) => b - a,
+            Operator::Not(a, b) => b - a,
+            Operator::LessEqual(a, b) => b - a,
+            Operator::GreaterEqual(a, b) => b - a,
+            Operator::Pipe(a, b) => b - a,
+            Operator::Ampersand(a, b) => b - a,
+        }
+    }
+}
+
+impl Length for Punctuation {
+    fn length(&self) -> usize {
+        match self {
+            Punctuation::Colon(a, b) => b - a,
+            Punctuation::PathSeparator(a, b) => b - a,
+            Punctuation::Semicolon(a, b) => b - a,
+            Punctuation::Comma(a, b) => b - a,
+            Punctuation::Dot(a, b) => b - a,
+            Punctuation::DotDot(a, b) => b - a,
+            Punctuation::DotDotEqual(a, b) => b - a,
+            Punctuation::Arrow(a, b) => b - a,
+            Punctuation::FatArrow(a, b) => b - a,
+            Punctuation::At(a, b) => b - a,
+            Punctuation::Dollar(a, b) => b - a,
+            Punctuation::Pound(a, b) => b - a,
+            Punctuation::Question(a, b) => b - a,
+        }
+    }
+}
+
+impl Length for Literal {
+    fn length(&self) -> usize {
+        match self {
+            Literal::Integer(a, b) => b - a,
+            Literal::Float(a, b) => b - a,
+            Literal::Str(a, b) => b - a,
+            Literal::Char(a, b) => b - a,
         }
     }
 }
@@ -223,6 +300,22 @@ pub enum Operator {
     Add(usize, usize),
     /// -
     Substract(usize, usize),
+    /// *
+    Multiply(usize, usize),
+    /// /
+    Divide(usize, usize),
+    /// %
+    Modulo(usize, usize),
+    /// !
+    Not(usize, usize),
+    /// <=
+    LessEqual(usize, usize),
+    /// >=
+    GreaterEqual(usize, usize),
+    /// |
+    Pipe(usize, usize),
+    /// &
+    Ampersand(usize, usize),
 }
 impl Operator {
     const ASSIGN_STRINGS: [&'static str; 1] = ["="];
@@ -232,15 +325,32 @@ impl Operator {
     const OR_STRINGS: [&'static str; 1] = ["||"];
     const ADD_STRINGS: [&'static str; 1] = ["+"];
     const SUB_STRINGS: [&'static str; 1] = ["-"];
+    const MUL_STRINGS: [&'static str; 1] = ["*"];
+    const DIV_STRINGS: [&'static str; 1] = ["/"];
+    const MOD_STRINGS: [&'static str; 1] = ["%"];
+    const NOT_STRINGS: [&'static str; 1] = ["!"];
+    const LE_STRINGS: [&'static str; 1] = ["<="];
+    const GE_STRINGS: [&'static str; 1] = [">="];
+    const PIPE_STRINGS: [&'static str; 1] = ["|"];
+    const AMP_STRINGS: [&'static str; 1] = ["&"];
 
-    const ALL_KW: [&'static TransformatorTuple<'_>; 7] = [
-        &Self::ASSIGN_TUPLE,
+    /// Longer operators come first, so that `==` is not read as two `=`.
+    const ALL_KW: [&'static TransformatorTuple<'_>; 15] = [
         &Self::EQ_TUPLE,
         &Self::UNEQ_TUPLE,
         &Self::AND_TUPLE,
         &Self::OR_TUPLE,
+        &Self::LE_TUPLE,
+        &Self::GE_TUPLE,
+        &Self::ASSIGN_TUPLE,
         &Self::ADD_TUPLE,
         &Self::SUB_TUPLE,
+        &Self::MUL_TUPLE,
+        &Self::DIV_TUPLE,
+        &Self::MOD_TUPLE,
+        &Self::NOT_TUPLE,
+        &Self::PIPE_TUPLE,
+        &Self::AMP_TUPLE,
     ];
     const ASSIGN_TUPLE: TransformatorTuple<'_> = (
         &Self::ASSIGN_STRINGS,
@@ -265,6 +375,22 @@ impl Operator {
         (&Self::ADD_STRINGS, Self::to_operator_add, Marker::Ignore);
     const SUB_TUPLE: TransformatorTuple<'_> =
         (&Self::SUB_STRINGS, Self::to_operator_sub, Marker::Ignore);
+    const MUL_TUPLE: TransformatorTuple<'_> =
+        (&Self::MUL_STRINGS, Self::to_operator_mul, Marker::Ignore);
+    const DIV_TUPLE: TransformatorTuple<'_> =
+        (&Self::DIV_STRINGS, Self::to_operator_div, Marker::Ignore);
+    const MOD_TUPLE: TransformatorTuple<'_> =
+        (&Self::MOD_STRINGS, Self::to_operator_mod, Marker::Ignore);
+    const NOT_TUPLE: TransformatorTuple<'_> =
+        (&Self::NOT_STRINGS, Self::to_operator_not, Marker::Ignore);
+    const LE_TUPLE: TransformatorTuple<'_> =
+        (&Self::LE_STRINGS, Self::to_operator_le, Marker::Ignore);
+    const GE_TUPLE: TransformatorTuple<'_> =
+        (&Self::GE_STRINGS, Self::to_operator_ge, Marker::Ignore);
+    const PIPE_TUPLE: TransformatorTuple<'_> =
+        (&Self::PIPE_STRINGS, Self::to_operator_pipe, Marker::Ignore);
+    const AMP_TUPLE: TransformatorTuple<'_> =
+        (&Self::AMP_STRINGS, Self::to_operator_amp, Marker::Ignore);
     fn to_operator_assign<'z>(start: usize, end: usize) -> Token<'z> {
         Token::Operator(Operator::Assign(start, end))
     }
@@ -286,6 +412,168 @@ impl Operator {
     fn to_operator_sub<'z>(start: usize, end: usize) -> Token<'z> {
         Token::Operator(Operator::Substract(start, end))
     }
+    fn to_operator_mul<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Multiply(start, end))
+    }
+    fn to_operator_div<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Divide(start, end))
+    }
+    fn to_operator_mod<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Modulo(start, end))
+    }
+    fn to_operator_not<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Not(start, end))
+    }
+    fn to_operator_le<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::LessEqual(start, end))
+    }
+    fn to_operator_ge<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::GreaterEqual(start, end))
+    }
+    fn to_operator_pipe<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Pipe(start, end))
+    }
+    fn to_operator_amp<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Operator(Operator::Ampersand(start, end))
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Punctuation {
+    /// :
+    Colon(usize, usize),
+    /// ::
+    PathSeparator(usize, usize),
+    /// ;
+    Semicolon(usize, usize),
+    /// ,
+    Comma(usize, usize),
+    /// .
+    Dot(usize, usize),
+    /// ..
+    DotDot(usize, usize),
+    /// ..=
+    DotDotEqual(usize, usize),
+    /// ->
+    Arrow(usize, usize),
+    /// =>
+    FatArrow(usize, usize),
+    /// @
+    At(usize, usize),
+    /// $
+    Dollar(usize, usize),
+    /// #
+    Pound(usize, usize),
+    /// ?
+    Question(usize, usize),
+}
+impl Punctuation {
+    const COLON_STRINGS: [&'static str; 1] = [":"];
+    const PATH_STRINGS: [&'static str; 1] = ["::"];
+    const SEMI_STRINGS: [&'static str; 1] = [";"];
+    const COMMA_STRINGS: [&'static str; 1] = [","];
+    const DOT_STRINGS: [&'static str; 1] = ["."];
+    const DOTDOT_STRINGS: [&'static str; 1] = [".."];
+    const DOTDOTEQ_STRINGS: [&'static str; 1] = ["..="];
+    const ARROW_STRINGS: [&'static str; 1] = ["->"];
+    const FAT_ARROW_STRINGS: [&'static str; 1] = ["=>"];
+    const AT_STRINGS: [&'static str; 1] = ["@"];
+    const DOLLAR_STRINGS: [&'static str; 1] = ["$"];
+    const POUND_STRINGS: [&'static str; 1] = ["#"];
+    const QUESTION_STRINGS: [&'static str; 1] = ["?"];
+
+    /// Punctuation is looked up before operators, so `->` and `=>` win over `-` and `=`.
+    const ALL_KW: [&'static TransformatorTuple<'_>; 13] = [
+        &Self::PATH_TUPLE,
+        &Self::COLON_TUPLE,
+        &Self::SEMI_TUPLE,
+        &Self::COMMA_TUPLE,
+        &Self::DOTDOTEQ_TUPLE,
+        &Self::DOTDOT_TUPLE,
+        &Self::DOT_TUPLE,
+        &Self::ARROW_TUPLE,
+        &Self::FAT_ARROW_TUPLE,
+        &Self::AT_TUPLE,
+        &Self::DOLLAR_TUPLE,
+        &Self::POUND_TUPLE,
+        &Self::QUESTION_TUPLE,
+    ];
+    const COLON_TUPLE: TransformatorTuple<'_> =
+        (&Self::COLON_STRINGS, Self::to_colon, Marker::Ignore);
+    const PATH_TUPLE: TransformatorTuple<'_> =
+        (&Self::PATH_STRINGS, Self::to_path_separator, Marker::Ignore);
+    const SEMI_TUPLE: TransformatorTuple<'_> =
+        (&Self::SEMI_STRINGS, Self::to_semicolon, Marker::Ignore);
+    const COMMA_TUPLE: TransformatorTuple<'_> =
+        (&Self::COMMA_STRINGS, Self::to_comma, Marker::Ignore);
+    const DOT_TUPLE: TransformatorTuple<'_> = (&Self::DOT_STRINGS, Self::to_dot, Marker::Ignore);
+    const DOTDOT_TUPLE: TransformatorTuple<'_> =
+        (&Self::DOTDOT_STRINGS, Self::to_dot_dot, Marker::Ignore);
+    const DOTDOTEQ_TUPLE: TransformatorTuple<'_> = (
+        &Self::DOTDOTEQ_STRINGS,
+        Self::to_dot_dot_equal,
+        Marker::Ignore,
+    );
+    const ARROW_TUPLE: TransformatorTuple<'_> =
+        (&Self::ARROW_STRINGS, Self::to_arrow, Marker::Ignore);
+    const FAT_ARROW_TUPLE: TransformatorTuple<'_> =
+        (&Self::FAT_ARROW_STRINGS, Self::to_fat_arrow, Marker::Ignore);
+    const AT_TUPLE: TransformatorTuple<'_> = (&Self::AT_STRINGS, Self::to_at, Marker::Ignore);
+    const DOLLAR_TUPLE: TransformatorTuple<'_> =
+        (&Self::DOLLAR_STRINGS, Self::to_dollar, Marker::Ignore);
+    const POUND_TUPLE: TransformatorTuple<'_> =
+        (&Self::POUND_STRINGS, Self::to_pound, Marker::Ignore);
+    const QUESTION_TUPLE: TransformatorTuple<'_> =
+        (&Self::QUESTION_STRINGS, Self::to_question, Marker::Ignore);
+    fn to_colon<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Colon(start, end))
+    }
+    fn to_path_separator<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::PathSeparator(start, end))
+    }
+    fn to_semicolon<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Semicolon(start, end))
+    }
+    fn to_comma<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Comma(start, end))
+    }
+    fn to_dot<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Dot(start, end))
+    }
+    fn to_dot_dot<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::DotDot(start, end))
+    }
+    fn to_dot_dot_equal<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::DotDotEqual(start, end))
+    }
+    fn to_arrow<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Arrow(start, end))
+    }
+    fn to_fat_arrow<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::FatArrow(start, end))
+    }
+    fn to_at<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::At(start, end))
+    }
+    fn to_dollar<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Dollar(start, end))
+    }
+    fn to_pound<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Pound(start, end))
+    }
+    fn to_question<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Punctuation(Punctuation::Question(start, end))
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    /// 42
+    Integer(usize, usize),
+    /// 4.2
+    Float(usize, usize),
+    /// "text", quotes included.
+    Str(usize, usize),
+    /// 'c', quotes included.
+    Char(usize, usize),
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Keyword {
@@ -293,6 +581,32 @@ pub enum Keyword {
     Struct(usize, usize),
     /// There is only one way to write let, so we shouldn't need to have two positions.
     Let(usize, usize),
+    Enum(usize, usize),
+    Match(usize, usize),
+    If(usize, usize),
+    Else(usize, usize),
+    While(usize, usize),
+    Loop(usize, usize),
+    Break(usize, usize),
+    Continue(usize, usize),
+    Return(usize, usize),
+    Mut(usize, usize),
+    True(usize, usize),
+    False(usize, usize),
+    Mod(usize, usize),
+    Use(usize, usize),
+    Pub(usize, usize),
+    As(usize, usize),
+    Move(usize, usize),
+    Impl(usize, usize),
+    Trait(usize, usize),
+    For(usize, usize),
+    Type(usize, usize),
+    Where(usize, usize),
+    Macro(usize, usize),
+    Const(usize, usize),
+    Static(usize, usize),
+    In(usize, usize),
 }
 /// It would be nice to be able to say
 /// "this is a constant array who should live nearby in memory"
@@ -302,24 +616,199 @@ impl Keyword {
     const FUNC_STRINGS: [&'static str; 3] = ["function", "fn", "fun"];
     const STRUCT_STRINGS: [&'static str; 2] = ["struct", "st"];
     const LET_STRINGS: [&'static str; 1] = ["let"];
-    const MARKERS: [&'static str; 2] = ["", " "];
-    const ALL_KW: [&'static TransformatorTuple<'_>; 3] =
-        [&Self::FN_STR, &Self::ST_STR, &Self::LET_STR];
+    const ENUM_STRINGS: [&'static str; 1] = ["enum"];
+    const MATCH_STRINGS: [&'static str; 1] = ["match"];
+    const IF_STRINGS: [&'static str; 1] = ["if"];
+    const ELSE_STRINGS: [&'static str; 1] = ["else"];
+    const WHILE_STRINGS: [&'static str; 1] = ["while"];
+    const LOOP_STRINGS: [&'static str; 1] = ["loop"];
+    const BREAK_STRINGS: [&'static str; 1] = ["break"];
+    const CONTINUE_STRINGS: [&'static str; 1] = ["continue"];
+    const RETURN_STRINGS: [&'static str; 1] = ["return"];
+    const MUT_STRINGS: [&'static str; 1] = ["mut"];
+    const TRUE_STRINGS: [&'static str; 1] = ["true"];
+    const FALSE_STRINGS: [&'static str; 1] = ["false"];
+    const MOD_STRINGS: [&'static str; 1] = ["mod"];
+    const USE_STRINGS: [&'static str; 1] = ["use"];
+    const PUB_STRINGS: [&'static str; 1] = ["pub"];
+    const AS_STRINGS: [&'static str; 1] = ["as"];
+    const MOVE_STRINGS: [&'static str; 1] = ["move"];
+    const IMPL_STRINGS: [&'static str; 1] = ["impl"];
+    const TRAIT_STRINGS: [&'static str; 1] = ["trait"];
+    const FOR_STRINGS: [&'static str; 1] = ["for"];
+    const TYPE_STRINGS: [&'static str; 1] = ["type"];
+    const WHERE_STRINGS: [&'static str; 1] = ["where"];
+    const MACRO_STRINGS: [&'static str; 1] = ["macro"];
+    const CONST_STRINGS: [&'static str; 1] = ["const"];
+    const STATIC_STRINGS: [&'static str; 1] = ["static"];
+    const IN_STRINGS: [&'static str; 1] = ["in"];
+    const ALL_KW: [&'static TransformatorTuple<'_>; 29] = [
+        &Self::FN_STR,
+        &Self::ST_STR,
+        &Self::LET_STR,
+        &Self::ENUM_STR,
+        &Self::MATCH_STR,
+        &Self::IF_STR,
+        &Self::ELSE_STR,
+        &Self::WHILE_STR,
+        &Self::LOOP_STR,
+        &Self::BREAK_STR,
+        &Self::CONTINUE_STR,
+        &Self::RETURN_STR,
+        &Self::MUT_STR,
+        &Self::TRUE_STR,
+        &Self::FALSE_STR,
+        &Self::MOD_STR,
+        &Self::USE_STR,
+        &Self::PUB_STR,
+        &Self::AS_STR,
+        &Self::MOVE_STR,
+        &Self::IMPL_STR,
+        &Self::TRAIT_STR,
+        &Self::FOR_STR,
+        &Self::TYPE_STR,
+        &Self::WHERE_STR,
+        &Self::MACRO_STR,
+        &Self::CONST_STR,
+        &Self::STATIC_STR,
+        &Self::IN_STR,
+    ];
     const FN_STR: TransformatorTuple<'_> = (
         &Self::FUNC_STRINGS,
         Self::to_keyword_function,
-        Marker::Ruled(&Self::MARKERS),
+        Marker::WordBoundary,
     );
     const ST_STR: TransformatorTuple<'_> = (
         &Self::STRUCT_STRINGS,
         Self::to_keyword_struct,
-        Marker::Ruled(&Self::MARKERS),
+        Marker::WordBoundary,
     );
     const LET_STR: TransformatorTuple<'_> = (
         &Self::LET_STRINGS,
         Self::to_keyword_let,
-        Marker::Ruled(&Self::MARKERS),
+        Marker::WordBoundary,
+    );
+    const ENUM_STR: TransformatorTuple<'_> = (
+        &Self::ENUM_STRINGS,
+        Self::to_keyword_enum,
+        Marker::WordBoundary,
+    );
+    const MATCH_STR: TransformatorTuple<'_> = (
+        &Self::MATCH_STRINGS,
+        Self::to_keyword_match,
+        Marker::WordBoundary,
+    );
+    const IF_STR: TransformatorTuple<'_> =
+        (&Self::IF_STRINGS, Self::to_keyword_if, Marker::WordBoundary);
+    const ELSE_STR: TransformatorTuple<'_> = (
+        &Self::ELSE_STRINGS,
+        Self::to_keyword_else,
+        Marker::WordBoundary,
+    );
+    const WHILE_STR: TransformatorTuple<'_> = (
+        &Self::WHILE_STRINGS,
+        Self::to_keyword_while,
+        Marker::WordBoundary,
+    );
+    const LOOP_STR: TransformatorTuple<'_> = (
+        &Self::LOOP_STRINGS,
+        Self::to_keyword_loop,
+        Marker::WordBoundary,
+    );
+    const BREAK_STR: TransformatorTuple<'_> = (
+        &Self::BREAK_STRINGS,
+        Self::to_keyword_break,
+        Marker::WordBoundary,
+    );
+    const CONTINUE_STR: TransformatorTuple<'_> = (
+        &Self::CONTINUE_STRINGS,
+        Self::to_keyword_continue,
+        Marker::WordBoundary,
+    );
+    const RETURN_STR: TransformatorTuple<'_> = (
+        &Self::RETURN_STRINGS,
+        Self::to_keyword_return,
+        Marker::WordBoundary,
+    );
+    const MUT_STR: TransformatorTuple<'_> = (
+        &Self::MUT_STRINGS,
+        Self::to_keyword_mut,
+        Marker::WordBoundary,
+    );
+    const TRUE_STR: TransformatorTuple<'_> = (
+        &Self::TRUE_STRINGS,
+        Self::to_keyword_true,
+        Marker::WordBoundary,
+    );
+    const FALSE_STR: TransformatorTuple<'_> = (
+        &Self::FALSE_STRINGS,
+        Self::to_keyword_false,
+        Marker::WordBoundary,
+    );
+    const MOD_STR: TransformatorTuple<'_> = (
+        &Self::MOD_STRINGS,
+        Self::to_keyword_mod,
+        Marker::WordBoundary,
+    );
+    const USE_STR: TransformatorTuple<'_> = (
+        &Self::USE_STRINGS,
+        Self::to_keyword_use,
+        Marker::WordBoundary,
+    );
+    const PUB_STR: TransformatorTuple<'_> = (
+        &Self::PUB_STRINGS,
+        Self::to_keyword_pub,
+        Marker::WordBoundary,
     );
+    const AS_STR: TransformatorTuple<'_> =
+        (&Self::AS_STRINGS, Self::to_keyword_as, Marker::WordBoundary);
+    const MOVE_STR: TransformatorTuple<'_> = (
+        &Self::MOVE_STRINGS,
+        Self::to_keyword_move,
+        Marker::WordBoundary,
+    );
+    const IMPL_STR: TransformatorTuple<'_> = (
+        &Self::IMPL_STRINGS,
+        Self::to_keyword_impl,
+        Marker::WordBoundary,
+    );
+    const TRAIT_STR: TransformatorTuple<'_> = (
+        &Self::TRAIT_STRINGS,
+        Self::to_keyword_trait,
+        Marker::WordBoundary,
+    );
+    const FOR_STR: TransformatorTuple<'_> = (
+        &Self::FOR_STRINGS,
+        Self::to_keyword_for,
+        Marker::WordBoundary,
+    );
+    const TYPE_STR: TransformatorTuple<'_> = (
+        &Self::TYPE_STRINGS,
+        Self::to_keyword_type,
+        Marker::WordBoundary,
+    );
+    const WHERE_STR: TransformatorTuple<'_> = (
+        &Self::WHERE_STRINGS,
+        Self::to_keyword_where,
+        Marker::WordBoundary,
+    );
+    const MACRO_STR: TransformatorTuple<'_> = (
+        &Self::MACRO_STRINGS,
+        Self::to_keyword_macro,
+        Marker::WordBoundary,
+    );
+    const CONST_STR: TransformatorTuple<'_> = (
+        &Self::CONST_STRINGS,
+        Self::to_keyword_const,
+        Marker::WordBoundary,
+    );
+    const STATIC_STR: TransformatorTuple<'_> = (
+        &Self::STATIC_STRINGS,
+        Self::to_keyword_static,
+        Marker::WordBoundary,
+    );
+    const IN_STR: TransformatorTuple<'_> =
+        (&Self::IN_STRINGS, Self::to_keyword_in, Marker::WordBoundary);
     fn to_keyword_function<'z>(start: usize, end: usize) -> Token<'z> {
         Token::Keyword(Keyword::Function(start, end))
     }
@@ -329,16 +818,95 @@ impl Keyword {
     fn to_keyword_let<'z>(start: usize, end: usize) -> Token<'z> {
         Token::Keyword(Keyword::Let(start, end))
     }
+    fn to_keyword_enum<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Enum(start, end))
+    }
+    fn to_keyword_match<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Match(start, end))
+    }
+    fn to_keyword_if<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::If(start, end))
+    }
+    fn to_keyword_else<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Else(start, end))
+    }
+    fn to_keyword_while<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::While(start, end))
+    }
+    fn to_keyword_loop<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Loop(start, end))
+    }
+    fn to_keyword_break<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Break(start, end))
+    }
+    fn to_keyword_continue<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Continue(start, end))
+    }
+    fn to_keyword_return<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Return(start, end))
+    }
+    fn to_keyword_mut<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Mut(start, end))
+    }
+    fn to_keyword_true<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::True(start, end))
+    }
+    fn to_keyword_false<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::False(start, end))
+    }
+    fn to_keyword_mod<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Mod(start, end))
+    }
+    fn to_keyword_use<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Use(start, end))
+    }
+    fn to_keyword_pub<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Pub(start, end))
+    }
+    fn to_keyword_as<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::As(start, end))
+    }
+    fn to_keyword_move<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Move(start, end))
+    }
+    fn to_keyword_impl<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Impl(start, end))
+    }
+    fn to_keyword_trait<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Trait(start, end))
+    }
+    fn to_keyword_for<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::For(start, end))
+    }
+    fn to_keyword_type<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Type(start, end))
+    }
+    fn to_keyword_where<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Where(start, end))
+    }
+    fn to_keyword_macro<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Macro(start, end))
+    }
+    fn to_keyword_const<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Const(start, end))
+    }
+    fn to_keyword_static<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::Static(start, end))
+    }
+    fn to_keyword_in<'z>(start: usize, end: usize) -> Token<'z> {
+        Token::Keyword(Keyword::In(start, end))
+    }
 }
 
 fn to_whitespace<'z>(start: usize, end: usize) -> Token<'z> {
     Token::Whitespace(start, end)
 }
-const IS_WHITESPACE: [&str; 1] = [" "];
+const IS_WHITESPACE: [&str; 4] = [" ", "\n", "\t", "\r"];
 const WHITESPACE: [&TransformatorTuple<'_>; 1] = [&(&IS_WHITESPACE, to_whitespace, Marker::Ignore)];
 
-const KEYS: [&[&TransformatorTuple<'static>]; 4] = [
+const KEYS: [&[&TransformatorTuple<'static>]; 5] = [
     &WHITESPACE,
+    &Punctuation::ALL_KW,
     &Operator::ALL_KW,
     &Keyword::ALL_KW,
     &Scope::ALL_KW,
@@ -346,6 +914,10 @@ const KEYS: [&[&TransformatorTuple<'static>]; 4] = [
 
 pub fn parse_token<'a>(cursor: &Cursor<'a>) -> Token<'a> {
     let previous = cursor.position();
+    // Comments have to be found before the `/` operator.
+    if let Some(token) = parse_comment(cursor, previous) {
+        return token;
+    }
     for transform in KEYS.iter() {
         for (references, to, marker) in transform.iter() {
             if let Some(token) = parse_with_strings(references, cursor, previous, to, marker) {
@@ -353,26 +925,32 @@ pub fn parse_token<'a>(cursor: &Cursor<'a>) -> Token<'a> {
             }
         }
     }
-    let mut prev = previous;
-    let mut next = previous + 1;
-    loop {
-        let view = cursor.extract_substring(prev, next);
-        if view.is_empty() {
-            return Token::Unknown(cursor.extract_substring(previous, prev));
-        } else if view == " " {
-            return Token::Unknown(cursor.extract_substring(previous, next));
-        } else {
-            prev += 1;
-            next += 1;
-        }
+    if let Some(token) = parse_literal(cursor, previous) {
+        return token;
+    }
+    if let Some(token) = parse_identifier(cursor, previous) {
+        return token;
     }
+    let len = cursor
+        .remaining()
+        .chars()
+        .next()
+        .map(char::len_utf8)
+        .unwrap_or(0);
+    Token::Unknown(cursor.extract_substring(previous, previous + len))
 }
 
 enum Marker {
-    Ruled(&'static [&'static str]),
+    /// The match must not be directly followed by a character of an identifier,
+    /// so `fna` is not read as `fn` + `a`.
+    WordBoundary,
     Ignore,
 }
 
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
 fn parse_with_strings<'a>(
     references: &[&'static str],
     cursor: &Cursor<'a>,
@@ -384,12 +962,12 @@ fn parse_with_strings<'a>(
         let len = id.len();
         if id == cursor.extract_substring(previous, previous + len) {
             match marker {
-                Marker::Ruled(rules) => {
-                    let next = cursor.extract_substring(previous + len, previous + len + 1);
-                    if rules.contains(&next) {
-                        return Some(to(previous, previous + len));
-                    } else {
+                Marker::WordBoundary => {
+                    let next = cursor.remaining()[len..].chars().next();
+                    if next.is_some_and(is_identifier_char) {
                         continue;
+                    } else {
+                        return Some(to(previous, previous + len));
                     }
                 }
                 Marker::Ignore => return Some(to(previous, previous + len)),
@@ -398,6 +976,68 @@ fn parse_with_strings<'a>(
     }
     None
 }
+
+fn parse_comment<'a>(cursor: &Cursor<'a>, previous: usize) -> Option<Token<'a>> {
+    let rest = cursor.remaining();
+    if !rest.starts_with("//") {
+        return None;
+    }
+    let len = rest.find('\n').unwrap_or(rest.len());
+    Some(Token::Comment(previous, previous + len))
+}
+
+/// Numbers, strings and chars. Escapes are kept as written, the parser
+/// is the one giving them a meaning.
+fn parse_literal<'a>(cursor: &Cursor<'a>, previous: usize) -> Option<Token<'a>> {
+    let rest = cursor.remaining();
+    let first = rest.chars().next()?;
+    if first.is_ascii_digit() {
+        let digits = |s: &str| {
+            s.find(|c: char| !(c.is_ascii_digit() || c == '_'))
+                .unwrap_or(s.len())
+        };
+        let mut len = digits(rest);
+        let after = &rest[len..];
+        // `1..2` is a range and `t.0.1` a field access, not floats.
+        if after.starts_with('.') && after[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            len += 1 + digits(&after[1..]);
+            return Some(Token::Literal(Literal::Float(previous, previous + len)));
+        }
+        return Some(Token::Literal(Literal::Integer(previous, previous + len)));
+    }
+    if first == '"' || first == '\'' {
+        let mut escaped = false;
+        for (id, c) in rest.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == first {
+                let end = previous + id + 1;
+                return Some(Token::Literal(if first == '"' {
+                    Literal::Str(previous, end)
+                } else {
+                    Literal::Char(previous, end)
+                }));
+            }
+        }
+        // Unterminated: the parser will complain about the unknown quote.
+        return None;
+    }
+    None
+}
+
+fn parse_identifier<'a>(cursor: &Cursor<'a>, previous: usize) -> Option<Token<'a>> {
+    let rest = cursor.remaining();
+    let first = rest.chars().next()?;
+    if !(first.is_alphabetic() || first == '_') {
+        return None;
+    }
+    let len = rest
+        .find(|c: char| !is_identifier_char(c))
+        .unwrap_or(rest.len());
+    Some(Token::Identifier(previous, previous + len))
+}
 #[test]
 fn name() {
     let c = Cursor::new("let");
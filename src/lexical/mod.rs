@@ -1,10 +1,37 @@
 use cursor::Cursor;
 
 mod cursor;
+mod tree;
+pub use cursor::{Keyword, Literal, Operator, Punctuation, Scope, Token, Tokens};
+pub use tree::{flatten, token_trees, Delimited, TokenTree};
 
-pub fn hello() {
-    let mut cursor_reader = Cursor::new("hello");
-    let tokens = cursor::Tokens::new(&mut cursor_reader);
-    let scopes = tokens.scopes();
-    dbg!(scopes);
+use crate::span::Span;
+
+/// A token as the parser sees it : its text and its place in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme<'input> {
+    pub token: Token<'input>,
+    pub text: &'input str,
+    pub span: Span,
+}
+
+/// Reads `input` and drops whitespace and comments. Spans are shifted by `base`,
+/// which lets several files share one range of positions.
+pub fn lex(input: &str, base: usize) -> Vec<Lexeme<'_>> {
+    let mut cursor = Cursor::new(input);
+    let mut position = 0;
+    let mut lexemes = vec![];
+    for token in cursor.read() {
+        let end = position + cursor::Length::length(&token);
+        match token {
+            Token::Whitespace(..) | Token::Comment(..) => {}
+            token => lexemes.push(Lexeme {
+                token,
+                text: &input[position..end],
+                span: Span::new(base + position, base + end),
+            }),
+        }
+        position = end;
+    }
+    lexemes
 }
@@ -0,0 +1,120 @@
+//! Token trees : the lexemes with each bracketed group gathered under its brackets,
+//! which is what macros match and write.
+
+use super::{Lexeme, Token};
+use crate::span::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenTree<'input> {
+    Token(Lexeme<'input>),
+    /// `( ... )`, `[ ... ]` or `{ ... }`
+    Delimited(Delimited<'input>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimited<'input> {
+    pub open: Lexeme<'input>,
+    pub trees: Vec<TokenTree<'input>>,
+    pub close: Lexeme<'input>,
+}
+
+impl<'input> TokenTree<'input> {
+    pub fn span(&self) -> Span {
+        match self {
+            TokenTree::Token(lexeme) => lexeme.span,
+            TokenTree::Delimited(group) => group.open.span.to(group.close.span),
+        }
+    }
+
+    /// Whether this is the single token `text`, which is not a literal.
+    pub fn is(&self, text: &str) -> bool {
+        matches!(self, TokenTree::Token(lexeme)
+            if lexeme.text == text && !matches!(lexeme.token, Token::Literal(_)))
+    }
+
+    fn flatten_into(&self, out: &mut Vec<Lexeme<'input>>) {
+        match self {
+            TokenTree::Token(lexeme) => out.push(lexeme.clone()),
+            TokenTree::Delimited(group) => {
+                out.push(group.open.clone());
+                for tree in &group.trees {
+                    tree.flatten_into(out);
+                }
+                out.push(group.close.clone());
+            }
+        }
+    }
+}
+
+/// The lexemes of `trees`, in order.
+pub fn flatten<'input>(trees: &[TokenTree<'input>]) -> Vec<Lexeme<'input>> {
+    let mut out = vec![];
+    for tree in trees {
+        tree.flatten_into(&mut out);
+    }
+    out
+}
+
+fn closer(open: &str) -> Option<&'static str> {
+    match open {
+        "(" => Some(")"),
+        "[" => Some("]"),
+        "{" => Some("}"),
+        _ => None,
+    }
+}
+
+/// Gathers `lexemes` into trees. Brackets that do not match are kept as plain
+/// tokens, the parser reports them.
+pub fn token_trees<'input>(lexemes: &[Lexeme<'input>]) -> Vec<TokenTree<'input>> {
+    // The open groups, innermost last, with the trees read so far at the bottom.
+    let mut open: Vec<(Lexeme<'input>, Vec<TokenTree<'input>>)> = vec![];
+    let mut trees = vec![];
+    for lexeme in lexemes {
+        let literal = matches!(lexeme.token, Token::Literal(_));
+        if !literal && closer(lexeme.text).is_some() {
+            open.push((lexeme.clone(), std::mem::take(&mut trees)));
+        } else if !literal
+            && open
+                .last()
+                .is_some_and(|(opener, _)| closer(opener.text) == Some(lexeme.text))
+        {
+            let (opener, outer) = open.pop().unwrap();
+            let inner = std::mem::replace(&mut trees, outer);
+            trees.push(TokenTree::Delimited(Delimited {
+                open: opener,
+                trees: inner,
+                close: lexeme.clone(),
+            }));
+        } else {
+            trees.push(TokenTree::Token(lexeme.clone()));
+        }
+    }
+    while let Some((opener, outer)) = open.pop() {
+        let inner = std::mem::replace(&mut trees, outer);
+        trees.push(TokenTree::Token(opener));
+        trees.extend(inner);
+    }
+    trees
+}
+
+#[cfg(test)]
+mod test {
+    use super::{flatten, token_trees, TokenTree};
+    use crate::lexical::lex;
+
+    #[test]
+    fn groups_matching_brackets() {
+        let lexemes = lex("f(a, [b]) { c } )", 0);
+        let trees = token_trees(&lexemes);
+        assert_eq!(trees.len(), 4);
+        let TokenTree::Delimited(call) = &trees[1] else {
+            panic!("{:?}", trees[1])
+        };
+        assert_eq!(call.open.text, "(");
+        assert!(matches!(call.trees[2], TokenTree::Delimited(_)));
+        // The stray `)` stays a token.
+        assert!(trees[3].is(")"));
+        assert_eq!(flatten(&trees), lexemes);
+    }
+}
@@ -1,4 +1,10 @@
 // pub mod cursor;
+pub mod codegen;
+pub mod diagnostics;
+pub mod driver;
 pub mod lexical;
 pub mod logger;
+pub mod semantic;
+pub mod span;
+pub mod syntax;
 // pub mod token;
@@ -47,7 +47,7 @@ pub fn setup_logger() -> Result<(), fern::InitError> {
             ))
         })
         .level(use_level())
-        .chain(std::io::stdout())
+        .chain(std::io::stderr())
         .chain(
             fern::Dispatch::new()
                 .format(|out, message, record| {
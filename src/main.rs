@@ -1,10 +1,95 @@
-use log::{debug, error, trace};
+use std::{env, path::Path, process};
+
+use log::error;
+use oxydant::driver;
 use oxydant::logger::setup_logger;
+use oxydant::syntax::dump::{self, Format};
+
+const USAGE: &str = "usage:
+    oxydant check <file>
+    oxydant parse [--dump-ast=tree|sexpr|json] <file>
+    oxydant build <file> [--out <dir>]";
+
 fn main() {
     let _ = setup_logger();
-    debug!("this should log either way!");
-    trace!("this should only log to file :)");
-    error!("this should log even in production build.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let code = match args[..] {
+        ["check", file] => check(Path::new(file)),
+        ["build", file] => build(Path::new(file), None),
+        ["build", file, "--out", out] => build(Path::new(file), Some(Path::new(out))),
+        ["parse", file] => parse(Path::new(file), Format::Tree),
+        ["parse", option, file] => match option
+            .strip_prefix("--dump-ast=")
+            .and_then(Format::from_name)
+        {
+            Some(format) => parse(Path::new(file), format),
+            None => {
+                eprintln!("{USAGE}");
+                2
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            2
+        }
+    };
+    process::exit(code);
+}
+
+fn parse(file: &Path, format: Format) -> i32 {
+    match driver::parse(file) {
+        Ok((session, module)) => {
+            print!("{}", dump::dump(&module, format));
+            eprint!("{}", session.report());
+            if session.has_errors() {
+                1
+            } else {
+                0
+            }
+        }
+        Err(err) => {
+            error!("could not read {}: {err}", file.display());
+            2
+        }
+    }
+}
+
+/// Without `--out`, the package goes to `target/oxydant/<name of the file>`.
+fn build(file: &Path, out: Option<&Path>) -> i32 {
+    let default_out = Path::new("target/oxydant").join(file.file_stem().unwrap_or_default());
+    let out = out.unwrap_or(&default_out);
+    match driver::build(file, out) {
+        Ok(session) => {
+            eprint!("{}", session.report());
+            if session.has_errors() {
+                1
+            } else {
+                0
+            }
+        }
+        Err(err) => {
+            error!("could not build {}: {err}", file.display());
+            2
+        }
+    }
+}
+
+fn check(file: &Path) -> i32 {
+    match driver::check(file) {
+        Ok(session) => {
+            eprint!("{}", session.report());
+            if session.has_errors() {
+                1
+            } else {
+                0
+            }
+        }
+        Err(err) => {
+            error!("could not read {}: {err}", file.display());
+            2
+        }
+    }
 }
 
 // Steps :
@@ -0,0 +1,465 @@
+//! Checks the attributes Oxydant knows, and reports the uses of deprecated items.
+//! Every other attribute, `#[inline]` or `#[test]`, is left as it is to rustc.
+//!
+//! A use is matched to its declaration through what `resolve` found for its path,
+//! and what `typeck` found for the methods called and the fields read.
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::resolve::{Res, Resolutions};
+use super::typeck::TypeckResults;
+use super::unused::adt;
+
+/// The attributes handled by Oxydant, which are not written to the Rust source.
+const KNOWN: &[&str] = &["deprecated"];
+
+/// Whether `attr` is handled by Oxydant rather than by rustc.
+pub fn is_known(attr: &Attribute) -> bool {
+    KNOWN.contains(&attr.name())
+}
+
+pub fn check_attributes(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut declarations = Declarations::default();
+    declarations.visit_module(module);
+    let mut checker = AttributeChecker {
+        declarations,
+        resolutions,
+        types,
+        in_deprecated: 0,
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// A declaration marked `#[deprecated]`.
+#[derive(Clone, Copy)]
+struct Deprecated<'ast> {
+    what: &'static str,
+    name: &'ast str,
+    note: Option<&'ast str>,
+    span: Span,
+}
+
+/// The note of a `#[deprecated]` attribute, `None` when it has none.
+fn deprecation_note(attr: &Attribute) -> Option<&str> {
+    let note = match &attr.meta {
+        Meta::NameValue(_, lit) => Some(lit),
+        Meta::List(_, list) => list.iter().find_map(|meta| match meta {
+            Meta::NameValue(path, lit) if path.to_text() == "note" => Some(lit),
+            _ => None,
+        }),
+        _ => None,
+    };
+    match note.map(|lit| &lit.kind) {
+        Some(LitKind::Str(note)) => Some(note),
+        _ => None,
+    }
+}
+
+fn deprecation<'ast>(
+    attrs: &'ast [Attribute],
+    what: &'static str,
+    name: &'ast Ident,
+) -> Option<Deprecated<'ast>> {
+    let attr = attrs.iter().find(|attr| attr.name() == "deprecated")?;
+    Some(Deprecated {
+        what,
+        name: &name.name,
+        note: deprecation_note(attr),
+        span: attr.span,
+    })
+}
+
+/// The deprecated declarations of the crate.
+#[derive(Default)]
+struct Declarations<'ast> {
+    /// Items, methods and associated functions, by their id.
+    items: HashMap<NodeId, Deprecated<'ast>>,
+    /// Variants, by their enum and their index.
+    variants: HashMap<(NodeId, usize), Deprecated<'ast>>,
+    /// Fields, by their struct or enum and their name.
+    fields: HashMap<(NodeId, &'ast str), Deprecated<'ast>>,
+    /// The struct or the enum whose fields are visited.
+    owner: Option<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Declarations<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let (what, name) = match &item.kind {
+            ItemKind::Function(function) => ("function", &function.name),
+            ItemKind::Struct(item) => ("struct", &item.name),
+            ItemKind::Enum(item) => ("enum", &item.name),
+            ItemKind::Trait(item) => ("trait", &item.name),
+            ItemKind::TyAlias(item) => ("type alias", &item.name),
+            ItemKind::Const(item) => ("constant", &item.name),
+            ItemKind::Static(item) => ("static", &item.name),
+            _ => {
+                visit::walk_item(self, item);
+                return;
+            }
+        };
+        if let Some(deprecated) = deprecation(&item.attrs, what, name) {
+            self.items.insert(item.id, deprecated);
+        }
+        self.owner = Some(item.id);
+        visit::walk_item(self, item);
+    }
+
+    fn visit_impl(&mut self, item: &'ast Impl) {
+        for assoc in &item.items {
+            if let ItemKind::Function(function) = &assoc.kind {
+                let name = &function.name;
+                let what = if function.params.first().is_some_and(Param::is_self) {
+                    "method"
+                } else {
+                    "associated function"
+                };
+                if let Some(deprecated) = deprecation(&assoc.attrs, what, name) {
+                    self.items.insert(assoc.id, deprecated);
+                }
+            }
+        }
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        // Items inside bodies are not reached through paths from outside.
+        visit::walk_generics(self, &function.generics);
+    }
+
+    fn visit_enum(&mut self, item: &'ast Enum) {
+        let owner = self.owner;
+        for (index, variant) in item.variants.iter().enumerate() {
+            let name = &variant.name;
+            if let (Some(owner), Some(deprecated)) =
+                (owner, deprecation(&variant.attrs, "variant", name))
+            {
+                self.variants.insert((owner, index), deprecated);
+            }
+            visit::walk_variant(self, variant);
+        }
+    }
+
+    fn visit_field_def(&mut self, field: &'ast FieldDef) {
+        let name = &field.name;
+        if let (Some(owner), Some(deprecated)) =
+            (self.owner, deprecation(&field.attrs, "field", name))
+        {
+            self.fields.insert((owner, &name.name), deprecated);
+        }
+    }
+}
+
+struct AttributeChecker<'ast, 'r, 'd> {
+    declarations: Declarations<'ast>,
+    resolutions: &'r Resolutions,
+    types: &'r TypeckResults,
+    /// How many deprecated items enclose the current node : their own uses of
+    /// deprecated items are not reported.
+    in_deprecated: usize,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for AttributeChecker<'ast, '_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let target = match &item.kind {
+            ItemKind::Function(_) => "function",
+            ItemKind::Struct(_) => "struct",
+            ItemKind::Enum(_) => "enum",
+            ItemKind::Mod(_) => "module",
+            ItemKind::Use(_) => "use",
+            ItemKind::Trait(_) => "trait",
+            ItemKind::Impl(_) => "impl",
+            ItemKind::TyAlias(_) => "type alias",
+            ItemKind::Const(_) => "constant",
+            ItemKind::Static(_) => "static",
+            ItemKind::MacroDef(_) => "macro",
+            ItemKind::Error => "item",
+        };
+        self.check_attrs(&item.attrs, target);
+        let deprecated = item.attrs.iter().any(|attr| attr.name() == "deprecated");
+        self.in_deprecated += usize::from(deprecated);
+        // The trait of an impl resolves under the id of the impl.
+        if let ItemKind::Impl(Impl {
+            of_trait: Some(path),
+            ..
+        }) = &item.kind
+        {
+            self.used_path(item.id, path);
+        }
+        visit::walk_item(self, item);
+        self.in_deprecated -= usize::from(deprecated);
+    }
+
+    fn visit_variant(&mut self, variant: &'ast Variant) {
+        self.check_attrs(&variant.attrs, "variant");
+        visit::walk_variant(self, variant);
+    }
+
+    fn visit_field_def(&mut self, field: &'ast FieldDef) {
+        self.check_attrs(&field.attrs, "field");
+        visit::walk_field_def(self, field);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        self.check_attrs(&stmt.attrs, "statement");
+        visit::walk_stmt(self, stmt);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let TyKind::Path(path) = &ty.kind {
+            self.used_path(ty.id, path);
+        }
+        visit::walk_ty(self, ty);
+    }
+
+    fn visit_generics(&mut self, generics: &'ast Generics) {
+        let bounds = generics.params.iter().flat_map(|param| &param.bounds);
+        let clauses = generics
+            .where_clause
+            .iter()
+            .flat_map(|clause| &clause.bounds);
+        for bound in bounds.chain(clauses) {
+            if let Some(Res::Item(id)) = self.resolutions.bound(bound) {
+                self.used(self.declarations.items.get(&id).copied(), bound.last().span);
+            }
+        }
+        visit::walk_generics(self, generics);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        match &pattern.kind {
+            PatKind::Path(path) | PatKind::TupleStruct(path, _) | PatKind::Struct { path, .. } => {
+                self.used_path(pattern.id, path)
+            }
+            _ => {}
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Path(path) => self.used_path(expr.id, path),
+            ExprKind::MethodCall { method, .. } => {
+                if let Some(id) = self.types.method(expr.id) {
+                    self.used(self.declarations.items.get(&id).copied(), method.span);
+                }
+            }
+            ExprKind::Field(base, field) => {
+                if let Some(owner) = adt(self.types.expr(base.id)) {
+                    self.used_field(owner, field);
+                }
+            }
+            ExprKind::StructLit { path, fields } => {
+                self.used_path(expr.id, path);
+                let owner = match self.resolutions.get(expr.id) {
+                    Some(Res::Item(id) | Res::Variant(id, _)) => id,
+                    _ => return visit::walk_expr(self, expr),
+                };
+                for field in fields.iter().filter(|field| !field.shorthand) {
+                    self.used_field(owner, &field.name);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl<'ast> AttributeChecker<'ast, '_, '_> {
+    fn check_attrs(&mut self, attrs: &[Attribute], target: &str) {
+        for attr in attrs {
+            match attr.name() {
+                "derive" => self.check_derive(attr, target),
+                "deprecated" => self.check_deprecated(attr, target),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_derive(&mut self, attr: &Attribute, target: &str) {
+        if !matches!(target, "struct" | "enum") {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    "`derive` may only be applied to `struct`s, `enum`s and `union`s",
+                    attr.span,
+                )
+                .with_note(format!("this attribute is on a {target}")),
+            );
+            return;
+        }
+        let well_formed = match &attr.meta {
+            Meta::List(_, list) => list.iter().all(|meta| matches!(meta, Meta::Word(_))),
+            _ => false,
+        };
+        if !well_formed {
+            self.diagnostics.push(
+                Diagnostic::error("malformed `derive` attribute input", attr.span)
+                    .with_note("the form is `#[derive(Trait1, Trait2, ...)]`"),
+            );
+        }
+    }
+
+    fn check_deprecated(&mut self, attr: &Attribute, target: &str) {
+        if matches!(target, "statement" | "impl" | "use") {
+            self.diagnostics.push(Diagnostic::error(
+                format!("`#[deprecated]` cannot be applied to a {target}"),
+                attr.span,
+            ));
+            return;
+        }
+        let string = |lit: &Lit| matches!(lit.kind, LitKind::Str(_));
+        let well_formed = match &attr.meta {
+            Meta::Word(_) => true,
+            Meta::NameValue(_, lit) => string(lit),
+            Meta::List(_, list) => list.iter().all(|meta| match meta {
+                Meta::NameValue(path, lit) => {
+                    matches!(path.to_text().as_str(), "since" | "note") && string(lit)
+                }
+                _ => false,
+            }),
+            Meta::Lit(_) => false,
+        };
+        if !well_formed {
+            self.diagnostics.push(
+                Diagnostic::error("malformed `deprecated` attribute input", attr.span).with_note(
+                    "the forms are `#[deprecated]`, `#[deprecated = \"note\"]` \
+                     and `#[deprecated(since = \"version\", note = \"note\")]`",
+                ),
+            );
+        }
+    }
+
+    /// The path held by the node `id`, which names an item or a variant, and the
+    /// enum of the variant.
+    fn used_path(&mut self, id: NodeId, path: &Path) {
+        let segment = |back: usize| path.segments.iter().rev().nth(back);
+        match self.resolutions.get(id) {
+            Some(Res::Item(item)) => {
+                let deprecated = self.declarations.items.get(&item).copied();
+                self.used(deprecated, path.last().span);
+            }
+            Some(Res::Variant(owner, index)) => {
+                let deprecated = self.declarations.variants.get(&(owner, index)).copied();
+                self.used(deprecated, path.last().span);
+                if let Some(segment) = segment(1) {
+                    let deprecated = self.declarations.items.get(&owner).copied();
+                    self.used(deprecated, segment.ident.span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn used_field(&mut self, owner: NodeId, field: &Ident) {
+        let deprecated = self
+            .declarations
+            .fields
+            .get(&(owner, field.name.as_str()))
+            .copied();
+        self.used(deprecated, field.span);
+    }
+
+    fn used(&mut self, found: Option<Deprecated>, span: Span) {
+        let Some(deprecated) = found else {
+            return;
+        };
+        if self.in_deprecated > 0 {
+            return;
+        }
+        let Deprecated {
+            what, name, note, ..
+        } = deprecated;
+        let message = match note {
+            Some(note) => format!("use of deprecated {what} `{name}`: {note}"),
+            None => format!("use of deprecated {what} `{name}`"),
+        };
+        self.diagnostics.push(
+            Diagnostic::warning(message, span)
+                .with_label(deprecated.span, format!("`{name}` is deprecated here")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_attributes;
+    use crate::semantic;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_attributes)
+    }
+
+    #[test]
+    fn reports_deprecated_uses() {
+        let messages = messages(
+            "struct Point { x: i32, #[deprecated] old_y: i32 }
+            impl Point {
+                #[deprecated = \"use `length` instead\"]
+                fn norm(&self) -> i32 { self.x }
+                fn length(&self) -> i32 { self.x + self.old_y }
+            }
+            enum Shape { #[deprecated] Square(i32), Circle(i32) }
+            #[deprecated(since = \"0.2\", note = \"use `g`\")]
+            fn f() -> i32 { legacy() }
+            #[deprecated]
+            fn legacy() -> i32 { 0 }
+            mod m { pub fn legacy() -> i32 { 1 } }
+            fn main() {
+                let p = Point { x: 1, old_y: 2 };
+                p.norm();
+                f();
+                legacy();
+                m::legacy();
+                let s = Shape::Square(1);
+                let c = Shape::Circle(2);
+            }
+            fn shadow() {
+                let legacy = 5;
+                let y = legacy;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "use of deprecated field `old_y`",
+                "use of deprecated field `old_y`",
+                "use of deprecated method `norm`: use `length` instead",
+                "use of deprecated function `f`: use `g`",
+                "use of deprecated function `legacy`",
+                "use of deprecated variant `Square`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_known_attributes() {
+        let messages = messages(
+            "#[derive(Debug, Clone)]
+            struct A { #[derive(Debug)] x: i32 }
+            #[derive = \"Debug\"]
+            enum B { C }
+            #[deprecated(reason = \"old\")]
+            #[inline]
+            fn f() { #[deprecated] let x = 1; }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "`derive` may only be applied to `struct`s, `enum`s and `union`s",
+                "malformed `derive` attribute input",
+                "malformed `deprecated` attribute input",
+                "`#[deprecated]` cannot be applied to a statement",
+            ]
+        );
+    }
+}
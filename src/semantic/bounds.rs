@@ -0,0 +1,623 @@
+//! Checks the type arguments of the crate against the generic parameters of the
+//! item they are given to : their number when they are written, `max::<Point>(a, b)`
+//! or `Pair<i32, i32>`, and the bounds each of them must satisfy. The arguments of
+//! a call that writes none are those `typeck` inferred, `f64` for `max(1.0, 2.0)`.
+//!
+//! Whether a type implements a trait is only decided when it can be : for the
+//! primitive types and the common traits of the standard library, and for the
+//! types and traits of the crate. Anything else is left to rustc. Traits are told
+//! apart by what their paths resolve to, not by their names.
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::consts::IntTy;
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+/// The traits of the standard library whose implementations for the primitive
+/// types are known, and which the types of the crate only get when asked for.
+const STD_TRAITS: &[&str] = &[
+    "Clone",
+    "Copy",
+    "Debug",
+    "Default",
+    "Display",
+    "Eq",
+    "Hash",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+];
+
+/// The supertraits of [`STD_TRAITS`].
+pub(super) const STD_SUPERTRAITS: &[(&str, &str)] = &[
+    ("Copy", "Clone"),
+    ("Eq", "PartialEq"),
+    ("Ord", "Eq"),
+    ("Ord", "PartialOrd"),
+    ("PartialOrd", "PartialEq"),
+];
+
+/// Whether the primitive type `ty` implements `bound`, one of [`STD_TRAITS`].
+fn primitive_implements(ty: &str, bound: &str) -> Option<bool> {
+    let implements = match ty {
+        _ if IntTy::from_name(ty).is_some() => true,
+        "bool" | "char" => true,
+        // Floats have `NaN`, which is neither equal to itself nor ordered.
+        "f32" | "f64" => !matches!(bound, "Eq" | "Ord" | "Hash"),
+        "String" => bound != "Copy",
+        "str" => !matches!(bound, "Clone" | "Copy" | "Default"),
+        _ => return None,
+    };
+    Some(implements)
+}
+
+pub fn check_bounds(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut items = Items {
+        resolutions,
+        generics: HashMap::new(),
+        params: HashMap::new(),
+        traits: HashMap::new(),
+        impls: vec![],
+        blanket: vec![],
+    };
+    items.collect(&module.items, None);
+    let mut checker = BoundChecker {
+        items,
+        types,
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// A trait, of the crate by its id or of the standard library by its name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TraitRef<'ast> {
+    Crate(NodeId),
+    Std(&'ast str),
+}
+
+/// The type of a trait impl : a struct or an enum of the crate, or a type named
+/// outside it.
+#[derive(Debug, PartialEq)]
+enum ImplTarget<'ast> {
+    Item(NodeId),
+    Named(&'ast str),
+}
+
+/// An item that takes type arguments.
+#[derive(Clone, Copy)]
+struct Generic<'ast> {
+    what: &'static str,
+    name: &'ast str,
+    generics: &'ast Generics,
+    /// The generics of the impl or of the trait of a method.
+    parent: Option<&'ast Generics>,
+}
+
+/// What the crate declares, by id.
+struct Items<'ast, 'r> {
+    resolutions: &'r Resolutions,
+    generics: HashMap<NodeId, Generic<'ast>>,
+    /// Each generic parameter, with its name and the generics declaring it.
+    params: HashMap<NodeId, (&'ast str, &'ast Generics)>,
+    traits: HashMap<NodeId, &'ast Trait>,
+    /// The trait impls, and those derived.
+    impls: Vec<(TraitRef<'ast>, ImplTarget<'ast>)>,
+    /// Traits implemented for every type, by `impl<T> Trait for T`.
+    blanket: Vec<TraitRef<'ast>>,
+}
+
+impl<'ast> Items<'ast, '_> {
+    fn collect(&mut self, items: &'ast [Item], parent: Option<&'ast Generics>) {
+        for item in items {
+            match &item.kind {
+                ItemKind::Mod(Mod {
+                    content: Some(module),
+                    ..
+                }) => self.collect(&module.items, None),
+                ItemKind::Function(function) => self.declare(
+                    item.id,
+                    match function.params.first() {
+                        Some(param) if param.is_self() => "method",
+                        _ => "function",
+                    },
+                    &function.name,
+                    &function.generics,
+                    parent,
+                ),
+                ItemKind::Struct(Struct { name, generics, .. })
+                | ItemKind::Enum(Enum { name, generics, .. }) => {
+                    let what = match item.kind {
+                        ItemKind::Struct(_) => "struct",
+                        _ => "enum",
+                    };
+                    self.declare(item.id, what, name, generics, None);
+                    for path in item.attrs.iter().flat_map(Attribute::derives) {
+                        let derived = TraitRef::Std(&path.last().name);
+                        self.impls.push((derived, ImplTarget::Item(item.id)));
+                    }
+                }
+                ItemKind::Trait(trait_item) => {
+                    self.declare(
+                        item.id,
+                        "trait",
+                        &trait_item.name,
+                        &trait_item.generics,
+                        None,
+                    );
+                    self.traits.insert(item.id, trait_item);
+                    self.collect(&trait_item.items, Some(&trait_item.generics));
+                }
+                ItemKind::Impl(imp) => {
+                    self.declare_params(&imp.generics);
+                    self.collect(&imp.items, Some(&imp.generics));
+                    let Some(path) = &imp.of_trait else {
+                        continue;
+                    };
+                    let Some(of_trait) = self.trait_ref(self.resolutions.get(item.id), path) else {
+                        continue;
+                    };
+                    let TyKind::Path(ty) = &imp.self_ty.kind else {
+                        continue;
+                    };
+                    match self.resolutions.get(imp.self_ty.id) {
+                        Some(Res::Item(id)) => self.impls.push((of_trait, ImplTarget::Item(id))),
+                        Some(Res::External) => self
+                            .impls
+                            .push((of_trait, ImplTarget::Named(&ty.last().name))),
+                        Some(Res::Generic(_)) => self.blanket.push(of_trait),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn declare(
+        &mut self,
+        id: NodeId,
+        what: &'static str,
+        name: &'ast Ident,
+        generics: &'ast Generics,
+        parent: Option<&'ast Generics>,
+    ) {
+        let generic = Generic {
+            what,
+            name: &name.name,
+            generics,
+            parent,
+        };
+        self.generics.insert(id, generic);
+        self.declare_params(generics);
+    }
+
+    fn declare_params(&mut self, generics: &'ast Generics) {
+        for param in &generics.params {
+            self.params.insert(param.id, (&param.name.name, generics));
+        }
+    }
+
+    /// The trait `path` resolved to `res` names.
+    fn trait_ref(&self, res: Option<Res>, path: &'ast Path) -> Option<TraitRef<'ast>> {
+        match res? {
+            Res::Item(id) => Some(TraitRef::Crate(id)),
+            Res::External => Some(TraitRef::Std(&path.last().name)),
+            _ => None,
+        }
+    }
+
+    /// The trait of a bound or of a supertrait.
+    fn bound(&self, path: &'ast Path) -> Option<TraitRef<'ast>> {
+        self.trait_ref(self.resolutions.bound(path), path)
+    }
+
+    /// Whether `ty` implements `bound`, when that can be told.
+    fn implements(&self, ty: &Type, bound: TraitRef<'ast>) -> Option<bool> {
+        if self.blanket.contains(&bound) {
+            return None;
+        }
+        let known = match bound {
+            TraitRef::Std(name) => STD_TRAITS.contains(&name),
+            TraitRef::Crate(_) => true,
+        };
+        let name = match ty {
+            // `Self` in a trait.
+            Type::Param(id, _) if self.traits.contains_key(id) => {
+                return Some(self.implies(TraitRef::Crate(*id), bound));
+            }
+            Type::Param(id, _) => {
+                let (name, generics) = self.params.get(id)?;
+                return Some(
+                    generics
+                        .bounds_of(name)
+                        .filter_map(|path| self.bound(path))
+                        .any(|declared| self.implies(declared, bound)),
+                );
+            }
+            Type::Adt(id, _, _) => {
+                let implemented = self.implemented(bound, ImplTarget::Item(*id));
+                return (implemented || known).then_some(implemented);
+            }
+            Type::Ref(_, inner) => {
+                return match bound {
+                    TraitRef::Std("Clone" | "Copy") => Some(true),
+                    TraitRef::Std("Default") => Some(false),
+                    TraitRef::Std(_) => self.implements(inner, bound),
+                    TraitRef::Crate(_) => None,
+                };
+            }
+            Type::Int(name) | Type::Float(name) => *name,
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::Str => "str",
+            Type::Named(name, args) if name == "String" && args.is_empty() => "String",
+            _ => return None,
+        };
+        match bound {
+            TraitRef::Std(bound) if known => primitive_implements(name, bound),
+            TraitRef::Std(_) => None,
+            TraitRef::Crate(_) => Some(self.implemented(bound, ImplTarget::Named(name))),
+        }
+    }
+
+    /// Whether the crate implements `bound` for `target`, or derives it, through the
+    /// traits having it as a supertrait.
+    fn implemented(&self, bound: TraitRef<'ast>, target: ImplTarget) -> bool {
+        self.impls
+            .iter()
+            .any(|(of_trait, ty)| *ty == target && self.implies(*of_trait, bound))
+    }
+
+    /// Whether a type implementing `declared` implements `bound`, through supertraits.
+    fn implies(&self, declared: TraitRef<'ast>, bound: TraitRef<'ast>) -> bool {
+        let mut pending = vec![declared];
+        let mut seen = vec![];
+        while let Some(current) = pending.pop() {
+            if current == bound {
+                return true;
+            }
+            if seen.contains(&current) {
+                continue;
+            }
+            seen.push(current);
+            match current {
+                TraitRef::Crate(id) => pending.extend(
+                    self.traits
+                        .get(&id)
+                        .into_iter()
+                        .flat_map(|found| &found.supertraits)
+                        .filter_map(|path| self.bound(path)),
+                ),
+                TraitRef::Std(name) => pending.extend(
+                    STD_SUPERTRAITS
+                        .iter()
+                        .filter(|(sub, _)| *sub == name)
+                        .map(|(_, sup)| TraitRef::Std(sup)),
+                ),
+            }
+        }
+        false
+    }
+
+    /// The type `ty` writes, as far as the bounds go.
+    fn written(&self, ty: &Ty) -> Type {
+        match &ty.kind {
+            TyKind::Path(path) => {
+                let name = &path.last().name;
+                let args = path.segments.last().map_or(vec![], |segment| {
+                    segment.args.iter().map(|arg| self.written(arg)).collect()
+                });
+                match self.resolutions.get(ty.id) {
+                    Some(Res::Item(id)) if self.generics.contains_key(&id) => {
+                        Type::Adt(id, name.clone(), args)
+                    }
+                    Some(Res::Generic(id)) => Type::Param(id, name.clone()),
+                    Some(Res::SelfTy(id)) if self.traits.contains_key(&id) => {
+                        Type::Param(id, name.clone())
+                    }
+                    Some(Res::External) => {
+                        Type::primitive(name).unwrap_or_else(|| Type::Named(name.clone(), args))
+                    }
+                    _ => Type::Unknown,
+                }
+            }
+            TyKind::Ref { mutable, inner } => Type::Ref(*mutable, Box::new(self.written(inner))),
+            TyKind::Tuple(tys) => Type::Tuple(tys.iter().map(|ty| self.written(ty)).collect()),
+            _ => Type::Unknown,
+        }
+    }
+}
+
+struct BoundChecker<'ast, 'r, 'd> {
+    items: Items<'ast, 'r>,
+    types: &'r TypeckResults,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for BoundChecker<'ast, '_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if let ItemKind::Impl(Impl {
+            of_trait: Some(path),
+            ..
+        }) = &item.kind
+        {
+            self.check_written(self.items.resolutions.get(item.id), path);
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_trait(&mut self, item: &'ast Trait) {
+        for path in &item.supertraits {
+            self.check_written(self.items.resolutions.bound(path), path);
+        }
+        visit::walk_trait(self, item);
+    }
+
+    fn visit_generics(&mut self, generics: &'ast Generics) {
+        let params = generics.params.iter().flat_map(|param| &param.bounds);
+        let clauses = generics
+            .where_clause
+            .iter()
+            .flat_map(|clause| &clause.bounds);
+        for path in params.chain(clauses) {
+            self.check_written(self.items.resolutions.bound(path), path);
+        }
+        visit::walk_generics(self, generics);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let TyKind::Path(path) = &ty.kind {
+            self.check_written(self.items.resolutions.get(ty.id), path);
+        }
+        visit::walk_ty(self, ty);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Path(path) => {
+                if let Some(Res::Item(id)) = self.items.resolutions.get(expr.id) {
+                    let segment = path.segments.last().unwrap();
+                    self.check_inferred(expr.id, id, segment, path.span);
+                }
+            }
+            ExprKind::MethodCall {
+                method,
+                generic_args,
+                ..
+            } => {
+                if let Some(id) = self.types.method(expr.id) {
+                    let segment = PathSegment {
+                        args: generic_args.clone(),
+                        ..PathSegment::new(method.clone())
+                    };
+                    self.check_inferred(expr.id, id, &segment, method.span);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl<'ast> BoundChecker<'ast, '_, '_> {
+    /// Checks the arguments written on the last segment of `path`, naming `res`.
+    fn check_written(&mut self, res: Option<Res>, path: &Path) {
+        let segment = path.segments.last().unwrap();
+        if segment.args.is_empty() {
+            return;
+        }
+        let Some(Res::Item(id)) = res else {
+            return;
+        };
+        let Some(generic) = self.items.generics.get(&id).copied() else {
+            return;
+        };
+        if !self.check_count(generic, segment) {
+            return;
+        }
+        for (param, arg) in generic.generics.params.iter().zip(&segment.args) {
+            let ty = self.items.written(arg);
+            self.check_param(generic, param, &ty, arg.span);
+        }
+    }
+
+    /// Checks the use `id` of the function `def` against the arguments `typeck`
+    /// inferred for it, and those written on `segment`.
+    fn check_inferred(&mut self, id: NodeId, def: NodeId, segment: &PathSegment, span: Span) {
+        let Some(generic) = self.items.generics.get(&def).copied() else {
+            return;
+        };
+        if !segment.args.is_empty() && !self.check_count(generic, segment) {
+            return;
+        }
+        let Some(args) = self.types.generic_args(id) else {
+            return;
+        };
+        let own = generic.generics.params.iter().map(Some);
+        let written = own.zip(segment.args.iter().map(|arg| arg.span));
+        let params = generic
+            .generics
+            .params
+            .iter()
+            .chain(generic.parent.into_iter().flat_map(|parent| &parent.params));
+        for param in params {
+            let Some(ty) = args.get(&param.id) else {
+                continue;
+            };
+            let span = written
+                .clone()
+                .find(|(own, _)| own.is_some_and(|own| own.id == param.id))
+                .map_or(span, |(_, span)| span);
+            let generics = match generic.generics.params.iter().any(|p| p.id == param.id) {
+                true => generic,
+                false => Generic {
+                    generics: generic.parent.unwrap(),
+                    ..generic
+                },
+            };
+            self.check_param(generics, param, ty, span);
+        }
+    }
+
+    /// Checks the number of arguments written on `segment`.
+    fn check_count(&mut self, generic: Generic<'ast>, segment: &PathSegment) -> bool {
+        let Generic {
+            what,
+            name,
+            generics,
+            ..
+        } = generic;
+        let expected = generics.params.len();
+        if segment.args.len() == expected {
+            return true;
+        }
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        self.diagnostics.push(
+            Diagnostic::error(
+                format!(
+                    "{what} takes {expected} generic argument{} but {} generic argument{} supplied",
+                    plural(expected),
+                    segment.args.len(),
+                    if segment.args.len() == 1 {
+                        " was"
+                    } else {
+                        "s were"
+                    },
+                ),
+                segment.ident.span,
+            )
+            .with_label(generics.span, format!("`{name}` is declared with these")),
+        );
+        false
+    }
+
+    /// Checks `ty`, given to `param` of `generic` at `span`, against its bounds.
+    fn check_param(&mut self, generic: Generic<'ast>, param: &GenericParam, ty: &Type, span: Span) {
+        for bound in generic.generics.bounds_of(&param.name.name) {
+            let Some(of_trait) = self.items.bound(bound) else {
+                continue;
+            };
+            if self.items.implements(ty, of_trait) != Some(false) {
+                continue;
+            }
+            let bound_name = &bound.last().name;
+            let mut diagnostic = Diagnostic::error(
+                format!("the trait bound `{ty}: {bound_name}` is not satisfied"),
+                span,
+            )
+            .with_label(
+                bound.span,
+                format!("required by this bound in `{}`", generic.name),
+            );
+            if let Type::Adt(..) = ty {
+                diagnostic = diagnostic
+                    .with_note(format!("consider implementing `{bound_name}` for `{ty}`"));
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_bounds;
+    use crate::semantic;
+
+    fn diagnostics(source: &str) -> Vec<crate::diagnostics::Diagnostic> {
+        semantic::test::diagnostics(source, check_bounds)
+    }
+
+    #[test]
+    fn checks_explicit_arguments() {
+        let diagnostics = diagnostics(
+            "trait Ordered: Ord {}
+            fn max<T: Ord>(a: T, b: T) -> T { a }
+            struct Pair<A, B> where A: Clone { a: A, b: B }
+            struct Point { x: f64 }
+            impl Point { fn pick<T: Ord>(&self, a: T) -> T { a } }
+            #[derive(PartialEq, Eq, PartialOrd, Ord)]
+            struct Meters { m: i32 }
+            fn main(p: Point, m: Meters, pair: Pair<i32>) {
+                max::<Point>(p, p);
+                max::<Meters>(m, m);
+                max::<i32>(1, 2);
+                max::<f64>(1.0, 2.0);
+                p.pick::<f64>(1.0);
+                p.pick::<i32, i32>(1);
+            }
+            fn inner<U: Ordered, V>(u: U, v: V) {
+                max::<U>(u, u);
+                max::<V>(v, v);
+            }",
+        );
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "struct takes 2 generic arguments but 1 generic argument was supplied",
+                "the trait bound `Point: Ord` is not satisfied",
+                "the trait bound `f64: Ord` is not satisfied",
+                "the trait bound `f64: Ord` is not satisfied",
+                "method takes 1 generic argument but 2 generic arguments were supplied",
+                "the trait bound `V: Ord` is not satisfied",
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].notes,
+            ["consider implementing `Ord` for `Point`"]
+        );
+    }
+
+    #[test]
+    fn checks_inferred_arguments() {
+        let diagnostics = diagnostics(
+            "mod a { pub trait Clone { fn foo(&self); } }
+            trait Shape { fn area(&self) -> f64; }
+            fn max<T: Ord>(a: T, b: T) -> T { a }
+            fn copy<T: Clone>(x: &T) -> T { x.clone() }
+            fn area<S: Shape>(s: &S) -> f64 { s.area() }
+            struct Point { x: f64 }
+            #[derive(Clone)]
+            struct Cell { v: i32 }
+            struct Square { side: f64 }
+            impl Shape for Square { fn area(&self) -> f64 { self.side * self.side } }
+            impl a::Clone for Point { fn foo(&self) {} }
+            struct Stack<T> { items: Vec<T> }
+            impl<T: Clone> Stack<T> {
+                fn top(&self) -> i32 { 0 }
+            }
+            fn main(p: Point, c: Cell, s: Square, stack: Stack<Point>) {
+                max(1.0, 2.0);
+                max(1, 2);
+                copy(&p);
+                copy(&c);
+                copy(& &c);
+                area(&s);
+                area(&c);
+                stack.top();
+            }",
+        );
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "the trait bound `f64: Ord` is not satisfied",
+                "the trait bound `Point: Clone` is not satisfied",
+                "the trait bound `Cell: Shape` is not satisfied",
+                "the trait bound `Point: Clone` is not satisfied",
+            ]
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! Finds what each closure captures, and which closures must own their captures.
+//!
+//! A closure captures the locals of the enclosing functions and closures that its
+//! body uses. It borrows them, unless it outlives them : a closure returned from a
+//! function or from another closure, or given to a new thread, is made `move`, as
+//! is one stored in a struct which outlives them, or bound to a local used so later.
+//!
+//! This runs before names are resolved : a new thread is a call to a path ending
+//! like `thread::spawn`, or to `spawn` brought in by a `use`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+use crate::syntax::visit_mut::{self, VisitorMut};
+
+pub fn infer_captures(module: &mut Module) {
+    Captures::default().visit_module(module);
+}
+
+#[derive(Default)]
+struct Captures {
+    /// The names bound in each scope, innermost last.
+    scopes: Vec<Vec<String>>,
+    /// The closures being visited, innermost last.
+    frames: Vec<Frame>,
+    /// Closures of the current function that outlive it.
+    escaping: HashSet<NodeId>,
+}
+
+struct Frame {
+    /// Number of scopes outside the closure : names found in those are captures.
+    depth: usize,
+    captures: Vec<Ident>,
+}
+
+impl Captures {
+    fn declare(&mut self, name: &Ident) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(name.name.clone());
+        }
+    }
+
+    fn use_name(&mut self, name: &Ident) {
+        let Some(depth) = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains(&name.name))
+        else {
+            // A function, a constant or an unknown name, none of which are captured.
+            return;
+        };
+        for frame in self.frames.iter_mut().filter(|frame| frame.depth > depth) {
+            if !frame
+                .captures
+                .iter()
+                .any(|capture| capture.name == name.name)
+            {
+                frame.captures.push(name.clone());
+            }
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(vec![]);
+        f(self);
+        self.scopes.pop();
+    }
+}
+
+impl VisitorMut for Captures {
+    fn visit_function(&mut self, function: &mut Function) {
+        self.escaping = escaping_closures(function);
+        self.scoped(|this| visit_mut::walk_function(this, function));
+    }
+
+    fn visit_block(&mut self, block: &mut Block) {
+        self.scoped(|this| visit_mut::walk_block(this, block));
+    }
+
+    fn visit_arm(&mut self, arm: &mut Arm) {
+        self.scoped(|this| visit_mut::walk_arm(this, arm));
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern) {
+        if let PatKind::Binding { name, .. } = &pattern.kind {
+            self.declare(name);
+        }
+        visit_mut::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Path(path) if path.segments.len() == 1 => self.use_name(path.last()),
+            ExprKind::Closure(closure) => {
+                self.visit_closure(closure);
+                if self.escaping.contains(&expr.id) && !closure.captures.is_empty() {
+                    closure.capture = CaptureBy::Value;
+                }
+            }
+            // The pattern binds in the body only.
+            ExprKind::ForLoop { pat, iter, body } => {
+                self.visit_expr(iter);
+                self.scoped(|this| {
+                    this.visit_pattern(pat);
+                    this.visit_block(body);
+                });
+            }
+            _ => visit_mut::walk_expr(self, expr),
+        }
+    }
+
+    fn visit_closure(&mut self, closure: &mut Closure) {
+        self.frames.push(Frame {
+            depth: self.scopes.len(),
+            captures: vec![],
+        });
+        self.scoped(|this| visit_mut::walk_closure(this, closure));
+        if let Some(frame) = self.frames.pop() {
+            closure.captures = frame.captures;
+        }
+    }
+}
+
+/// The closures of `function` that can be used after it returns.
+fn escaping_closures(function: &Function) -> HashSet<NodeId> {
+    let mut escapes = Escapes::default();
+    if let Some(body) = &function.body {
+        escapes.mark_tail(body);
+    }
+    escapes.scoped(|this| visit::walk_function(this, function));
+    // A local escaping takes the value it was bound to along, which may name other
+    // locals in turn.
+    let mut escaped: HashSet<NodeId> = HashSet::new();
+    let mut resolved = 0;
+    let mut taken = vec![false; escapes.bound.len()];
+    loop {
+        for path in &escapes.paths[resolved..] {
+            escaped.extend(escapes.locals.get(path));
+        }
+        resolved = escapes.paths.len();
+        let Some(index) = (0..taken.len())
+            .find(|&index| !taken[index] && escaped.contains(&escapes.bound[index].0))
+        else {
+            break;
+        };
+        taken[index] = true;
+        escapes.mark(escapes.bound[index].1);
+    }
+    escapes.ids
+}
+
+#[derive(Default)]
+struct Escapes<'ast> {
+    ids: HashSet<NodeId>,
+    /// Paths to locals used where their value escapes.
+    paths: Vec<NodeId>,
+    /// `let name = <init>;`, by the id of the binding.
+    bound: Vec<(NodeId, &'ast Expr)>,
+    /// The names bound in each scope, innermost last, with the id of their binding.
+    scopes: Vec<Vec<(&'ast str, NodeId)>>,
+    /// The binding each path to a local names, so that shadowed locals stay apart.
+    locals: HashMap<NodeId, NodeId>,
+}
+
+impl<'ast> Escapes<'ast> {
+    /// `expr` gives its value to something that outlives the current function.
+    fn mark(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Closure(_) => {
+                self.ids.insert(expr.id);
+            }
+            ExprKind::Path(path) if path.segments.len() == 1 => self.paths.push(expr.id),
+            ExprKind::Paren(inner) => self.mark(inner),
+            ExprKind::StructLit { fields, .. } => {
+                for field in fields {
+                    self.mark(&field.expr);
+                }
+            }
+            ExprKind::Block(block) => self.mark_tail(block),
+            ExprKind::If(_, then, otherwise) => {
+                self.mark_tail(then);
+                if let Some(otherwise) = otherwise {
+                    self.mark(otherwise);
+                }
+            }
+            ExprKind::Match(_, arms) => {
+                for arm in arms {
+                    self.mark(&arm.body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mark_tail(&mut self, block: &'ast Block) {
+        if let Some(tail) = block.tail() {
+            self.mark(tail);
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(vec![]);
+        f(self);
+        self.scopes.pop();
+    }
+}
+
+impl<'ast> Visitor<'ast> for Escapes<'ast> {
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.scoped(|this| visit::walk_block(this, block));
+    }
+
+    fn visit_arm(&mut self, arm: &'ast Arm) {
+        self.scoped(|this| visit::walk_arm(this, arm));
+    }
+
+    fn visit_closure(&mut self, closure: &'ast Closure) {
+        self.scoped(|this| visit::walk_closure(this, closure));
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        if let (PatKind::Binding { sub: None, .. }, Some(init)) = (&local.pat.kind, &local.init) {
+            self.bound.push((local.pat.id, init));
+        }
+        visit::walk_local(self, local);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        if let (PatKind::Binding { name, .. }, Some(scope)) =
+            (&pattern.kind, self.scopes.last_mut())
+        {
+            scope.push((&name.name, pattern.id));
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Path(path) if path.segments.len() == 1 => {
+                let name = &path.last().name;
+                let binding = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .find(|(bound, _)| *bound == name);
+                if let Some((_, binding)) = binding {
+                    self.locals.insert(expr.id, *binding);
+                }
+            }
+            ExprKind::Return(Some(value)) => self.mark(value),
+            ExprKind::Call(callee, args) if spawns_thread(callee) => {
+                for arg in args {
+                    self.mark(arg);
+                }
+            }
+            // What a closure returns outlives the closure's own locals.
+            ExprKind::Closure(closure) => self.mark(&closure.body),
+            // The pattern binds in the body only.
+            ExprKind::ForLoop { pat, iter, body } => {
+                self.visit_expr(iter);
+                self.scoped(|this| {
+                    this.visit_pattern(pat);
+                    this.visit_block(body);
+                });
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// Whether `callee` is `std::thread::spawn`, whose closure must be `'static`.
+fn spawns_thread(callee: &Expr) -> bool {
+    let ExprKind::Path(path) = &callee.kind else {
+        return false;
+    };
+    let names: Vec<_> = path
+        .segments
+        .iter()
+        .map(|s| s.ident.name.as_str())
+        .collect();
+    matches!(names[..], [.., "thread", "spawn"] | ["spawn"])
+}
+
+#[cfg(test)]
+mod test {
+    use super::infer_captures;
+    use crate::syntax::{ast::*, parse, visit::Visitor};
+
+    #[derive(Default)]
+    struct Closures(Vec<(CaptureBy, Vec<String>)>);
+
+    impl<'ast> Visitor<'ast> for Closures {
+        fn visit_closure(&mut self, closure: &'ast Closure) {
+            let names = closure.captures.iter().map(|c| c.name.clone()).collect();
+            self.0.push((closure.capture, names));
+            crate::syntax::visit::walk_closure(self, closure)
+        }
+    }
+
+    fn closures(input: &str) -> Vec<(CaptureBy, Vec<String>)> {
+        let (mut module, errors) = parse(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        infer_captures(&mut module);
+        let mut closures = Closures::default();
+        closures.visit_module(&module);
+        closures.0
+    }
+
+    #[test]
+    fn captures_outer_locals_only() {
+        let found = closures(
+            "fn main() {
+                let a = 1;
+                let b = 2;
+                let f = |x| { let b = x; a + b + helper(x) };
+                f(b);
+            }",
+        );
+        assert_eq!(found, [(CaptureBy::Ref, vec!["a".to_string()])]);
+    }
+
+    #[test]
+    fn moves_escaping_closures() {
+        let found = closures(
+            "fn adder(n: i32) -> Adder {
+                let g = |x| x + n;
+                let h = fun (x: i32) -> i32 { x * n };
+                h(1);
+                Adder { f: |x| x - n, g: g }
+            }
+            fn curry(a: i32) { |b| |c| a + b + c }
+            fn run(s: String) {
+                let f = || s.len();
+                std::thread::spawn(f);
+                thread::spawn(|| s.len());
+                spawn(|| s.len());
+                scope.spawn(|| s.len());
+            }
+            fn local(t: String) -> usize {
+                let f = || t.len();
+                let j = Job { run: f };
+                (j.run)();
+                let k = Job { run: || t.len() };
+                let l = k;
+                thread::spawn(l);
+                t.len()
+            }",
+        );
+        let capture: Vec<_> = found.iter().map(|(capture, _)| *capture).collect();
+        assert_eq!(
+            capture,
+            [
+                CaptureBy::Value,
+                CaptureBy::Ref,
+                CaptureBy::Value,
+                CaptureBy::Value,
+                CaptureBy::Value,
+                CaptureBy::Value,
+                CaptureBy::Value,
+                CaptureBy::Value,
+                CaptureBy::Ref,
+                CaptureBy::Ref,
+                CaptureBy::Value
+            ]
+        );
+        // The inner closure captures `a` and `b`, so the outer one captures `a` too.
+        assert_eq!(found[3].1, ["a"]);
+        assert_eq!(found[4].1, ["a", "b"]);
+    }
+
+    #[test]
+    fn tells_shadowed_locals_apart() {
+        let found = closures(
+            "fn run(s: String, t: String) {
+                {
+                    let f = || s.len();
+                    f();
+                }
+                let f = || t.len();
+                thread::spawn(f);
+                let n = s.len();
+            }",
+        );
+        let capture: Vec<_> = found.iter().map(|(capture, _)| *capture).collect();
+        assert_eq!(capture, [CaptureBy::Ref, CaptureBy::Value]);
+    }
+}
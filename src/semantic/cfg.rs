@@ -0,0 +1,543 @@
+//! Control-flow graphs of the function bodies, for the passes that follow the order
+//! in which the code runs.
+//!
+//! A body is cut into basic blocks : straight sequences of [`Action`]s, ending with a
+//! [`Terminator`] naming the blocks that may run next. The actions only record what
+//! happens to places, the locals and their fields : the analyses look up the rest in
+//! the tree. Expressions are lowered in the order Rust evaluates them, the operands
+//! before the operation.
+//!
+//! Closures are not lowered into the body that creates them : creating one is an
+//! [`Action::Capture`].
+
+use crate::span::Span;
+use crate::syntax::ast::*;
+
+/// The index of a basic block. The entry of the body is the block `0`.
+pub type BlockId = usize;
+
+#[derive(Debug)]
+pub struct Cfg<'ast> {
+    pub blocks: Vec<BasicBlock<'ast>>,
+    /// The block running into the end of the body.
+    pub exit: BlockId,
+    /// The block each statement of the body starts in.
+    pub stmts: Vec<(&'ast Stmt, BlockId)>,
+    /// The `loop` expressions, with the block starting each iteration and the block
+    /// `break` goes to.
+    pub loops: Vec<(&'ast Expr, BlockId, BlockId)>,
+    /// The `let...else` statements, with the block starting their `else` block and
+    /// the block it ends in.
+    pub els: Vec<(&'ast Local, BlockId, BlockId)>,
+}
+
+#[derive(Debug)]
+pub struct BasicBlock<'ast> {
+    pub actions: Vec<Action<'ast>>,
+    pub terminator: Terminator<'ast>,
+}
+
+#[derive(Debug)]
+pub enum Action<'ast> {
+    /// A place, a local or a path to one of its fields, is used.
+    Use {
+        place: &'ast Expr,
+        kind: UseKind<'ast>,
+    },
+    /// A place gets a new value.
+    Assign {
+        place: &'ast Expr,
+        value: &'ast Expr,
+    },
+    /// The bindings of a pattern get their values : from `init`, the value of a
+    /// `let` or the scrutinee of a `match`. It is `None` for `let x;` and the
+    /// pattern of a `for`.
+    Bind {
+        pat: &'ast Pattern,
+        init: Option<&'ast Expr>,
+    },
+    /// A closure is created, borrowing the locals it captures, or taking them if it
+    /// is `move`.
+    Capture { closure: &'ast Expr },
+    /// The end of a block, where its locals go away, by the ids of their bindings.
+    ScopeEnd { bindings: Vec<NodeId>, span: Span },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UseKind<'ast> {
+    /// Read by value : moved, unless its type is `Copy`.
+    Consume,
+    /// `&place` or `&mut place`.
+    Borrow { mutable: bool },
+    /// The receiver of this method call, borrowed or moved depending on the method.
+    Receiver(&'ast Expr),
+    /// Read without being moved : the scrutinee of a `match`, an operand of `==`.
+    Inspect,
+}
+
+#[derive(Debug)]
+pub enum Terminator<'ast> {
+    Goto(BlockId),
+    /// The condition of an `if`, a loop or a `match` : one of the blocks runs next.
+    Branch(Vec<BlockId>),
+    /// Leaves the body, with the value of `return` or of the tail of the body.
+    Return(Option<&'ast Expr>),
+    /// Nothing runs after, the end of a block following a `return` or a `break`.
+    Unreachable,
+}
+
+impl<'ast> Cfg<'ast> {
+    /// The graph of a function body.
+    pub fn build(function: &'ast Function) -> Option<Self> {
+        let body = function.body.as_ref()?;
+        let mut builder = Builder {
+            blocks: vec![BasicBlock {
+                actions: vec![],
+                terminator: Terminator::Unreachable,
+            }],
+            current: 0,
+            loops: vec![],
+            scopes: vec![],
+            stmts: vec![],
+            loop_exprs: vec![],
+            els: vec![],
+        };
+        builder.lower_block(body);
+        let exit = builder.current;
+        builder.terminate(Terminator::Return(body.tail()));
+        // The block opened after the last terminator is never reached.
+        builder.blocks.pop();
+        Some(Cfg {
+            blocks: builder.blocks,
+            exit,
+            stmts: builder.stmts,
+            loops: builder.loop_exprs,
+            els: builder.els,
+        })
+    }
+
+    /// The blocks reached from `start`, `start` included.
+    pub fn reachable(&self, start: BlockId) -> Vec<bool> {
+        let mut reached = vec![false; self.blocks.len()];
+        let mut work = vec![start];
+        while let Some(block) = work.pop() {
+            if !std::mem::replace(&mut reached[block], true) {
+                work.extend(self.successors(block));
+            }
+        }
+        reached
+    }
+
+    pub fn successors(&self, block: BlockId) -> &[BlockId] {
+        match &self.blocks[block].terminator {
+            Terminator::Goto(target) => std::slice::from_ref(target),
+            Terminator::Branch(targets) => targets,
+            Terminator::Return(_) | Terminator::Unreachable => &[],
+        }
+    }
+
+    /// The blocks leading to each block.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for block in 0..self.blocks.len() {
+            for &successor in self.successors(block) {
+                predecessors[successor].push(block);
+            }
+        }
+        predecessors
+    }
+
+    /// The state at the start of each block, `None` for the blocks never reached.
+    /// The body starts in the default state, and `transfer` applies an action to the
+    /// state before it. Each block is run again until the states stop growing.
+    pub fn forward<S: FlowState>(
+        &self,
+        mut transfer: impl FnMut(&Action<'ast>, &mut S),
+    ) -> Vec<Option<S>> {
+        let mut states: Vec<Option<S>> = vec![None; self.blocks.len()];
+        states[0] = Some(S::default());
+        let mut work = vec![0];
+        while let Some(block) = work.pop() {
+            let Some(mut state) = states[block].clone() else {
+                continue;
+            };
+            for action in &self.blocks[block].actions {
+                transfer(action, &mut state);
+            }
+            for &successor in self.successors(block) {
+                let changed = match &mut states[successor] {
+                    Some(next) => next.join(&state),
+                    next @ None => {
+                        *next = Some(state.clone());
+                        true
+                    }
+                };
+                if changed {
+                    work.push(successor);
+                }
+            }
+        }
+        states
+    }
+}
+
+/// What a pass following the paths through a body knows at a point of it, see
+/// [`Cfg::forward`].
+pub trait FlowState: Clone + Default {
+    /// Adds what may be true on another path. Returns whether anything changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+/// Where `break` and `continue` go in a loop.
+struct Loop {
+    exit: BlockId,
+    head: BlockId,
+}
+
+struct Builder<'ast> {
+    blocks: Vec<BasicBlock<'ast>>,
+    current: BlockId,
+    loops: Vec<Loop>,
+    /// The bindings declared in each enclosing block.
+    scopes: Vec<Vec<NodeId>>,
+    stmts: Vec<(&'ast Stmt, BlockId)>,
+    /// The `loop` expressions, see [`Cfg::loops`].
+    loop_exprs: Vec<(&'ast Expr, BlockId, BlockId)>,
+    /// The `let...else` statements, see [`Cfg::els`].
+    els: Vec<(&'ast Local, BlockId, BlockId)>,
+}
+
+impl<'ast> Builder<'ast> {
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock {
+            actions: vec![],
+            terminator: Terminator::Unreachable,
+        });
+        self.blocks.len() - 1
+    }
+
+    fn push(&mut self, action: Action<'ast>) {
+        self.blocks[self.current].actions.push(action);
+    }
+
+    /// Ends the current block. What follows goes into a new block, only reached if a
+    /// later terminator jumps to it.
+    fn terminate(&mut self, terminator: Terminator<'ast>) {
+        self.blocks[self.current].terminator = terminator;
+        self.current = self.new_block();
+    }
+
+    fn goto(&mut self, target: BlockId) {
+        self.blocks[self.current].terminator = Terminator::Goto(target);
+        self.current = target;
+    }
+
+    /// Ends the current block with a branch to the blocks returned, in order.
+    fn branch<const N: usize>(&mut self) -> [BlockId; N] {
+        let targets = [(); N].map(|_| self.new_block());
+        self.blocks[self.current].terminator = Terminator::Branch(targets.to_vec());
+        targets
+    }
+
+    fn lower_block(&mut self, block: &'ast Block) {
+        self.scopes.push(vec![]);
+        for stmt in &block.stmts {
+            self.stmts.push((stmt, self.current));
+            match &stmt.kind {
+                StmtKind::Let(local) => self.lower_local(local),
+                StmtKind::Expr(expr) | StmtKind::Semi(expr) => self.lower_expr(expr),
+            }
+        }
+        let bindings = self.scopes.pop().unwrap_or_default();
+        let end = Span::new(block.span.end.saturating_sub(1), block.span.end);
+        self.push(Action::ScopeEnd {
+            bindings,
+            span: end.with_ctxt(block.span.ctxt),
+        });
+    }
+
+    fn lower_local(&mut self, local: &'ast Local) {
+        if let Some(init) = &local.init {
+            self.lower_expr(init);
+        }
+        if let Some(els) = &local.els {
+            let [matched, otherwise] = self.branch();
+            self.current = otherwise;
+            self.lower_block(els);
+            self.els.push((local, otherwise, self.current));
+            self.terminate(Terminator::Unreachable);
+            self.current = matched;
+        }
+        self.bind(&local.pat, local.init.as_ref());
+    }
+
+    fn bind(&mut self, pat: &'ast Pattern, init: Option<&'ast Expr>) {
+        self.push(Action::Bind { pat, init });
+        if let Some(scope) = self.scopes.last_mut() {
+            bindings(pat, scope);
+        }
+    }
+
+    /// Lowers `expr` for its value.
+    fn lower_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Lit(_) | ExprKind::Error => {}
+            ExprKind::Closure(_) => self.push(Action::Capture { closure: expr }),
+            ExprKind::Path(_) | ExprKind::Field(..) | ExprKind::Index(..) if is_place(expr) => {
+                self.use_place(expr, UseKind::Consume)
+            }
+            ExprKind::Unary(UnOp::Deref, _) if is_place(expr) => {
+                self.use_place(expr, UseKind::Consume)
+            }
+            ExprKind::Path(_) => {}
+            ExprKind::Field(base, _) | ExprKind::Unary(_, base) => self.lower_expr(base),
+            ExprKind::Try(inner) => {
+                self.lower_expr(inner);
+                // `?` returns the error.
+                let [ok, err] = self.branch();
+                self.current = err;
+                self.terminate(Terminator::Return(None));
+                self.current = ok;
+            }
+            ExprKind::Index(base, index) => {
+                self.lower_expr(base);
+                self.lower_expr(index);
+            }
+            ExprKind::Binary(BinOp::And | BinOp::Or, lhs, rhs) => {
+                self.lower_expr(lhs);
+                let [rhs_block, join] = self.branch();
+                self.current = rhs_block;
+                self.lower_expr(rhs);
+                self.goto(join);
+            }
+            ExprKind::Binary(op, lhs, rhs) if op.is_comparison() => {
+                self.inspect(lhs);
+                self.inspect(rhs);
+            }
+            ExprKind::Binary(_, lhs, rhs) => {
+                self.lower_expr(lhs);
+                self.lower_expr(rhs);
+            }
+            ExprKind::AddrOf { mutable, expr } => match is_place(expr) {
+                true => self.use_place(expr, UseKind::Borrow { mutable: *mutable }),
+                false => self.lower_expr(expr),
+            },
+            ExprKind::Assign(place, value) => {
+                self.lower_expr(value);
+                match is_place(place) {
+                    true => {
+                        self.lower_operands(place);
+                        self.push(Action::Assign { place, value });
+                    }
+                    false => self.lower_expr(place),
+                }
+            }
+            ExprKind::Call(callee, args) => {
+                // A closure is called through a reference.
+                self.inspect(callee);
+                for arg in args {
+                    self.lower_expr(arg);
+                }
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                match is_place(receiver) {
+                    true => self.use_place(receiver, UseKind::Receiver(expr)),
+                    false => self.lower_expr(receiver),
+                }
+                for arg in args {
+                    self.lower_expr(arg);
+                }
+            }
+            ExprKind::Tuple(exprs) | ExprKind::Array(exprs) => {
+                for expr in exprs {
+                    self.lower_expr(expr);
+                }
+            }
+            ExprKind::Repeat(elem, len) => {
+                self.lower_expr(elem);
+                self.lower_expr(len);
+            }
+            ExprKind::Range { lo, hi, .. } => {
+                for bound in [lo, hi].into_iter().flatten() {
+                    self.lower_expr(bound);
+                }
+            }
+            ExprKind::StructLit { fields, .. } => {
+                for field in fields {
+                    self.lower_expr(&field.expr);
+                }
+            }
+            ExprKind::Paren(inner) => self.lower_expr(inner),
+            ExprKind::Block(block) => self.lower_block(block),
+            ExprKind::If(cond, then, otherwise) => {
+                self.lower_expr(cond);
+                let [then_block, else_block] = self.branch();
+                let join = self.new_block();
+                self.current = then_block;
+                self.lower_block(then);
+                self.goto(join);
+                self.current = else_block;
+                if let Some(otherwise) = otherwise {
+                    self.lower_expr(otherwise);
+                }
+                self.goto(join);
+            }
+            ExprKind::While(cond, body) => {
+                let head = self.new_block();
+                self.goto(head);
+                self.lower_expr(cond);
+                let [body_block, exit] = self.branch();
+                self.current = body_block;
+                self.lower_loop_body(body, exit, head);
+                self.current = exit;
+            }
+            ExprKind::Loop(body) => {
+                let head = self.new_block();
+                let exit = self.new_block();
+                self.loop_exprs.push((expr, head, exit));
+                self.goto(head);
+                self.lower_loop_body(body, exit, head);
+                self.current = exit;
+            }
+            ExprKind::ForLoop { pat, iter, body } => {
+                self.lower_expr(iter);
+                let head = self.new_block();
+                self.goto(head);
+                let [body_block, exit] = self.branch();
+                self.current = body_block;
+                self.scopes.push(vec![]);
+                self.bind(pat, None);
+                self.lower_loop_body(body, exit, head);
+                self.scopes.pop();
+                self.current = exit;
+            }
+            ExprKind::Match(scrutinee, arms) => {
+                self.inspect(scrutinee);
+                let targets: Vec<_> = arms.iter().map(|_| self.new_block()).collect();
+                self.blocks[self.current].terminator = Terminator::Branch(targets.clone());
+                let join = self.new_block();
+                for (arm, target) in arms.iter().zip(targets) {
+                    self.current = target;
+                    self.scopes.push(vec![]);
+                    self.bind(&arm.pat, Some(scrutinee));
+                    if let Some(guard) = &arm.guard {
+                        self.lower_expr(guard);
+                    }
+                    self.lower_expr(&arm.body);
+                    self.scopes.pop();
+                    self.goto(join);
+                }
+                self.current = join;
+            }
+            ExprKind::Return(value) => {
+                if let Some(value) = value {
+                    self.lower_expr(value);
+                }
+                self.terminate(Terminator::Return(value.as_deref()));
+            }
+            ExprKind::Break(value) => {
+                if let Some(value) = value {
+                    self.lower_expr(value);
+                }
+                match self.loops.last() {
+                    Some(target) => {
+                        let exit = target.exit;
+                        self.blocks[self.current].terminator = Terminator::Goto(exit);
+                        self.current = self.new_block();
+                    }
+                    None => self.terminate(Terminator::Unreachable),
+                }
+            }
+            ExprKind::Continue => match self.loops.last() {
+                Some(target) => {
+                    let head = target.head;
+                    self.blocks[self.current].terminator = Terminator::Goto(head);
+                    self.current = self.new_block();
+                }
+                None => self.terminate(Terminator::Unreachable),
+            },
+        }
+    }
+
+    fn lower_loop_body(&mut self, body: &'ast Block, exit: BlockId, head: BlockId) {
+        self.loops.push(Loop { exit, head });
+        self.lower_block(body);
+        self.loops.pop();
+        self.goto(head);
+    }
+
+    /// Lowers `expr` read without being moved.
+    fn inspect(&mut self, expr: &'ast Expr) {
+        match is_place(expr) {
+            true => self.use_place(expr, UseKind::Inspect),
+            false => self.lower_expr(expr),
+        }
+    }
+
+    fn use_place(&mut self, place: &'ast Expr, kind: UseKind<'ast>) {
+        self.lower_operands(place);
+        self.push(Action::Use { place, kind });
+    }
+
+    /// Lowers the indices of a place, `i` in `xs[i].name`, evaluated before it.
+    fn lower_operands(&mut self, place: &'ast Expr) {
+        match &place.kind {
+            ExprKind::Paren(inner) | ExprKind::Field(inner, _) => self.lower_operands(inner),
+            ExprKind::Index(base, index) => {
+                self.lower_operands(base);
+                self.lower_expr(index);
+            }
+            ExprKind::Unary(UnOp::Deref, inner) => self.lower_operands(inner),
+            _ => {}
+        }
+    }
+}
+
+/// Whether `expr` names a place : a local, or a field, an element or the target of a
+/// reference reached from one.
+pub fn is_place(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Path(path) => path.segments.len() == 1,
+        ExprKind::Paren(inner)
+        | ExprKind::Field(inner, _)
+        | ExprKind::Index(inner, _)
+        | ExprKind::Unary(UnOp::Deref, inner) => is_place(inner),
+        _ => false,
+    }
+}
+
+/// The ids of the bindings of a pattern.
+pub fn bindings(pattern: &Pattern, out: &mut Vec<NodeId>) {
+    match &pattern.kind {
+        PatKind::Binding { sub, .. } => {
+            out.push(pattern.id);
+            if let Some(sub) = sub {
+                bindings(sub, out);
+            }
+        }
+        PatKind::Tuple(patterns) | PatKind::TupleStruct(_, patterns) | PatKind::Or(patterns) => {
+            for pattern in patterns {
+                bindings(pattern, out);
+            }
+        }
+        PatKind::Struct { fields, .. } => {
+            for field in fields {
+                bindings(&field.pat, out);
+            }
+        }
+        PatKind::Paren(inner) => bindings(inner, out),
+        _ => {}
+    }
+}
+
+/// The place as the messages name it : `p.x`, `v[_]` or `*r`.
+pub fn place_text(place: &Expr) -> String {
+    match &place.kind {
+        ExprKind::Path(path) => path.to_text(),
+        ExprKind::Paren(inner) => place_text(inner),
+        ExprKind::Field(base, name) => format!("{}.{}", place_text(base), name.name),
+        ExprKind::Index(base, _) => format!("{}[_]", place_text(base)),
+        ExprKind::Unary(UnOp::Deref, inner) => format!("*{}", place_text(inner)),
+        _ => "value".to_string(),
+    }
+}
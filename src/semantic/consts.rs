@@ -0,0 +1,1867 @@
+//! Evaluates the initializer of every `const` and `static` while compiling, so an
+//! initializer that cannot be computed, or whose value does not fit its type, is
+//! reported against the Oxydant source rather than by rustc.
+//!
+//! The evaluator knows the literals, the arithmetic, comparison and logical
+//! operators, the other constants of the crate, the `MIN` and `MAX` of the integer
+//! types, the tuples and arrays, and the structs and enum variants of the crate
+//! built from those. What comes from the standard library is left to rustc, as are
+//! the `u128` values too large for an `i128` and the arrays longer than
+//! [`MAX_ARRAY_LEN`].
+//!
+//! A call to a `const fn` of the crate, free or associated, evaluates its body with
+//! the values of the arguments : its blocks and `let` statements, `if`, `match` and
+//! `return`. The loops and the assignments are left to rustc. An error inside a call
+//! is labelled with every call leading to it, the innermost first. The calls nest at
+//! most [`MAX_FRAMES`] deep, and one constant goes through at most [`MAX_STEPS`]
+//! expressions, so a recursion without end stops.
+//!
+//! The length of every array type and repeat expression, `[u8; N]` and `[0; N]`, is
+//! a `usize` constant too, and is evaluated wherever it is written.
+//!
+//! The type aliases the constants are written with are expanded first, and those
+//! expanding to themselves are reported as cycles, like the constants.
+//!
+//! The constants, types and functions are those the paths resolve to, whichever
+//! module declares them.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::resolve::{Res, Resolutions};
+
+/// The longest array whose elements are computed.
+const MAX_ARRAY_LEN: u64 = 4096;
+
+/// How deep the calls to `const fn` may nest.
+const MAX_FRAMES: usize = 64;
+
+/// How many expressions the evaluation of one constant may go through.
+const MAX_STEPS: u64 = 1_000_000;
+
+pub fn check_consts(module: &Module, resolutions: &Resolutions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut items = Items::default();
+    items.visit_module(module);
+    let mut evaluator = Evaluator::new(&items, resolutions, diagnostics);
+    for &decl in &items.order {
+        if !evaluator.values.contains_key(&decl.id) {
+            evaluator.evaluate(decl);
+        }
+    }
+}
+
+/// The lengths of the array types and repeat expressions, by the id of the length,
+/// for those which could be evaluated. What could not is reported by
+/// [`check_consts`].
+pub(super) fn array_lengths(module: &Module, resolutions: &Resolutions) -> HashMap<NodeId, u64> {
+    let mut items = Items::default();
+    items.visit_module(module);
+    Evaluator::new(&items, resolutions, &mut vec![]).lengths
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct IntTy {
+    pub(super) name: &'static str,
+    bits: u32,
+    pub(super) signed: bool,
+}
+
+/// `isize` and `usize` are taken as on 64-bit targets.
+const INT_TYS: &[IntTy] = &[
+    IntTy::new("i8", 8, true),
+    IntTy::new("i16", 16, true),
+    IntTy::new("i32", 32, true),
+    IntTy::new("i64", 64, true),
+    IntTy::new("i128", 128, true),
+    IntTy::new("isize", 64, true),
+    IntTy::new("u8", 8, false),
+    IntTy::new("u16", 16, false),
+    IntTy::new("u32", 32, false),
+    IntTy::new("u64", 64, false),
+    IntTy::new("u128", 128, false),
+    IntTy::new("usize", 64, false),
+];
+
+const I32: IntTy = INT_TYS[2];
+const USIZE: IntTy = INT_TYS[11];
+
+impl IntTy {
+    const fn new(name: &'static str, bits: u32, signed: bool) -> Self {
+        Self { name, bits, signed }
+    }
+
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        INT_TYS.iter().copied().find(|ty| ty.name == name)
+    }
+
+    pub(super) fn min(self) -> i128 {
+        if self.signed {
+            i128::MIN >> (128 - self.bits)
+        } else {
+            0
+        }
+    }
+
+    pub(super) fn max(self) -> i128 {
+        if self.signed || self.bits == 128 {
+            i128::MAX >> (128 - self.bits)
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Values past `i128::MAX` cannot be computed, see the module documentation.
+    fn is_u128(self) -> bool {
+        self.name == "u128"
+    }
+
+    /// The error for the literal `written`, which does not fit the type.
+    pub(super) fn out_of_range(self, written: &str, span: Span) -> Diagnostic {
+        Diagnostic::error(format!("literal out of range for `{}`", self.name), span).with_note(
+            format!(
+                "the literal `{written}` does not fit into the type `{}` whose range is `{}..={}`",
+                self.name,
+                self.min(),
+                self.max()
+            ),
+        )
+    }
+}
+
+/// The types the evaluator can tell apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type<'a> {
+    Int(IntTy),
+    /// `f32` or `f64`
+    Float(&'static str),
+    Bool,
+    Char,
+    /// `&str`
+    Str,
+    /// A struct or an enum of the crate.
+    Adt(&'a str),
+    /// `(i32, bool)`, `()` is the unit type.
+    Tuple(Vec<Type<'a>>),
+    /// `[u8; 4]`
+    Array(Box<Type<'a>>, u64),
+}
+
+impl fmt::Display for Type<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int(ty) => f.write_str(ty.name),
+            Type::Float(name) => f.write_str(name),
+            Type::Bool => f.write_str("bool"),
+            Type::Char => f.write_str("char"),
+            Type::Str => f.write_str("&str"),
+            Type::Adt(name) => f.write_str(name),
+            Type::Tuple(tys) => {
+                let tys = tys.iter().map(Type::to_string).collect();
+                f.write_str(&tuple_text(tys))
+            }
+            Type::Array(ty, len) => write!(f, "[{ty}; {len}]"),
+        }
+    }
+}
+
+/// The value of a constant.
+#[derive(Debug, Clone, PartialEq)]
+enum Value<'ast> {
+    Int(i128, IntTy),
+    Float(f64, &'static str),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    /// `Point { x: 1, y: 2 }`, `Shape::Circle(1.0)` or `Shape::Empty`.
+    Adt {
+        ty: &'ast str,
+        path: String,
+        fields: Fields<'ast>,
+    },
+    Tuple(Vec<Value<'ast>>),
+    /// The type of the elements, and the elements.
+    Array(Type<'ast>, Vec<Value<'ast>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Fields<'ast> {
+    Unit,
+    Tuple(Vec<Value<'ast>>),
+    Named(Vec<(&'ast str, Value<'ast>)>),
+}
+
+impl<'ast> Value<'ast> {
+    fn ty(&self) -> Type<'ast> {
+        match self {
+            Value::Int(_, ty) => Type::Int(*ty),
+            Value::Float(_, name) => Type::Float(name),
+            Value::Bool(_) => Type::Bool,
+            Value::Char(_) => Type::Char,
+            Value::Str(_) => Type::Str,
+            Value::Adt { ty, .. } => Type::Adt(ty),
+            Value::Tuple(values) => Type::Tuple(values.iter().map(Value::ty).collect()),
+            Value::Array(ty, values) => Type::Array(Box::new(ty.clone()), values.len() as u64),
+        }
+    }
+}
+
+/// Written the way rustc shows values in its messages, e.g. `200_u8`.
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value, ty) => write!(f, "{value}_{}", ty.name),
+            Value::Float(value, name) => write!(f, "{value:?}_{name}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Char(value) => write!(f, "{value:?}"),
+            Value::Str(value) => write!(f, "{value:?}"),
+            Value::Tuple(values) => {
+                f.write_str(&tuple_text(values.iter().map(Value::to_string).collect()))
+            }
+            Value::Array(_, values) => {
+                let values: Vec<_> = values.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Adt { path, fields, .. } => {
+                f.write_str(path)?;
+                match fields {
+                    Fields::Unit => Ok(()),
+                    Fields::Tuple(values) => {
+                        let values: Vec<_> = values.iter().map(Value::to_string).collect();
+                        write!(f, "({})", values.join(", "))
+                    }
+                    Fields::Named(fields) => {
+                        let fields: Vec<_> = fields
+                            .iter()
+                            .map(|(name, value)| format!("{name}: {value}"))
+                            .collect();
+                        write!(f, " {{ {} }}", fields.join(", "))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Decl<'ast> {
+    /// The id of the item.
+    id: NodeId,
+    item: &'ast Const,
+    is_static: bool,
+}
+
+/// The error for a cycle of declarations, by what each is and its name, the first
+/// one `verb` again by the last.
+fn cycle_error(verb: &str, cycle: &[(&str, &Ident)]) -> Diagnostic {
+    let (what, first) = cycle[0];
+    let mut diagnostic = Diagnostic::error(
+        format!("cycle detected when {verb} {what} `{}`", first.name),
+        first.span,
+    );
+    for (what, name) in &cycle[1..] {
+        diagnostic = diagnostic.with_note(format!(
+            "...which requires {verb} {what} `{}`...",
+            name.name
+        ));
+    }
+    diagnostic.with_note(format!(
+        "...which again requires {verb} {what} `{}`, completing the cycle",
+        first.name
+    ))
+}
+
+impl Decl<'_> {
+    fn what(&self) -> &'static str {
+        if self.is_static {
+            "static"
+        } else {
+            "constant"
+        }
+    }
+}
+
+/// What the crate declares, by the id of the item.
+#[derive(Default)]
+struct Items<'ast> {
+    consts: HashMap<NodeId, Decl<'ast>>,
+    /// Every constant and static, in the order of the source.
+    order: Vec<Decl<'ast>>,
+    aliases: HashMap<NodeId, (&'ast Ident, &'ast Ty)>,
+    /// Every type alias, in the order of the source.
+    alias_order: Vec<NodeId>,
+    structs: HashMap<NodeId, &'ast Struct>,
+    enums: HashMap<NodeId, &'ast Enum>,
+    functions: HashMap<NodeId, &'ast Function>,
+    /// The functions of the inherent impls.
+    methods: HashMap<NodeId, &'ast Function>,
+    /// The lengths of the array types and of the repeat expressions.
+    lengths: Vec<&'ast Expr>,
+}
+
+impl<'ast> Visitor<'ast> for Items<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Const(constant) | ItemKind::Static(constant) => {
+                let decl = Decl {
+                    id: item.id,
+                    item: constant,
+                    is_static: matches!(item.kind, ItemKind::Static(_)),
+                };
+                self.consts.insert(item.id, decl);
+                self.order.push(decl);
+            }
+            ItemKind::TyAlias(TyAlias { name, ty: Some(ty) }) => {
+                self.aliases.insert(item.id, (name, ty));
+                self.alias_order.push(item.id);
+            }
+            ItemKind::Struct(def) => {
+                self.structs.insert(item.id, def);
+            }
+            ItemKind::Enum(def) => {
+                self.enums.insert(item.id, def);
+            }
+            ItemKind::Function(function) => {
+                self.functions.insert(item.id, function);
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+
+    // The associated items of traits are not evaluated, the lengths they hold are.
+    fn visit_trait(&mut self, item: &'ast Trait) {
+        Lengths(&mut self.lengths).visit_trait(item);
+    }
+
+    fn visit_impl(&mut self, item: &'ast Impl) {
+        if item.of_trait.is_none() {
+            for assoc in &item.items {
+                if let ItemKind::Function(function) = &assoc.kind {
+                    self.methods.insert(assoc.id, function);
+                }
+            }
+        }
+        Lengths(&mut self.lengths).visit_impl(item);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        Lengths(&mut self.lengths).visit_ty(ty);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        Lengths(&mut self.lengths).visit_expr(expr);
+    }
+}
+
+/// Collects the array lengths of whatever it visits.
+struct Lengths<'a, 'ast>(&'a mut Vec<&'ast Expr>);
+
+impl<'ast> Visitor<'ast> for Lengths<'_, 'ast> {
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let TyKind::Array(_, len) = &ty.kind {
+            self.0.push(len);
+        }
+        visit::walk_ty(self, ty);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let ExprKind::Repeat(_, len) = &expr.kind {
+            self.0.push(len);
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// Collects the items the paths of the types it visits resolve to, the aliases a
+/// type may name.
+struct Names<'a>(&'a Resolutions, Vec<NodeId>);
+
+impl<'ast> Visitor<'ast> for Names<'_> {
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let Some(Res::Item(id)) = self.0.get(ty.id) {
+            self.1.push(id);
+        }
+        visit::walk_ty(self, ty);
+    }
+}
+
+/// A local of a block being evaluated.
+#[derive(Clone)]
+enum Binding<'ast> {
+    Value(Value<'ast>),
+    /// `let x = 1;`, whose type is the one `x` is used at. The initializer is evaluated
+    /// at each use, with the locals it saw.
+    Deferred(&'ast Expr, Vec<Scope<'ast>>),
+}
+
+type Scope<'ast> = HashMap<&'ast str, Binding<'ast>>;
+
+/// A call to a `const fn` being evaluated.
+struct Frame<'ast> {
+    /// The function as the call names it, `Point::new`.
+    name: String,
+    call: Span,
+    /// The type of the values the function returns.
+    output: Option<Type<'ast>>,
+    /// The first scope of the function in [`Evaluator::scopes`].
+    scopes: usize,
+}
+
+enum State<'ast> {
+    InProgress,
+    /// `None` when the value could not be computed, the reason was reported.
+    Done(Option<Value<'ast>>),
+}
+
+enum Expansion {
+    InProgress,
+    /// Whether the alias stands for a type, rather than for a cycle.
+    Done(bool),
+}
+
+struct Evaluator<'ast, 'r, 'd> {
+    items: &'ast Items<'ast>,
+    resolutions: &'r Resolutions,
+    /// The constants already evaluated, or being evaluated, by id.
+    values: HashMap<NodeId, State<'ast>>,
+    /// The type aliases already expanded, or being expanded, by id.
+    expansions: HashMap<NodeId, Expansion>,
+    /// The array lengths which could be evaluated, by the id of their expression.
+    lengths: HashMap<NodeId, u64>,
+    /// The constants being evaluated, the innermost last.
+    stack: Vec<Decl<'ast>>,
+    /// The type aliases being expanded, the innermost last.
+    expanding: Vec<&'ast Ident>,
+    /// The locals of the blocks being evaluated, the innermost last.
+    scopes: Vec<Scope<'ast>>,
+    /// The calls being evaluated, the innermost last.
+    frames: Vec<Frame<'ast>>,
+    /// The value of the `return` being evaluated, until its call takes it.
+    returned: Option<Value<'ast>>,
+    /// How many expressions the current constant went through.
+    steps: u64,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast, 'r, 'd> Evaluator<'ast, 'r, 'd> {
+    /// Expands the type aliases and evaluates the array lengths.
+    fn new(
+        items: &'ast Items<'ast>,
+        resolutions: &'r Resolutions,
+        diagnostics: &'d mut Vec<Diagnostic>,
+    ) -> Self {
+        let mut evaluator = Evaluator {
+            resolutions,
+            values: HashMap::new(),
+            expansions: HashMap::new(),
+            lengths: HashMap::new(),
+            stack: vec![],
+            expanding: vec![],
+            scopes: vec![],
+            frames: vec![],
+            returned: None,
+            steps: 0,
+            diagnostics,
+            items,
+        };
+        for &id in &items.alias_order {
+            evaluator.expand(id);
+        }
+        for &len in &items.lengths {
+            evaluator.steps = 0;
+            if let Some(Value::Int(value, _)) = evaluator.eval_checked(len, Some(Type::Int(USIZE)))
+            {
+                evaluator.lengths.insert(len.id, value as u64);
+            }
+        }
+        evaluator
+    }
+
+    /// Reports `diagnostic` with the calls leading to it.
+    fn error(&mut self, mut diagnostic: Diagnostic) -> Option<Value<'ast>> {
+        let mut frames = self.frames.iter().rev().peekable();
+        while let Some(frame) = frames.next() {
+            // A recursion shows each of its calls once.
+            let mut repeated = 1;
+            while frames.next_if(|next| next.call == frame.call).is_some() {
+                repeated += 1;
+            }
+            let label = match repeated {
+                1 => format!("inside this call to `{}`", frame.name),
+                _ => format!("inside this call to `{}`, {repeated} times", frame.name),
+            };
+            diagnostic = diagnostic.with_label(frame.call, label);
+        }
+        if !self.frames.is_empty() {
+            diagnostic = diagnostic.with_note(match self.stack.last() {
+                Some(decl) => format!(
+                    "while evaluating the initializer of {} `{}`",
+                    decl.what(),
+                    decl.item.name.name
+                ),
+                None => "while evaluating the length of an array".to_string(),
+            });
+        }
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    /// Reports a mismatch of types, unless it is in the body of a `const fn`, which
+    /// `typeck` checks with the other function bodies.
+    fn mismatch(&mut self, diagnostic: Diagnostic) -> Option<Value<'ast>> {
+        if !self.frames.is_empty() {
+            return None;
+        }
+        self.error(diagnostic)
+    }
+
+    /// `constants`, `statics` or `constant functions`, for messages about what the
+    /// current one cannot do.
+    fn context(&self) -> &'static str {
+        if !self.frames.is_empty() {
+            return "constant functions";
+        }
+        match self.stack.last() {
+            Some(decl) if decl.is_static => "statics",
+            _ => "constants",
+        }
+    }
+
+    fn evaluate(&mut self, decl: Decl<'ast>) -> Option<Value<'ast>> {
+        self.values.insert(decl.id, State::InProgress);
+        // A constant has the same value wherever it is used, a call using it does not
+        // lend it its locals.
+        let frames = std::mem::take(&mut self.frames);
+        let scopes = std::mem::take(&mut self.scopes);
+        let steps = std::mem::take(&mut self.steps);
+        self.stack.push(decl);
+        let expected = self.resolve_ty(&decl.item.ty);
+        let value = self.eval_checked(&decl.item.expr, expected);
+        self.stack.pop();
+        (self.frames, self.scopes, self.steps) = (frames, scopes, steps);
+        self.values.insert(decl.id, State::Done(value.clone()));
+        value
+    }
+
+    /// The constant or the static the path of the node `id` names.
+    fn constant(&self, id: NodeId) -> Option<Decl<'ast>> {
+        match self.resolutions.get(id)? {
+            Res::Item(item) => self.items.consts.get(&item).copied(),
+            _ => None,
+        }
+    }
+
+    /// The value of `decl`, used at `span`.
+    fn value_of(&mut self, decl: Decl<'ast>, span: Span) -> Option<Value<'ast>> {
+        if decl.is_static && self.context() != "statics" {
+            let context = self.context();
+            return self.error(
+                Diagnostic::error(format!("{context} cannot refer to statics"), span).with_note(
+                    "consider extracting the value of the `static` to a `const`, and referring to that",
+                ),
+            );
+        }
+        match self.values.get(&decl.id) {
+            Some(State::Done(value)) => value.clone(),
+            Some(State::InProgress) => self.cycle(decl.id),
+            None => self.evaluate(decl),
+        }
+    }
+
+    /// The constant `id` is used while it is being evaluated.
+    fn cycle(&mut self, id: NodeId) -> Option<Value<'ast>> {
+        let start = self.stack.iter().position(|decl| decl.id == id).unwrap();
+        let cycle: Vec<_> = self.stack[start..]
+            .iter()
+            .map(|decl| (decl.what(), &decl.item.name))
+            .collect();
+        self.error(cycle_error("evaluating", &cycle))
+    }
+
+    /// Expands the type alias `id` and those it names, reporting the cycles they go
+    /// through. Returns whether it stands for a type.
+    fn expand(&mut self, id: NodeId) -> bool {
+        let Some(&(ident, ty)) = self.items.aliases.get(&id) else {
+            return true;
+        };
+        match self.expansions.get(&id) {
+            Some(Expansion::Done(expands)) => return *expands,
+            Some(Expansion::InProgress) => {
+                let start = self
+                    .expanding
+                    .iter()
+                    .position(|&alias| std::ptr::eq(alias, ident))
+                    .unwrap();
+                let cycle: Vec<_> = self.expanding[start..]
+                    .iter()
+                    .map(|&alias| ("type alias", alias))
+                    .collect();
+                self.error(cycle_error("expanding", &cycle));
+                return false;
+            }
+            None => {}
+        }
+        self.expansions.insert(id, Expansion::InProgress);
+        self.expanding.push(ident);
+        let mut names = Names(self.resolutions, vec![]);
+        names.visit_ty(ty);
+        let mut expands = true;
+        for name in names.1 {
+            expands &= self.expand(name);
+        }
+        self.expanding.pop();
+        self.expansions.insert(id, Expansion::Done(expands));
+        expands
+    }
+
+    fn resolve_ty(&self, ty: &'ast Ty) -> Option<Type<'ast>> {
+        let path = match &ty.kind {
+            TyKind::Ref {
+                mutable: false,
+                inner,
+            } => {
+                return matches!(&inner.kind, TyKind::Path(path) if path.to_text() == "str")
+                    .then_some(Type::Str)
+            }
+            TyKind::Path(path) => path,
+            TyKind::Tuple(tys) => {
+                return tys
+                    .iter()
+                    .map(|ty| self.resolve_ty(ty))
+                    .collect::<Option<_>>()
+                    .map(Type::Tuple)
+            }
+            TyKind::Array(inner, len) => {
+                let inner = self.resolve_ty(inner)?;
+                return Some(Type::Array(Box::new(inner), *self.lengths.get(&len.id)?));
+            }
+            _ => return None,
+        };
+        if path.segments.iter().any(|segment| !segment.args.is_empty()) {
+            return None;
+        }
+        let Some(Res::Item(id)) = self.resolutions.get(ty.id) else {
+            let [segment] = &path.segments[..] else {
+                return None;
+            };
+            return match segment.ident.name.as_str() {
+                "f32" => Some(Type::Float("f32")),
+                "f64" => Some(Type::Float("f64")),
+                "bool" => Some(Type::Bool),
+                "char" => Some(Type::Char),
+                name => IntTy::from_name(name).map(Type::Int),
+            };
+        };
+        if let Some((_, aliased)) = self.items.aliases.get(&id) {
+            let expands = matches!(self.expansions.get(&id), Some(Expansion::Done(true)));
+            return expands.then(|| self.resolve_ty(aliased)).flatten();
+        }
+        let (name, generics) = match (self.items.structs.get(&id), self.items.enums.get(&id)) {
+            (Some(item), _) => (&item.name, &item.generics),
+            (_, Some(item)) => (&item.name, &item.generics),
+            _ => return None,
+        };
+        generics.params.is_empty().then_some(Type::Adt(&name.name))
+    }
+
+    /// Evaluates `expr` and checks its value against the type `expected`.
+    fn eval_checked(
+        &mut self,
+        expr: &'ast Expr,
+        expected: Option<Type<'ast>>,
+    ) -> Option<Value<'ast>> {
+        let value = self.eval(expr, expected.clone())?;
+        self.check(value, expected.as_ref(), expr.span)
+    }
+
+    fn check(
+        &mut self,
+        value: Value<'ast>,
+        expected: Option<&Type>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        match expected {
+            Some(expected) if *expected != value.ty() => {
+                let note = format!("expected `{expected}`, found `{}`", value.ty());
+                self.mismatch(Diagnostic::error("mismatched types", span).with_note(note))
+            }
+            _ => Some(value),
+        }
+    }
+
+    /// The type of `expr` when it can be told without evaluating it, which the
+    /// literals of a binary operation take.
+    fn type_hint(&self, expr: &'ast Expr) -> Option<Type<'ast>> {
+        match &expr.kind {
+            ExprKind::Path(path) => match &path.segments[..] {
+                [segment] if self.local(&segment.ident.name).is_some() => {
+                    match self.local(&segment.ident.name)? {
+                        Binding::Value(value) => Some(value.ty()),
+                        Binding::Deferred(..) => None,
+                    }
+                }
+                [ty, constant] if matches!(constant.ident.name.as_str(), "MIN" | "MAX") => {
+                    IntTy::from_name(&ty.ident.name).map(Type::Int)
+                }
+                _ => self.resolve_ty(&self.constant(expr.id)?.item.ty),
+            },
+            ExprKind::Paren(inner) | ExprKind::Unary(UnOp::Neg | UnOp::Not, inner) => {
+                self.type_hint(inner)
+            }
+            ExprKind::Binary(op, lhs, rhs) if !op.is_comparison() && !is_logical(*op) => {
+                self.type_hint(lhs).or_else(|| self.type_hint(rhs))
+            }
+            ExprKind::Call(callee, _) => {
+                let function = self.const_fn(callee.id)?;
+                self.resolve_ty(function.output.as_ref()?)
+            }
+            _ => None,
+        }
+    }
+
+    fn eval(&mut self, expr: &'ast Expr, expected: Option<Type<'ast>>) -> Option<Value<'ast>> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            if self.steps == MAX_STEPS + 1 {
+                return self.error(
+                    Diagnostic::error("constant evaluation is taking too long", expr.span)
+                        .with_note(format!(
+                            "the evaluation went through {MAX_STEPS} expressions without ending"
+                        )),
+                );
+            }
+            return None;
+        }
+        match &expr.kind {
+            ExprKind::Lit(lit) => self.literal(lit, false, expected, expr.span),
+            ExprKind::Paren(inner) => self.eval(inner, expected),
+            ExprKind::Unary(op, inner) => self.unary(*op, inner, expected, expr.span),
+            ExprKind::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs, expected, expr.span),
+            ExprKind::Path(path) => self.path(expr.id, path, expected, expr.span),
+            ExprKind::Call(callee, args) => self.call(callee, args, expr.span),
+            ExprKind::StructLit { path, fields } => self.struct_lit(expr.id, path, fields),
+            ExprKind::Tuple(elements) => self.tuple(elements, expected),
+            ExprKind::Array(elements) => self.array(elements, expected, expr.span),
+            ExprKind::Repeat(element, len) => self.repeat(element, len, expected, expr.span),
+            ExprKind::Index(base, index) => self.index(base, index, expr.span),
+            ExprKind::Field(base, name) => self.field(base, name),
+            ExprKind::Block(block) => self.block(block, expected),
+            ExprKind::If(condition, then, els) => {
+                let Value::Bool(condition) = self.eval_checked(condition, Some(Type::Bool))? else {
+                    unreachable!("checked against `bool`")
+                };
+                match (condition, els) {
+                    (true, _) => self.block(then, expected),
+                    (false, Some(els)) => self.eval(els, expected),
+                    (false, None) => Some(Value::Tuple(vec![])),
+                }
+            }
+            ExprKind::Match(scrutinee, arms) => self.match_expr(scrutinee, arms, expected),
+            ExprKind::Return(value) if !self.frames.is_empty() => {
+                let expected = self.frames.last().unwrap().output.clone();
+                self.returned = Some(match value {
+                    Some(value) => self.eval_checked(value, expected)?,
+                    None => Value::Tuple(vec![]),
+                });
+                // Unwinds to the call, see `call_const`.
+                None
+            }
+            ExprKind::Return(_) => self.error(Diagnostic::error(
+                "`return` outside of a function body",
+                expr.span,
+            )),
+            ExprKind::Break(_) | ExprKind::Continue => self.error(Diagnostic::error(
+                "`break` and `continue` outside of a loop",
+                expr.span,
+            )),
+            // Left to rustc.
+            _ => None,
+        }
+    }
+
+    /// `negated` when the literal is the operand of `-`, so `-128` fits an `i8`.
+    fn literal(
+        &mut self,
+        lit: &Lit,
+        negated: bool,
+        expected: Option<Type>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        // As `typeck` shows the type of a literal whose type is not known yet.
+        let mismatch = |found: &str| {
+            let note = format!("expected `{}`, found `{found}`", expected.as_ref().unwrap());
+            Diagnostic::error("mismatched types", span).with_note(note)
+        };
+        match &lit.kind {
+            LitKind::Int(value) => {
+                let ty = match expected {
+                    Some(Type::Int(ty)) => ty,
+                    None => I32,
+                    Some(_) => return self.mismatch(mismatch("{integer}")),
+                };
+                let value = i128::try_from(*value).ok()?;
+                let value = if negated { -value } else { value };
+                if value < ty.min() || value > ty.max() {
+                    if ty.is_u128() {
+                        return None;
+                    }
+                    let written = if negated {
+                        format!("-{}", -value)
+                    } else {
+                        value.to_string()
+                    };
+                    return self.error(ty.out_of_range(&written, span));
+                }
+                Some(Value::Int(value, ty))
+            }
+            LitKind::Float(value) => {
+                let name = match expected {
+                    Some(Type::Float(name)) => name,
+                    None => "f64",
+                    Some(_) => return self.mismatch(mismatch("{float}")),
+                };
+                let value = if negated { -value } else { *value };
+                Some(Value::Float(round(value, name), name))
+            }
+            LitKind::Bool(value) => Some(Value::Bool(*value)),
+            LitKind::Char(value) => Some(Value::Char(*value)),
+            LitKind::Str(value) => Some(Value::Str(value.clone())),
+        }
+    }
+
+    fn unary(
+        &mut self,
+        op: UnOp,
+        inner: &'ast Expr,
+        expected: Option<Type<'ast>>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        if let (UnOp::Neg, ExprKind::Lit(lit)) = (op, &inner.kind) {
+            if matches!(lit.kind, LitKind::Int(_) | LitKind::Float(_))
+                && !matches!(expected, Some(Type::Int(IntTy { signed: false, .. })))
+            {
+                return self.literal(lit, true, expected, span);
+            }
+        }
+        let value = self.eval(inner, expected)?;
+        let cannot = |this: &mut Self, note: Option<&str>| {
+            let mut diagnostic = Diagnostic::error(
+                format!(
+                    "cannot apply unary operator `{}` to type `{}`",
+                    if op == UnOp::Neg { "-" } else { "!" },
+                    value.ty()
+                ),
+                span,
+            );
+            if let Some(note) = note {
+                diagnostic = diagnostic.with_note(note);
+            }
+            this.error(diagnostic)
+        };
+        match (op, &value) {
+            (UnOp::Neg, Value::Int(_, ty)) if !ty.signed => {
+                cannot(self, Some("unsigned values cannot be negated"))
+            }
+            (UnOp::Neg, Value::Int(v, ty)) => {
+                self.int_result(v.checked_neg(), *ty, span, || format!("-{value}"))
+            }
+            (UnOp::Neg, Value::Float(v, name)) => Some(Value::Float(-v, name)),
+            (UnOp::Not, Value::Bool(v)) => Some(Value::Bool(!v)),
+            (UnOp::Not, Value::Int(v, ty)) if ty.signed => Some(Value::Int(!v, *ty)),
+            (UnOp::Not, Value::Int(v, ty)) => Some(Value::Int(ty.max() - v, *ty)),
+            (UnOp::Deref, _) => None,
+            _ => cannot(self, None),
+        }
+    }
+
+    /// Checks that an integer operation neither overflowed nor left its type.
+    fn int_result(
+        &mut self,
+        result: Option<i128>,
+        ty: IntTy,
+        span: Span,
+        operation: impl FnOnce() -> String,
+    ) -> Option<Value<'ast>> {
+        match result {
+            Some(value) if value >= ty.min() && value <= ty.max() => Some(Value::Int(value, ty)),
+            _ if ty.is_u128() => None,
+            _ => self.error(Diagnostic::error(
+                format!("attempt to compute `{}`, which would overflow", operation()),
+                span,
+            )),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinOp,
+        lhs: &'ast Expr,
+        rhs: &'ast Expr,
+        expected: Option<Type<'ast>>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        if is_logical(op) {
+            let Value::Bool(left) = self.eval_checked(lhs, Some(Type::Bool))? else {
+                unreachable!("checked against `bool`")
+            };
+            // `false && x` and `true || x` do not look at `x`.
+            if left == (op == BinOp::Or) {
+                return Some(Value::Bool(left));
+            }
+            return self.eval_checked(rhs, Some(Type::Bool));
+        }
+        let operands = match expected {
+            Some(Type::Int(_) | Type::Float(_)) if !op.is_comparison() => expected,
+            _ => None,
+        };
+        let operands = operands
+            .or_else(|| self.type_hint(lhs))
+            .or_else(|| self.type_hint(rhs));
+        let left = self.eval_checked(lhs, operands)?;
+        let right = self.eval_checked(rhs, Some(left.ty()))?;
+        if op.is_comparison() {
+            return self.compare(op, &left, &right, span);
+        }
+        let describe = || format!("{left} {} {right}", op.as_str());
+        match (&left, &right) {
+            (Value::Int(l, ty), Value::Int(r, _)) => {
+                let (l, r, ty) = (*l, *r, *ty);
+                if matches!(op, BinOp::Div | BinOp::Rem) && r == 0 {
+                    let message = if op == BinOp::Div {
+                        format!("attempt to divide `{left}` by zero")
+                    } else {
+                        format!(
+                            "attempt to calculate the remainder of `{left}` with a divisor of zero"
+                        )
+                    };
+                    return self.error(Diagnostic::error(message, span));
+                }
+                let result = match op {
+                    BinOp::Add => l.checked_add(r),
+                    BinOp::Sub => l.checked_sub(r),
+                    BinOp::Mul => l.checked_mul(r),
+                    BinOp::Div => l.checked_div(r),
+                    _ => l.checked_rem(r),
+                };
+                self.int_result(result, ty, span, describe)
+            }
+            (Value::Float(l, name), Value::Float(r, _)) => {
+                let result = match op {
+                    BinOp::Add => l + r,
+                    BinOp::Sub => l - r,
+                    BinOp::Mul => l * r,
+                    BinOp::Div => l / r,
+                    _ => l % r,
+                };
+                Some(Value::Float(round(result, name), name))
+            }
+            _ => self.error(Diagnostic::error(
+                format!(
+                    "binary operation `{}` cannot be applied to type `{}`",
+                    op.as_str(),
+                    left.ty()
+                ),
+                span,
+            )),
+        }
+    }
+
+    /// Only primitive values compare in a constant, `==` on other types is a call
+    /// to `PartialEq`.
+    fn compare(
+        &mut self,
+        op: BinOp,
+        left: &Value,
+        right: &Value,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        let ordering = match (left, right) {
+            (Value::Int(l, _), Value::Int(r, _)) => l.partial_cmp(r),
+            (Value::Float(l, _), Value::Float(r, _)) => l.partial_cmp(r),
+            (Value::Bool(l), Value::Bool(r)) => l.partial_cmp(r),
+            (Value::Char(l), Value::Char(r)) => l.partial_cmp(r),
+            _ => {
+                let context = self.context();
+                return self.error(Diagnostic::error(
+                    format!(
+                        "cannot call non-const operator `{}` on `{}` in {context}",
+                        op.as_str(),
+                        left.ty()
+                    ),
+                    span,
+                ));
+            }
+        };
+        let result = ordering.is_some_and(|ordering| match op {
+            BinOp::Eq => ordering.is_eq(),
+            BinOp::Ne => ordering.is_ne(),
+            BinOp::Lt => ordering.is_lt(),
+            BinOp::Le => ordering.is_le(),
+            BinOp::Gt => ordering.is_gt(),
+            _ => ordering.is_ge(),
+        });
+        // `NaN` is unordered, and only unequal to everything.
+        Some(Value::Bool(
+            result || (ordering.is_none() && op == BinOp::Ne),
+        ))
+    }
+
+    /// The path of the expression `id`.
+    fn path(
+        &mut self,
+        id: NodeId,
+        path: &'ast Path,
+        expected: Option<Type<'ast>>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        if let [segment] = &path.segments[..] {
+            if let Some(binding) = self.local(&segment.ident.name) {
+                return self.read(binding.clone(), expected);
+            }
+        }
+        if let [ty, constant] = &path.segments[..] {
+            if let Some(ty) = IntTy::from_name(&ty.ident.name) {
+                return match constant.ident.name.as_str() {
+                    "MIN" => Some(Value::Int(ty.min(), ty)),
+                    "MAX" if !ty.is_u128() => Some(Value::Int(ty.max(), ty)),
+                    _ => None,
+                };
+            }
+        }
+        if let Some(decl) = self.constant(id) {
+            return self.value_of(decl, span);
+        }
+        if let Some((item, variant)) = self.variant(id) {
+            return matches!(variant.data, VariantData::Unit).then(|| Value::Adt {
+                ty: &item.name.name,
+                path: path.to_text(),
+                fields: Fields::Unit,
+            });
+        }
+        // A unit struct.
+        self.structure(id)
+            .filter(|item| item.fields.is_empty())
+            .map(|item| Value::Adt {
+                ty: &item.name.name,
+                path: path.to_text(),
+                fields: Fields::Unit,
+            })
+    }
+
+    /// The variant of a crate enum that the path of the node `id`, `Shape::Circle`,
+    /// names.
+    fn variant(&self, id: NodeId) -> Option<(&'ast Enum, &'ast Variant)> {
+        let Res::Variant(item, index) = self.resolutions.get(id)? else {
+            return None;
+        };
+        let item = self.items.enums.get(&item)?;
+        Some((item, &item.variants[index]))
+    }
+
+    /// The struct of the crate that the path of the node `id` names.
+    fn structure(&self, id: NodeId) -> Option<&'ast Struct> {
+        match self.resolutions.get(id)? {
+            Res::Item(item) => self.items.structs.get(&item).copied(),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, callee: &'ast Expr, args: &'ast [Expr], span: Span) -> Option<Value<'ast>> {
+        let ExprKind::Path(path) = &callee.kind else {
+            return None;
+        };
+        if let Some((item, variant)) = self.variant(callee.id) {
+            let VariantData::Tuple(tys) = &variant.data else {
+                return None;
+            };
+            let mut values = vec![];
+            for (arg, ty) in args.iter().zip(tys) {
+                let expected = self.resolve_ty(ty);
+                values.push(self.eval_checked(arg, expected)?);
+            }
+            return Some(Value::Adt {
+                ty: &item.name.name,
+                path: path.to_text(),
+                fields: Fields::Tuple(values),
+            });
+        }
+        if let Some(function) = self.const_fn(callee.id) {
+            return self.call_const(path, function, args, span);
+        }
+        let what = match self.resolutions.get(callee.id) {
+            Some(Res::Item(id)) if self.items.functions.contains_key(&id) => "function",
+            Some(Res::Item(id)) if self.items.methods.contains_key(&id) => "associated function",
+            _ => return None,
+        };
+        let context = self.context();
+        self.error(
+            Diagnostic::error(
+                format!(
+                    "cannot call non-const {what} `{}` in {context}",
+                    path.to_text()
+                ),
+                callee.span,
+            )
+            .with_note(format!(
+                "calls in {context} are limited to constant functions, tuple structs and tuple variants"
+            )),
+        )
+    }
+
+    /// The `const fn` of the crate that the path of the node `id`, `size` or
+    /// `Point::new`, names.
+    fn const_fn(&self, id: NodeId) -> Option<&'ast Function> {
+        let Res::Item(id) = self.resolutions.get(id)? else {
+            return None;
+        };
+        let function = self
+            .items
+            .functions
+            .get(&id)
+            .or_else(|| self.items.methods.get(&id))?;
+        function.is_const.then_some(function)
+    }
+
+    /// Evaluates the body of `function` with the values of `args`. What a generic
+    /// function returns is left to rustc.
+    fn call_const(
+        &mut self,
+        path: &Path,
+        function: &'ast Function,
+        args: &'ast [Expr],
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        let body = function.body.as_ref()?;
+        if args.len() != function.params.len() || !function.generics.params.is_empty() {
+            return None;
+        }
+        let mut values = vec![];
+        for (arg, param) in args.iter().zip(&function.params) {
+            let expected = self.resolve_ty(&param.ty);
+            values.push(self.eval_checked(arg, expected)?);
+        }
+        if self.frames.len() == MAX_FRAMES {
+            return self.error(
+                Diagnostic::error("reached the maximum number of nested calls", span).with_note(
+                    format!("calls to `const fn` nest at most {MAX_FRAMES} deep in constants"),
+                ),
+            );
+        }
+        let output = match &function.output {
+            Some(ty) => self.resolve_ty(ty),
+            None => Some(Type::Tuple(vec![])),
+        };
+        let scopes = self.scopes.len();
+        self.frames.push(Frame {
+            name: path.to_text(),
+            call: span,
+            output: output.clone(),
+            scopes,
+        });
+        self.scopes.push(Scope::new());
+        let mut bound = true;
+        for (param, value) in function.params.iter().zip(&values) {
+            bound = bound && self.matches(&param.pat, value) == Some(true);
+        }
+        let value = if bound {
+            self.block(body, output)
+        } else {
+            None
+        };
+        // A `return` ends the evaluation of the body without a value.
+        let value = value.or_else(|| self.returned.take());
+        self.scopes.truncate(scopes);
+        self.frames.pop();
+        value
+    }
+
+    /// The struct literal of the expression `id`.
+    fn struct_lit(
+        &mut self,
+        id: NodeId,
+        path: &'ast Path,
+        fields: &'ast [FieldInit],
+    ) -> Option<Value<'ast>> {
+        let (ty, defs) = match self.variant(id) {
+            Some((item, variant)) => match &variant.data {
+                VariantData::Struct(defs) => (&item.name.name, defs),
+                _ => return None,
+            },
+            None => {
+                let item = self.structure(id)?;
+                (&item.name.name, &item.fields)
+            }
+        };
+        let mut values = vec![];
+        for field in fields {
+            let def = defs.iter().find(|def| def.name.name == field.name.name)?;
+            let expected = self.resolve_ty(&def.ty);
+            let value = self.eval_checked(&field.expr, expected)?;
+            values.push((field.name.name.as_str(), value));
+        }
+        Some(Value::Adt {
+            ty,
+            path: path.to_text(),
+            fields: Fields::Named(values),
+        })
+    }
+
+    /// `(1, true)`, the elements take the types of the expected tuple.
+    fn tuple(
+        &mut self,
+        elements: &'ast [Expr],
+        expected: Option<Type<'ast>>,
+    ) -> Option<Value<'ast>> {
+        let expected = match expected {
+            Some(Type::Tuple(tys)) if tys.len() == elements.len() => {
+                tys.into_iter().map(Some).collect()
+            }
+            _ => vec![None; elements.len()],
+        };
+        elements
+            .iter()
+            .zip(expected)
+            .map(|(element, expected)| self.eval_checked(element, expected))
+            .collect::<Option<_>>()
+            .map(Value::Tuple)
+    }
+
+    /// `[1, 2, 3]`, whose elements all have the type of the first one.
+    fn array(
+        &mut self,
+        elements: &'ast [Expr],
+        expected: Option<Type<'ast>>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        let mut element_ty = match expected {
+            Some(Type::Array(ty, len)) => {
+                if len != elements.len() as u64 {
+                    return self.size_mismatch(len, elements.len() as u64, span);
+                }
+                Some(*ty)
+            }
+            _ => None,
+        };
+        element_ty = element_ty.or_else(|| elements.iter().find_map(|e| self.type_hint(e)));
+        let mut values = vec![];
+        for element in elements {
+            let value = self.eval_checked(element, element_ty.clone())?;
+            element_ty.get_or_insert_with(|| value.ty());
+            values.push(value);
+        }
+        // Nothing tells the type of the elements of `[]`.
+        Some(Value::Array(element_ty?, values))
+    }
+
+    /// `[0; N]`, once `N` is evaluated, see [`check_consts`].
+    fn repeat(
+        &mut self,
+        element: &'ast Expr,
+        len: &'ast Expr,
+        expected: Option<Type<'ast>>,
+        span: Span,
+    ) -> Option<Value<'ast>> {
+        let len = *self.lengths.get(&len.id)?;
+        let element_ty = match expected {
+            Some(Type::Array(ty, expected)) => {
+                if expected != len {
+                    return self.size_mismatch(expected, len, span);
+                }
+                Some(*ty)
+            }
+            _ => None,
+        };
+        let value = self.eval_checked(element, element_ty)?;
+        if len > MAX_ARRAY_LEN {
+            return None;
+        }
+        Some(Value::Array(value.ty(), vec![value; len as usize]))
+    }
+
+    fn size_mismatch(&mut self, expected: u64, found: u64, span: Span) -> Option<Value<'ast>> {
+        self.mismatch(
+            Diagnostic::error("mismatched types", span).with_note(format!(
+                "expected an array with a size of {expected}, found one with a size of {found}"
+            )),
+        )
+    }
+
+    /// `PRIMES[1]`, a slice such as `PRIMES[1..]` is left to rustc.
+    fn index(&mut self, base: &'ast Expr, index: &'ast Expr, span: Span) -> Option<Value<'ast>> {
+        if matches!(index.kind, ExprKind::Range { .. }) {
+            return None;
+        }
+        let values = match self.eval(base, None)? {
+            Value::Array(_, values) => values,
+            Value::Adt { .. } => return None,
+            value => {
+                return self.error(Diagnostic::error(
+                    format!("cannot index into a value of type `{}`", value.ty()),
+                    span,
+                ))
+            }
+        };
+        let Value::Int(position, _) = self.eval_checked(index, Some(Type::Int(USIZE)))? else {
+            unreachable!("checked against `usize`")
+        };
+        match values.get(position as usize) {
+            Some(value) => Some(value.clone()),
+            None => self.error(Diagnostic::error(
+                format!(
+                    "index out of bounds: the length is {} but the index is {position}",
+                    values.len()
+                ),
+                span,
+            )),
+        }
+    }
+
+    /// `PAIR.0` or `ORIGIN.x`.
+    fn field(&mut self, base: &'ast Expr, name: &Ident) -> Option<Value<'ast>> {
+        let values = match self.eval(base, None)? {
+            Value::Tuple(values) => values,
+            Value::Adt {
+                fields: Fields::Named(fields),
+                ..
+            } => {
+                return fields
+                    .into_iter()
+                    .find(|(field, _)| *field == name.name)
+                    .map(|(_, value)| value)
+            }
+            _ => return None,
+        };
+        match name.name.parse().ok().and_then(|i: usize| values.get(i)) {
+            Some(value) => Some(value.clone()),
+            None => self.error(Diagnostic::error(
+                format!(
+                    "no field `{}` on type `{}`",
+                    name.name,
+                    Value::Tuple(values).ty()
+                ),
+                name.span,
+            )),
+        }
+    }
+
+    /// The local `name` of the call being evaluated, or of the initializer outside of
+    /// calls.
+    fn local(&self, name: &str) -> Option<&Binding<'ast>> {
+        let start = self.frames.last().map_or(0, |frame| frame.scopes);
+        self.scopes[start..]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+
+    /// The value of a local, used where a value of type `expected` is.
+    fn read(
+        &mut self,
+        binding: Binding<'ast>,
+        expected: Option<Type<'ast>>,
+    ) -> Option<Value<'ast>> {
+        match binding {
+            Binding::Value(value) => Some(value),
+            Binding::Deferred(init, seen) => {
+                let start = self.frames.last().map_or(0, |frame| frame.scopes);
+                let scopes = self.scopes.split_off(start);
+                self.scopes.extend(seen);
+                let value = self.eval_checked(init, expected);
+                self.scopes.truncate(start);
+                self.scopes.extend(scopes);
+                value
+            }
+        }
+    }
+
+    /// `{ let x = 1; x + 1 }`, whose locals end with it.
+    fn block(&mut self, block: &'ast Block, expected: Option<Type<'ast>>) -> Option<Value<'ast>> {
+        let scopes = self.scopes.len();
+        self.scopes.push(Scope::new());
+        let value = self.stmts(block, expected);
+        self.scopes.truncate(scopes);
+        value
+    }
+
+    fn stmts(&mut self, block: &'ast Block, expected: Option<Type<'ast>>) -> Option<Value<'ast>> {
+        for stmt in &block.stmts {
+            match &stmt.kind {
+                StmtKind::Let(local) => self.local_stmt(local)?,
+                StmtKind::Expr(expr)
+                    if block.tail().is_some_and(|tail| std::ptr::eq(tail, expr)) =>
+                {
+                    return self.eval_checked(expr, expected)
+                }
+                StmtKind::Expr(expr) | StmtKind::Semi(expr) => {
+                    self.eval(expr, None)?;
+                }
+            }
+        }
+        Some(Value::Tuple(vec![]))
+    }
+
+    /// `let pattern: Ty = init;`, or `let x = init;` whose type is told by its uses.
+    fn local_stmt(&mut self, local: &'ast Local) -> Option<()> {
+        let init = local.init.as_ref()?;
+        let expected = match &local.ty {
+            Some(ty) => Some(self.resolve_ty(ty)?),
+            None => self.type_hint(init),
+        };
+        if let (
+            None,
+            PatKind::Binding {
+                name, sub: None, ..
+            },
+        ) = (&expected, &local.pat.kind)
+        {
+            let start = self.frames.last().map_or(0, |frame| frame.scopes);
+            let seen = self.scopes[start..].to_vec();
+            let deferred = Binding::Deferred(init, seen);
+            self.scopes.last_mut()?.insert(&name.name, deferred);
+            return Some(());
+        }
+        let value = self.eval_checked(init, expected)?;
+        if self.matches(&local.pat, &value)? {
+            return Some(());
+        }
+        // The `else` block diverges.
+        self.block(local.els.as_ref()?, None)?;
+        None
+    }
+
+    fn match_expr(
+        &mut self,
+        scrutinee: &'ast Expr,
+        arms: &'ast [Arm],
+        expected: Option<Type<'ast>>,
+    ) -> Option<Value<'ast>> {
+        let hint = self.type_hint(scrutinee);
+        let value = self.eval(scrutinee, hint)?;
+        for arm in arms {
+            let scopes = self.scopes.len();
+            self.scopes.push(Scope::new());
+            let result = self.arm(arm, &value, expected.clone());
+            self.scopes.truncate(scopes);
+            if let Some(value) = result? {
+                return Some(value);
+            }
+        }
+        // A match without an arm for the value is reported by the exhaustiveness check.
+        None
+    }
+
+    /// The value of the arm, `Some(None)` when it does not match.
+    fn arm(
+        &mut self,
+        arm: &'ast Arm,
+        value: &Value<'ast>,
+        expected: Option<Type<'ast>>,
+    ) -> Option<Option<Value<'ast>>> {
+        if !self.matches(&arm.pat, value)? {
+            return Some(None);
+        }
+        if let Some(guard) = &arm.guard {
+            let Value::Bool(guard) = self.eval_checked(guard, Some(Type::Bool))? else {
+                unreachable!("checked against `bool`")
+            };
+            if !guard {
+                return Some(None);
+            }
+        }
+        self.eval_checked(&arm.body, expected).map(Some)
+    }
+
+    /// Whether `value` matches `pattern`, whose bindings go in the innermost scope.
+    /// `None` when it cannot be told.
+    fn matches(&mut self, pattern: &'ast Pattern, value: &Value<'ast>) -> Option<bool> {
+        match (&pattern.kind, value) {
+            (PatKind::Wild | PatKind::Rest, _) => Some(true),
+            (PatKind::Binding { name, sub, .. }, _) => {
+                if let Some(sub) = sub {
+                    if !self.matches(sub, value)? {
+                        return Some(false);
+                    }
+                }
+                let binding = Binding::Value(value.clone());
+                self.scopes.last_mut()?.insert(&name.name, binding);
+                Some(true)
+            }
+            (PatKind::Lit(lit), _) => Some(self.eval(lit, Some(value.ty()))? == *value),
+            (PatKind::Range { lo, hi, inclusive }, _) => {
+                if let Some(lo) = lo {
+                    let lo = self.eval(lo, Some(value.ty()))?;
+                    if order(value, &lo)?.is_lt() {
+                        return Some(false);
+                    }
+                }
+                if let Some(hi) = hi {
+                    let hi = self.eval(hi, Some(value.ty()))?;
+                    let ordering = order(value, &hi)?;
+                    if ordering.is_gt() || (ordering.is_eq() && !inclusive) {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            (PatKind::Tuple(patterns), Value::Tuple(values)) => self.matches_all(patterns, values),
+            (PatKind::Paren(inner), _) => self.matches(inner, value),
+            (PatKind::TupleStruct(_, patterns), Value::Adt { fields, .. }) => {
+                if !self.is_ctor(pattern.id, value)? {
+                    return Some(false);
+                }
+                let Fields::Tuple(values) = fields else {
+                    return None;
+                };
+                self.matches_all(patterns, values)
+            }
+            (PatKind::Struct { fields, .. }, Value::Adt { fields: values, .. }) => {
+                if !self.is_ctor(pattern.id, value)? {
+                    return Some(false);
+                }
+                let Fields::Named(values) = values else {
+                    return None;
+                };
+                for field in fields {
+                    let (_, value) = values.iter().find(|(name, _)| *name == field.name.name)?;
+                    if !self.matches(&field.pat, value)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            (PatKind::Path(_), _) => match self.constant(pattern.id) {
+                Some(decl) => Some(self.value_of(decl, pattern.span)? == *value),
+                None => self.is_ctor(pattern.id, value),
+            },
+            (PatKind::Or(alternatives), _) => {
+                for alternative in alternatives {
+                    if self.matches(alternative, value)? {
+                        return Some(true);
+                    }
+                }
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+
+    /// The elements of a tuple, or the fields of a tuple variant, against their
+    /// patterns, one of which may be `..`.
+    fn matches_all(&mut self, patterns: &'ast [Pattern], values: &[Value<'ast>]) -> Option<bool> {
+        let rest = patterns
+            .iter()
+            .position(|pattern| matches!(pattern.kind, PatKind::Rest));
+        let (before, after) = match rest {
+            Some(rest) => (&patterns[..rest], &patterns[rest + 1..]),
+            None if patterns.len() == values.len() => (patterns, &[][..]),
+            None => return None,
+        };
+        if before.len() + after.len() > values.len() {
+            return None;
+        }
+        let pairs = before
+            .iter()
+            .zip(values)
+            .chain(after.iter().zip(&values[values.len() - after.len()..]));
+        for (pattern, value) in pairs {
+            if !self.matches(pattern, value)? {
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    /// Whether `value` was built by the struct or the variant the path of the pattern
+    /// `id` names.
+    fn is_ctor(&self, id: NodeId, value: &Value) -> Option<bool> {
+        let Value::Adt {
+            ty, path: built, ..
+        } = value
+        else {
+            return None;
+        };
+        match self.variant(id) {
+            Some((item, variant)) => {
+                let built = built.rsplit("::").next().unwrap_or(built);
+                Some(item.name.name == *ty && variant.name.name == built)
+            }
+            None => Some(self.structure(id)?.name.name == *ty),
+        }
+    }
+}
+
+/// How a value compares to the bound of a range pattern.
+fn order(value: &Value, bound: &Value) -> Option<Ordering> {
+    match (value, bound) {
+        (Value::Int(value, _), Value::Int(bound, _)) => Some(value.cmp(bound)),
+        (Value::Char(value), Value::Char(bound)) => Some(value.cmp(bound)),
+        (Value::Float(value, _), Value::Float(bound, _)) => value.partial_cmp(bound),
+        _ => None,
+    }
+}
+
+fn is_logical(op: BinOp) -> bool {
+    matches!(op, BinOp::And | BinOp::Or)
+}
+
+/// Computations on `f32` are done on `f64` and rounded after each step.
+fn round(value: f64, name: &str) -> f64 {
+    if name == "f32" {
+        value as f32 as f64
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_consts;
+    use crate::semantic::resolve::resolve_names;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        let (module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let resolutions = resolve_names(&module, &mut vec![]);
+        let mut diagnostics = vec![];
+        check_consts(&module, &resolutions, &mut diagnostics);
+        diagnostics
+            .into_iter()
+            .map(|d| match d.notes.first() {
+                Some(note) => format!("{}: {note}", d.message),
+                None => d.message,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn evaluates_valid_initializers() {
+        let messages = messages(
+            "type Meters = u32;
+            struct Point { x: i32, y: i32 }
+            enum Shape { Circle(f64), Empty }
+            const KB: Meters = 1024;
+            const MB: Meters = KB * KB;
+            const LOW: i8 = -128;
+            const MIDDLE: i64 = (i64::MAX - 1) / 2 + 1;
+            const HALF: f32 = 1.0 / 3.0;
+            const BIG: bool = MB > 1000 && !(LOW == 0);
+            const ORIGIN: Point = Point { x: 0, y: -1 };
+            static SHAPES: Shape = Shape::Circle(2.5);
+            static NAME: &str = \"oxy\";
+            static COPY: Shape = SHAPES;",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_invalid_initializers() {
+        let messages = messages(
+            "fn size() -> u8 { 1 }
+            static COUNT: u8 = 3;
+            const A: u8 = 200 + 100;
+            const C: i32 = 10 % (3 - 3);
+            const D: u8 = 256;
+            const E: u8 = -1;
+            const F: bool = 1;
+            const G: u8 = size();
+            const H: u8 = COUNT;
+            const I: i32 = J + 1;
+            const J: i32 = I * 2;
+            const K: u16 = 5;
+            const L: i64 = K;",
+        );
+        assert_eq!(
+            messages,
+            [
+                "attempt to compute `200_u8 + 100_u8`, which would overflow",
+                "attempt to calculate the remainder of `10_i32` with a divisor of zero",
+                "literal out of range for `u8`: the literal `256` does not fit into the type `u8` whose range is `0..=255`",
+                "cannot apply unary operator `-` to type `u8`: unsigned values cannot be negated",
+                "mismatched types: expected `bool`, found `{integer}`",
+                "cannot call non-const function `size` in constants: calls in constants are limited to constant functions, tuple structs and tuple variants",
+                "constants cannot refer to statics: consider extracting the value of the `static` to a `const`, and referring to that",
+                "cycle detected when evaluating constant `I`: ...which requires evaluating constant `J`...",
+                "mismatched types: expected `i64`, found `u16`",
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluates_constants_through_their_paths() {
+        let messages = messages(
+            "mod m { pub const X: i32 = 2147483647; }
+            const Y: i32 = m::X + 1;
+            mod n { pub const X: i32 = i32::MAX; pub const Z: i32 = X + 1; }
+            const X: i32 = 1;
+            const W: i32 = n::X + X;",
+        );
+        assert_eq!(
+            messages,
+            [
+                "attempt to compute `2147483647_i32 + 1_i32`, which would overflow",
+                "attempt to compute `2147483647_i32 + 1_i32`, which would overflow",
+                "attempt to compute `2147483647_i32 + 1_i32`, which would overflow",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_cycles_of_type_aliases() {
+        let messages = messages(
+            "type A = A;
+            type B = C;
+            type C = (u8, Vec<B>);
+            type D = B;
+            type E = u8;
+            const X: D = 1;
+            const Y: E = 1;",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cycle detected when expanding type alias `A`: ...which again requires expanding type alias `A`, completing the cycle",
+                "cycle detected when expanding type alias `B`: ...which requires expanding type alias `C`...",
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluates_calls_to_const_fn() {
+        let messages = messages(
+            "struct Point { x: i32, y: i32 }
+            enum Shape { Circle(u32), Square { side: u32 }, Empty }
+            impl Point {
+                const fn new(x: i32) -> Point { Point { x, y: 0 } }
+            }
+            const fn area(shape: Shape) -> u32 {
+                match shape {
+                    Shape::Circle(r) if r > 100 => 0,
+                    Shape::Circle(r) => 3 * r * r,
+                    Shape::Square { side } => side * side,
+                    Shape::Empty => 0,
+                }
+            }
+            const fn fact(n: u64) -> u64 {
+                if n == 0 { return 1; }
+                let m = n - 1;
+                n * fact(m)
+            }
+            const fn half(n: u8) -> u8 {
+                let two = 2;
+                n / two
+            }
+            const X: i32 = Point::new(3).x;
+            const A: u32 = area(Shape::Circle(3)) + area(Shape::Square { side: 4 });
+            const F: u64 = fact(20);
+            const H: u8 = half(200);
+            const N: usize = 2;
+            const fn first(xs: [u8; N]) -> u8 { match xs[0] { 0..=9 => 1, _ => 2 } }
+            const P: u8 = first([3, 4]);",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_errors_inside_calls() {
+        let (module, errors) = parse(
+            "fn size() -> u8 { 1 }
+            const fn div(a: i32, b: i32) -> i32 { a / b }
+            const fn ratio(a: i32) -> i32 { div(a, a - 5) }
+            const fn forever(n: u64) -> u64 { forever(n + 1) }
+            const fn sized() -> u8 { size() }
+            const R: i32 = ratio(5);
+            const Z: u64 = forever(0);
+            const S: u8 = sized();",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let resolutions = resolve_names(&module, &mut vec![]);
+        let mut diagnostics = vec![];
+        check_consts(&module, &resolutions, &mut diagnostics);
+        let traces: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let labels = diagnostic.labels.iter().map(|(_, label)| label.as_str());
+                (diagnostic.message.as_str(), labels.collect::<Vec<_>>())
+            })
+            .collect();
+        assert_eq!(
+            traces,
+            [
+                (
+                    "attempt to divide `5_i32` by zero",
+                    vec!["inside this call to `div`", "inside this call to `ratio`"]
+                ),
+                (
+                    "reached the maximum number of nested calls",
+                    vec![
+                        "inside this call to `forever`, 63 times",
+                        "inside this call to `forever`"
+                    ]
+                ),
+                (
+                    "cannot call non-const function `size` in constant functions",
+                    vec!["inside this call to `sized`"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluates_tuples_arrays_and_lengths() {
+        let messages = messages(
+            "const N: usize = 3;
+            const PRIMES: [u32; N] = [2, 3, 5];
+            const ZEROS: [u8; 4] = [0; 4];
+            const PAIR: (i32, (bool, char)) = (-1, (true, 'a'));
+            const SECOND: u32 = PRIMES[1] + PRIMES[N - 1];
+            const FLAG: bool = PAIR.1.0;
+            const SHORT: [u32; 2] = [1, 2, 3];
+            const OUT: u32 = PRIMES[N];
+            const MISSING: i32 = PAIR.2;
+            const MIXED: [u8; 2] = [1, true];
+            const WRONG: (i32, bool) = (1, 2);
+            const COUNT: i32 = 2;
+            fn f(xs: [i32; COUNT]) {
+                let ys = [0; -1];
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "mismatched types: expected `usize`, found `i32`",
+                "cannot apply unary operator `-` to type `usize`: unsigned values cannot be negated",
+                "mismatched types: expected an array with a size of 2, found one with a size of 3",
+                "index out of bounds: the length is 3 but the index is 3",
+                "no field `2` on type `(i32, (bool, char))`",
+                "mismatched types: expected `u8`, found `bool`",
+                "mismatched types: expected `bool`, found `{integer}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_logical_operators_on_other_types() {
+        let messages = messages(
+            "const A: i32 = 1;
+            const X: bool = A && true;
+            const Y: bool = (1 + 2) || true;
+            const fn f(x: i32) -> bool { x && true }
+            const Z: bool = f(1);",
+        );
+        // The body of `f` is checked by `typeck`.
+        assert_eq!(
+            messages,
+            [
+                "mismatched types: expected `bool`, found `i32`",
+                "mismatched types: expected `bool`, found `i32`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,929 @@
+//! Checks that the arms of each `match` cover every value of the matched type, and
+//! warns about the arms no value reaches.
+//!
+//! This is the usefulness algorithm rustc uses, from "Warnings for pattern matching"
+//! (Maranget, 2007) : a pattern is useful after some rows when a value matches it and
+//! none of the rows. An arm nothing reaches is not useful after the arms above it,
+//! and a match is exhaustive when `_` is not useful after all its arms.
+//!
+//! Patterns are lowered to the constructors of the types [`typeck`](super::typeck)
+//! found for them : the variants of an enum, the only constructor of a struct or of
+//! a tuple, and ranges of integers, of `char`s and of `bool`s. Strings, floats and
+//! constants are only covered by a wildcard. The arms with a guard cover nothing. A
+//! match with patterns of unknown types is left to rustc.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::consts::IntTy;
+use super::prelude;
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+/// The number of patterns not covered listed in a message, before "and 2 more".
+const LISTED: usize = 3;
+
+pub fn check_exhaustiveness(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut checker = Checker {
+        patterns: Patterns::new(module, resolutions, types),
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+#[derive(Default)]
+struct Items<'ast> {
+    structs: HashMap<NodeId, &'ast Struct>,
+    enums: HashMap<NodeId, &'ast Enum>,
+}
+
+impl<'ast> Visitor<'ast> for Items<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Struct(def) => {
+                self.structs.insert(item.id, def);
+            }
+            ItemKind::Enum(def) => {
+                self.enums.insert(item.id, def);
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+/// The values of a type, as constructors.
+#[derive(Debug)]
+enum Domain {
+    /// The variants of an enum, with the path and the fields of each.
+    Enum(Vec<(String, Fields)>),
+    /// A struct, with its name and the names of its fields.
+    Struct(String, Vec<String>),
+    /// A tuple, with its length.
+    Tuple(usize),
+    /// Values numbered from one to another : integers, `char`s and `bool`s.
+    Values(Scalar),
+    /// Strings, floats, and the types which are not known.
+    Infinite,
+}
+
+#[derive(Debug)]
+enum Fields {
+    Unit,
+    Tuple(usize),
+    Named(Vec<String>),
+}
+
+impl Fields {
+    fn of(data: &VariantData) -> Fields {
+        match data {
+            VariantData::Unit => Fields::Unit,
+            VariantData::Tuple(tys) => Fields::Tuple(tys.len()),
+            VariantData::Struct(fields) => Fields::Named(names(fields)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Fields::Unit => 0,
+            Fields::Tuple(len) => *len,
+            Fields::Named(names) => names.len(),
+        }
+    }
+}
+
+fn names(fields: &[FieldDef]) -> Vec<String> {
+    fields.iter().map(|field| field.name.name.clone()).collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    Bool,
+    Char,
+    Int(IntTy),
+}
+
+impl Scalar {
+    /// The values of the type, as disjoint ranges.
+    fn ranges(self) -> Vec<(i128, i128)> {
+        match self {
+            Scalar::Bool => vec![(0, 1)],
+            // Without the surrogates, which are not `char`s.
+            Scalar::Char => vec![(0, 0xD7FF), (0xE000, 0x10FFFF)],
+            Scalar::Int(ty) => vec![(ty.min(), ty.max())],
+        }
+    }
+
+    fn value(self, value: i128) -> String {
+        match self {
+            Scalar::Bool => (value != 0).to_string(),
+            Scalar::Char => {
+                let c = u32::try_from(value).ok().and_then(char::from_u32);
+                format!("{:?}", c.unwrap_or_default())
+            }
+            Scalar::Int(ty) if value == ty.min() && ty.signed => format!("{}::MIN", ty.name),
+            Scalar::Int(ty) if value == ty.max() => format!("{}::MAX", ty.name),
+            Scalar::Int(_) => value.to_string(),
+        }
+    }
+}
+
+impl Domain {
+    fn arity(&self, ctor: &Ctor) -> usize {
+        match (self, ctor) {
+            (Domain::Enum(variants), Ctor::Variant(index)) => variants[*index].1.len(),
+            (Domain::Struct(_, fields), Ctor::Single) => fields.len(),
+            (Domain::Tuple(len), Ctor::Single) => *len,
+            _ => 0,
+        }
+    }
+
+    /// Every constructor of the type, the ranges split so that each of `heads`
+    /// covers each of them entirely or not at all. `None` when there are too many.
+    fn ctors(&self, heads: &[&Ctor]) -> Option<Vec<Ctor>> {
+        Some(match self {
+            Domain::Enum(variants) => (0..variants.len()).map(Ctor::Variant).collect(),
+            Domain::Struct(..) | Domain::Tuple(_) => vec![Ctor::Single],
+            Domain::Values(scalar) => split(&scalar.ranges(), heads),
+            Domain::Infinite => return None,
+        })
+    }
+
+    /// Whether `ctor` is one of the constructors of the type.
+    fn accepts(&self, ctor: &Ctor) -> bool {
+        match (self, ctor) {
+            (Domain::Enum(variants), Ctor::Variant(index)) => *index < variants.len(),
+            (Domain::Struct(..) | Domain::Tuple(_), Ctor::Single) => true,
+            (Domain::Values(_) | Domain::Infinite, Ctor::Range(..)) => true,
+            (Domain::Infinite, Ctor::Str(_)) | (_, Ctor::Opaque) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(usize),
+    /// The only constructor of a struct or a tuple.
+    Single,
+    /// The values from one to another, both included.
+    Range(i128, i128),
+    Str(String),
+    /// A float or a constant, only covered by a wildcard.
+    Opaque,
+}
+
+/// Whether every value `ctor` builds is built by `head`.
+fn covers(head: &Ctor, ctor: &Ctor) -> bool {
+    match (head, ctor) {
+        (Ctor::Range(lo, hi), Ctor::Range(start, end)) => lo <= start && end <= hi,
+        (Ctor::Opaque, _) => false,
+        _ => head == ctor,
+    }
+}
+
+/// Cuts `ranges` at the bounds of the ranges of `heads`.
+fn split(ranges: &[(i128, i128)], heads: &[&Ctor]) -> Vec<Ctor> {
+    let mut cuts: Vec<i128> = heads
+        .iter()
+        .flat_map(|head| match head {
+            Ctor::Range(lo, hi) => vec![*lo, hi.saturating_add(1)],
+            _ => vec![],
+        })
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    let mut pieces = vec![];
+    for &(lo, hi) in ranges {
+        let mut start = lo;
+        for &cut in &cuts {
+            if cut > start && cut <= hi {
+                pieces.push(Ctor::Range(start, cut - 1));
+                start = cut;
+            }
+        }
+        pieces.push(Ctor::Range(start, hi));
+    }
+    pieces
+}
+
+/// A pattern, as constructors applied to patterns.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Rc<Domain>, Vec<Pat>),
+    Or(Vec<Pat>),
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (ctor, domain, fields) = match self {
+            Pat::Wild => return f.write_str("_"),
+            Pat::Or(alternatives) => return list(f, alternatives, " | "),
+            Pat::Ctor(ctor, domain, fields) => (ctor, &**domain, fields),
+        };
+        match (domain, ctor) {
+            (Domain::Enum(variants), Ctor::Variant(index)) => {
+                let (path, shape) = &variants[*index];
+                f.write_str(path)?;
+                match shape {
+                    Fields::Unit => Ok(()),
+                    Fields::Tuple(_) => {
+                        f.write_str("(")?;
+                        list(f, fields, ", ")?;
+                        f.write_str(")")
+                    }
+                    Fields::Named(names) => named(f, names, fields),
+                }
+            }
+            (Domain::Struct(name, names), _) if names.is_empty() => f.write_str(name),
+            (Domain::Struct(name, names), _) => {
+                f.write_str(name)?;
+                named(f, names, fields)
+            }
+            (Domain::Tuple(_), _) => {
+                f.write_str("(")?;
+                list(f, fields, ", ")?;
+                f.write_str(if fields.len() == 1 { ",)" } else { ")" })
+            }
+            (Domain::Values(scalar), Ctor::Range(lo, hi)) if lo == hi => {
+                f.write_str(&scalar.value(*lo))
+            }
+            (Domain::Values(scalar), Ctor::Range(lo, hi)) => {
+                write!(f, "{}..={}", scalar.value(*lo), scalar.value(*hi))
+            }
+            _ => f.write_str("_"),
+        }
+    }
+}
+
+fn list(f: &mut fmt::Formatter, patterns: &[Pat], separator: &str) -> fmt::Result {
+    for (i, pattern) in patterns.iter().enumerate() {
+        if i > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{pattern}")?;
+    }
+    Ok(())
+}
+
+/// `{ x: 0, .. }`, leaving out the fields any value matches.
+fn named(f: &mut fmt::Formatter, names: &[String], fields: &[Pat]) -> fmt::Result {
+    let shown: Vec<_> = names
+        .iter()
+        .zip(fields)
+        .filter(|(_, field)| !matches!(field, Pat::Wild))
+        .map(|(name, field)| format!("{name}: {field}"))
+        .collect();
+    match (shown.is_empty(), shown.len() == names.len()) {
+        (true, true) => f.write_str(" {}"),
+        (true, false) => f.write_str(" { .. }"),
+        (false, true) => write!(f, " {{ {} }}", shown.join(", ")),
+        (false, false) => write!(f, " {{ {}, .. }}", shown.join(", ")),
+    }
+}
+
+type Row = Vec<Pat>;
+
+/// The rows, with those starting with an or-pattern replaced by one row for each
+/// of its alternatives.
+fn expand(matrix: &[Row]) -> Vec<Row> {
+    fn expand_row(row: Row, out: &mut Vec<Row>) {
+        match row.first() {
+            Some(Pat::Or(alternatives)) => {
+                for alternative in alternatives {
+                    let mut row = row.clone();
+                    row[0] = alternative.clone();
+                    expand_row(row, out);
+                }
+            }
+            _ => out.push(row),
+        }
+    }
+    let mut rows = vec![];
+    for row in matrix {
+        expand_row(row.clone(), &mut rows);
+    }
+    rows
+}
+
+/// The constructors the rows start with, and the type they build.
+fn column(matrix: &[Row]) -> (Vec<&Ctor>, Option<&Rc<Domain>>) {
+    let mut heads = vec![];
+    let mut domain = None;
+    for row in matrix {
+        if let Pat::Ctor(ctor, of, _) = &row[0] {
+            heads.push(ctor);
+            domain.get_or_insert(of);
+        }
+    }
+    (heads, domain)
+}
+
+/// The rows matching what `ctor` builds, with their first pattern replaced by the
+/// `arity` patterns of its fields.
+fn specialize(matrix: &[Row], ctor: &Ctor, arity: usize) -> Vec<Row> {
+    matrix
+        .iter()
+        .filter_map(|row| {
+            let mut fields = match &row[0] {
+                Pat::Ctor(head, _, fields) if covers(head, ctor) => fields.clone(),
+                Pat::Ctor(..) | Pat::Or(_) => return None,
+                Pat::Wild => vec![Pat::Wild; arity],
+            };
+            fields.extend_from_slice(&row[1..]);
+            Some(fields)
+        })
+        .collect()
+}
+
+/// The rows starting with a wildcard, without it.
+fn default(matrix: &[Row]) -> Vec<Row> {
+    matrix
+        .iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Whether some value matches `row` and none of the rows of `matrix`.
+fn useful(matrix: &[Row], row: &[Pat]) -> bool {
+    let Some((head, rest)) = row.split_first() else {
+        return matrix.is_empty();
+    };
+    let matrix = expand(matrix);
+    let (heads, domain) = column(&matrix);
+    let with = |ctor: &Ctor, fields: Vec<Pat>| {
+        let specialized = specialize(&matrix, ctor, fields.len());
+        let mut row = fields;
+        row.extend_from_slice(rest);
+        useful(&specialized, &row)
+    };
+    match head {
+        Pat::Or(alternatives) => alternatives.iter().any(|alternative| {
+            let mut row = vec![alternative.clone()];
+            row.extend_from_slice(rest);
+            useful(&matrix, &row)
+        }),
+        Pat::Ctor(Ctor::Range(lo, hi), ..) => split(&[(*lo, *hi)], &heads)
+            .iter()
+            .any(|ctor| with(ctor, vec![])),
+        Pat::Ctor(ctor, _, fields) => with(ctor, fields.clone()),
+        Pat::Wild => {
+            let complete = domain.and_then(|domain| {
+                let ctors = domain.ctors(&heads)?;
+                let covered = |ctor: &Ctor| heads.iter().any(|head| covers(head, ctor));
+                ctors.iter().all(covered).then_some((domain, ctors))
+            });
+            match complete {
+                Some((domain, ctors)) => ctors
+                    .iter()
+                    .any(|ctor| with(ctor, vec![Pat::Wild; domain.arity(ctor)])),
+                None => useful(&default(&matrix), rest),
+            }
+        }
+    }
+}
+
+/// The values of `columns` patterns no row of `matrix` matches, as patterns.
+fn witnesses(matrix: &[Row], columns: usize) -> Vec<Row> {
+    if columns == 0 {
+        return match matrix.is_empty() {
+            true => vec![vec![]],
+            false => vec![],
+        };
+    }
+    let matrix = expand(matrix);
+    let (heads, domain) = column(&matrix);
+    let ctors = domain.and_then(|domain| domain.ctors(&heads));
+    let missing: Vec<_> = ctors
+        .iter()
+        .flatten()
+        .filter(|ctor| !heads.iter().any(|head| covers(head, ctor)))
+        .cloned()
+        .collect();
+    if let (Some(domain), Some(ctors), true) = (domain, &ctors, missing.is_empty()) {
+        let mut found = vec![];
+        for ctor in ctors {
+            let arity = domain.arity(ctor);
+            for mut witness in witnesses(&specialize(&matrix, ctor, arity), arity + columns - 1) {
+                let rest = witness.split_off(arity);
+                let mut row = vec![Pat::Ctor(ctor.clone(), domain.clone(), witness)];
+                row.extend(rest);
+                found.push(row);
+            }
+        }
+        return found;
+    }
+    let rest = witnesses(&default(&matrix), columns - 1);
+    if rest.is_empty() {
+        return rest;
+    }
+    let firsts: Vec<Pat> = match domain {
+        Some(domain) if !heads.is_empty() && !missing.is_empty() => merge(domain, missing)
+            .into_iter()
+            .map(|ctor| {
+                let fields = vec![Pat::Wild; domain.arity(&ctor)];
+                Pat::Ctor(ctor, domain.clone(), fields)
+            })
+            .collect(),
+        _ => vec![Pat::Wild],
+    };
+    let mut found = vec![];
+    for first in firsts {
+        for rest in &rest {
+            let mut row = vec![first.clone()];
+            row.extend_from_slice(rest);
+            found.push(row);
+        }
+    }
+    found
+}
+
+/// Joins the consecutive ranges of integers and of `char`s.
+fn merge(domain: &Domain, ctors: Vec<Ctor>) -> Vec<Ctor> {
+    if !matches!(domain, Domain::Values(Scalar::Int(_) | Scalar::Char)) {
+        return ctors;
+    }
+    let mut merged: Vec<Ctor> = vec![];
+    for ctor in ctors {
+        if let (Some(Ctor::Range(_, end)), Ctor::Range(lo, hi)) = (merged.last_mut(), &ctor) {
+            if end.checked_add(1) == Some(*lo) {
+                *end = *hi;
+                continue;
+            }
+        }
+        merged.push(ctor);
+    }
+    merged
+}
+
+/// Lowers the patterns of a crate to constructors.
+pub struct Patterns<'ast, 'r> {
+    items: Items<'ast>,
+    resolutions: &'r Resolutions,
+    types: &'r TypeckResults,
+}
+
+struct Checker<'ast, 'r, 'd> {
+    patterns: Patterns<'ast, 'r>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let ExprKind::Match(scrutinee, arms) = &expr.kind {
+            self.check_match(scrutinee, arms);
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl Checker<'_, '_, '_> {
+    fn check_match(&mut self, scrutinee: &Expr, arms: &[Arm]) {
+        let Some(patterns) = arms
+            .iter()
+            .map(|arm| self.patterns.lower(&arm.pat))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+        if arms.is_empty() {
+            return self.check_empty(scrutinee);
+        }
+        let mut matrix: Vec<Row> = vec![];
+        // The first arm matching any value, which the arms below are reported against.
+        let mut catchall = None;
+        for (arm, pattern) in arms.iter().zip(patterns) {
+            match (&arm.pat.kind, &pattern) {
+                (PatKind::Or(alternatives), Pat::Or(lowered)) => {
+                    let mut rows = matrix.clone();
+                    let mut unreachable = vec![];
+                    for (alternative, lowered) in alternatives.iter().zip(lowered) {
+                        if !useful(&rows, std::slice::from_ref(lowered)) {
+                            unreachable.push(alternative.span);
+                        }
+                        rows.push(vec![lowered.clone()]);
+                    }
+                    if unreachable.len() == alternatives.len() {
+                        self.unreachable(arm.pat.span, catchall);
+                    } else {
+                        for span in unreachable {
+                            self.unreachable(span, None);
+                        }
+                    }
+                }
+                _ if !useful(&matrix, std::slice::from_ref(&pattern)) => {
+                    self.unreachable(arm.pat.span, catchall);
+                }
+                _ => {}
+            }
+            if arm.guard.is_none() {
+                if matches!(pattern, Pat::Wild) {
+                    catchall.get_or_insert(arm.pat.span);
+                }
+                matrix.push(vec![pattern]);
+            }
+        }
+        let missing: Vec<Pat> = witnesses(&matrix, 1)
+            .into_iter()
+            .filter_map(|mut witness| witness.pop())
+            .collect();
+        if !missing.is_empty() {
+            self.non_exhaustive(scrutinee, &missing);
+        }
+    }
+
+    /// A `match` without arms is only exhaustive for an enum without variants.
+    fn check_empty(&mut self, scrutinee: &Expr) {
+        let Some(ty) = self.patterns.types.expr(scrutinee.id) else {
+            return;
+        };
+        if matches!(ty, Type::Unknown | Type::Var(_) | Type::Never) {
+            return;
+        }
+        let domain = Rc::new(self.patterns.domain(Some(ty)));
+        let Domain::Enum(variants) = &*domain else {
+            let message = format!("non-exhaustive patterns: type `{ty}` is non-empty");
+            self.diagnostics.push(
+                Diagnostic::error(message, scrutinee.span)
+                    .with_note("add an arm for each possible value, or a wildcard arm"),
+            );
+            return;
+        };
+        let missing: Vec<_> = (0..variants.len())
+            .map(|index| {
+                let ctor = Ctor::Variant(index);
+                let fields = vec![Pat::Wild; domain.arity(&ctor)];
+                Pat::Ctor(ctor, domain.clone(), fields)
+            })
+            .collect();
+        if !missing.is_empty() {
+            self.non_exhaustive(scrutinee, &missing);
+        }
+    }
+
+    fn non_exhaustive(&mut self, scrutinee: &Expr, missing: &[Pat]) {
+        let mut shown: Vec<String> = vec![];
+        for pattern in missing {
+            let pattern = format!("`{pattern}`");
+            if !shown.contains(&pattern) {
+                shown.push(pattern);
+            }
+        }
+        let count = shown.len();
+        let listed = match count {
+            1 => shown.remove(0),
+            _ if count <= LISTED => {
+                let last = shown.pop().unwrap_or_default();
+                format!("{} and {last}", shown.join(", "))
+            }
+            _ => format!("{} and {} more", shown[..LISTED].join(", "), count - LISTED),
+        };
+        let mut diagnostic = Diagnostic::error(
+            format!("non-exhaustive patterns: {listed} not covered"),
+            scrutinee.span,
+        );
+        if let Some(ty) = self.patterns.types.expr(scrutinee.id) {
+            diagnostic = diagnostic.with_note(format!("the matched value is of type `{ty}`"));
+        }
+        let them = if count == 1 { "it" } else { "them" };
+        self.diagnostics.push(diagnostic.with_note(format!(
+            "add an arm for {them}, or a wildcard arm `_ => ...`"
+        )));
+    }
+
+    fn unreachable(&mut self, span: Span, catchall: Option<Span>) {
+        let mut diagnostic = Diagnostic::warning("unreachable pattern", span);
+        if let Some(catchall) = catchall {
+            diagnostic = diagnostic.with_label(catchall, "matches any value");
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+impl<'ast, 'r> Patterns<'ast, 'r> {
+    pub fn new(
+        module: &'ast Module,
+        resolutions: &'r Resolutions,
+        types: &'r TypeckResults,
+    ) -> Self {
+        let mut items = Items::default();
+        items.visit_module(module);
+        Self {
+            items,
+            resolutions,
+            types,
+        }
+    }
+
+    /// Whether some value of its type does not match `pattern`, as for a `let`. A
+    /// pattern of a type which is not known, or which does not fit its type, is left
+    /// to rustc.
+    pub fn is_refutable(&self, pattern: &Pattern) -> bool {
+        self.lower(pattern)
+            .is_some_and(|pattern| useful(&[vec![pattern]], &[Pat::Wild]))
+    }
+
+    /// The constructors of the type matched by a pattern.
+    fn domain(&self, ty: Option<&Type>) -> Domain {
+        let mut ty = ty;
+        while let Some(Type::Ref(_, inner)) = ty {
+            ty = Some(inner);
+        }
+        match ty {
+            Some(Type::Adt(id, name, _)) => {
+                if let Some(def) = self.items.enums.get(id) {
+                    let variants = def.variants.iter().map(|variant| {
+                        let path = format!("{name}::{}", variant.name.name);
+                        (path, Fields::of(&variant.data))
+                    });
+                    Domain::Enum(variants.collect())
+                } else if let Some(def) = self.items.structs.get(id) {
+                    Domain::Struct(name.clone(), names(&def.fields))
+                } else {
+                    Domain::Infinite
+                }
+            }
+            Some(Type::Named(name, _)) => match prelude::ENUMS.iter().find(|(ty, _)| ty == name) {
+                Some((_, variants)) => Domain::Enum(
+                    variants
+                        .iter()
+                        .map(|&variant| match variant {
+                            "None" => (variant.to_string(), Fields::Unit),
+                            _ => (variant.to_string(), Fields::Tuple(1)),
+                        })
+                        .collect(),
+                ),
+                None => Domain::Infinite,
+            },
+            Some(Type::Tuple(tys)) => Domain::Tuple(tys.len()),
+            Some(Type::Bool) => Domain::Values(Scalar::Bool),
+            Some(Type::Char) => Domain::Values(Scalar::Char),
+            // The values of `u128` above the largest `i128` are left out.
+            Some(Type::Int(name)) => match IntTy::from_name(name) {
+                Some(ty) => Domain::Values(Scalar::Int(ty)),
+                None => Domain::Infinite,
+            },
+            _ => Domain::Infinite,
+        }
+    }
+
+    /// The pattern as constructors, `None` when it does not fit the type found for it.
+    fn lower(&self, pattern: &Pattern) -> Option<Pat> {
+        let domain = self.domain(self.types.pattern(pattern.id));
+        let res = self.resolutions.get(pattern.id);
+        let (ctor, fields) = match &pattern.kind {
+            PatKind::Wild | PatKind::Rest => return Some(Pat::Wild),
+            PatKind::Error => return None,
+            PatKind::Binding { sub: None, .. } => return Some(Pat::Wild),
+            PatKind::Binding { sub: Some(sub), .. } => return self.lower(sub),
+            PatKind::Paren(inner) => return self.lower(inner),
+            PatKind::Or(alternatives) => {
+                let alternatives = alternatives
+                    .iter()
+                    .map(|alternative| self.lower(alternative));
+                return alternatives.collect::<Option<_>>().map(Pat::Or);
+            }
+            PatKind::Lit(expr) => (literal(expr)?, vec![]),
+            PatKind::Range { lo, hi, inclusive } => {
+                let bounds = match &domain {
+                    Domain::Values(scalar) => scalar.ranges(),
+                    _ => vec![],
+                };
+                let bound = |bound: &Option<Box<Expr>>, default: Option<i128>| match bound {
+                    Some(expr) => match literal(expr)? {
+                        Ctor::Range(value, _) => Some(value),
+                        _ => None,
+                    },
+                    None => default,
+                };
+                let start = bound(lo, bounds.first().map(|(lo, _)| *lo))?;
+                let mut end = bound(hi, bounds.last().map(|(_, hi)| *hi))?;
+                // `1..5` stops before its end, `1..` does not.
+                if !inclusive && hi.is_some() {
+                    end = end.checked_sub(1)?;
+                }
+                if start > end {
+                    return None;
+                }
+                (Ctor::Range(start, end), vec![])
+            }
+            PatKind::Tuple(patterns) => (Ctor::Single, self.positional(patterns, &domain, 0)?),
+            PatKind::TupleStruct(path, patterns) => {
+                let index = self.variant(res, path, &domain)?;
+                (
+                    Ctor::Variant(index),
+                    self.positional(patterns, &domain, index)?,
+                )
+            }
+            PatKind::Struct { path, fields, .. } => {
+                let (ctor, names) = match &domain {
+                    Domain::Struct(_, names) => (Ctor::Single, names),
+                    Domain::Enum(variants) => {
+                        let index = self.variant(res, path, &domain)?;
+                        match &variants[index].1 {
+                            Fields::Named(names) => (Ctor::Variant(index), names),
+                            _ => return None,
+                        }
+                    }
+                    _ => return None,
+                };
+                let lowered = names
+                    .iter()
+                    .map(
+                        |name| match fields.iter().find(|field| field.name.name == *name) {
+                            Some(field) => self.lower(&field.pat),
+                            None => Some(Pat::Wild),
+                        },
+                    )
+                    .collect::<Option<_>>()?;
+                (ctor, lowered)
+            }
+            PatKind::Path(path) => match res {
+                Some(Res::Item(id)) if self.items.structs.contains_key(&id) => {
+                    (Ctor::Single, vec![])
+                }
+                Some(Res::Item(_)) => (Ctor::Opaque, vec![]),
+                _ => (Ctor::Variant(self.variant(res, path, &domain)?), vec![]),
+            },
+        };
+        if !domain.accepts(&ctor) || domain.arity(&ctor) != fields.len() {
+            return None;
+        }
+        Some(Pat::Ctor(ctor, Rc::new(domain), fields))
+    }
+
+    /// The index of the variant a pattern names.
+    fn variant(&self, res: Option<Res>, path: &Path, domain: &Domain) -> Option<usize> {
+        match (res?, domain) {
+            (Res::Variant(_, index), _) => Some(index),
+            // `Some`, `None`, `Ok` and `Err`.
+            (Res::External, Domain::Enum(variants)) => variants
+                .iter()
+                .position(|(name, _)| *name == path.last().name),
+            _ => None,
+        }
+    }
+
+    /// The patterns of the fields of a tuple or a tuple variant, with `_` in place of
+    /// the fields a `..` skips.
+    fn positional(&self, patterns: &[Pattern], domain: &Domain, index: usize) -> Option<Vec<Pat>> {
+        let len = match domain {
+            Domain::Tuple(len) => *len,
+            Domain::Enum(variants) => variants.get(index)?.1.len(),
+            _ => return None,
+        };
+        let rest = patterns.iter().position(|p| p.kind == PatKind::Rest);
+        let count = patterns.len() - usize::from(rest.is_some());
+        if count > len || (rest.is_none() && count != len) {
+            return None;
+        }
+        let mut lowered = vec![];
+        for (i, pattern) in patterns.iter().enumerate() {
+            if Some(i) == rest {
+                lowered.extend(vec![Pat::Wild; len - count]);
+            } else {
+                lowered.push(self.lower(pattern)?);
+            }
+        }
+        Some(lowered)
+    }
+}
+
+/// The constructor of a literal pattern, `-1` included.
+fn literal(expr: &Expr) -> Option<Ctor> {
+    match &expr.kind {
+        ExprKind::Lit(lit) => Some(match &lit.kind {
+            LitKind::Int(value) => {
+                let value = i128::try_from(*value).unwrap_or(i128::MAX);
+                Ctor::Range(value, value)
+            }
+            LitKind::Char(c) => Ctor::Range(i128::from(u32::from(*c)), i128::from(u32::from(*c))),
+            LitKind::Bool(b) => Ctor::Range(i128::from(*b), i128::from(*b)),
+            LitKind::Str(text) => Ctor::Str(text.clone()),
+            LitKind::Float(_) => Ctor::Opaque,
+        }),
+        ExprKind::Unary(UnOp::Neg, inner) => match literal(inner)? {
+            Ctor::Range(value, _) => Some(Ctor::Range(-value, -value)),
+            ctor => Some(ctor),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_exhaustiveness;
+    use crate::semantic;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_exhaustiveness)
+    }
+
+    const SHAPE: &str = "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }";
+
+    #[test]
+    fn accepts_exhaustive_matches() {
+        let messages = messages(&format!(
+            "{SHAPE}
+            fn area(s: &Shape) -> f64 {{
+                match s {{
+                    Shape::Circle(r) => r * r,
+                    Shape::Rect {{ w, h }} => w * h,
+                    Shape::Empty => 0.0,
+                }}
+            }}
+            fn pairs(a: bool, b: Option<i32>) -> i32 {{
+                match (a, b) {{
+                    (true, Some(x)) => x,
+                    (true, None) | (false, None) => 0,
+                    (false, Some(_)) => 1,
+                }}
+            }}
+            fn bytes(x: u8, c: char) -> i32 {{
+                let d = match c {{ 'a'..='z' => 1, _ => 0 }};
+                match x {{ 0 => d, 1..=127 => 1, 128..=255 => 2 }}
+            }}
+            fn signs(n: i8, r: Result<i32, i32>) -> i32 {{
+                let s = match r {{ Ok(v) if v > 0 => v, Ok(_) => 0, Err(e) => e }};
+                match n {{ -128..=-1 => s, 0 => 0, 1.. => 1 }}
+            }}
+            struct Marker;
+            fn unit(m: Marker) -> i32 {{
+                match m {{ Marker => 0 }}
+            }}
+            fn nested(o: Option<i32>) -> i32 {{
+                match o {{ (Some(k)) => k, (None) => 0 }}
+            }}
+            fn main() {{}}"
+        ));
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_patterns_not_covered() {
+        let messages = messages(&format!(
+            "{SHAPE}
+            fn area(s: &Shape) -> f64 {{
+                match s {{ Shape::Circle(r) => r * r, Shape::Empty => 0.0 }}
+            }}
+            fn pairs(a: bool, b: Option<i32>) -> i32 {{
+                match (a, b) {{ (true, Some(x)) => x, (false, None) => 0 }}
+            }}
+            fn bytes(x: u8) -> i32 {{
+                match x {{ 0 => 0, 1..=9 => 1, 200..=255 => 2 }}
+            }}
+            fn guarded(n: i32, s: &str) -> i32 {{
+                let t = match s {{ \"a\" => 1, \"b\" => 2 }};
+                match n {{ x if x > 0 => t, 0 => 0 }}
+            }}
+            fn wide(x: u32) -> i32 {{
+                match x {{ 1 => 1, 3 => 3, 5 => 5, 7 => 7 }}
+            }}
+            fn main() {{}}"
+        ));
+        assert_eq!(
+            messages,
+            [
+                "non-exhaustive patterns: `Shape::Rect { .. }` not covered",
+                "non-exhaustive patterns: `(false, Some(_))` and `(true, None)` not covered",
+                "non-exhaustive patterns: `10..=199` not covered",
+                "non-exhaustive patterns: `_` not covered",
+                "non-exhaustive patterns: `i32::MIN..=-1` and `1..=i32::MAX` not covered",
+                "non-exhaustive patterns: `0`, `2`, `4` and 2 more not covered",
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_unreachable_arms() {
+        let messages = messages(&format!(
+            "{SHAPE}
+            fn size(s: Shape) -> i32 {{
+                match s {{
+                    Shape::Rect {{ w, .. }} => 1,
+                    Shape::Rect {{ h, .. }} => 2,
+                    _ => 0,
+                    Shape::Empty => 3,
+                }}
+            }}
+            fn letters(c: char) -> i32 {{
+                match c {{ 'a' | 'b' => 1, 'b' | 'c' => 2, 'c' => 3, 'a'..='z' => 4, _ => 0 }}
+            }}
+            fn main() {{}}"
+        ));
+        assert_eq!(messages, ["unreachable pattern"; 4]);
+    }
+}
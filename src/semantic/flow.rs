@@ -0,0 +1,566 @@
+//! Checks following the paths through the [`Cfg`] of each function body :
+//! - a function returning a value does not reach the end of its body without one ;
+//! - a `let` without a value is assigned before any use, on every path leading to
+//!   the use, and only once unless it is `mut` ;
+//! - no statement comes after one that never completes, like `return`, `break` or a
+//!   `loop` nothing leaves, the `unreachable_code` lint ;
+//! - something leaves each `loop` : a `break`, a `return` or a `?`, the
+//!   `clippy::infinite_loop` lint ;
+//! - `break` and `continue` are inside a loop, and not across a closure.
+//!
+//! The warnings are not reported under an `#[allow(...)]` of their lint, on an item
+//! or a statement. Only the expressions of the language are known to never
+//! complete : a call that never returns is taken to return. A body the parser
+//! recovered from is not checked for its value, the parse error says enough.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::cfg::{self, Action, BlockId, Cfg, FlowState, Terminator};
+use super::resolve::{Res, Resolutions};
+use super::unused::allows;
+
+/// The lints of rustc this pass reports in its place.
+pub const LINTS: &[&str] = &["unreachable_code"];
+
+pub fn check_flow(module: &Module, resolutions: &Resolutions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut checker = Checker {
+        resolutions,
+        diagnostics,
+        allowed: vec![],
+        reached: HashMap::new(),
+        endless: HashSet::new(),
+    };
+    checker.visit_module(module);
+}
+
+/// A binding of a `let` without a value.
+struct Deferred<'ast> {
+    name: &'ast Ident,
+    mutable: bool,
+}
+
+/// What may be true at a point of the body about the deferred bindings, on one of
+/// the paths leading to it.
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// The bindings that may not be assigned yet.
+    unassigned: HashSet<NodeId>,
+    /// The bindings that may be assigned, with their first assignment.
+    assigned: HashMap<NodeId, Span>,
+}
+
+impl FlowState for State {
+    fn join(&mut self, other: &State) -> bool {
+        let before = self.unassigned.len() + self.assigned.len();
+        self.unassigned.extend(&other.unassigned);
+        for (id, span) in &other.assigned {
+            self.assigned.entry(*id).or_insert(*span);
+        }
+        self.unassigned.len() + self.assigned.len() != before
+    }
+}
+
+struct Checker<'ast, 'r, 'd> {
+    resolutions: &'r Resolutions,
+    diagnostics: &'d mut Vec<Diagnostic>,
+    /// The lints allowed where the visitor is.
+    allowed: Vec<&'ast str>,
+    /// Whether each statement of the body being checked is reached, by address.
+    reached: HashMap<*const Stmt, bool>,
+    /// The `loop` expressions of the body being checked that nothing leaves.
+    endless: HashSet<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let allowed = self.allowed.len();
+        self.allowed.extend(allows(&item.attrs));
+        visit::walk_item(self, item);
+        self.allowed.truncate(allowed);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let allowed = self.allowed.len();
+        self.allowed.extend(allows(&stmt.attrs));
+        visit::walk_stmt(self, stmt);
+        self.allowed.truncate(allowed);
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        let Some(cfg) = Cfg::build(function) else {
+            return;
+        };
+        let reached = cfg.reachable(0);
+        let mut strays = Strays {
+            loops: 0,
+            closure: None,
+            diagnostics: self.diagnostics,
+            recovered: false,
+        };
+        strays.visit_function(function);
+        if !strays.recovered {
+            self.check_return(function, &cfg, &reached);
+        }
+        self.check_init(function, &cfg);
+        self.reached = cfg
+            .stmts
+            .iter()
+            .map(|&(stmt, block)| (stmt as *const Stmt, reached[block]))
+            .collect();
+        self.endless = cfg
+            .loops
+            .iter()
+            .filter(|&&(_, head, exit)| reached[head] && never_left(&cfg, head, exit))
+            .map(|(expr, _, _)| expr.id)
+            .collect();
+        visit::walk_function(self, function);
+    }
+
+    // The statements after the first one not reached are not checked.
+    fn visit_block(&mut self, block: &'ast Block) {
+        let unreached = block
+            .stmts
+            .iter()
+            .position(|stmt| self.reached.get(&(stmt as *const Stmt)) == Some(&false));
+        if let Some(index) = unreached {
+            self.report_unreachable(block, index);
+        }
+        for stmt in &block.stmts[..unreached.unwrap_or(block.stmts.len())] {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if self.endless.contains(&expr.id) && !self.is_allowed("infinite_loop") {
+            let keyword = Span::new(expr.span.start, expr.span.start + "loop".len());
+            self.diagnostics.push(
+                Diagnostic::warning("infinite loop detected", keyword.with_ctxt(expr.span.ctxt))
+                    .with_note("no `break` leaves this loop, and nothing inside it returns")
+                    .with_note(
+                        "if it is meant to run forever, allow `clippy::infinite_loop` on the function",
+                    ),
+            );
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl<'ast> Checker<'ast, '_, '_> {
+    fn is_allowed(&self, lint: &str) -> bool {
+        self.allowed.contains(&lint)
+    }
+
+    /// Reports a body reaching its end without a value, when the function returns
+    /// one. The type of the value at the end of the body is checked by `typeck`.
+    fn check_return(&mut self, function: &'ast Function, cfg: &Cfg<'ast>, reached: &[bool]) {
+        let (Some(output), Some(body)) = (&function.output, &function.body) else {
+            return;
+        };
+        let unit = matches!(&output.kind, TyKind::Tuple(tys) if tys.is_empty());
+        if unit || body.tail().is_some() || !reached[cfg.exit] {
+            return;
+        }
+        let end = Span::new(body.span.end.saturating_sub(1), body.span.end);
+        self.diagnostics.push(
+            Diagnostic::error("not all paths return a value", output.span)
+                .with_label(
+                    end.with_ctxt(body.span.ctxt),
+                    format!(
+                        "`{}` reaches the end of its body without a value",
+                        function.name.name
+                    ),
+                )
+                .with_note(
+                    "end the body with a value, or each path reaching its end with a `return`",
+                ),
+        );
+    }
+
+    /// Checks the uses and the assignments of the bindings of the `let` statements
+    /// without a value : in the order of the paths through the body, a binding is
+    /// assigned before it is used, and once unless it is `mut`.
+    fn check_init(&mut self, function: &'ast Function, cfg: &Cfg<'ast>) {
+        let mut collector = Collector::default();
+        collector.visit_function(function);
+        let deferred = collector.deferred;
+        if deferred.is_empty() {
+            return;
+        }
+        let states = cfg.forward(|action, state| self.apply(action, &deferred, state, None));
+        // A use reached again through a loop is reported once.
+        let mut reported = HashSet::new();
+        for (block, state) in cfg.blocks.iter().zip(states) {
+            let Some(mut state) = state else {
+                continue;
+            };
+            for action in &block.actions {
+                self.apply(action, &deferred, &mut state, Some(&mut reported));
+            }
+        }
+    }
+
+    /// Updates `state` after `action`, reporting its errors when `reported` is set.
+    fn apply(
+        &mut self,
+        action: &Action<'ast>,
+        deferred: &HashMap<NodeId, Deferred<'ast>>,
+        state: &mut State,
+        mut reported: Option<&mut HashSet<Span>>,
+    ) {
+        let mut report = |span| {
+            reported
+                .as_mut()
+                .is_some_and(|reported| reported.insert(span))
+        };
+        match *action {
+            Action::Bind { pat, init: None } => {
+                let mut bindings = vec![];
+                cfg::bindings(pat, &mut bindings);
+                for id in bindings.into_iter().filter(|id| deferred.contains_key(id)) {
+                    state.unassigned.insert(id);
+                    state.assigned.remove(&id);
+                }
+            }
+            Action::Use { place, .. } => {
+                let Some((id, binding)) = self.deferred(place, deferred) else {
+                    return;
+                };
+                if !state.unassigned.contains(&id) || !report(place.span) {
+                    return;
+                }
+                let name = binding.name.display_name();
+                let message = if state.assigned.contains_key(&id) {
+                    format!("used binding `{name}` is possibly-uninitialized")
+                } else {
+                    format!("used binding `{name}` isn't initialized")
+                };
+                self.diagnostics
+                    .push(Diagnostic::error(message, place.span).with_label(
+                        binding.name.span,
+                        "binding declared here but left uninitialized",
+                    ));
+            }
+            Action::Assign { place, .. } => {
+                let Some((id, binding)) = self.deferred(place, deferred) else {
+                    return;
+                };
+                let name = binding.name.display_name();
+                let whole = !matches!(place.kind, ExprKind::Field(..) | ExprKind::Index(..));
+                if !whole {
+                    if state.unassigned.contains(&id) && report(place.span) {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                format!(
+                                    "partially assigned binding `{name}` isn't fully initialized"
+                                ),
+                                place.span,
+                            )
+                            .with_label(
+                                binding.name.span,
+                                "binding declared here but left uninitialized",
+                            ),
+                        );
+                    }
+                    return;
+                }
+                match state.assigned.get(&id) {
+                    Some(&first) if !binding.mutable && report(place.span) => {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                format!("cannot assign twice to immutable variable `{name}`"),
+                                place.span,
+                            )
+                            .with_label(first, format!("first assignment to `{name}`"))
+                            .with_suggestion(
+                                binding.name.span,
+                                "consider changing this to be mutable",
+                                format!("mut {name}"),
+                            ),
+                        );
+                    }
+                    Some(_) => {}
+                    None => {
+                        state.assigned.insert(id, place.span);
+                    }
+                }
+                state.unassigned.remove(&id);
+            }
+            _ => {}
+        }
+    }
+
+    /// The deferred binding `place` is, or is a field or an element of.
+    fn deferred<'a>(
+        &self,
+        place: &Expr,
+        deferred: &'a HashMap<NodeId, Deferred<'ast>>,
+    ) -> Option<(NodeId, &'a Deferred<'ast>)> {
+        match &place.kind {
+            ExprKind::Paren(inner) | ExprKind::Field(inner, _) | ExprKind::Index(inner, _) => {
+                self.deferred(inner, deferred)
+            }
+            ExprKind::Path(_) => match self.resolutions.get(place.id) {
+                Some(Res::Local(id)) => deferred.get(&id).map(|binding| (id, binding)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn report_unreachable(&mut self, block: &'ast Block, index: usize) {
+        let stmt = &block.stmts[index];
+        let allowed = allows(&stmt.attrs).any(|lint| lint == "unreachable_code");
+        if allowed || self.is_allowed("unreachable_code") {
+            return;
+        }
+        let tail = index + 1 == block.stmts.len() && matches!(stmt.kind, StmtKind::Expr(_));
+        let what = if tail { "expression" } else { "statement" };
+        let mut diagnostic = Diagnostic::warning(format!("unreachable {what}"), stmt.span);
+        if let Some(previous) = index.checked_sub(1).map(|previous| &block.stmts[previous]) {
+            diagnostic = diagnostic.with_label(
+                previous.span,
+                "any code following this expression is unreachable",
+            );
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Whether the loop starting each iteration at `head` repeats, and nothing leaves it.
+fn never_left(cfg: &Cfg, head: BlockId, exit: BlockId) -> bool {
+    let inside = cfg.reachable(head);
+    let repeats =
+        (0..cfg.blocks.len()).any(|block| inside[block] && cfg.successors(block).contains(&head));
+    let returns = (0..cfg.blocks.len()).any(|block| {
+        inside[block] && matches!(cfg.blocks[block].terminator, Terminator::Return(_))
+    });
+    repeats && !returns && !inside[exit]
+}
+
+/// Reports the `break` and `continue` of a function outside of its loops, and finds
+/// whether the parser left an expression out of it.
+struct Strays<'d> {
+    /// The loops around the expression visited, in the innermost closure.
+    loops: usize,
+    /// The innermost closure around the expression visited.
+    closure: Option<Span>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+    recovered: bool,
+}
+
+impl<'ast> Visitor<'ast> for Strays<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        let keyword = match &expr.kind {
+            ExprKind::Break(_) => "break",
+            ExprKind::Continue => "continue",
+            ExprKind::Loop(_) | ExprKind::While(..) | ExprKind::ForLoop { .. } => {
+                self.loops += 1;
+                visit::walk_expr(self, expr);
+                self.loops -= 1;
+                return;
+            }
+            ExprKind::Closure(_) => {
+                let loops = std::mem::take(&mut self.loops);
+                let closure = self.closure.replace(expr.span);
+                visit::walk_expr(self, expr);
+                (self.loops, self.closure) = (loops, closure);
+                return;
+            }
+            ExprKind::Error => {
+                self.recovered = true;
+                return;
+            }
+            _ => "",
+        };
+        if !keyword.is_empty() && self.loops == 0 {
+            let span = Span::new(expr.span.start, expr.span.start + keyword.len())
+                .with_ctxt(expr.span.ctxt);
+            self.diagnostics.push(match self.closure {
+                Some(closure) => {
+                    Diagnostic::error(format!("`{keyword}` inside of a closure"), span)
+                        .with_label(closure, "enclosing closure")
+                }
+                None => Diagnostic::error(format!("`{keyword}` outside of a loop"), span),
+            });
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// The bindings of the `let` statements without a value of a function.
+#[derive(Default)]
+struct Collector<'ast> {
+    deferred: HashMap<NodeId, Deferred<'ast>>,
+    in_deferred: bool,
+}
+
+impl<'ast> Visitor<'ast> for Collector<'ast> {
+    fn visit_local(&mut self, local: &'ast Local) {
+        let in_deferred = std::mem::replace(&mut self.in_deferred, local.init.is_none());
+        visit::walk_local(self, local);
+        self.in_deferred = in_deferred;
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        if let (true, PatKind::Binding { name, mutable, .. }) = (self.in_deferred, &pattern.kind) {
+            let mutable = *mutable;
+            self.deferred.insert(pattern.id, Deferred { name, mutable });
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    // The closures are not part of the graph of the body.
+    fn visit_closure(&mut self, _closure: &'ast Closure) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_flow;
+    use crate::semantic;
+    use crate::semantic::resolve::resolve_names;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, |module, resolutions, _, diagnostics| {
+            check_flow(module, resolutions, diagnostics)
+        })
+    }
+
+    #[test]
+    fn reports_missing_returns_and_unreachable_code() {
+        let messages = messages(
+            "fn sign(x: i32) -> i32 {
+                if x > 0 {
+                    return 1;
+                }
+                let y = x;
+            }
+            fn all(x: i32) -> i32 {
+                if x > 0 { return 1; } else { return 2; }
+            }
+            fn ends(x: i32) -> i32 {
+                return x;
+            }
+            fn early(x: i32) -> i32 {
+                return x;
+                let y = 2;
+                y
+            }
+            fn first(v: Vec<i32>) -> i32 {
+                for x in v {
+                    break;
+                    x;
+                }
+                #[allow(unreachable_code)]
+                {
+                    return 0;
+                    1
+                }
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "not all paths return a value",
+                "unreachable statement",
+                "unreachable statement",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_deferred_initialization() {
+        let messages = messages(
+            "struct Point { x: i32 }
+            fn init(c: bool) -> i32 {
+                let x;
+                let y: i32;
+                if c { x = 1; }
+                let z;
+                if c { z = 1; } else { z = 2; }
+                let w;
+                w = 1;
+                w = 2;
+                let mut m;
+                m = 1;
+                m = 2;
+                let p: Point;
+                p.x = 1;
+                x + y + z + w + m
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot assign twice to immutable variable `w`",
+                "partially assigned binding `p` isn't fully initialized",
+                "used binding `x` is possibly-uninitialized",
+                "used binding `y` isn't initialized",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_loops_nothing_leaves() {
+        let messages = messages(
+            "fn spin() {
+                let mut n = 0;
+                loop { n = n + 1; }
+            }
+            #[allow(clippy::infinite_loop)]
+            fn serve() {
+                loop {}
+            }
+            fn counted() -> i32 {
+                let mut n = 0;
+                loop {
+                    n = n + 1;
+                    if n > 3 { break; }
+                }
+                loop {
+                    if n > 5 { return n; }
+                    n = n + 1;
+                }
+            }",
+        );
+        assert_eq!(messages, ["infinite loop detected"]);
+    }
+
+    #[test]
+    fn reports_break_and_continue_outside_of_loops() {
+        let messages = messages(
+            "fn main(v: Vec<i32>) {
+                let f = || { break; };
+                loop {
+                    let g = || { continue; };
+                    for x in v { continue; }
+                    break;
+                }
+                if true { continue; }
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "`break` inside of a closure",
+                "`continue` inside of a closure",
+                "`continue` outside of a loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_value_of_recovered_bodies() {
+        let (module, errors) = parse("fn c() -> i32 { 1 + }\nfn d() -> i32 { let x = ; }");
+        assert_eq!(errors.len(), 2);
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        check_flow(&module, &resolutions, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+}
@@ -0,0 +1,57 @@
+//! Passes over the parsed crate, between parsing and code generation. Each pass
+//! either fills the information left empty by the parser or reports diagnostics.
+
+pub mod attributes;
+pub mod bounds;
+pub mod captures;
+pub mod cfg;
+pub mod consts;
+pub mod exhaustiveness;
+pub mod flow;
+pub mod mutability;
+pub mod operators;
+pub mod ownership;
+pub mod prelude;
+pub mod propagation;
+pub mod refutability;
+pub mod resolve;
+pub mod traits;
+pub mod typeck;
+pub mod unused;
+
+/// What the tests of the passes share.
+#[cfg(test)]
+mod test {
+    use crate::diagnostics::Diagnostic;
+    use crate::syntax::{ast::Module, parse};
+
+    use super::resolve::{resolve_names, Resolutions};
+    use super::typeck::{check_types, TypeckResults};
+
+    /// The diagnostics `pass` reports on `source`, which must parse, resolve and
+    /// type check without any.
+    pub fn diagnostics(
+        source: &str,
+        pass: impl FnOnce(&Module, &Resolutions, &TypeckResults, &mut Vec<Diagnostic>),
+    ) -> Vec<Diagnostic> {
+        let (module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        let types = check_types(&module, &resolutions, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        pass(&module, &resolutions, &types, &mut diagnostics);
+        diagnostics
+    }
+
+    /// The messages of the [`diagnostics`] `pass` reports on `source`.
+    pub fn messages(
+        source: &str,
+        pass: impl FnOnce(&Module, &Resolutions, &TypeckResults, &mut Vec<Diagnostic>),
+    ) -> Vec<String> {
+        diagnostics(source, pass)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+}
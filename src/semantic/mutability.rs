@@ -0,0 +1,432 @@
+//! Checks that what is written to may be : assignments, `&mut` borrows and the calls
+//! of methods taking `&mut self`. The place written to must go back to a `mut`
+//! binding, or through a `&mut` reference, and never through a `&` reference. The
+//! statics are immutable.
+//!
+//! The methods taking `&mut self` are known for the impls of the crate and for the
+//! types of the prelude, see [`prelude::MUTATING_METHODS`]. Places whose type is not
+//! known by [`typeck`](super::typeck) are left to rustc.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::cfg::place_text;
+use super::prelude;
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+pub fn check_mutability(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut bindings = Bindings::default();
+    bindings.visit_module(module);
+    let mut checker = Checker {
+        bindings,
+        resolutions,
+        types,
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// Where a binding comes from.
+enum Origin<'ast> {
+    /// `let`, with its initializer. `let x;` may be assigned once.
+    Let(&'ast Local),
+    Param(&'ast Param),
+    /// A pattern of `match`, `for` or a closure.
+    Other,
+}
+
+struct Binding<'ast> {
+    name: &'ast Ident,
+    mutable: bool,
+    origin: Origin<'ast>,
+}
+
+/// The bindings of the crate by pattern id, the statics by item id, and the methods
+/// taking `&mut self`.
+#[derive(Default)]
+struct Bindings<'ast> {
+    bindings: HashMap<NodeId, Binding<'ast>>,
+    statics: HashMap<NodeId, &'ast Ident>,
+    mut_self: HashSet<NodeId>,
+}
+
+impl<'ast> Bindings<'ast> {
+    fn add(&mut self, pattern: &'ast Pattern, origin: impl Fn() -> Origin<'ast> + Copy) {
+        match &pattern.kind {
+            PatKind::Binding { name, mutable, sub } => {
+                self.bindings.entry(pattern.id).or_insert(Binding {
+                    name,
+                    mutable: *mutable,
+                    origin: origin(),
+                });
+                if let Some(sub) = sub {
+                    self.add(sub, origin);
+                }
+            }
+            PatKind::Tuple(patterns)
+            | PatKind::TupleStruct(_, patterns)
+            | PatKind::Or(patterns) => {
+                for pattern in patterns {
+                    self.add(pattern, origin);
+                }
+            }
+            PatKind::Struct { fields, .. } => {
+                for field in fields {
+                    self.add(&field.pat, origin);
+                }
+            }
+            PatKind::Paren(inner) => self.add(inner, origin),
+            _ => {}
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Bindings<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Function(function) => {
+                let mut_self = function.params.first().is_some_and(|param| {
+                    param.is_self() && matches!(param.ty.kind, TyKind::Ref { mutable: true, .. })
+                });
+                if mut_self {
+                    self.mut_self.insert(item.id);
+                }
+            }
+            ItemKind::Static(constant) => {
+                self.statics.insert(item.id, &constant.name);
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        self.add(&local.pat, || Origin::Let(local));
+        visit::walk_local(self, local);
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        self.add(&param.pat, || Origin::Param(param));
+        visit::walk_param(self, param);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        self.add(pattern, || Origin::Other);
+    }
+}
+
+/// Why a place cannot be written to.
+enum Blame<'ast> {
+    /// It belongs to a binding without `mut`.
+    Binding(NodeId),
+    /// It belongs to a static, by item id.
+    Static(NodeId),
+    /// It is reached through this expression, a `&` reference.
+    BehindRef(&'ast Expr),
+}
+
+/// What is done to a place.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Assign,
+    Borrow,
+}
+
+struct Checker<'ast, 'r, 'd> {
+    bindings: Bindings<'ast>,
+    resolutions: &'r Resolutions,
+    types: &'r TypeckResults,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Assign(lhs, _) => self.check_place(lhs, Access::Assign),
+            ExprKind::AddrOf {
+                mutable: true,
+                expr,
+            } => self.check_place(expr, Access::Borrow),
+            ExprKind::MethodCall {
+                receiver, method, ..
+            } if self.mutates(expr.id, receiver, method) => {
+                match self.types.expr(receiver.id) {
+                    Some(Type::Ref(true, _)) => {}
+                    // `r.push(1)` borrows `*r`.
+                    Some(Type::Ref(false, _)) => {
+                        let place = format!("*{}", place_text(receiver));
+                        self.report(&place, receiver, Blame::BehindRef(receiver), Access::Borrow);
+                    }
+                    _ => self.check_place(receiver, Access::Borrow),
+                }
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl<'ast> Checker<'ast, '_, '_> {
+    /// Whether the method called takes `&mut self`.
+    fn mutates(&self, id: NodeId, receiver: &Expr, method: &Ident) -> bool {
+        if let Some(function) = self.types.method(id) {
+            return self.bindings.mut_self.contains(&function);
+        }
+        let mut ty = self.types.expr(receiver.id);
+        while let Some(Type::Ref(_, inner)) = ty {
+            ty = Some(inner);
+        }
+        matches!(ty, Some(Type::Named(name, _)) if prelude::is_mutating(name, &method.name))
+    }
+
+    fn check_place(&mut self, place: &'ast Expr, access: Access) {
+        if let Some(blame) = self.blame(place) {
+            self.report(&place_text(place), place, blame, access);
+        }
+    }
+
+    /// Why `place` cannot be written to, if it cannot.
+    fn blame(&self, place: &'ast Expr) -> Option<Blame<'ast>> {
+        match &place.kind {
+            ExprKind::Paren(inner) => self.blame(inner),
+            ExprKind::Path(_) => match self.resolutions.get(place.id) {
+                Some(Res::Local(id)) => {
+                    let binding = self.bindings.bindings.get(&id)?;
+                    let deferred =
+                        matches!(binding.origin, Origin::Let(local) if local.init.is_none());
+                    (!binding.mutable && !deferred).then_some(Blame::Binding(id))
+                }
+                Some(Res::Item(id)) if self.bindings.statics.contains_key(&id) => {
+                    Some(Blame::Static(id))
+                }
+                _ => None,
+            },
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => {
+                match self.types.expr(base.id)? {
+                    Type::Ref(false, _) => Some(Blame::BehindRef(base)),
+                    Type::Ref(true, _) | Type::Unknown | Type::Param(..) => None,
+                    _ => self.blame(base),
+                }
+            }
+            ExprKind::Unary(UnOp::Deref, inner) => match self.types.expr(inner.id)? {
+                Type::Ref(false, _) => Some(Blame::BehindRef(inner)),
+                Type::Named(name, _) if name == "Box" => self.blame(inner),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, place: &str, expr: &Expr, blame: Blame<'ast>, access: Access) {
+        let diagnostic = match blame {
+            Blame::Binding(id) => {
+                let binding = &self.bindings.bindings[&id];
+                let name = binding.name.display_name();
+                let whole = place == name;
+                let message = match (access, whole, &binding.origin) {
+                    (Access::Assign, true, Origin::Param(_)) => {
+                        format!("cannot assign to immutable argument `{name}`")
+                    }
+                    (Access::Assign, true, _) => {
+                        format!("cannot assign twice to immutable variable `{name}`")
+                    }
+                    (Access::Assign, false, _) => {
+                        format!(
+                            "cannot assign to `{place}`, as `{name}` is not declared as mutable"
+                        )
+                    }
+                    (Access::Borrow, true, _) => {
+                        format!(
+                            "cannot borrow `{place}` as mutable, as it is not declared as mutable"
+                        )
+                    }
+                    (Access::Borrow, false, _) => format!(
+                        "cannot borrow `{place}` as mutable, as `{name}` is not declared as mutable"
+                    ),
+                };
+                Diagnostic::error(message, expr.span).with_suggestion(
+                    binding.name.span,
+                    "consider changing this to be mutable",
+                    format!("mut {name}"),
+                )
+            }
+            Blame::Static(id) => {
+                let name = &self.bindings.statics[&id].name;
+                let message = match (access, place == name) {
+                    (Access::Assign, true) => format!("cannot assign to immutable static item `{name}`"),
+                    (Access::Assign, false) => {
+                        format!("cannot assign to `{place}`, as `{name}` is an immutable static item")
+                    }
+                    (Access::Borrow, true) => {
+                        format!("cannot borrow immutable static item `{name}` as mutable")
+                    }
+                    (Access::Borrow, false) => format!(
+                        "cannot borrow `{place}` as mutable, as `{name}` is an immutable static item"
+                    ),
+                };
+                Diagnostic::error(message, expr.span)
+            }
+            Blame::BehindRef(reference) => {
+                let message = match access {
+                    Access::Assign => {
+                        format!("cannot assign to `{place}`, which is behind a `&` reference")
+                    }
+                    Access::Borrow => format!(
+                        "cannot borrow `{place}` as mutable, as it is behind a `&` reference"
+                    ),
+                };
+                let diagnostic = Diagnostic::error(message, expr.span);
+                match self.mutable_reference(reference) {
+                    Some((span, replacement)) => diagnostic.with_suggestion(
+                        span,
+                        "consider changing this to be a mutable reference",
+                        replacement,
+                    ),
+                    None => diagnostic,
+                }
+            }
+        };
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Where the `&` reference `reference` could have been made `&mut` : the type
+    /// of a parameter, or the `&` of `let r = &x;`.
+    fn mutable_reference(&self, reference: &Expr) -> Option<(Span, String)> {
+        let Some(Res::Local(id)) = self.resolutions.get(reference.id) else {
+            return None;
+        };
+        match self.bindings.bindings.get(&id)?.origin {
+            Origin::Param(param) => match &param.ty.kind {
+                TyKind::Ref {
+                    mutable: false,
+                    inner,
+                } => {
+                    let inner = match param.is_self() {
+                        true => "self".to_string(),
+                        false => inner.to_text(),
+                    };
+                    Some((param.ty.span, format!("&mut {inner}")))
+                }
+                _ => None,
+            },
+            Origin::Let(Local {
+                ty: None,
+                init:
+                    Some(Expr {
+                        kind:
+                            ExprKind::AddrOf {
+                                mutable: false,
+                                expr,
+                            },
+                        span,
+                        ..
+                    }),
+                ..
+            }) => Some((Span::new(span.start, expr.span.start), "&mut ".to_string())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_mutability;
+    use crate::semantic;
+
+    /// The messages, followed by the replacement they suggest.
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::diagnostics(source, check_mutability)
+            .into_iter()
+            .map(|d| match d.suggestion {
+                Some(suggestion) => format!("{}: `{}`", d.message, suggestion.replacement),
+                None => d.message,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_mutable_places() {
+        let messages = messages(
+            "struct Counter { n: i32, items: Vec<i32> }
+            impl Counter {
+                fn bump(&mut self) { self.n = self.n + 1; self.items.push(self.n); }
+                fn reset(mut self) -> Self { self.n = 0; self }
+            }
+            fn fill(out: &mut Vec<i32>, c: &mut Counter) { out.push(1); c.bump(); c.n = 2; }
+            fn main() {
+                let mut c = Counter { n: 0, items: Vec::new() };
+                c.bump();
+                c.items.push(2);
+                let r = &mut c;
+                r.n = 1;
+                *r = Counter { n: 3, items: Vec::new() };
+                let later;
+                later = 3;
+                let mut grid = [[0; 2]; 2];
+                grid[0][1] = later;
+                let t = c.items.len();
+            }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_writes_to_immutable_places() {
+        let messages = messages(
+            "struct Counter { n: i32 }
+            impl Counter {
+                fn bump(&mut self) { self.n = self.n + 1; }
+                fn peek(&self) -> i32 { self.n = 3; self.n }
+            }
+            fn take(c: &Counter, x: i32) { c.bump(); x = 2; }
+            static LIMIT: i32 = 1;
+            static ORIGIN: Counter = Counter { n: 0 };
+            fn statics() {
+                LIMIT = 2;
+                ORIGIN.n = 1;
+                let r = &mut LIMIT;
+                ORIGIN.bump();
+            }
+            fn main() {
+                let x = 1;
+                x = 2;
+                let c = Counter { n: 0 };
+                c.bump();
+                c.n = 4;
+                let v = Vec::new();
+                v.push(1);
+                let r = &c;
+                r.n = 5;
+                let m = &mut c;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot assign to `self.n`, which is behind a `&` reference: `&mut self`",
+                "cannot borrow `*c` as mutable, as it is behind a `&` reference: `&mut Counter`",
+                "cannot assign to immutable argument `x`: `mut x`",
+                "cannot assign to immutable static item `LIMIT`",
+                "cannot assign to `ORIGIN.n`, as `ORIGIN` is an immutable static item",
+                "cannot borrow immutable static item `LIMIT` as mutable",
+                "cannot borrow immutable static item `ORIGIN` as mutable",
+                "cannot assign twice to immutable variable `x`: `mut x`",
+                "cannot borrow `c` as mutable, as it is not declared as mutable: `mut c`",
+                "cannot assign to `c.n`, as `c` is not declared as mutable: `mut c`",
+                "cannot borrow `v` as mutable, as it is not declared as mutable: `mut v`",
+                "cannot assign to `r.n`, which is behind a `&` reference: `&mut `",
+                "cannot borrow `c` as mutable, as it is not declared as mutable: `mut c`",
+            ]
+        );
+    }
+}
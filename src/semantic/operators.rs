@@ -0,0 +1,571 @@
+//! Operator overloading. `operator +` in an impl is moved to an impl of the trait
+//! behind the operator, and the impls of the traits of `std::ops` get the path of
+//! the trait and their `Output` type when the crate leaves them out :
+//!
+//! ```text
+//! impl Vec2 { operator +(self, other: Vec2) -> Vec2 { ... } }
+//! impl std::ops::Add for Vec2 { type Output = Vec2; fn add(self, other: Vec2) -> Vec2 { ... } }
+//! ```
+//!
+//! The operators applied to a value whose type is a struct or an enum of the crate
+//! are then checked against the impls and the derives of that type. Values of other
+//! types, or whose type is not known yet, are left to rustc.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+/// The traits of `std::ops` which can be overloaded, all of them with an `Output`.
+const OPS_TRAITS: &[&str] = &["Add", "Sub", "Mul", "Div", "Rem", "Neg", "Not"];
+
+pub fn lower_operators(module: &mut Module) {
+    let mut names = Names::default();
+    names.visit_module(module);
+    lower_items(&mut module.items, &names);
+}
+
+/// The traits the crate declares, and the names its `use` bring in scope. An ops
+/// trait named like one of them is left as written.
+#[derive(Default)]
+struct Names {
+    traits: HashSet<String>,
+    imported: HashSet<String>,
+    /// Whether `std::ops::*` is imported.
+    ops_glob: bool,
+}
+
+impl<'ast> Visitor<'ast> for Names {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if let ItemKind::Trait(item) = &item.kind {
+            self.traits.insert(item.name.name.clone());
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_use_tree(&mut self, tree: &'ast UseTree) {
+        match &tree.kind {
+            UseTreeKind::Simple(Some(alias)) => {
+                self.imported.insert(alias.name.clone());
+            }
+            UseTreeKind::Simple(None) => {
+                self.imported.insert(tree.prefix.last().name.clone());
+            }
+            UseTreeKind::Glob => {
+                self.ops_glob |= tree
+                    .prefix
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident.name == "ops");
+            }
+            UseTreeKind::Nested(_) => {}
+        }
+        visit::walk_use_tree(self, tree);
+    }
+}
+
+fn lower_items(items: &mut Vec<Item>, names: &Names) {
+    let mut lowered = Vec::with_capacity(items.len());
+    for mut item in items.drain(..) {
+        match &mut item.kind {
+            ItemKind::Mod(Mod {
+                content: Some(content),
+                ..
+            }) => lower_items(&mut content.items, names),
+            ItemKind::Impl(imp) if imp.of_trait.is_none() => {
+                let (operators, methods) = imp.items.drain(..).partition(
+                    |item| matches!(&item.kind, ItemKind::Function(f) if f.operator.is_some()),
+                );
+                imp.items = methods;
+                let trait_impls: Vec<_> = operators
+                    .into_iter()
+                    .map(|operator| trait_impl(imp, operator))
+                    .collect();
+                let keep = !imp.items.is_empty() || trait_impls.is_empty();
+                if keep {
+                    lowered.push(item);
+                }
+                for mut trait_impl in trait_impls {
+                    if let ItemKind::Impl(imp) = &mut trait_impl.kind {
+                        complete_ops_impl(imp, names);
+                    }
+                    lowered.push(trait_impl);
+                }
+                continue;
+            }
+            ItemKind::Impl(imp) => complete_ops_impl(imp, names),
+            _ => {}
+        }
+        lowered.push(item);
+    }
+    *items = lowered;
+}
+
+/// `impl Add for Vec2 { fn add(..) }` for `operator +` in `impl Vec2`. The right
+/// operand gives the type argument of the trait when it is not the type itself.
+fn trait_impl(imp: &Impl, mut operator: Item) -> Item {
+    let ItemKind::Function(function) = &operator.kind else {
+        unreachable!("only functions are operators");
+    };
+    let op = function.operator.expect("an operator");
+    let (trait_name, _) = op.trait_method().expect("an overloadable operator");
+    let mut segment = PathSegment::new(Ident {
+        name: trait_name.to_string(),
+        span: function.name.span,
+        written: None,
+    });
+    if let (OverloadedOp::Binary(op), Some(rhs)) = (op, function.params.get(1)) {
+        let rhs = match (&rhs.ty.kind, op) {
+            // `PartialEq::eq` takes its operand by reference.
+            (TyKind::Ref { inner, .. }, BinOp::Eq) => inner,
+            _ => &rhs.ty,
+        };
+        let rhs_text = rhs.to_text();
+        if rhs_text != "Self" && rhs_text != imp.self_ty.to_text() {
+            segment.args.push(rhs.clone());
+        }
+    }
+    // The items of a trait impl take the visibility of the trait.
+    operator.vis = Visibility::Private;
+    let span = operator.span;
+    Item {
+        id: NodeId::next(),
+        attrs: vec![],
+        vis: Visibility::Private,
+        kind: ItemKind::Impl(Impl {
+            generics: imp.generics.clone(),
+            of_trait: Some(Path {
+                segments: vec![segment],
+                span,
+            }),
+            self_ty: imp.self_ty.clone(),
+            items: vec![operator],
+        }),
+        span,
+    }
+}
+
+/// Writes `Add` as `std::ops::Add` unless the crate names something `Add`, and adds
+/// the `Output` type from the return type of the method.
+fn complete_ops_impl(imp: &mut Impl, names: &Names) {
+    let Some(path) = &mut imp.of_trait else {
+        return;
+    };
+    let name = path.last().name.clone();
+    let qualified = match &path.segments[..] {
+        [_] => false,
+        [.., ops, _] => ops.ident.name == "ops",
+        [] => return,
+    };
+    if !OPS_TRAITS.contains(&name.as_str()) || (!qualified && names.traits.contains(&name)) {
+        return;
+    }
+    if !qualified && !names.ops_glob && !names.imported.contains(&name) {
+        let span = path.span;
+        let prefix = ["std", "ops"].map(|name| {
+            PathSegment::new(Ident {
+                name: name.to_string(),
+                span,
+                written: None,
+            })
+        });
+        path.segments.splice(0..0, prefix);
+    }
+    let has_output = imp
+        .items
+        .iter()
+        .any(|item| matches!(&item.kind, ItemKind::TyAlias(alias) if alias.name.name == "Output"));
+    let method = imp.items.iter().find_map(|item| match &item.kind {
+        ItemKind::Function(function) if function.name.name == name.to_lowercase() => {
+            Some((function, item.span))
+        }
+        _ => None,
+    });
+    let (false, Some((function, span))) = (has_output, method) else {
+        return;
+    };
+    let output = function.output.clone().unwrap_or(Ty {
+        id: NodeId::next(),
+        kind: TyKind::Tuple(vec![]),
+        span,
+    });
+    imp.items.insert(
+        0,
+        Item {
+            id: NodeId::next(),
+            attrs: vec![],
+            vis: Visibility::Private,
+            kind: ItemKind::TyAlias(TyAlias {
+                name: Ident {
+                    name: "Output".to_string(),
+                    span,
+                    written: None,
+                },
+                ty: Some(output),
+            }),
+            span,
+        },
+    );
+}
+
+pub fn check_operators(module: &Module, diagnostics: &mut Vec<Diagnostic>) {
+    let mut items = Items::default();
+    items.visit_module(module);
+    let mut checker = Checker {
+        items,
+        self_ty: None,
+        locals: HashMap::new(),
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// What the crate declares, by name.
+#[derive(Default)]
+struct Items<'ast> {
+    /// The structs and the enums.
+    types: HashSet<&'ast str>,
+    /// `(trait, type)` for each impl and each derive.
+    impls: HashSet<(&'ast str, &'ast str)>,
+    /// Traits implemented for every type, by `impl<T> Trait for T`.
+    blanket: HashSet<&'ast str>,
+    /// The type the free functions return.
+    functions: HashMap<&'ast str, &'ast str>,
+    /// The type the associated functions return, by type and name : `Vec2::new`.
+    methods: HashMap<(&'ast str, &'ast str), &'ast str>,
+}
+
+impl<'ast> Visitor<'ast> for Items<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Struct(Struct { name, .. }) | ItemKind::Enum(Enum { name, .. }) => {
+                self.types.insert(&name.name);
+                for path in item.attrs.iter().flat_map(Attribute::derives) {
+                    self.impls.insert((&path.last().name, &name.name));
+                }
+            }
+            ItemKind::Function(function) => {
+                if let Some(output) = function.output.as_ref().and_then(path_name) {
+                    self.functions.insert(&function.name.name, output);
+                }
+            }
+            ItemKind::Impl(imp) => {
+                let Some(ty) = path_name(&imp.self_ty) else {
+                    return;
+                };
+                match &imp.of_trait {
+                    Some(path) if imp.generics.params.iter().any(|p| p.name.name == ty) => {
+                        self.blanket.insert(&path.last().name);
+                    }
+                    Some(path) => {
+                        self.impls.insert((&path.last().name, ty));
+                    }
+                    None => {
+                        for item in &imp.items {
+                            let ItemKind::Function(function) = &item.kind else {
+                                continue;
+                            };
+                            match function.output.as_ref().and_then(path_name) {
+                                Some("Self") => self.methods.insert((ty, &function.name.name), ty),
+                                Some(output) => {
+                                    self.methods.insert((ty, &function.name.name), output)
+                                }
+                                None => None,
+                            };
+                        }
+                    }
+                }
+                // The bodies of the methods hold no items.
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+impl Items<'_> {
+    fn implements(&self, ty: &str, trait_name: &str) -> bool {
+        self.blanket.contains(trait_name) || self.impls.contains(&(trait_name, ty))
+    }
+}
+
+/// The name of a type written as a path, `Vec2` for `Vec2` or `Vec2<f32>`.
+fn path_name(ty: &Ty) -> Option<&str> {
+    match &ty.kind {
+        TyKind::Path(path) => Some(&path.last().name),
+        _ => None,
+    }
+}
+
+/// The trait that overloads `op`, none for `&&` and `||`.
+fn binary_trait(op: BinOp) -> Option<&'static str> {
+    match op {
+        BinOp::Eq | BinOp::Ne => Some("PartialEq"),
+        BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => Some("PartialOrd"),
+        BinOp::And | BinOp::Or => None,
+        op => OverloadedOp::Binary(op)
+            .trait_method()
+            .map(|(trait_name, _)| trait_name),
+    }
+}
+
+struct Checker<'ast, 'd> {
+    items: Items<'ast>,
+    /// The type of `Self` in the impl being checked.
+    self_ty: Option<&'ast str>,
+    /// The locals whose type is a struct or an enum of the crate.
+    locals: HashMap<&'ast str, &'ast str>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Checker<'ast, '_> {
+    /// The struct or the enum of the crate named `name`, `Self` included.
+    fn crate_type(&self, name: &'ast str) -> Option<&'ast str> {
+        let name = match name {
+            "Self" => self.self_ty?,
+            name => name,
+        };
+        self.items.types.contains(name).then_some(name)
+    }
+
+    /// The type of `expr`, when it is known to be a struct or an enum of the crate.
+    fn type_of(&self, expr: &'ast Expr) -> Option<&'ast str> {
+        match &expr.kind {
+            ExprKind::Paren(inner) => self.type_of(inner),
+            ExprKind::StructLit { path, .. } => self.crate_type(&path.last().name),
+            ExprKind::Path(path) => match &path.segments[..] {
+                [segment] => self.locals.get(segment.ident.name.as_str()).copied(),
+                _ => None,
+            },
+            ExprKind::Call(callee, _) => {
+                let ExprKind::Path(path) = &callee.kind else {
+                    return None;
+                };
+                let output = match &path.segments[..] {
+                    [function] => self.items.functions.get(function.ident.name.as_str()),
+                    [ty, function] => {
+                        let ty = self.crate_type(&ty.ident.name)?;
+                        self.items.methods.get(&(ty, function.ident.name.as_str()))
+                    }
+                    _ => None,
+                };
+                self.crate_type(output?)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_> {
+    fn visit_impl(&mut self, item: &'ast Impl) {
+        let outer = self.self_ty.take();
+        self.self_ty = path_name(&item.self_ty);
+        visit::walk_impl(self, item);
+        self.self_ty = outer;
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        let outer = std::mem::take(&mut self.locals);
+        visit::walk_function(self, function);
+        self.locals = outer;
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        visit::walk_param(self, param);
+        if let (Some(name), Some(ty)) = (param.name(), path_name(&param.ty)) {
+            if let Some(ty) = self.crate_type(ty) {
+                self.locals.insert(&name.name, ty);
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        let outer = self.locals.clone();
+        visit::walk_block(self, block);
+        self.locals = outer;
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        visit::walk_local(self, local);
+        let ty = match &local.ty {
+            Some(ty) => path_name(ty).and_then(|ty| self.crate_type(ty)),
+            None => local.init.as_ref().and_then(|init| self.type_of(init)),
+        };
+        if let (
+            PatKind::Binding {
+                name, sub: None, ..
+            },
+            Some(ty),
+        ) = (&local.pat.kind, ty)
+        {
+            self.locals.insert(&name.name, ty);
+        }
+    }
+
+    /// A binding shadows the local of the same name.
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        if let PatKind::Binding { name, .. } = &pattern.kind {
+            self.locals.remove(name.name.as_str());
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        visit::walk_expr(self, expr);
+        match &expr.kind {
+            ExprKind::Binary(op, lhs, _) => {
+                let (Some(trait_name), Some(ty)) = (binary_trait(*op), self.type_of(lhs)) else {
+                    return;
+                };
+                if !self.items.implements(ty, trait_name) {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "binary operation `{}` cannot be applied to type `{ty}`",
+                                op.as_str()
+                            ),
+                            expr.span,
+                        )
+                        .with_label(lhs.span, ty)
+                        .with_note(format!(
+                            "an implementation of `{trait_name}` might be missing for `{ty}`"
+                        )),
+                    );
+                }
+            }
+            ExprKind::Unary(op @ (UnOp::Neg | UnOp::Not), operand) => {
+                let Some(ty) = self.type_of(operand) else {
+                    return;
+                };
+                let op = OverloadedOp::Unary(*op);
+                let (trait_name, _) = op.trait_method().expect("`-` and `!` are overloadable");
+                if !self.items.implements(ty, trait_name) {
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "cannot apply unary operator `{}` to type `{ty}`",
+                                op.as_str()
+                            ),
+                            expr.span,
+                        )
+                        .with_note(format!(
+                            "an implementation of `{trait_name}` might be missing for `{ty}`"
+                        )),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_operators, lower_operators};
+    use crate::codegen::rust::module_source;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        let (mut module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        lower_operators(&mut module);
+        let mut diagnostics = vec![];
+        check_operators(&module, &mut diagnostics);
+        diagnostics.into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn lowers_operator_declarations() {
+        let (mut module, errors) = parse(
+            "struct Vec2 { x: f64, y: f64 }
+            impl Vec2 {
+                pub fn len(&self) -> f64 { self.x }
+                operator +(self, other: Vec2) -> Vec2 { Vec2 { x: self.x + other.x, y: self.y + other.y } }
+                operator *(self, k: f64) -> Vec2 { Vec2 { x: self.x * k, y: self.y * k } }
+                operator -(self) -> Self { Vec2 { x: -self.x, y: -self.y } }
+                operator ==(&self, other: &Vec2) -> bool { self.x == other.x }
+            }
+            impl Sub for Vec2 {
+                fn sub(self, other: Vec2) -> Vec2 { Vec2 { x: self.x - other.x, y: self.y - other.y } }
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        lower_operators(&mut module);
+        let source = module_source(&module);
+        for expected in [
+            "impl Vec2 {\n    pub fn len(&self) -> f64",
+            "impl std::ops::Add for Vec2 {\n    type Output = Vec2;\n\n    fn add(self, other: Vec2) -> Vec2",
+            "impl std::ops::Mul<f64> for Vec2 {\n    type Output = Vec2;\n\n    fn mul(self, k: f64) -> Vec2",
+            "impl std::ops::Neg for Vec2 {\n    type Output = Self;\n\n    fn neg(self) -> Self",
+            "impl PartialEq for Vec2 {\n    fn eq(&self, other: &Vec2) -> bool",
+            "impl std::ops::Sub for Vec2 {\n    type Output = Vec2;\n\n    fn sub(",
+        ] {
+            assert!(source.contains(expected), "{expected:?} in\n{source}");
+        }
+    }
+
+    #[test]
+    fn leaves_imported_and_crate_traits() {
+        let (mut module, _) = parse(
+            "use std::ops::Add;
+            trait Mul { fn mul(self) -> i32; }
+            struct Meters { value: f64 }
+            impl Add for Meters { type Output = f64; fn add(self, other: Meters) -> f64 { 0.0 } }
+            impl Mul for Meters { fn mul(self) -> i32 { 1 } }",
+        );
+        lower_operators(&mut module);
+        let source = module_source(&module);
+        assert!(source.contains("impl Add for Meters {\n    type Output = f64;\n\n    fn add"));
+        assert!(source.contains("impl Mul for Meters {\n    fn mul"));
+    }
+
+    #[test]
+    fn accepts_overloaded_operators() {
+        let messages = messages(
+            "#[derive(PartialEq, PartialOrd)]
+            struct Meters { value: f64 }
+            struct Vec2 { x: f64, y: f64 }
+            impl Vec2 {
+                fn new(x: f64, y: f64) -> Self { Vec2 { x, y } }
+                operator +(self, other: Vec2) -> Vec2 { other }
+                operator -(self) -> Vec2 { self }
+            }
+            impl Mul<f64> for Vec2 { fn mul(self, k: f64) -> Vec2 { self } }
+            fn f(a: Vec2, m: Meters, n: Meters) {
+                let b = Vec2::new(1.0, 2.0);
+                let c = a + b * 2.0;
+                let d = -c;
+                let same = m == n && m < n;
+                let a = 1;
+                let e = a * 2 - 1;
+            }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_missing_implementations() {
+        let messages = messages(
+            "struct Vec2 { x: f64, y: f64 }
+            enum Color { Red, Green }
+            fn origin() -> Vec2 { Vec2 { x: 0.0, y: 0.0 } }
+            fn f(a: Vec2, c: Color) {
+                let b = origin() - a;
+                let same = c == Color::Red;
+                let flipped = !c;
+                let far = Vec2 { x: 1.0, y: 1.0 } > a;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "binary operation `-` cannot be applied to type `Vec2`",
+                "binary operation `==` cannot be applied to type `Color`",
+                "cannot apply unary operator `!` to type `Color`",
+                "binary operation `>` cannot be applied to type `Vec2`",
+            ]
+        );
+    }
+}
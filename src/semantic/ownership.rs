@@ -0,0 +1,862 @@
+//! Ownership checking : a simplified borrow checker over the [`Cfg`] of each
+//! function body, for the errors rustc would otherwise report against generated code.
+//!
+//! Three things are checked :
+//! - a place is not used after being moved, on any path leading to the use ;
+//! - nothing is moved out of a reference or out of an index ;
+//! - a reference held by a local is not used once the local it borrows went out of
+//!   scope or was moved, and no reference to a local is returned.
+//!
+//! Places are locals and paths through their fields. They are moved by a value, a
+//! pattern binding them by value, or a `move` closure capturing them. What goes
+//! through a call or a method returning a reference is left to rustc, as are the
+//! types [`typeck`](super::typeck) does not know, which are taken to be `Copy`.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::cfg::{self, place_text, Action, Cfg, FlowState, Terminator, UseKind};
+use super::prelude;
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+/// The types of the standard library owning what they hold.
+const OWNING: &[&str] = &[
+    "String", "Vec", "Box", "HashMap", "HashSet", "BTreeMap", "BTreeSet", "VecDeque", "Rc", "Arc",
+];
+
+pub fn check_ownership(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut items = Items {
+        resolutions,
+        copy: HashSet::new(),
+        by_value: HashSet::new(),
+    };
+    items.visit_module(module);
+    let mut checker = Checker {
+        copy: items.copy,
+        by_value: items.by_value,
+        resolutions,
+        types,
+        diagnostics,
+        names: HashMap::new(),
+        params: HashSet::new(),
+        reported: HashSet::new(),
+    };
+    checker.visit_module(module);
+}
+
+/// The types implementing `Copy`, and the methods taking `self`.
+struct Items<'r> {
+    resolutions: &'r Resolutions,
+    copy: HashSet<NodeId>,
+    by_value: HashSet<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Items<'_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let is_copy = |path: &Path| path.last().name == "Copy";
+        match &item.kind {
+            ItemKind::Struct(_) | ItemKind::Enum(_)
+                if item.attrs.iter().flat_map(Attribute::derives).any(is_copy) =>
+            {
+                self.copy.insert(item.id);
+            }
+            ItemKind::Impl(imp) if imp.of_trait.as_ref().is_some_and(is_copy) => {
+                if let Some(Res::Item(id)) = self.resolutions.get(imp.self_ty.id) {
+                    self.copy.insert(id);
+                }
+            }
+            ItemKind::Function(function) => {
+                let by_value = function.params.first().is_some_and(|param| {
+                    param.is_self() && !matches!(param.ty.kind, TyKind::Ref { .. })
+                });
+                if by_value {
+                    self.by_value.insert(item.id);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+/// The uses of the locals declared outside of a closure, in its body.
+struct Captures<'ast, 'r> {
+    resolutions: &'r Resolutions,
+    /// The bindings declared in the closure.
+    inner: HashSet<NodeId>,
+    seen: HashSet<NodeId>,
+    uses: Vec<&'ast Expr>,
+}
+
+impl<'ast> Visitor<'ast> for Captures<'ast, '_> {
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        if let PatKind::Binding { .. } = pattern.kind {
+            self.inner.insert(pattern.id);
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let (ExprKind::Path(_), Some(Res::Local(local))) =
+            (&expr.kind, self.resolutions.get(expr.id))
+        {
+            if !self.inner.contains(&local) && self.seen.insert(local) {
+                self.uses.push(expr);
+            }
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// A local, or a path through its fields : `p`, `p.name`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Place {
+    local: NodeId,
+    fields: Vec<String>,
+}
+
+impl Place {
+    /// Whether `self` is `other`, or a field of it.
+    fn within(&self, other: &Place) -> bool {
+        self.local == other.local && self.fields.starts_with(&other.fields)
+    }
+}
+
+/// What may be true at a point of the body, on one of the paths leading to it.
+#[derive(Debug, Clone, Default)]
+struct State<'ast> {
+    /// The places that may have been moved, with the expression moving them.
+    moved: HashMap<Place, &'ast Expr>,
+    /// The locals each local may borrow, with where they were borrowed.
+    loans: HashMap<NodeId, HashSet<(NodeId, Span)>>,
+    /// The locals whose scope ended, with where.
+    dead: HashMap<NodeId, Span>,
+    /// The loans whose local was moved after them, with the expression moving it.
+    invalidated: HashMap<(NodeId, Span), &'ast Expr>,
+}
+
+impl<'ast> FlowState for State<'ast> {
+    fn join(&mut self, other: &State<'ast>) -> bool {
+        let before = self.size();
+        for (place, expr) in &other.moved {
+            self.moved.entry(place.clone()).or_insert(expr);
+        }
+        for (local, loans) in &other.loans {
+            self.loans.entry(*local).or_default().extend(loans);
+        }
+        for (local, span) in &other.dead {
+            self.dead.entry(*local).or_insert(*span);
+        }
+        for (loan, expr) in &other.invalidated {
+            self.invalidated.entry(*loan).or_insert(expr);
+        }
+        before != self.size()
+    }
+}
+
+impl<'ast> State<'ast> {
+    fn size(&self) -> (usize, usize, usize, usize) {
+        let loans = self.loans.values().map(HashSet::len).sum();
+        (
+            self.moved.len(),
+            loans,
+            self.dead.len(),
+            self.invalidated.len(),
+        )
+    }
+
+    /// Records that `place`, of the local `local`, moves : the loans of `local` the
+    /// locals hold may no longer be used.
+    fn invalidate(&mut self, local: NodeId, place: &'ast Expr) {
+        let loans = self.loans.values().flatten();
+        for &(owner, borrow) in loans.filter(|(owner, _)| *owner == local) {
+            self.invalidated.entry((owner, borrow)).or_insert(place);
+        }
+    }
+}
+
+struct Checker<'ast, 'r, 'd> {
+    copy: HashSet<NodeId>,
+    by_value: HashSet<NodeId>,
+    resolutions: &'r Resolutions,
+    types: &'r TypeckResults,
+    diagnostics: &'d mut Vec<Diagnostic>,
+    /// The names of the bindings of the function being checked.
+    names: HashMap<NodeId, &'ast str>,
+    params: HashSet<NodeId>,
+    /// The uses already reported, reached again through another path.
+    reported: HashSet<Span>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_function(&mut self, function: &'ast Function) {
+        let Some(cfg) = Cfg::build(function) else {
+            return;
+        };
+        self.names.clear();
+        self.params.clear();
+        let mut params = vec![];
+        for param in &function.params {
+            cfg::bindings(&param.pat, &mut params);
+        }
+        self.params.extend(params);
+        visit::walk_function(self, function);
+        self.check_body(&cfg);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        if let PatKind::Binding { name, .. } = &pattern.kind {
+            self.names.insert(pattern.id, &name.name);
+        }
+        visit::walk_pattern(self, pattern);
+    }
+}
+
+impl<'ast> Checker<'ast, '_, '_> {
+    /// Finds what may be true at the start of each block, then checks the blocks.
+    fn check_body(&mut self, cfg: &Cfg<'ast>) {
+        let states = cfg.forward(|action, state| self.apply(action, state, false));
+        for (block, state) in cfg.blocks.iter().zip(states) {
+            let Some(mut state) = state else {
+                continue;
+            };
+            for action in &block.actions {
+                self.apply(action, &mut state, true);
+            }
+            if let Terminator::Return(Some(value)) = block.terminator {
+                self.check_return(value, &state);
+            }
+        }
+    }
+
+    /// Updates `state` after `action`, reporting its errors when `report` is set.
+    fn apply(&mut self, action: &Action<'ast>, state: &mut State<'ast>, report: bool) {
+        match *action {
+            Action::Use { place, kind } => {
+                if report {
+                    self.check_loans(place, state);
+                }
+                let ty = self.types.expr(place.id);
+                let moves = match kind {
+                    UseKind::Consume => !self.is_copy(ty),
+                    UseKind::Receiver(call) => self.consumes(call) && !self.is_copy(ty),
+                    UseKind::Borrow { .. } | UseKind::Inspect => false,
+                };
+                match self.place(place) {
+                    Some(target) => {
+                        if report {
+                            let borrows = !moves && !matches!(kind, UseKind::Consume);
+                            self.check_moved(&target, place, borrows, state);
+                        }
+                        if moves {
+                            state.invalidate(target.local, place);
+                            state.moved.entry(target).or_insert(place);
+                        }
+                    }
+                    None if moves && report => self.check_move_out(place),
+                    None => {}
+                }
+            }
+            Action::Assign { place, value } => {
+                if let Some(target) = self.place(place) {
+                    state.moved.retain(|moved, _| !moved.within(&target));
+                    if target.fields.is_empty() {
+                        state
+                            .invalidated
+                            .retain(|(owner, _), _| *owner != target.local);
+                        let loans = self.loans_of(value, state);
+                        state.loans.insert(target.local, loans);
+                    }
+                }
+            }
+            Action::Bind { pat, init } => {
+                let mut ids = vec![];
+                cfg::bindings(pat, &mut ids);
+                for id in &ids {
+                    state.moved.retain(|moved, _| moved.local != *id);
+                    state.dead.remove(id);
+                    state.loans.remove(id);
+                }
+                if let (PatKind::Binding { sub: None, .. }, Some(init)) = (&pat.kind, init) {
+                    let loans = self.loans_of(init, state);
+                    state.loans.insert(pat.id, loans);
+                }
+                // A binding taking a part of the scrutinee by value moves that part.
+                if let Some((init, place)) = init.and_then(|init| Some((init, self.place(init)?))) {
+                    let mut moved = vec![];
+                    self.pattern_moves(pat, place, &mut moved);
+                    for target in moved {
+                        state.invalidate(target.local, init);
+                        state.moved.entry(target).or_insert(init);
+                    }
+                }
+            }
+            Action::Capture { closure } => {
+                let ExprKind::Closure(inner) = &closure.kind else {
+                    return;
+                };
+                for capture in self.captures(inner) {
+                    let Some(target) = self.place(capture) else {
+                        continue;
+                    };
+                    let moves = inner.capture == CaptureBy::Value
+                        && !self.is_copy(self.types.expr(capture.id));
+                    if report {
+                        self.check_moved(&target, capture, !moves, state);
+                    }
+                    if moves {
+                        state.invalidate(target.local, capture);
+                        state.moved.entry(target).or_insert(capture);
+                    }
+                }
+            }
+            Action::ScopeEnd { ref bindings, span } => {
+                for binding in bindings {
+                    state.dead.insert(*binding, span);
+                }
+            }
+        }
+    }
+
+    /// Whether values of the type `ty` are copied rather than moved.
+    fn is_copy(&self, ty: Option<&Type>) -> bool {
+        let Some(ty) = ty else {
+            return true;
+        };
+        match ty {
+            Type::Ref(mutable, _) => !mutable,
+            Type::Tuple(tys) => tys.iter().all(|ty| self.is_copy(Some(ty))),
+            Type::Array(inner, _) => self.is_copy(Some(inner)),
+            Type::Adt(id, ..) => self.copy.contains(id),
+            Type::Named(name, args) => match name.as_str() {
+                "Option" | "Result" => args.iter().all(|ty| self.is_copy(Some(ty))),
+                name => !OWNING.contains(&name),
+            },
+            _ => true,
+        }
+    }
+
+    /// Whether the method called takes `self`, moving its receiver.
+    fn consumes(&self, call: &Expr) -> bool {
+        let ExprKind::MethodCall {
+            receiver, method, ..
+        } = &call.kind
+        else {
+            return false;
+        };
+        if let Some(function) = self.types.method(call.id) {
+            return self.by_value.contains(&function);
+        }
+        matches!(self.types.expr(receiver.id), Some(Type::Named(name, _))
+            if prelude::is_consuming(name, &method.name))
+    }
+
+    /// The place `expr` names, unless it goes through a reference or an index.
+    fn place(&self, expr: &Expr) -> Option<Place> {
+        match &expr.kind {
+            ExprKind::Paren(inner) => self.place(inner),
+            ExprKind::Path(_) => match self.resolutions.get(expr.id) {
+                Some(Res::Local(local)) => Some(Place {
+                    local,
+                    fields: vec![],
+                }),
+                _ => None,
+            },
+            ExprKind::Field(base, name) => match self.types.expr(base.id) {
+                Some(Type::Ref(..)) => None,
+                _ => {
+                    let mut place = self.place(base)?;
+                    place.fields.push(name.name.clone());
+                    Some(place)
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// The local a place is reached from, through anything.
+    fn root(&self, expr: &Expr) -> Option<NodeId> {
+        match &expr.kind {
+            ExprKind::Path(_) => match self.resolutions.get(expr.id) {
+                Some(Res::Local(local)) => Some(local),
+                _ => None,
+            },
+            ExprKind::Paren(inner)
+            | ExprKind::Field(inner, _)
+            | ExprKind::Index(inner, _)
+            | ExprKind::Unary(UnOp::Deref, inner) => self.root(inner),
+            _ => None,
+        }
+    }
+
+    /// The local owning the data of a place, when it is not behind a reference.
+    fn owner(&self, expr: &Expr) -> Option<NodeId> {
+        match &expr.kind {
+            ExprKind::Paren(inner) => self.owner(inner),
+            ExprKind::Path(_) => self.root(expr),
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => match self.types.expr(base.id) {
+                Some(Type::Ref(..)) => None,
+                _ => self.owner(base),
+            },
+            ExprKind::Unary(UnOp::Deref, inner) => match self.types.expr(inner.id) {
+                Some(Type::Named(name, _)) if name == "Box" => self.owner(inner),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The locals the value of `expr` borrows.
+    fn loans_of(&self, expr: &Expr, state: &State) -> HashSet<(NodeId, Span)> {
+        let through = |local: Option<NodeId>| {
+            local
+                .and_then(|local| state.loans.get(&local))
+                .cloned()
+                .unwrap_or_default()
+        };
+        match &expr.kind {
+            ExprKind::Paren(inner) => self.loans_of(inner, state),
+            ExprKind::Block(block) => block
+                .tail()
+                .map(|tail| self.loans_of(tail, state))
+                .unwrap_or_default(),
+            ExprKind::AddrOf { expr: place, .. } => match self.owner(place) {
+                Some(owner) => HashSet::from([(owner, expr.span)]),
+                // `&r.name` borrows what `r` borrows.
+                None => through(self.root(place)),
+            },
+            ExprKind::Path(_) => through(self.root(expr)),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// The parts of `place` the bindings of `pattern` take by value.
+    fn pattern_moves(&self, pattern: &Pattern, place: Place, out: &mut Vec<Place>) {
+        let field = |name: &str| {
+            let mut place = place.clone();
+            place.fields.push(name.to_string());
+            place
+        };
+        match &pattern.kind {
+            PatKind::Binding { .. } if !self.is_copy(self.types.binding(pattern.id)) => {
+                out.push(place)
+            }
+            PatKind::Binding { sub: Some(sub), .. } => self.pattern_moves(sub, place, out),
+            PatKind::Paren(inner) => self.pattern_moves(inner, place, out),
+            // The fields of a struct are moved apart, those of a variant with it.
+            PatKind::Struct { fields, .. }
+                if matches!(self.resolutions.get(pattern.id), Some(Res::Item(_))) =>
+            {
+                for pat in fields {
+                    self.pattern_moves(&pat.pat, field(&pat.name.name), out);
+                }
+            }
+            PatKind::Tuple(patterns) => {
+                for (index, pat) in patterns.iter().enumerate() {
+                    self.pattern_moves(pat, field(&index.to_string()), out);
+                }
+            }
+            PatKind::Struct { fields, .. } => {
+                for pat in fields {
+                    self.pattern_moves(&pat.pat, place.clone(), out);
+                }
+            }
+            PatKind::TupleStruct(_, patterns) | PatKind::Or(patterns) => {
+                for pat in patterns {
+                    self.pattern_moves(pat, place.clone(), out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The first use of each local a closure captures, in its body.
+    fn captures(&self, closure: &'ast Closure) -> Vec<&'ast Expr> {
+        let mut captures = Captures {
+            resolutions: self.resolutions,
+            inner: HashSet::new(),
+            seen: HashSet::new(),
+            uses: vec![],
+        };
+        for param in &closure.params {
+            captures.visit_pattern(&param.pat);
+        }
+        captures.visit_expr(&closure.body);
+        captures.uses
+    }
+
+    fn name(&self, local: NodeId) -> &'ast str {
+        self.names.get(&local).copied().unwrap_or("_")
+    }
+
+    fn check_moved(&mut self, target: &Place, expr: &Expr, borrows: bool, state: &State<'ast>) {
+        let moved = state
+            .moved
+            .iter()
+            .filter(|(moved, _)| target.within(moved) || moved.within(target))
+            .min_by_key(|(_, moved)| moved.span.start);
+        let Some((place, moved)) = moved else {
+            return;
+        };
+        if !self.reported.insert(expr.span) {
+            return;
+        }
+        let verb = if borrows { "borrow" } else { "use" };
+        let whole = if target.within(place) {
+            ""
+        } else {
+            "partially "
+        };
+        let label = if moved.span.start >= expr.span.start {
+            "value moved here, in previous iteration of loop"
+        } else {
+            "value moved here"
+        };
+        let ty = self.types.expr(moved.id).unwrap_or(&Type::Unknown);
+        self.diagnostics.push(
+            Diagnostic::error(
+                format!("{verb} of {whole}moved value: `{}`", place_text(expr)),
+                expr.span,
+            )
+            .with_label(moved.span, label)
+            .with_note(format!(
+                "move occurs because `{}` has type `{ty}`, which does not implement the `Copy` trait",
+                place_text(moved)
+            )),
+        );
+    }
+
+    /// Reports a move out of a reference or out of an index.
+    fn check_move_out(&mut self, place: &Expr) {
+        let Some(message) = self.move_out(place, place) else {
+            return;
+        };
+        let ty = self.types.expr(place.id).unwrap_or(&Type::Unknown);
+        let value = match place.kind {
+            ExprKind::Index(..) => "value".to_string(),
+            _ => format!("`{}`", place_text(place)),
+        };
+        self.diagnostics.push(
+            Diagnostic::error(message, place.span).with_note(format!(
+                "move occurs because {value} has type `{ty}`, which does not implement the `Copy` trait"
+            )),
+        );
+    }
+
+    fn move_out(&self, place: &Expr, expr: &Expr) -> Option<String> {
+        let behind = |ty: Option<&Type>| match ty {
+            Some(Type::Ref(mutable, _)) => {
+                let kind = if *mutable { "mutable" } else { "shared" };
+                Some(format!(
+                    "cannot move out of `{}` which is behind a {kind} reference",
+                    place_text(place)
+                ))
+            }
+            _ => None,
+        };
+        match &expr.kind {
+            ExprKind::Paren(inner) => self.move_out(place, inner),
+            ExprKind::Field(base, _) => {
+                behind(self.types.expr(base.id)).or_else(|| self.move_out(place, base))
+            }
+            ExprKind::Unary(UnOp::Deref, inner) => match self.types.expr(inner.id) {
+                Some(Type::Named(name, _)) if name == "Box" => self.move_out(place, inner),
+                ty => behind(ty),
+            },
+            ExprKind::Index(base, _) => {
+                let mut ty = self.types.expr(base.id).unwrap_or(&Type::Unknown);
+                while let Type::Ref(_, inner) = ty {
+                    ty = inner;
+                }
+                Some(format!("cannot move out of index of `{ty}`"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reports the use of a reference to a local whose scope ended or which moved.
+    fn check_loans(&mut self, place: &Expr, state: &State) {
+        let Some(loans) = self.root(place).and_then(|root| state.loans.get(&root)) else {
+            return;
+        };
+        let mut moves: Vec<_> = loans
+            .iter()
+            .filter_map(|&loan| Some((loan, *state.invalidated.get(&loan)?)))
+            .collect();
+        moves.sort_by_key(|(_, moved)| moved.span.start);
+        for ((owner, borrow), moved) in moves {
+            if !self.reported.insert(moved.span) {
+                continue;
+            }
+            let name = self.name(owner);
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "cannot move out of `{}` because it is borrowed",
+                        place_text(moved)
+                    ),
+                    moved.span,
+                )
+                .with_label(borrow, format!("borrow of `{name}` occurs here"))
+                .with_label(place.span, "borrow later used here"),
+            );
+        }
+        let mut loans: Vec<_> = loans
+            .iter()
+            .filter_map(|(owner, borrow)| Some((*owner, *borrow, *state.dead.get(owner)?)))
+            .collect();
+        loans.sort_by_key(|(_, borrow, _)| borrow.start);
+        for (owner, borrow, end) in loans {
+            if !self.reported.insert(borrow) {
+                continue;
+            }
+            let name = self.name(owner);
+            self.diagnostics.push(
+                Diagnostic::error(format!("`{name}` does not live long enough"), borrow)
+                    .with_label(end, format!("`{name}` dropped here while still borrowed"))
+                    .with_label(place.span, "borrow later used here"),
+            );
+        }
+    }
+
+    /// Reports a reference to a local leaving the function.
+    fn check_return(&mut self, value: &Expr, state: &State) {
+        let mut value = value;
+        while let ExprKind::Paren(inner) = &value.kind {
+            value = inner;
+        }
+        let kind = |local| {
+            if self.params.contains(&local) {
+                "function parameter"
+            } else {
+                "local variable"
+            }
+        };
+        if let ExprKind::AddrOf { expr: place, .. } = &value.kind {
+            if let Some(owner) = self.owner(place) {
+                let message = match self.place(place) {
+                    Some(target) if target.fields.is_empty() => format!(
+                        "cannot return reference to {} `{}`",
+                        kind(owner),
+                        self.name(owner)
+                    ),
+                    _ => format!(
+                        "cannot return reference to local data `{}`",
+                        place_text(place)
+                    ),
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(message, value.span)
+                        .with_note("returns a reference to data owned by the current function"),
+                );
+                return;
+            }
+        }
+        let mut loans: Vec<_> = self.loans_of(value, state).into_iter().collect();
+        loans.sort_by_key(|(_, borrow)| borrow.start);
+        if let Some((owner, borrow)) = loans.first() {
+            let name = self.name(*owner);
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!("cannot return value referencing {} `{name}`", kind(*owner)),
+                    value.span,
+                )
+                .with_label(*borrow, format!("`{name}` is borrowed here")),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_ownership;
+    use crate::semantic;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_ownership)
+    }
+
+    #[test]
+    fn accepts_moves_on_disjoint_paths() {
+        let messages = messages(
+            "#[derive(Clone, Copy)]
+            struct Point { x: i32, y: i32 }
+            fn take(s: String) -> usize { s.len() }
+            fn first(v: &Vec<String>) -> &String { let r = &v[0]; r }
+            fn main() {
+                let p = Point { x: 1, y: 2 };
+                let q = p;
+                let sum = p.x + q.y;
+                let mut s = String::from(\"a\");
+                let mut i = 0;
+                while i < 3 {
+                    take(s);
+                    s = String::new();
+                    i = i + 1;
+                }
+                let a = String::from(\"b\");
+                let b = if i > 0 { a } else { String::new() };
+                let o = Some(b);
+                match o {
+                    Some(t) => { take(t); }
+                    None => {}
+                }
+                let v: Vec<String> = Vec::new();
+                for item in v.iter() { let n = item.len(); }
+                for item in v { take(item); }
+                let n = s.len();
+                let c = || s.len();
+                let k = 5;
+                let d = move || k + c();
+                let e = k + s.len();
+                let pair = (String::new(), 1);
+                match pair { (name, n) => { take(name); } }
+                let n = pair.1;
+            }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_uses_after_move() {
+        let messages = messages(
+            "struct Person { name: String, age: u32 }
+            fn consume(p: Person) -> u32 { p.age }
+            fn main() {
+                let p = Person { name: String::from(\"a\"), age: 3 };
+                consume(p);
+                let age = p.age;
+                let q = Person { name: String::from(\"b\"), age: 4 };
+                let name = q.name;
+                consume(q);
+                let v: Vec<String> = Vec::new();
+                let mut i = 0;
+                while i < 3 {
+                    let w = v;
+                    i = i + 1;
+                }
+                let s = String::from(\"s\");
+                let t = s;
+                let n = s.len();
+                let c = String::from(\"c\");
+                let f = move || c.len();
+                let l = c.len();
+                let m = String::from(\"m\");
+                match m { x => { let y = x; } }
+                let z = m;
+                let u = String::from(\"u\");
+                let w = u;
+                let g = || u.len();
+                let r = Person { name: String::from(\"r\"), age: 5 };
+                match r { Person { name, age } => {} }
+                let age = r.age;
+                let again = r.name;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "use of moved value: `p.age`",
+                "use of partially moved value: `q`",
+                "use of moved value: `v`",
+                "borrow of moved value: `s`",
+                "borrow of moved value: `c`",
+                "use of moved value: `m`",
+                "borrow of moved value: `u`",
+                "use of moved value: `r.name`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_moves_out_of_borrowed_content() {
+        let messages = messages(
+            "struct Person { name: String }
+            fn name(p: &Person) -> String { p.name }
+            fn first(v: &Vec<String>) -> String { v[0] }
+            fn unbox(b: Box<String>) -> String { *b }
+            fn main() {}",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot move out of `p.name` which is behind a shared reference",
+                "cannot move out of index of `Vec<String>`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_borrows_outliving_their_owner() {
+        let messages = messages(
+            "struct Person { name: String }
+            fn dangling() -> &str { let s = String::from(\"s\"); &s }
+            fn field(p: Person) -> &String { &p.name }
+            fn through() -> &String { let s = String::from(\"s\"); let r = &s; r }
+            fn main() {
+                let r;
+                {
+                    let x = String::from(\"x\");
+                    r = &x;
+                }
+                let n = r.len();
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot return reference to local variable `s`",
+                "cannot return reference to local data `p.name`",
+                "cannot return value referencing local variable `s`",
+                "`x` does not live long enough",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_moves_of_borrowed_locals() {
+        let messages = messages(
+            "fn take(s: String) -> usize { s.len() }
+            fn main() {
+                let s = String::from(\"s\");
+                let r = &s;
+                take(s);
+                let n = r.len();
+                let t = String::from(\"t\");
+                let u = &t;
+                let m = u.len();
+                take(t);
+                let mut v = String::from(\"v\");
+                let mut i = 0;
+                while i < 3 {
+                    let w = &v;
+                    let k = w.len();
+                    take(v);
+                    v = String::new();
+                    i = i + 1;
+                }
+                let a = String::from(\"a\");
+                let b = &a;
+                let c = move || a.len();
+                let d = b.len();
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot move out of `s` because it is borrowed",
+                "cannot move out of `a` because it is borrowed",
+            ]
+        );
+    }
+}
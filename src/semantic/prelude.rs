@@ -0,0 +1,193 @@
+//! The names every module uses without importing them. The generated code gets them
+//! from the prelude of Rust, this module lets the passes recognize them.
+//!
+//! A type of the crate with the same name shadows the prelude one, and its variants
+//! with it.
+
+/// The enums of the prelude, with their variants.
+pub const ENUMS: &[(&str, &[&str])] = &[("Option", &["Some", "None"]), ("Result", &["Ok", "Err"])];
+
+/// The enum of the prelude declaring the variant `name` : `Option` for `Some`.
+pub fn enum_of(name: &str) -> Option<&'static str> {
+    ENUMS
+        .iter()
+        .find(|(_, variants)| variants.contains(&name))
+        .map(|(ty, _)| *ty)
+}
+
+/// The types and the traits of the prelude, primitive types included.
+pub const TYPES: &[&str] = &[
+    "bool",
+    "char",
+    "str",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "f32",
+    "f64",
+    "Box",
+    "Option",
+    "Result",
+    "String",
+    "Vec",
+    "AsMut",
+    "AsRef",
+    "Clone",
+    "Copy",
+    "Default",
+    "DoubleEndedIterator",
+    "Drop",
+    "Eq",
+    "ExactSizeIterator",
+    "Extend",
+    "Fn",
+    "FnMut",
+    "FnOnce",
+    "From",
+    "FromIterator",
+    "Into",
+    "IntoIterator",
+    "Iterator",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+    "Send",
+    "Sized",
+    "Sync",
+    "ToOwned",
+    "ToString",
+    "TryFrom",
+    "TryInto",
+];
+
+/// The functions of the prelude. The variants of [`ENUMS`] are values too.
+pub const FUNCTIONS: &[&str] = &["drop"];
+
+/// The methods taking `&mut self` of the types of the prelude.
+pub const MUTATING_METHODS: &[(&str, &[&str])] = &[
+    (
+        "Vec",
+        &[
+            "push",
+            "pop",
+            "insert",
+            "remove",
+            "clear",
+            "truncate",
+            "extend",
+            "append",
+            "retain",
+            "dedup",
+            "sort",
+            "sort_by",
+            "sort_by_key",
+            "sort_unstable",
+            "reverse",
+            "drain",
+            "swap",
+            "swap_remove",
+            "iter_mut",
+            "resize",
+            "fill",
+        ],
+    ),
+    (
+        "String",
+        &[
+            "push",
+            "push_str",
+            "pop",
+            "insert",
+            "insert_str",
+            "remove",
+            "clear",
+            "truncate",
+            "extend",
+        ],
+    ),
+    (
+        "Option",
+        &["take", "replace", "insert", "get_or_insert", "as_mut"],
+    ),
+];
+
+/// Whether the method `name` of the prelude type `ty` takes `&mut self`.
+pub fn is_mutating(ty: &str, name: &str) -> bool {
+    MUTATING_METHODS
+        .iter()
+        .any(|(owner, methods)| *owner == ty && methods.contains(&name))
+}
+
+/// The methods taking `self` of the types of the prelude, which move their receiver.
+pub const CONSUMING_METHODS: &[(&str, &[&str])] = &[
+    ("Vec", &["into_iter", "into_boxed_slice"]),
+    ("String", &["into_bytes", "into_boxed_str"]),
+    (
+        "Option",
+        &[
+            "unwrap",
+            "expect",
+            "unwrap_or",
+            "unwrap_or_else",
+            "unwrap_or_default",
+            "map",
+            "and_then",
+            "ok_or",
+        ],
+    ),
+    (
+        "Result",
+        &[
+            "unwrap",
+            "expect",
+            "unwrap_or",
+            "unwrap_or_else",
+            "unwrap_or_default",
+            "map",
+            "map_err",
+            "and_then",
+            "ok",
+            "err",
+        ],
+    ),
+];
+
+/// Whether the method `name` of the prelude type `ty` takes `self`.
+pub fn is_consuming(ty: &str, name: &str) -> bool {
+    CONSUMING_METHODS
+        .iter()
+        .any(|(owner, methods)| *owner == ty && methods.contains(&name))
+}
+
+/// The crates every crate can name, `std::process::exit`.
+pub const CRATES: &[&str] = &["std", "core", "alloc"];
+
+/// Whether `name` is a value of the prelude : a function or a variant.
+pub fn is_value(name: &str) -> bool {
+    FUNCTIONS.contains(&name) || enum_of(name).is_some()
+}
+
+/// The traits of the standard library imported for their methods, which no path
+/// names : `use std::fmt::Write;` for `write_str`.
+pub const METHOD_TRAITS: &[&str] = &[
+    "Any",
+    "BufRead",
+    "Borrow",
+    "BorrowMut",
+    "Error",
+    "FromStr",
+    "Hash",
+    "Hasher",
+    "Read",
+    "Seek",
+    "Write",
+];
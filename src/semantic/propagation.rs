@@ -0,0 +1,356 @@
+//! Checks each `expr?` against the function or closure it returns from. `?` on an
+//! `Option` needs a function returning an `Option`, and on a `Result` a function
+//! returning a `Result` whose error type converts from the error of `expr`. rustc
+//! reports those as missing `FromResidual` or `From` implementations, which says
+//! little about what went wrong.
+//!
+//! The types of `expr` and of the function are those `typeck` found. Operands whose
+//! type is not known are left to rustc, as are the functions returning a type that
+//! may implement `Try` : a generic parameter or a type from outside the crate.
+
+use std::collections::HashSet;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+pub fn check_propagation(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut conversions = Conversions {
+        resolutions,
+        impls: vec![],
+        blanket: HashSet::new(),
+    };
+    conversions.visit_module(module);
+    let mut checker = Checker {
+        conversions,
+        types,
+        scopes: vec![],
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// What `?` applies to.
+#[derive(Debug, Clone, Copy)]
+enum Carrier<'t> {
+    Option,
+    /// With its error type, when it is known.
+    Result(Option<&'t Type>),
+}
+
+impl<'t> Carrier<'t> {
+    fn of(ty: &'t Type) -> Option<Carrier<'t>> {
+        match ty {
+            Type::Named(name, _) if name == "Option" => Some(Carrier::Option),
+            // `fmt::Result` names its error type itself.
+            Type::Named(name, args) if name == "Result" => Some(Carrier::Result(args.get(1))),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Carrier::Option => "Option",
+            Carrier::Result(_) => "Result",
+        }
+    }
+}
+
+/// The type a function returns, as far as `?` is concerned.
+enum Output<'t> {
+    Carrier(Carrier<'t>),
+    /// A type which `?` cannot return.
+    Other(&'t Type),
+    /// Left to rustc.
+    Unknown,
+}
+
+impl<'t> Output<'t> {
+    fn of(ty: Option<&'t Type>) -> Output<'t> {
+        let Some(ty) = ty else {
+            return Output::Unknown;
+        };
+        if let Some(carrier) = Carrier::of(ty) {
+            return Output::Carrier(carrier);
+        }
+        match ty {
+            Type::Named(name, _) if name == "String" => Output::Other(ty),
+            Type::Named(..) | Type::Param(..) | Type::Never | Type::Var(_) | Type::Unknown => {
+                Output::Unknown
+            }
+            _ => Output::Other(ty),
+        }
+    }
+}
+
+/// The `impl From<Source> for Target` of the crate.
+struct Conversions<'ast, 'r> {
+    resolutions: &'r Resolutions,
+    /// `(target, source)`, by the definition of the target.
+    impls: Vec<(NodeId, &'ast Ty)>,
+    /// The targets of `impl<E> From<E> for Target`, which convert from anything.
+    blanket: HashSet<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Conversions<'ast, '_> {
+    fn visit_impl(&mut self, item: &'ast Impl) {
+        let Some(of_trait) = &item.of_trait else {
+            return;
+        };
+        let segment = of_trait.segments.last().unwrap();
+        let ("From", [source]) = (segment.ident.name.as_str(), &segment.args[..]) else {
+            return;
+        };
+        let Some(Res::Item(target)) = self.resolutions.get(item.self_ty.id) else {
+            return;
+        };
+        match self.resolutions.get(source.id) {
+            Some(Res::Generic(_)) => {
+                self.blanket.insert(target);
+            }
+            _ => self.impls.push((target, source)),
+        }
+    }
+}
+
+impl Conversions<'_, '_> {
+    /// Whether the crate converts `from` into the type `target` defines.
+    fn converts(&self, from: &Type, target: NodeId) -> bool {
+        self.blanket.contains(&target)
+            || self
+                .impls
+                .iter()
+                .filter(|(to, _)| *to == target)
+                .any(|(_, source)| self.is(source, from))
+    }
+
+    /// Whether `ty` is written `source`. Types from outside the crate are compared
+    /// by name, as typeck knows them.
+    fn is(&self, source: &Ty, ty: &Type) -> bool {
+        match (self.resolutions.get(source.id), ty, &source.kind) {
+            (Some(Res::Item(id)), Type::Adt(def, ..), _) => id == *def,
+            (_, Type::Named(name, _), TyKind::Path(path)) => {
+                path.segments.last().unwrap().ident.name == *name
+            }
+            _ => source.to_text() == ty.to_string(),
+        }
+    }
+}
+
+/// A function or a closure, which `?` returns from.
+struct Scope<'t> {
+    what: &'static str,
+    output: Output<'t>,
+    /// The return type, or the name of a function returning `()`.
+    span: Span,
+}
+
+struct Checker<'ast, 'r, 't, 'd> {
+    conversions: Conversions<'ast, 'r>,
+    types: &'t TypeckResults,
+    scopes: Vec<Scope<'t>>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_, '_> {
+    fn visit_function(&mut self, function: &'ast Function) {
+        let Some(body) = &function.body else {
+            return;
+        };
+        let span = function
+            .output
+            .as_ref()
+            .map_or(function.name.span, |ty| ty.span);
+        self.scopes.push(Scope {
+            what: "function",
+            output: Output::of(self.types.output(body.id)),
+            span,
+        });
+        visit::walk_function(self, function);
+        self.scopes.pop();
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Try(operand) => self.check(operand, expr.span),
+            ExprKind::Closure(closure) => {
+                let output = match self.types.expr(expr.id) {
+                    Some(Type::Fn(_, output)) => Output::of(Some(output)),
+                    _ => Output::Unknown,
+                };
+                let span = closure
+                    .output
+                    .as_ref()
+                    .map_or(closure.body.span, |ty| ty.span);
+                self.scopes.push(Scope {
+                    what: "closure",
+                    output,
+                    span,
+                });
+                visit::walk_expr(self, expr);
+                self.scopes.pop();
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl Checker<'_, '_, '_, '_> {
+    fn check(&mut self, operand: &Expr, span: Span) {
+        // In a constant, `?` is left to rustc.
+        let Some(scope) = self.scopes.last() else {
+            return;
+        };
+        let expected = match scope.output {
+            Output::Carrier(carrier) => carrier,
+            Output::Unknown => return,
+            Output::Other(ty) => {
+                let diagnostic = Diagnostic::error(
+                    format!(
+                        "the `?` operator can only be used in a {} that returns `Result` or `Option`",
+                        scope.what
+                    ),
+                    span,
+                )
+                .with_label(scope.span, format!("this {} returns `{ty}`", scope.what));
+                self.diagnostics.push(diagnostic);
+                return;
+            }
+        };
+        let Some(found) = self.types.expr(operand.id).and_then(Carrier::of) else {
+            return;
+        };
+        match (found, expected) {
+            (Carrier::Option, Carrier::Option) => {}
+            (Carrier::Result(from), Carrier::Result(to)) => {
+                if let (Some(from), Some(to)) = (from, to) {
+                    self.check_conversion(from, to, span);
+                }
+            }
+            _ => {
+                let note = match found {
+                    Carrier::Option => "use `.ok_or(error)?` to turn the `Option` into a `Result`",
+                    Carrier::Result(_) => "use `.ok()?` to discard the error of the `Result`",
+                };
+                let diagnostic = Diagnostic::error(
+                    format!(
+                        "the `?` operator can only be used on `{0}`s, not `{1}`s, in a {2} that returns `{0}`",
+                        expected.name(),
+                        found.name(),
+                        scope.what
+                    ),
+                    span,
+                )
+                .with_label(
+                    scope.span,
+                    format!("this {} returns `{}`", scope.what, expected.name()),
+                )
+                .with_note(note);
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    /// `?` converts the error with `From`, which is only known for the error types
+    /// of the crate.
+    fn check_conversion(&mut self, from: &Type, to: &Type, span: Span) {
+        let Type::Adt(target, ..) = to else {
+            return;
+        };
+        if from == to
+            || matches!(from, Type::Var(_) | Type::Unknown)
+            || self.conversions.converts(from, *target)
+        {
+            return;
+        }
+        self.diagnostics.push(
+            Diagnostic::error(format!("`?` couldn't convert the error to `{to}`"), span)
+                .with_note(format!(
+                    "the trait `From<{from}>` is not implemented for `{to}`"
+                ))
+                .with_note(format!(
+                    "add `impl From<{from}> for {to}`, or convert the error with `.map_err(...)?`"
+                )),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_propagation;
+    use crate::semantic;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_propagation)
+    }
+
+    #[test]
+    fn accepts_compatible_functions() {
+        let messages = messages(
+            "use std::io;
+            struct ParseError;
+            enum AppError { Io(io::Error), Parse }
+            impl From<ParseError> for AppError { fn from(e: ParseError) -> Self { AppError::Parse } }
+            type AppResult = Result<i32, AppError>;
+            fn parse(s: String) -> Result<i32, ParseError> { Err(ParseError) }
+            fn first(xs: Vec<i32>) -> Option<i32> { xs.get(0).copied() }
+            fn run(s: String, n: Option<i32>) -> AppResult {
+                let a = parse(s.clone())?;
+                let b = read(s)?;
+                let f = |x: Option<i32>| -> Option<i32> { Some(x? + 1) };
+                Ok(a + b)
+            }
+            fn both(xs: Vec<i32>, n: Option<i32>) -> Option<i32> {
+                let n = first(xs)? + n?;
+                Some(n)
+            }
+            fn read(s: String) -> io::Result<i32> { Ok(1) }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_incompatible_functions() {
+        let messages = messages(
+            "struct Config { n: i32 }
+            enum AppError { Parse }
+            mod a { pub fn g() -> Option<i32> { None } }
+            struct S;
+            impl S { fn g(&self) -> Result<i32, String> { Ok(1) } }
+            fn parse(s: String) -> Result<i32, String> { Ok(1) }
+            fn first(xs: Vec<i32>) -> Option<i32> { None }
+            fn main() { let n = parse(String::new())?; }
+            fn load(s: String) -> Config { Config { n: parse(s)? } }
+            fn run(xs: Vec<i32>) -> Result<i32, AppError> { Ok(first(xs)?) }
+            fn pick(s: String) -> Option<i32> { let f = || 1; Some(parse(s)?) }
+            fn convert(s: String) -> Result<i32, AppError> { Ok(parse(s)?) }
+            fn local() -> Option<i32> { let r: Result<i32, String> = Ok(1); Some(r?) }
+            fn module() -> Result<i32, String> { Ok(a::g()?) }
+            fn method(s: S) -> Option<i32> { Some(s.g()?) }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "the `?` operator can only be used in a function that returns `Result` or `Option`",
+                "the `?` operator can only be used in a function that returns `Result` or `Option`",
+                "the `?` operator can only be used on `Result`s, not `Option`s, in a function that returns `Result`",
+                "the `?` operator can only be used on `Option`s, not `Result`s, in a function that returns `Option`",
+                "`?` couldn't convert the error to `AppError`",
+                "the `?` operator can only be used on `Option`s, not `Result`s, in a function that returns `Option`",
+                "the `?` operator can only be used on `Result`s, not `Option`s, in a function that returns `Result`",
+                "the `?` operator can only be used on `Option`s, not `Result`s, in a function that returns `Option`",
+            ]
+        );
+    }
+}
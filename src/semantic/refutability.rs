@@ -0,0 +1,199 @@
+//! Checks that the patterns of `let`, of the parameters and of `for` loops match
+//! every value, unless a `let` has an `else` block, which must then diverge.
+//!
+//! A pattern is refutable when `_` is still useful after it, as
+//! [`exhaustiveness`](super::exhaustiveness) finds for the arms of a `match`. The
+//! `else` block of a `let` diverges when the [`Cfg`] of the function never reaches
+//! its end, or when it calls a function of another crate, which may never return as
+//! `std::process::exit` does.
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::cfg::Cfg;
+use super::exhaustiveness::Patterns;
+use super::resolve::Resolutions;
+use super::typeck::{Type, TypeckResults};
+
+pub fn check_refutability(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut checker = Checker {
+        patterns: Patterns::new(module, resolutions, types),
+        types,
+        completes: HashMap::new(),
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+struct Checker<'ast, 'r, 'd> {
+    patterns: Patterns<'ast, 'r>,
+    types: &'r TypeckResults,
+    /// Whether the `else` block of each `let...else` of the function being checked
+    /// may reach its end, by the id of the `let`.
+    completes: HashMap<NodeId, bool>,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_function(&mut self, function: &'ast Function) {
+        self.completes.clear();
+        if let Some(cfg) = Cfg::build(function) {
+            for &(local, start, end) in &cfg.els {
+                self.completes.insert(local.id, cfg.reachable(start)[end]);
+            }
+        }
+        visit::walk_function(self, function);
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        match &local.els {
+            None => self.irrefutable(&local.pat, "local binding", "`let` bindings"),
+            Some(els) => {
+                if !self.patterns.is_refutable(&local.pat) {
+                    self.diagnostics.push(
+                        Diagnostic::warning("irrefutable `let...else` pattern", local.pat.span)
+                            .with_note(
+                                "this pattern always matches, so the `else` block is never run",
+                            ),
+                    );
+                }
+                if !self.diverges(local.id, els) {
+                    self.diagnostics.push(
+                        Diagnostic::error("`else` clause of `let...else` does not diverge", els.span)
+                            .with_note(
+                                "the `else` block must end the function, the loop or the iteration, with `return`, `break` or `continue`",
+                            ),
+                    );
+                }
+            }
+        }
+        visit::walk_local(self, local);
+    }
+
+    fn visit_param(&mut self, param: &'ast Param) {
+        self.irrefutable(&param.pat, "function argument", "function arguments");
+        visit::walk_param(self, param);
+    }
+
+    fn visit_closure(&mut self, closure: &'ast Closure) {
+        for param in &closure.params {
+            self.irrefutable(&param.pat, "closure argument", "closure arguments");
+        }
+        visit::walk_closure(self, closure);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let ExprKind::ForLoop { pat, .. } = &expr.kind {
+            self.irrefutable(pat, "`for` loop binding", "`for` loop bindings");
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+impl Checker<'_, '_, '_> {
+    /// Whether the `else` block `els` of the `let` `local` never completes. The
+    /// blocks of the closures, which have no graph of their own, are left to rustc.
+    fn diverges(&self, local: NodeId, els: &Block) -> bool {
+        let calls_other_crate = els.stmts.iter().any(|stmt| match &stmt.kind {
+            StmtKind::Expr(expr) | StmtKind::Semi(expr) => {
+                matches!(expr.kind, ExprKind::Call(..))
+                    && matches!(self.types.expr(expr.id), None | Some(Type::Unknown))
+            }
+            StmtKind::Let(_) => false,
+        });
+        calls_other_crate || self.completes.get(&local) != Some(&true)
+    }
+
+    fn irrefutable(&mut self, pattern: &Pattern, what: &str, requirer: &str) {
+        if !self.patterns.is_refutable(pattern) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::error(format!("refutable pattern in {what}"), pattern.span)
+            .with_note(format!(
+                "{requirer} require an irrefutable pattern, like a `struct` or an `enum` with only one variant"
+            ));
+        if what == "local binding" {
+            diagnostic = diagnostic
+                .with_note("use `let ... else { ... }` to handle the values that do not match");
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_refutability;
+    use crate::semantic;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_refutability)
+    }
+
+    #[test]
+    fn accepts_irrefutable_patterns() {
+        let messages = messages(
+            "struct Point { x: i32, y: i32 }
+            enum Single { Only(i32) }
+            enum E { A(i32), B(i32) }
+            fn next() -> Option<i32> { None }
+            fn f((a, b): (i32, i32), Point { x, .. }: Point, Single::Only(m): Single, e: E, maybe: Option<i32>, parsed: Result<i32, String>) {
+                let (c, (d, _)) = (a, (b, 1));
+                let Point { x: px, y: py } = Point { x, y: 2 };
+                let Single::Only(n) = Single::Only(1);
+                let E::A(k) | E::B(k) = e;
+                let add = |(l, r): (i32, i32)| l + r;
+                for (i, x) in [(1, 2)] { }
+                let Some(v) = maybe else { return; };
+                let Ok(w) = parsed else { std::process::exit(1) };
+                loop { let Some(n) = next() else { break; }; }
+                let Some(z) = maybe else { loop {} };
+            }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_refutable_patterns() {
+        let messages = messages(
+            "enum Shape { Circle(f64), Empty }
+            const ZERO: i32 = 0;
+            mod h { pub fn log() {} }
+            fn area(Shape::Circle(r): Shape) {}
+            fn report() {}
+            fn f(maybe: Option<i32>, pair: (i32, i32), n: i32, s: Shape) {
+                let Some(x) = maybe;
+                let (1, y) = pair;
+                let ZERO = n;
+                for Ok(line) in [Ok(1), Err(2)] {}
+                let g = |None: Option<i32>| 1;
+                let (a, b) = pair else { return; };
+                let Shape::Empty = s else { report(); };
+                let Some(y) = maybe else { h::log(); };
+                let Some(z) = maybe else { loop { break; } };
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "refutable pattern in function argument",
+                "refutable pattern in local binding",
+                "refutable pattern in local binding",
+                "refutable pattern in local binding",
+                "refutable pattern in `for` loop binding",
+                "refutable pattern in closure argument",
+                "irrefutable `let...else` pattern",
+                "`else` clause of `let...else` does not diverge",
+                "`else` clause of `let...else` does not diverge",
+                "`else` clause of `let...else` does not diverge",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1203 @@
+//! Name resolution : every path of the crate is bound to what it names, an item, a
+//! variant, a local or a generic parameter.
+//!
+//! Items are looked up in the tree of modules, where each module has a namespace
+//! for the types and one for the values, and brings in the names of its `use`.
+//! Locals, parameters and generics live in nested scopes, ribs, pushed for each
+//! function, block, closure and arm : a `let` shadows what came before it, until
+//! the end of its block.
+//!
+//! Names from outside the crate, from the prelude or reached through a type, like
+//! the methods of a trait, are left to rustc : they resolve to [`Res::External`].
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::prelude;
+
+/// What a name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Res {
+    /// An item of the crate : a function, a type, a trait, a constant or a module.
+    /// Methods are reached through their type, `Vec2::new`.
+    Item(NodeId),
+    /// The variant of an enum of the crate, by the enum and the index of the variant.
+    Variant(NodeId, usize),
+    /// A parameter or a local, by the id of its binding.
+    Local(NodeId),
+    /// A generic parameter.
+    Generic(NodeId),
+    /// `Self`, by the id of its impl or trait.
+    SelfTy(NodeId),
+    /// A primitive type, a name of the prelude, of another crate, or an associated
+    /// item rustc finds through its type.
+    External,
+}
+
+/// What each path of the crate resolves to, by the id of the expression, the type or
+/// the pattern holding it. The trait of a trait impl is under the id of the impl.
+#[derive(Debug, Default)]
+pub struct Resolutions {
+    paths: HashMap<NodeId, Res>,
+    /// The simple `use` trees whose name some path goes through, by their span.
+    used_imports: HashSet<Span>,
+    /// The simple `use` trees reported for naming nothing, or a private item.
+    failed_imports: HashSet<Span>,
+    /// The traits of the crate in scope at each method call.
+    traits: HashMap<NodeId, Vec<NodeId>>,
+    /// The traits of the bounds and of the supertraits, by the span of their path.
+    bounds: HashMap<Span, Res>,
+}
+
+impl Resolutions {
+    pub fn get(&self, id: NodeId) -> Option<Res> {
+        self.paths.get(&id).copied()
+    }
+
+    /// The trait of a bound, `Ord` in `T: Ord`, or of a supertrait.
+    pub fn bound(&self, path: &Path) -> Option<Res> {
+        self.bounds.get(&path.span).copied()
+    }
+
+    /// Whether the name brought in by the simple `use` tree at `span` is used.
+    pub fn is_used_import(&self, span: Span) -> bool {
+        self.used_imports.contains(&span)
+    }
+
+    /// Whether the simple `use` tree at `span` was reported, for naming nothing or
+    /// a private item.
+    pub fn is_failed_import(&self, span: Span) -> bool {
+        self.failed_imports.contains(&span)
+    }
+
+    /// The traits of the crate whose methods the method call `id` may call : those
+    /// its module declares or imports.
+    pub fn traits_in_scope(&self, id: NodeId) -> &[NodeId] {
+        self.traits.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub fn resolve_names(module: &Module, diagnostics: &mut Vec<Diagnostic>) -> Resolutions {
+    let mut krate = Crate::default();
+    let mut impls = vec![];
+    krate.collect(module, "crate", None, None, &mut impls, diagnostics);
+    krate.collect_methods(impls, diagnostics);
+    let mut resolver = Resolver {
+        krate,
+        module: 0,
+        ribs: vec![],
+        traits: HashMap::new(),
+        resolutions: Resolutions::default(),
+        diagnostics,
+    };
+    resolver.visit_module(module);
+    let mut resolutions = resolver.resolutions;
+    for data in &resolver.krate.modules {
+        let used = data.imports.values().filter(|import| import.used.get());
+        resolutions
+            .used_imports
+            .extend(used.map(|import| import.span));
+    }
+    resolutions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Namespace {
+    Type,
+    Value,
+}
+
+impl Namespace {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Namespace::Type => "type",
+            Namespace::Value => "value",
+        }
+    }
+}
+
+/// An item, as its module declares it.
+#[derive(Clone, Copy)]
+struct Decl<'ast> {
+    res: Res,
+    /// `function`, `struct`... for the messages.
+    kind: &'static str,
+    name: &'ast Ident,
+    public: bool,
+}
+
+/// `use a::b as c;` brings `c` in scope.
+struct Import<'ast> {
+    path: Vec<&'ast Ident>,
+    public: bool,
+    /// The simple tree, `b as c`.
+    span: Span,
+    /// Whether a path went through the import.
+    used: Cell<bool>,
+}
+
+#[derive(Default)]
+struct ModuleData<'ast> {
+    name: &'ast str,
+    /// The `mod` item, missing for the root.
+    id: Option<NodeId>,
+    parent: Option<usize>,
+    types: HashMap<&'ast str, Decl<'ast>>,
+    values: HashMap<&'ast str, Decl<'ast>>,
+    imports: HashMap<&'ast str, Import<'ast>>,
+    /// Whether the module has a `use path::*`, which may bring in any name.
+    glob: bool,
+}
+
+impl<'ast> ModuleData<'ast> {
+    fn names(&self, ns: Namespace) -> &HashMap<&'ast str, Decl<'ast>> {
+        match ns {
+            Namespace::Type => &self.types,
+            Namespace::Value => &self.values,
+        }
+    }
+}
+
+/// A step of a path : a module of the crate, or anything else.
+#[derive(Clone, Copy)]
+enum Step {
+    Module(usize),
+    Res(Res),
+}
+
+/// Why a path does not resolve.
+enum PathError {
+    Private(Diagnostic),
+    Unresolved(Diagnostic),
+}
+
+impl PathError {
+    fn into_diagnostic(self) -> Diagnostic {
+        match self {
+            PathError::Private(diagnostic) | PathError::Unresolved(diagnostic) => diagnostic,
+        }
+    }
+}
+
+/// Imports may name each other : past this depth, they are left to rustc.
+const MAX_IMPORT_DEPTH: usize = 16;
+
+/// The modules of the crate, the root first, and what the paths go through.
+#[derive(Default)]
+struct Crate<'ast> {
+    modules: Vec<ModuleData<'ast>>,
+    /// The module each `mod` item opens.
+    mods: HashMap<NodeId, usize>,
+    enums: HashMap<NodeId, &'ast Enum>,
+    /// The methods of the inherent impls, by type and name.
+    methods: HashMap<(NodeId, &'ast str), NodeId>,
+    /// The type of each inherent impl, which `Self` names inside it.
+    self_tys: HashMap<NodeId, NodeId>,
+    /// The associated types of every impl, by impl and name : `Self::Out`.
+    assoc_tys: HashMap<(NodeId, &'ast str), NodeId>,
+    traits: HashSet<NodeId>,
+}
+
+impl<'ast> Crate<'ast> {
+    /// Declares the items of `module` and of its children, and returns its index.
+    fn collect(
+        &mut self,
+        module: &'ast Module,
+        name: &'ast str,
+        id: Option<NodeId>,
+        parent: Option<usize>,
+        impls: &mut Vec<(usize, &'ast Item)>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> usize {
+        let index = self.modules.len();
+        self.modules.push(ModuleData {
+            name,
+            id,
+            parent,
+            ..ModuleData::default()
+        });
+        for item in &module.items {
+            let public = item.vis == Visibility::Public;
+            let (ns, kind, name) = match &item.kind {
+                ItemKind::Function(function) => (Namespace::Value, "function", &function.name),
+                ItemKind::Const(constant) => (Namespace::Value, "constant", &constant.name),
+                ItemKind::Static(constant) => (Namespace::Value, "static", &constant.name),
+                ItemKind::Struct(item) => (Namespace::Type, "struct", &item.name),
+                ItemKind::Enum(enum_) => {
+                    self.enums.insert(item.id, enum_);
+                    (Namespace::Type, "enum", &enum_.name)
+                }
+                ItemKind::Trait(trait_) => {
+                    self.traits.insert(item.id);
+                    (Namespace::Type, "trait", &trait_.name)
+                }
+                ItemKind::TyAlias(alias) => (Namespace::Type, "type alias", &alias.name),
+                ItemKind::Mod(child) => {
+                    if let Some(content) = &child.content {
+                        let child_index = self.collect(
+                            content,
+                            &child.name.name,
+                            Some(item.id),
+                            Some(index),
+                            impls,
+                            diagnostics,
+                        );
+                        self.mods.insert(item.id, child_index);
+                    }
+                    (Namespace::Type, "module", &child.name)
+                }
+                ItemKind::Use(tree) => {
+                    self.import(index, tree, vec![], public);
+                    continue;
+                }
+                ItemKind::Impl(_) => {
+                    impls.push((index, item));
+                    continue;
+                }
+                ItemKind::MacroDef(_) | ItemKind::Error => continue,
+            };
+            let decl = Decl {
+                res: Res::Item(item.id),
+                kind,
+                name,
+                public,
+            };
+            // A unit struct is also the value of its type, `let m = Marker;`. A second
+            // definition is reported once, for its type.
+            let unit = matches!(&item.kind, ItemKind::Struct(item) if item.fields.is_empty());
+            if self.declare(index, ns, decl, diagnostics) && unit {
+                self.declare(index, Namespace::Value, decl, diagnostics);
+            }
+        }
+        index
+    }
+
+    /// Declares `decl` in the namespace `ns` of `module`, unless the name is taken.
+    /// Returns whether it was declared.
+    fn declare(
+        &mut self,
+        module: usize,
+        ns: Namespace,
+        decl: Decl<'ast>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> bool {
+        let name = decl.name;
+        let names = match ns {
+            Namespace::Type => &mut self.modules[module].types,
+            Namespace::Value => &mut self.modules[module].values,
+        };
+        if let Some(previous) = names.get(name.name.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(
+                    format!("the name `{}` is defined multiple times", name.name),
+                    name.span,
+                )
+                .with_label(
+                    previous.name.span,
+                    format!(
+                        "previous definition of the {} `{}` here",
+                        previous.kind, name.name
+                    ),
+                )
+                .with_note(format!(
+                    "`{}` must be defined only once in the {} namespace of this module",
+                    name.name,
+                    ns.as_str()
+                )),
+            );
+            return false;
+        }
+        names.insert(&name.name, decl);
+        true
+    }
+
+    /// Records the names a `use` tree brings in `module`.
+    fn import(
+        &mut self,
+        module: usize,
+        tree: &'ast UseTree,
+        mut prefix: Vec<&'ast Ident>,
+        public: bool,
+    ) {
+        prefix.extend(tree.prefix.segments.iter().map(|segment| &segment.ident));
+        match &tree.kind {
+            UseTreeKind::Simple(alias) => {
+                // `use a::b::{self}` imports `b`.
+                if prefix.len() > 1 && prefix.last().is_some_and(|last| last.name == "self") {
+                    prefix.pop();
+                }
+                let Some(last) = prefix.last() else {
+                    return;
+                };
+                let name = alias.as_ref().unwrap_or(last);
+                self.modules[module].imports.insert(
+                    &name.name,
+                    Import {
+                        path: prefix,
+                        public,
+                        span: tree.span,
+                        used: Cell::new(false),
+                    },
+                );
+            }
+            UseTreeKind::Nested(trees) => {
+                for tree in trees {
+                    self.import(module, tree, prefix.clone(), public);
+                }
+            }
+            UseTreeKind::Glob => self.modules[module].glob = true,
+        }
+    }
+
+    /// Records the methods of the inherent impls, once every type is declared, and the
+    /// associated types of every impl.
+    fn collect_methods(
+        &mut self,
+        impls: Vec<(usize, &'ast Item)>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let mut spans: HashMap<(NodeId, &str), Span> = HashMap::new();
+        for (module, item) in impls {
+            let ItemKind::Impl(imp) = &item.kind else {
+                continue;
+            };
+            for assoc in &imp.items {
+                if let ItemKind::TyAlias(alias) = &assoc.kind {
+                    self.assoc_tys.insert((item.id, &alias.name.name), assoc.id);
+                }
+            }
+            let TyKind::Path(path) = &imp.self_ty.kind else {
+                continue;
+            };
+            let Ok(Res::Item(ty)) = self.resolve_path(module, &idents(path), Namespace::Type, 0)
+            else {
+                continue;
+            };
+            if imp.of_trait.is_some() {
+                continue;
+            }
+            self.self_tys.insert(item.id, ty);
+            for item in &imp.items {
+                let ItemKind::Function(function) = &item.kind else {
+                    continue;
+                };
+                let name = &function.name;
+                if let Some(&previous) = spans.get(&(ty, name.name.as_str())) {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            format!("duplicate definitions with name `{}`", name.name),
+                            name.span,
+                        )
+                        .with_label(previous, format!("other definition for `{}`", name.name)),
+                    );
+                    continue;
+                }
+                spans.insert((ty, &name.name), name.span);
+                self.methods.insert((ty, &name.name), item.id);
+            }
+        }
+    }
+
+    /// What `name` means in `module` : one of its items, a name it imports, or a name
+    /// of the prelude.
+    fn lookup(&self, module: usize, name: &str, ns: Namespace, depth: usize) -> Option<Res> {
+        let data = &self.modules[module];
+        if let Some(decl) = data.names(ns).get(name) {
+            return Some(decl.res);
+        }
+        if let Some(import) = data.imports.get(name) {
+            match self.resolve_import(module, import, ns, depth) {
+                Some(res) => {
+                    import.used.set(true);
+                    return Some(res);
+                }
+                // The import names something of the other namespace only.
+                None if self
+                    .resolve_import(module, import, other(ns), depth)
+                    .is_some() => {}
+                // A broken import is reported where it is written.
+                None => {
+                    import.used.set(true);
+                    return Some(Res::External);
+                }
+            }
+        }
+        let in_prelude = match ns {
+            Namespace::Type => prelude::TYPES.contains(&name) || prelude::CRATES.contains(&name),
+            Namespace::Value => prelude::is_value(name),
+        };
+        (data.glob || in_prelude).then_some(Res::External)
+    }
+
+    /// The traits of the crate in scope in `module`, every one when it has a glob
+    /// import.
+    fn traits_in_scope(&self, module: usize) -> Vec<NodeId> {
+        let data = &self.modules[module];
+        let mut traits: Vec<_> = if data.glob {
+            self.traits.iter().copied().collect()
+        } else {
+            let declared = data.types.values().map(|decl| decl.res);
+            let imported = data
+                .imports
+                .values()
+                .filter_map(|import| self.resolve_import(module, import, Namespace::Type, 0));
+            declared
+                .chain(imported)
+                .filter_map(|res| match res {
+                    Res::Item(id) if self.traits.contains(&id) => Some(id),
+                    _ => None,
+                })
+                .collect()
+        };
+        traits.sort();
+        traits.dedup();
+        traits
+    }
+
+    fn resolve_import(
+        &self,
+        module: usize,
+        import: &Import,
+        ns: Namespace,
+        depth: usize,
+    ) -> Option<Res> {
+        if depth > MAX_IMPORT_DEPTH {
+            return Some(Res::External);
+        }
+        self.resolve_path(module, &import.path, ns, depth + 1).ok()
+    }
+
+    /// Resolves a path written in `module`, from its first segment.
+    fn resolve_path(
+        &self,
+        module: usize,
+        path: &[&Ident],
+        ns: Namespace,
+        depth: usize,
+    ) -> Result<Res, PathError> {
+        let Some((first, rest)) = path.split_first() else {
+            return Ok(Res::External);
+        };
+        let step = match first.name.as_str() {
+            "crate" => Step::Module(0),
+            "self" if !rest.is_empty() => Step::Module(module),
+            "super" => Step::Module(self.parent(module, first)?),
+            name => {
+                let first_ns = if rest.is_empty() { ns } else { Namespace::Type };
+                match self.lookup(module, name, first_ns, depth) {
+                    Some(res) => self.step(res),
+                    None if rest.is_empty() => {
+                        return Err(PathError::Unresolved(Diagnostic::error(
+                            format!(
+                                "cannot find {} `{}` in this scope",
+                                first_ns.as_str(),
+                                first.display_name()
+                            ),
+                            first.span,
+                        )))
+                    }
+                    None => {
+                        let what = if name.starts_with(char::is_uppercase) {
+                            "type"
+                        } else {
+                            "crate or module"
+                        };
+                        return Err(PathError::Unresolved(Diagnostic::error(
+                            format!("failed to resolve: use of undeclared {what} `{name}`"),
+                            first.span,
+                        )));
+                    }
+                }
+            }
+        };
+        self.resolve_rest(step, rest, ns, module, depth)
+    }
+
+    /// Follows the segments after `step`, the last one in `ns`. `from` is the module
+    /// writing the path, which may see the private items of its ancestors.
+    fn resolve_rest(
+        &self,
+        mut step: Step,
+        rest: &[&Ident],
+        ns: Namespace,
+        from: usize,
+        depth: usize,
+    ) -> Result<Res, PathError> {
+        for (i, segment) in rest.iter().enumerate() {
+            let last = i + 1 == rest.len();
+            let name = segment.name.as_str();
+            step = match step {
+                Step::Module(module) if name == "super" => {
+                    Step::Module(self.parent(module, segment)?)
+                }
+                Step::Module(module) => {
+                    let segment_ns = if last { ns } else { Namespace::Type };
+                    self.step(self.module_member(module, segment, segment_ns, from, depth)?)
+                }
+                Step::Res(Res::Item(id)) => {
+                    let Some(enum_) = self.enums.get(&id) else {
+                        return Ok(self.method(id, name));
+                    };
+                    if let Some(index) = enum_.variants.iter().position(|v| v.name.name == name) {
+                        Step::Res(Res::Variant(id, index))
+                    } else if self.methods.contains_key(&(id, name))
+                        || !name.starts_with(char::is_uppercase)
+                    {
+                        return Ok(self.method(id, name));
+                    } else {
+                        return Err(PathError::Unresolved(Diagnostic::error(
+                            format!(
+                                "no variant or associated item named `{name}` found for enum `{}`",
+                                enum_.name.name
+                            ),
+                            segment.span,
+                        )));
+                    }
+                }
+                Step::Res(Res::SelfTy(id)) if self.assoc_tys.contains_key(&(id, name)) => {
+                    Step::Res(Res::Item(self.assoc_tys[&(id, name)]))
+                }
+                Step::Res(Res::SelfTy(id)) => {
+                    return Ok(match self.self_tys.get(&id) {
+                        Some(&ty) => self.method(ty, name),
+                        None => Res::External,
+                    })
+                }
+                Step::Res(_) => return Ok(Res::External),
+            };
+        }
+        Ok(match step {
+            Step::Module(module) => self.modules[module].id.map_or(Res::External, Res::Item),
+            Step::Res(res) => res,
+        })
+    }
+
+    /// The item `segment` of `module`, when `from` may see it.
+    fn module_member(
+        &self,
+        module: usize,
+        segment: &Ident,
+        ns: Namespace,
+        from: usize,
+        depth: usize,
+    ) -> Result<Res, PathError> {
+        let data = &self.modules[module];
+        let name = segment.name.as_str();
+        if let Some(decl) = data.names(ns).get(name) {
+            if !decl.public && !self.is_ancestor(module, from) {
+                return Err(PathError::Private(
+                    Diagnostic::error(format!("{} `{name}` is private", decl.kind), segment.span)
+                        .with_label(
+                            decl.name.span,
+                            format!("the {} `{name}` is defined here", decl.kind),
+                        ),
+                ));
+            }
+            return Ok(decl.res);
+        }
+        if let Some(import) = data.imports.get(name) {
+            if import.public || self.is_ancestor(module, from) {
+                if let Some(res) = self.resolve_import(module, import, ns, depth) {
+                    import.used.set(true);
+                    return Ok(res);
+                }
+            }
+        }
+        if data.glob {
+            return Ok(Res::External);
+        }
+        let what = match ns {
+            Namespace::Type => "type or module",
+            Namespace::Value => "value",
+        };
+        Err(PathError::Unresolved(Diagnostic::error(
+            format!("cannot find {what} `{name}` in module `{}`", data.name),
+            segment.span,
+        )))
+    }
+
+    fn step(&self, res: Res) -> Step {
+        match res {
+            Res::Item(id) => self
+                .mods
+                .get(&id)
+                .map_or(Step::Res(res), |&m| Step::Module(m)),
+            res => Step::Res(res),
+        }
+    }
+
+    /// The method `name` of the inherent impls of `ty`, or something rustc finds
+    /// through the traits of `ty`.
+    fn method(&self, ty: NodeId, name: &str) -> Res {
+        self.methods
+            .get(&(ty, name))
+            .map_or(Res::External, |&id| Res::Item(id))
+    }
+
+    fn parent(&self, module: usize, segment: &Ident) -> Result<usize, PathError> {
+        self.modules[module].parent.ok_or_else(|| {
+            PathError::Unresolved(Diagnostic::error(
+                "there are too many leading `super` keywords",
+                segment.span,
+            ))
+        })
+    }
+
+    /// Whether `ancestor` is `module` or one of the modules holding it.
+    fn is_ancestor(&self, ancestor: usize, mut module: usize) -> bool {
+        loop {
+            if module == ancestor {
+                return true;
+            }
+            match self.modules[module].parent {
+                Some(parent) => module = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+fn other(ns: Namespace) -> Namespace {
+    match ns {
+        Namespace::Type => Namespace::Value,
+        Namespace::Value => Namespace::Type,
+    }
+}
+
+fn idents(path: &Path) -> Vec<&Ident> {
+    path.segments.iter().map(|segment| &segment.ident).collect()
+}
+
+/// The names a pattern binds, those of the first alternative of an or-pattern.
+fn bindings<'ast>(pattern: &'ast Pattern, out: &mut Vec<&'ast Ident>) {
+    match &pattern.kind {
+        PatKind::Binding { name, sub, .. } => {
+            out.push(name);
+            if let Some(sub) = sub {
+                bindings(sub, out);
+            }
+        }
+        PatKind::Tuple(patterns) | PatKind::TupleStruct(_, patterns) => {
+            for pattern in patterns {
+                bindings(pattern, out);
+            }
+        }
+        PatKind::Paren(inner) => bindings(inner, out),
+        PatKind::Struct { fields, .. } => {
+            for field in fields {
+                bindings(&field.pat, out);
+            }
+        }
+        PatKind::Or(alternatives) => {
+            if let Some(first) = alternatives.first() {
+                bindings(first, out);
+            }
+        }
+        PatKind::Wild
+        | PatKind::Lit(_)
+        | PatKind::Range { .. }
+        | PatKind::Path(_)
+        | PatKind::Rest
+        | PatKind::Error => {}
+    }
+}
+
+/// The names one function, block, closure or arm declares.
+#[derive(Default)]
+struct Rib<'ast> {
+    types: HashMap<&'ast str, Res>,
+    values: HashMap<&'ast str, Res>,
+}
+
+struct Resolver<'ast, 'd> {
+    krate: Crate<'ast>,
+    /// The module being resolved.
+    module: usize,
+    /// The ribs of the item being resolved, innermost last.
+    ribs: Vec<Rib<'ast>>,
+    /// The traits in scope in each module with a method call.
+    traits: HashMap<usize, Vec<NodeId>>,
+    resolutions: Resolutions,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Resolver<'ast, '_> {
+    fn scoped(&mut self, rib: Rib<'ast>, walk: impl FnOnce(&mut Self)) {
+        self.ribs.push(rib);
+        walk(self);
+        self.ribs.pop();
+    }
+
+    /// A rib with the parameters of `generics`, reporting those declared twice.
+    fn generics_rib(&mut self, generics: &'ast Generics) -> Rib<'ast> {
+        let mut rib = Rib::default();
+        for param in &generics.params {
+            let previous = rib.types.insert(&param.name.name, Res::Generic(param.id));
+            if previous.is_some() {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "the name `{}` is already used for a generic parameter",
+                        param.name.name
+                    ),
+                    param.name.span,
+                ));
+            }
+        }
+        rib
+    }
+
+    /// Reports the names bound twice by `patterns`, the parameters of a function or
+    /// of a closure, or a single pattern.
+    fn unique_bindings(&mut self, patterns: &[&'ast Pattern], place: &str) {
+        let mut names = vec![];
+        for pattern in patterns {
+            bindings(pattern, &mut names);
+        }
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].iter().any(|previous| previous.name == name.name) {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "identifier `{}` is bound more than once in {place}",
+                        name.name
+                    ),
+                    name.span,
+                ));
+            }
+        }
+    }
+
+    /// Resolves `path`, in `ns`, for the node `id`. `what` names the expected kind of
+    /// item in the messages.
+    fn resolve(&mut self, id: NodeId, path: &'ast Path, ns: Namespace, what: &str) {
+        if let Some(res) = self.resolve_path(path, ns, what) {
+            self.resolutions.paths.insert(id, res);
+        }
+    }
+
+    /// Resolves the traits of `bounds`, which have no node of their own.
+    fn resolve_traits(&mut self, bounds: &'ast [Path]) {
+        for bound in bounds {
+            if let Some(res) = self.resolve_path(bound, Namespace::Type, "trait") {
+                self.resolutions.bounds.insert(bound.span, res);
+            }
+        }
+    }
+
+    fn resolve_path(&mut self, path: &'ast Path, ns: Namespace, what: &str) -> Option<Res> {
+        let segments = idents(path);
+        let (first, rest) = segments.split_first()?;
+        let first_ns = if rest.is_empty() { ns } else { Namespace::Type };
+        let in_ribs = self.ribs.iter().rev().find_map(|rib| {
+            let names = match first_ns {
+                Namespace::Type => &rib.types,
+                Namespace::Value => &rib.values,
+            };
+            names.get(first.name.as_str()).copied()
+        });
+        let resolved = match in_ribs {
+            Some(res) if rest.is_empty() => Ok(res),
+            Some(res) => self
+                .krate
+                .resolve_rest(Step::Res(res), rest, ns, self.module, 0),
+            None => self
+                .krate
+                .resolve_path(self.module, &segments, ns, 0)
+                .map_err(|err| match err {
+                    PathError::Unresolved(_) if rest.is_empty() => {
+                        PathError::Unresolved(Diagnostic::error(
+                            format!(
+                                "cannot find {what} `{}` in this scope",
+                                first.display_name()
+                            ),
+                            first.span,
+                        ))
+                    }
+                    err => err,
+                }),
+        };
+        resolved
+            .map_err(|err| self.diagnostics.push(err.into_diagnostic()))
+            .ok()
+    }
+
+    /// Reports the imports of a `use` item that name nothing.
+    fn check_import(&mut self, tree: &'ast UseTree, mut prefix: Vec<&'ast Ident>) {
+        prefix.extend(tree.prefix.segments.iter().map(|segment| &segment.ident));
+        match &tree.kind {
+            UseTreeKind::Simple(_) => {
+                if prefix.len() > 1 && prefix.last().is_some_and(|last| last.name == "self") {
+                    prefix.pop();
+                }
+                // Paths naming another crate are left to rustc.
+                let external = prefix.first().is_some_and(|first| {
+                    !matches!(first.name.as_str(), "crate" | "self" | "super")
+                        && !self.krate.modules[self.module]
+                            .types
+                            .contains_key(first.name.as_str())
+                });
+                if external {
+                    return;
+                }
+                let module = self.module;
+                let err = match self
+                    .krate
+                    .resolve_path(module, &prefix, Namespace::Value, 0)
+                {
+                    Ok(_) => return,
+                    Err(err) => err,
+                };
+                let err = match self.krate.resolve_path(module, &prefix, Namespace::Type, 0) {
+                    Ok(_) => return,
+                    Err(PathError::Private(private)) => private,
+                    Err(PathError::Unresolved(_)) => match err {
+                        PathError::Private(private) => private,
+                        PathError::Unresolved(unresolved) => {
+                            let path: Vec<_> = prefix.iter().map(|i| i.name.as_str()).collect();
+                            Diagnostic::error(
+                                format!("unresolved import `{}`", path.join("::")),
+                                tree.span,
+                            )
+                            .with_note(unresolved.message)
+                        }
+                    },
+                };
+                self.resolutions.failed_imports.insert(tree.span);
+                self.diagnostics.push(err);
+            }
+            UseTreeKind::Nested(trees) => {
+                for tree in trees {
+                    self.check_import(tree, prefix.clone());
+                }
+            }
+            UseTreeKind::Glob => {}
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Resolver<'ast, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Mod(_) => {
+                let Some(&module) = self.krate.mods.get(&item.id) else {
+                    return;
+                };
+                let outer = std::mem::replace(&mut self.module, module);
+                let ribs = std::mem::take(&mut self.ribs);
+                visit::walk_item(self, item);
+                self.module = outer;
+                self.ribs = ribs;
+            }
+            ItemKind::Use(tree) => self.check_import(tree, vec![]),
+            ItemKind::Impl(imp) => {
+                let mut rib = self.generics_rib(&imp.generics);
+                rib.types.insert("Self", Res::SelfTy(item.id));
+                self.scoped(rib, |this| {
+                    if let Some(path) = &imp.of_trait {
+                        this.resolve(item.id, path, Namespace::Type, "trait");
+                    }
+                    visit::walk_item(this, item);
+                });
+            }
+            ItemKind::Trait(item_trait) => {
+                let mut rib = self.generics_rib(&item_trait.generics);
+                rib.types.insert("Self", Res::SelfTy(item.id));
+                self.scoped(rib, |this| {
+                    this.resolve_traits(&item_trait.supertraits);
+                    visit::walk_item(this, item);
+                });
+            }
+            ItemKind::Struct(Struct { generics, .. }) | ItemKind::Enum(Enum { generics, .. }) => {
+                let rib = self.generics_rib(generics);
+                self.scoped(rib, |this| visit::walk_item(this, item));
+            }
+            _ => visit::walk_item(self, item),
+        }
+    }
+
+    fn visit_generics(&mut self, generics: &'ast Generics) {
+        for param in &generics.params {
+            self.resolve_traits(&param.bounds);
+        }
+        for predicate in &generics.where_clause {
+            self.resolve_traits(&predicate.bounds);
+        }
+        visit::walk_generics(self, generics);
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        let rib = self.generics_rib(&function.generics);
+        self.scoped(rib, |this| {
+            let params: Vec<_> = function.params.iter().map(|param| &param.pat).collect();
+            this.unique_bindings(&params, "this parameter list");
+            this.scoped(Rib::default(), |this| visit::walk_function(this, function));
+        });
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.scoped(Rib::default(), |this| visit::walk_block(this, block));
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        self.unique_bindings(&[&local.pat], "the same pattern");
+        visit::walk_local(self, local);
+    }
+
+    fn visit_closure(&mut self, closure: &'ast Closure) {
+        let params: Vec<_> = closure.params.iter().map(|param| &param.pat).collect();
+        self.unique_bindings(&params, "this parameter list");
+        self.scoped(Rib::default(), |this| visit::walk_closure(this, closure));
+    }
+
+    fn visit_arm(&mut self, arm: &'ast Arm) {
+        self.unique_bindings(&[&arm.pat], "the same pattern");
+        self.scoped(Rib::default(), |this| visit::walk_arm(this, arm));
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        match &pattern.kind {
+            PatKind::Binding { name, .. } => {
+                let rib = self.ribs.last_mut().expect("patterns are inside a rib");
+                rib.values.insert(&name.name, Res::Local(pattern.id));
+            }
+            PatKind::Path(path) => self.resolve(
+                pattern.id,
+                path,
+                Namespace::Value,
+                "unit struct, unit variant or constant",
+            ),
+            PatKind::TupleStruct(path, _) => self.resolve(
+                pattern.id,
+                path,
+                Namespace::Value,
+                "tuple struct or tuple variant",
+            ),
+            PatKind::Struct { path, .. } => self.resolve(
+                pattern.id,
+                path,
+                Namespace::Type,
+                "struct, variant or union type",
+            ),
+            _ => {}
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        if let TyKind::Path(path) = &ty.kind {
+            self.resolve(ty.id, path, Namespace::Type, "type");
+        }
+        visit::walk_ty(self, ty);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Path(path) => self.resolve(expr.id, path, Namespace::Value, "value"),
+            ExprKind::Call(callee, args) => {
+                if let ExprKind::Path(path) = &callee.kind {
+                    self.resolve(callee.id, path, Namespace::Value, "function");
+                    self.visit_path(path);
+                } else {
+                    self.visit_expr(callee);
+                }
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+                return;
+            }
+            ExprKind::StructLit { path, .. } => self.resolve(
+                expr.id,
+                path,
+                Namespace::Type,
+                "struct, variant or union type",
+            ),
+            ExprKind::MethodCall { .. } => {
+                let traits = match self.traits.get(&self.module) {
+                    Some(traits) => traits.clone(),
+                    None => {
+                        let traits = self.krate.traits_in_scope(self.module);
+                        self.traits.insert(self.module, traits.clone());
+                        traits
+                    }
+                };
+                if !traits.is_empty() {
+                    self.resolutions.traits.insert(expr.id, traits);
+                }
+            }
+            // The pattern is bound in the body only.
+            ExprKind::ForLoop { pat, iter, body } => {
+                self.visit_expr(iter);
+                self.unique_bindings(&[pat], "the same pattern");
+                self.scoped(Rib::default(), |this| {
+                    this.visit_pattern(pat);
+                    this.visit_block(body);
+                });
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve_names, Res};
+    use crate::syntax::ast::*;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        let (module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        resolve_names(&module, &mut diagnostics);
+        diagnostics.into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn binds_names_to_declarations() {
+        let (module, _) = parse(
+            "fn f(x: i32) -> i32 {
+                let y = x;
+                let x = y + 1;
+                x
+            }",
+        );
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let ItemKind::Function(function) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let body = function.body.as_ref().unwrap();
+        let local = |i: usize| match &body.stmts[i].kind {
+            StmtKind::Let(local) => local,
+            kind => panic!("{kind:?}"),
+        };
+        let param = function.params[0].pat.id;
+        assert_eq!(
+            resolutions.get(local(0).init.as_ref().unwrap().id),
+            Some(Res::Local(param))
+        );
+        // The last `x` is the shadowing one.
+        let StmtKind::Expr(tail) = &body.stmts[2].kind else {
+            panic!("{:?}", body.stmts[2])
+        };
+        assert_eq!(resolutions.get(tail.id), Some(Res::Local(local(1).pat.id)));
+        let TyKind::Path(_) = &function.params[0].ty.kind else {
+            panic!()
+        };
+        assert_eq!(
+            resolutions.get(function.params[0].ty.id),
+            Some(Res::External)
+        );
+    }
+
+    #[test]
+    fn accepts_declared_names() {
+        let messages = messages(
+            "use std::collections::HashMap;
+            mod shapes {
+                pub enum Shape { Circle(f64), Empty }
+                pub fn area(s: &Shape) -> f64 { match s { Shape::Circle(r) => r * r, Shape::Empty => 0.0 } }
+                fn helper() -> i32 { super::TOP }
+                pub mod inner { pub fn call() -> i32 { super::helper() } }
+            }
+            use shapes::{Shape, area};
+            const TOP: i32 = 1;
+            struct Vec2<T> { x: T }
+            struct Marker;
+            impl<T: Clone> Vec2<T> {
+                fn new(x: T) -> Self { Self { x } }
+                fn get(&self) -> T { self.x.clone() }
+            }
+            fn main() {
+                let s = Shape::Circle(1.0);
+                let a = area(&s) + shapes::area(&shapes::Shape::Empty);
+                let v = Vec2::new(1);
+                let w: Vec2<i32> = Vec2 { x: v.get() };
+                let m: HashMap<String, i32> = HashMap::new();
+                let f = |n: i32| n + TOP;
+                let Marker = Marker;
+                for (i, x) in [(1, 2)] { let y = i + x; }
+                if let_ok(Some(1)) { std::process::exit(crate::shapes::inner::call()); }
+            }
+            fn let_ok(o: Option<i32>) -> bool { match o { Some(_) | None => true } }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_undeclared_names() {
+        let messages = messages(
+            "struct Point { x: i32 }
+            fn f(p: Pointt) -> i32 {
+                { let inner = 1; }
+                let q = Piont { x: 1 };
+                missing(inner);
+                let Shape::Circle = p;
+                nowhere::g();
+                x
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot find type `Pointt` in this scope",
+                "cannot find struct, variant or union type `Piont` in this scope",
+                "cannot find function `missing` in this scope",
+                "cannot find value `inner` in this scope",
+                "failed to resolve: use of undeclared type `Shape`",
+                "failed to resolve: use of undeclared crate or module `nowhere`",
+                "cannot find value `x` in this scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_and_private_items() {
+        let messages = messages(
+            "mod shapes {
+                fn secret() {}
+                pub fn area() {}
+                pub enum Shape { Circle }
+            }
+            use shapes::secret;
+            use shapes::perimeter;
+            struct Point {}
+            enum Point { A }
+            fn point() {}
+            struct Unit;
+            fn Unit() {}
+            struct Marker;
+            struct Marker;
+            fn f<T, T>(a: i32, (a, b): (i32, i32)) {
+                shapes::secret();
+                let s = shapes::Shape::Square;
+                let (c, c) = (1, 2);
+            }
+            impl Point { fn new() {} }
+            impl Point { fn new() {} }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "the name `Point` is defined multiple times",
+                "the name `Unit` is defined multiple times",
+                "the name `Marker` is defined multiple times",
+                "duplicate definitions with name `new`",
+                "function `secret` is private",
+                "unresolved import `shapes::perimeter`",
+                "the name `T` is already used for a generic parameter",
+                "identifier `a` is bound more than once in this parameter list",
+                "function `secret` is private",
+                "no variant or associated item named `Square` found for enum `Shape`",
+                "identifier `c` is bound more than once in the same pattern",
+            ]
+        );
+    }
+}
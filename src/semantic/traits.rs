@@ -0,0 +1,507 @@
+//! Checks each `impl Trait for Type` against the trait, when the trait is declared
+//! in the crate : every item without a default must be there, and nothing else, and
+//! each method takes `self` the way the trait's does, and as many parameters of the
+//! same types. Traits from Rust, such as `Display`, are left to rustc.
+//!
+//! Types are compared as they are written, once their paths are resolved, with
+//! `Self::Out` standing for the type the impl gives `Out`. Those naming a generic
+//! parameter or a type alias are left to rustc.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::resolve::{Res, Resolutions};
+
+pub fn check_impls(module: &Module, resolutions: &Resolutions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut traits = Traits::default();
+    traits.visit_module(module);
+    let mut checker = ImplChecker {
+        traits: traits.traits,
+        aliases: traits.aliases,
+        resolutions,
+        diagnostics,
+    };
+    checker.visit_module(module);
+}
+
+/// The traits of the crate, and its type aliases, by id.
+#[derive(Default)]
+struct Traits<'ast> {
+    traits: HashMap<NodeId, &'ast Trait>,
+    aliases: HashSet<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Traits<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Trait(found) => {
+                self.traits.insert(item.id, found);
+            }
+            ItemKind::TyAlias(_) => {
+                self.aliases.insert(item.id);
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+struct ImplChecker<'ast, 'r, 'd> {
+    traits: HashMap<NodeId, &'ast Trait>,
+    aliases: HashSet<NodeId>,
+    resolutions: &'r Resolutions,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'ast> Visitor<'ast> for ImplChecker<'ast, '_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Trait(found) => self.no_visibility(&found.items),
+            ItemKind::Impl(imp) => {
+                if let Some(path) = &imp.of_trait {
+                    self.no_visibility(&imp.items);
+                    let found = match self.resolutions.get(item.id) {
+                        Some(Res::Item(id)) => self.traits.get(&id).copied(),
+                        _ => None,
+                    };
+                    if let Some(found) = found {
+                        self.check_impl(imp, path, found);
+                    }
+                }
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+impl<'ast> ImplChecker<'ast, '_, '_> {
+    /// The items of a trait, and of its impls, are as public as the trait.
+    fn no_visibility(&mut self, items: &[Item]) {
+        for item in items.iter().filter(|item| item.vis == Visibility::Public) {
+            self.diagnostics.push(
+                Diagnostic::error("unnecessary visibility qualifier", item.span)
+                    .with_note("trait items always share the visibility of their trait"),
+            );
+        }
+    }
+
+    fn check_impl(&mut self, item: &Impl, path: &Path, found: &Trait) {
+        let trait_name = &found.name.name;
+        for assoc in &item.items {
+            let Some((name, what)) = assoc_name(assoc) else {
+                continue;
+            };
+            if let Some(declared) = declared(&found.items, name, what) {
+                if let (ItemKind::Function(declared), ItemKind::Function(function)) =
+                    (&declared.kind, &assoc.kind)
+                {
+                    self.check_signature(item, trait_name, declared, function);
+                }
+            } else {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "{what} `{}` is not a member of trait `{trait_name}`",
+                            name.name
+                        ),
+                        name.span,
+                    )
+                    .with_label(found.name.span, format!("`{trait_name}` is declared here")),
+                );
+            }
+        }
+        let missing: Vec<_> = found
+            .items
+            .iter()
+            .filter(|required| !has_default(required))
+            .filter_map(assoc_name)
+            .filter(|(name, what)| declared(&item.items, name, what).is_none())
+            .collect();
+        if !missing.is_empty() {
+            let names = missing
+                .iter()
+                .map(|(name, _)| format!("`{}`", name.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut diagnostic = Diagnostic::error(
+                format!("not all trait items implemented, missing: {names}"),
+                path.span,
+            );
+            for (name, _) in missing {
+                diagnostic =
+                    diagnostic.with_label(name.span, format!("`{}` from trait", name.name));
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Compares a method of an impl with its declaration in the trait.
+    fn check_signature(
+        &mut self,
+        imp: &Impl,
+        trait_name: &str,
+        declared: &Function,
+        function: &Function,
+    ) {
+        let name = &function.name;
+        let expected_self = declared.params.first().filter(|param| param.is_self());
+        let found_self = function.params.first().filter(|param| param.is_self());
+        match (expected_self, found_self) {
+            (Some(expected), None) => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "method `{}` has a `{}` declaration in the trait, but not in the impl",
+                            name.name,
+                            self_text(expected)
+                        ),
+                        name.span,
+                    )
+                    .with_label(
+                        expected.span,
+                        format!("`{}` used in trait", self_text(expected)),
+                    ),
+                );
+                return;
+            }
+            (None, Some(found)) => {
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!(
+                            "method `{}` has a `{}` declaration in the impl, but not in the trait",
+                            name.name,
+                            self_text(found)
+                        ),
+                        found.span,
+                    )
+                    .with_label(
+                        declared.name.span,
+                        format!("trait method declared without `{}`", self_text(found)),
+                    ),
+                );
+                return;
+            }
+            _ => {}
+        }
+        if declared.params.len() != function.params.len() {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let (expected, found) = (declared.params.len(), function.params.len());
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "method `{}` has {found} parameter{} but the declaration in trait `{trait_name}::{}` has {expected}",
+                        name.name,
+                        plural(found),
+                        name.name,
+                    ),
+                    name.span,
+                )
+                .with_label(
+                    declared.name.span,
+                    format!("trait requires {expected} parameter{}", plural(expected)),
+                ),
+            );
+            return;
+        }
+        let params = declared.params.iter().zip(&function.params);
+        let mismatch = params
+            .map(|(expected, found)| (Some(&expected.ty), Some(&found.ty), found.span))
+            .chain([(
+                declared.output.as_ref(),
+                function.output.as_ref(),
+                function.output.as_ref().map_or(name.span, |ty| ty.span),
+            )])
+            .find(|&(expected, found, _)| self.same_output(imp, expected, found) == Some(false));
+        if let Some((expected, found, span)) = mismatch {
+            let text = |ty: Option<&Ty>| ty.map_or("()".to_string(), Ty::to_text);
+            let mut diagnostic = Diagnostic::error(
+                format!("method `{}` has an incompatible type for trait", name.name),
+                span,
+            )
+            .with_note(format!(
+                "expected `{}`, found `{}`",
+                text(expected),
+                text(found)
+            ));
+            if let Some(expected) = expected {
+                diagnostic = diagnostic.with_label(expected.span, "type in trait");
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Compares two types of a signature, `()` when they are left out.
+    fn same_output(&self, imp: &Impl, expected: Option<&Ty>, found: Option<&Ty>) -> Option<bool> {
+        match (expected, found) {
+            (Some(expected), Some(found)) => self.same(imp, expected, found),
+            (Some(ty), None) | (None, Some(ty)) if is_unit(ty) => Some(true),
+            // `false` unless the type is left to rustc.
+            (Some(ty), None) | (None, Some(ty)) => self.same(imp, ty, ty).map(|_| false),
+            (None, None) => Some(true),
+        }
+    }
+
+    /// Whether the type `expected`, of the trait, is `found`, of `imp`, when that can
+    /// be told : `Self` in the trait is the type of the impl.
+    fn same<'a>(&self, imp: &'a Impl, expected: &'a Ty, found: &'a Ty) -> Option<bool> {
+        match (assoc_ty(imp, expected), assoc_ty(imp, found)) {
+            (Some(expected), _) => return self.same(imp, expected?, found),
+            (_, Some(found)) => return self.same(imp, expected, found?),
+            _ => {}
+        }
+        let res = |ty: &Ty| match &ty.kind {
+            TyKind::Path(_) => self.resolutions.get(ty.id),
+            _ => None,
+        };
+        match (res(expected), res(found)) {
+            (Some(Res::SelfTy(_)), Some(Res::SelfTy(_))) => return Some(true),
+            (Some(Res::SelfTy(_)), _) if is_self(expected) => {
+                return self.same(imp, &imp.self_ty, found)
+            }
+            (_, Some(Res::SelfTy(_))) if is_self(found) => {
+                return self.same(imp, expected, &imp.self_ty)
+            }
+            _ => {}
+        }
+        let all = |expected: &[Ty], found: &[Ty]| {
+            if expected.len() != found.len() {
+                return Some(false);
+            }
+            let mut same = Some(true);
+            for (expected, found) in expected.iter().zip(found) {
+                match self.same(imp, expected, found) {
+                    Some(true) => {}
+                    Some(false) => return Some(false),
+                    None => same = None,
+                }
+            }
+            same
+        };
+        match (&expected.kind, &found.kind) {
+            (TyKind::Path(expected_path), TyKind::Path(found_path)) => {
+                let same_item = match (res(expected), res(found)) {
+                    (Some(Res::Item(a)), _) | (_, Some(Res::Item(a)))
+                        if self.aliases.contains(&a) =>
+                    {
+                        return None
+                    }
+                    (Some(Res::Item(a)), Some(Res::Item(b))) => a == b,
+                    (Some(Res::External), Some(Res::External)) => {
+                        expected_path.last().name == found_path.last().name
+                    }
+                    (Some(Res::Item(_)), Some(Res::External))
+                    | (Some(Res::External), Some(Res::Item(_))) => false,
+                    _ => return None,
+                };
+                if !same_item {
+                    return Some(false);
+                }
+                let args = |path: &'a Path| path.segments.last().map_or(&[][..], |s| &s.args);
+                all(args(expected_path), args(found_path))
+            }
+            (
+                TyKind::Ref {
+                    mutable: expected_mut,
+                    inner: expected,
+                },
+                TyKind::Ref {
+                    mutable: found_mut,
+                    inner: found,
+                },
+            ) => {
+                if expected_mut != found_mut {
+                    return Some(false);
+                }
+                self.same(imp, expected, found)
+            }
+            (TyKind::Tuple(expected), TyKind::Tuple(found)) => all(expected, found),
+            (TyKind::Slice(expected), TyKind::Slice(found)) => self.same(imp, expected, found),
+            // The lengths are left to rustc.
+            (TyKind::Array(expected, _), TyKind::Array(found, _)) => {
+                self.same(imp, expected, found).filter(|same| !same)
+            }
+            (TyKind::Fn { .. }, _) | (_, TyKind::Fn { .. }) => None,
+            _ => {
+                // Different kinds of types, unless a path is left to rustc.
+                let known = |ty: &Ty| match &ty.kind {
+                    TyKind::Path(_) => match res(ty) {
+                        Some(Res::Item(id)) => !self.aliases.contains(&id),
+                        res => res == Some(Res::External),
+                    },
+                    _ => true,
+                };
+                (known(expected) && known(found)).then_some(false)
+            }
+        }
+    }
+}
+
+/// The name of a function or an associated type, with what it is.
+fn assoc_name(item: &Item) -> Option<(&Ident, &'static str)> {
+    match &item.kind {
+        ItemKind::Function(function) => Some((&function.name, "method")),
+        ItemKind::TyAlias(alias) => Some((&alias.name, "type")),
+        _ => None,
+    }
+}
+
+/// The item `name` of `items`, a function or an associated type.
+fn declared<'a>(items: &'a [Item], name: &Ident, what: &str) -> Option<&'a Item> {
+    items.iter().find(|item| {
+        assoc_name(item).is_some_and(|(other, kind)| other.name == name.name && kind == what)
+    })
+}
+
+/// The type `imp` gives the associated type `ty` names, when `ty` is `Self::Out` :
+/// `None` inside when the impl does not give one.
+fn assoc_ty<'a>(imp: &'a Impl, ty: &Ty) -> Option<Option<&'a Ty>> {
+    let TyKind::Path(path) = &ty.kind else {
+        return None;
+    };
+    let [first, name] = &path.segments[..] else {
+        return None;
+    };
+    if first.ident.name != "Self" {
+        return None;
+    }
+    let item = declared(&imp.items, &name.ident, "type");
+    let aliased = item.and_then(|item| match &item.kind {
+        ItemKind::TyAlias(alias) => alias.ty.as_ref(),
+        _ => None,
+    });
+    // `type Out = Self::Out;` is left to rustc.
+    Some(aliased.filter(|aliased| assoc_ty(imp, aliased).is_none()))
+}
+
+/// `&self`, `&mut self` or `self`, as the first parameter of a method.
+fn self_text(param: &Param) -> &'static str {
+    match &param.ty.kind {
+        TyKind::Ref { mutable: true, .. } => "&mut self",
+        TyKind::Ref { .. } => "&self",
+        _ => "self",
+    }
+}
+
+fn is_self(ty: &Ty) -> bool {
+    matches!(&ty.kind, TyKind::Path(path) if path.segments.len() == 1 && path.last().name == "Self")
+}
+
+fn is_unit(ty: &Ty) -> bool {
+    matches!(&ty.kind, TyKind::Tuple(tys) if tys.is_empty())
+}
+
+fn has_default(item: &Item) -> bool {
+    match &item.kind {
+        ItemKind::Function(function) => function.body.is_some(),
+        ItemKind::TyAlias(alias) => alias.ty.is_some(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_impls;
+    use crate::semantic::resolve::resolve_names;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        let (module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        check_impls(&module, &resolutions, &mut diagnostics);
+        diagnostics.into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn reports_missing_and_extra_items() {
+        let messages = messages(
+            "trait Shape {
+                type Unit;
+                fn area(&self) -> f64;
+                fn name(&self) -> f64 { 0.0 }
+            }
+            struct Square { side: f64 }
+            impl Shape for Square {
+                pub fn area(&self) -> f64 { self.side * self.side }
+                fn perimeter(&self) -> f64 { 4.0 * self.side }
+            }
+            impl Square { pub fn new(side: f64) -> Square { Square { side } } }
+            mod a { pub trait Clone { fn foo(&self); } }
+            impl Clone for Square { fn clone(&self) -> Square { Square { side: self.side } } }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "unnecessary visibility qualifier",
+                "method `perimeter` is not a member of trait `Shape`",
+                "not all trait items implemented, missing: `Unit`",
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_method_signatures() {
+        let messages = messages(
+            "type Meters = f64;
+            trait Shape {
+                fn area(&self) -> f64;
+                fn scale(&mut self, k: f64);
+                fn unit() -> Self;
+                fn same(&self, other: &Self) -> bool;
+                fn size(&self) -> Meters;
+                fn convert<T>(&self, x: T) -> T;
+            }
+            struct Square { side: f64 }
+            impl Shape for Square {
+                fn area(&self) -> i32 { 0 }
+                fn scale(&self, k: f64) {}
+                fn unit(&self) -> Square { Square { side: 1.0 } }
+                fn same(&self, other: &Square) -> bool { true }
+                fn size(&self) -> f64 { self.side }
+                fn convert<T>(&self, x: T) -> T { x }
+            }
+            struct Circle { r: f64 }
+            impl Shape for Circle {
+                fn area(self) -> f64 { 0.0 }
+                fn scale(&mut self, k: f64, extra: f64) {}
+                fn unit() -> Circle { Circle { r: 1.0 } }
+                fn same(&self, other: &Circle) -> String { String::new() }
+                fn size(&self) -> Meters { self.r }
+                fn convert<T>(&self, x: T) -> T { x }
+            }
+            trait Source {
+                type Out;
+                fn get(&self) -> Self::Out;
+                fn put(&mut self, out: Self::Out);
+            }
+            impl Source for Circle {
+                type Out = f64;
+                fn get(&self) -> f64 { self.r }
+                fn put(&mut self, out: Self::Out) {}
+            }
+            impl Source for Square {
+                type Out = f64;
+                fn get(&self) -> i32 { 0 }
+                fn put(&mut self, out: f64) {}
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "method `area` has an incompatible type for trait",
+                "method `scale` has an incompatible type for trait",
+                "method `unit` has a `&self` declaration in the impl, but not in the trait",
+                "method `area` has an incompatible type for trait",
+                "method `scale` has 3 parameters but the declaration in trait `Shape::scale` has 2",
+                "method `same` has an incompatible type for trait",
+                "method `get` has an incompatible type for trait",
+            ]
+        );
+    }
+}
@@ -0,0 +1,3207 @@
+//! Type checking. Every expression and every binding of the crate gets a [`Type`],
+//! and the mismatches are reported before any Rust is generated.
+//!
+//! Signatures are always written down : parameters, return types, fields and
+//! constants. Inside a body, each `let` without a type and each closure parameter
+//! gets an inference variable, which unification binds to the type it is used as.
+//! Integer literals get a variable of their own that only binds to an integer type,
+//! and default to `i32`, floats to `f64`. Once the body is checked, each literal must
+//! fit the type it got, and no value negated may be unsigned. Generic parameters are
+//! opaque in the body of their item, where only their bounds give them methods and
+//! operators, and replaced by fresh variables at each use of the item, bound to the
+//! type arguments the use writes. The initializers of
+//! constants and the lengths of arrays are checked by `consts`, which evaluates them.
+//!
+//! Method calls are resolved the way rustc does, through the inherent impls and the
+//! traits in scope, see [`Checker::method_call`]. What the crate cannot know, the
+//! types of other crates beyond their names and their methods, is [`Type::Unknown`] :
+//! it matches anything and rustc checks it.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+use crate::syntax::visit_mut::{self, VisitorMut};
+
+use super::bounds::STD_SUPERTRAITS;
+use super::consts::{self, IntTy};
+use super::resolve::{Res, Resolutions};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Char,
+    Str,
+    /// `i32`, `usize`...
+    Int(&'static str),
+    /// `f32` or `f64`
+    Float(&'static str),
+    /// `(i32, bool)`, `()` is the unit type.
+    Tuple(Vec<Type>),
+    /// `[i32; 3]`, the length is known when `consts` could evaluate it.
+    Array(Box<Type>, Option<u64>),
+    Slice(Box<Type>),
+    Ref(bool, Box<Type>),
+    /// A struct or an enum of the crate, with its type arguments.
+    Adt(NodeId, String, Vec<Type>),
+    /// A type from outside the crate, `String` or `Vec<i32>`.
+    Named(String, Vec<Type>),
+    /// A generic parameter, inside the item declaring it.
+    Param(NodeId, String),
+    /// `fn(i32) -> bool`, for functions and closures.
+    Fn(Vec<Type>, Box<Type>),
+    /// The type of `return`, `break` and `loop {}`, which never produce a value.
+    Never,
+    /// An inference variable, see [`Infer`].
+    Var(u32),
+    Unknown,
+}
+
+impl Type {
+    pub fn unit() -> Type {
+        Type::Tuple(vec![])
+    }
+
+    /// The primitive type named `name`.
+    pub(super) fn primitive(name: &str) -> Option<Type> {
+        Some(match name {
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "str" => Type::Str,
+            "f32" => Type::Float("f32"),
+            "f64" => Type::Float("f64"),
+            _ => Type::Int(IntTy::from_name(name)?.name),
+        })
+    }
+
+    /// Replaces the generic parameters found in `args`.
+    fn subst(&self, args: &HashMap<NodeId, Type>) -> Type {
+        let all = |tys: &[Type]| tys.iter().map(|ty| ty.subst(args)).collect();
+        match self {
+            Type::Param(id, _) => args.get(id).cloned().unwrap_or_else(|| self.clone()),
+            Type::Tuple(tys) => Type::Tuple(all(tys)),
+            Type::Array(inner, len) => Type::Array(Box::new(inner.subst(args)), *len),
+            Type::Slice(inner) => Type::Slice(Box::new(inner.subst(args))),
+            Type::Ref(mutable, inner) => Type::Ref(*mutable, Box::new(inner.subst(args))),
+            Type::Adt(id, name, tys) => Type::Adt(*id, name.clone(), all(tys)),
+            Type::Named(name, tys) => Type::Named(name.clone(), all(tys)),
+            Type::Fn(inputs, output) => Type::Fn(all(inputs), Box::new(output.subst(args))),
+            _ => self.clone(),
+        }
+    }
+}
+
+/// The type as Oxydant code writes it, once inferred.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Infer::default().display(self))
+    }
+}
+
+/// The types found by [`check_types`].
+#[derive(Debug, Default)]
+pub struct TypeckResults {
+    exprs: HashMap<NodeId, Type>,
+    bindings: HashMap<NodeId, Type>,
+    patterns: HashMap<NodeId, Type>,
+    methods: HashMap<NodeId, Callee>,
+    generic_args: HashMap<NodeId, HashMap<NodeId, Type>>,
+    outputs: HashMap<NodeId, Type>,
+}
+
+impl TypeckResults {
+    /// The type of an expression.
+    pub fn expr(&self, id: NodeId) -> Option<&Type> {
+        self.exprs.get(&id)
+    }
+
+    /// The type a function returns, by the id of its body.
+    pub fn output(&self, body: NodeId) -> Option<&Type> {
+        self.outputs.get(&body)
+    }
+
+    /// The type of the local or parameter bound by a binding pattern.
+    pub fn binding(&self, id: NodeId) -> Option<&Type> {
+        self.bindings.get(&id)
+    }
+
+    /// The type of the values a pattern matches, once the references it goes
+    /// through are removed : `Shape` for `Shape::Empty` against `&Shape`.
+    pub fn pattern(&self, id: NodeId) -> Option<&Type> {
+        self.patterns.get(&id)
+    }
+
+    /// The method of the crate a method call calls.
+    pub fn method(&self, id: NodeId) -> Option<NodeId> {
+        self.methods.get(&id).map(|callee| callee.def)
+    }
+
+    /// The method a method call calls, with the adjustments of its receiver.
+    pub fn callee(&self, id: NodeId) -> Option<&Callee> {
+        self.methods.get(&id)
+    }
+
+    /// The types the generic parameters of a function of the crate get at one of its
+    /// uses, a path or a method call, by parameter : those of the function and of
+    /// its impl.
+    pub fn generic_args(&self, id: NodeId) -> Option<&HashMap<NodeId, Type>> {
+        self.generic_args.get(&id)
+    }
+}
+
+/// Writes on each method call the method it calls, for the code generation and for
+/// the tools jumping to definitions.
+pub fn record_callees(module: &mut Module, types: &TypeckResults) {
+    struct Recorder<'t>(&'t TypeckResults);
+
+    impl VisitorMut for Recorder<'_> {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            if let ExprKind::MethodCall { callee, .. } = &mut expr.kind {
+                *callee = self.0.callee(expr.id).cloned();
+            }
+            visit_mut::walk_expr(self, expr);
+        }
+    }
+
+    Recorder(types).visit_module(module);
+}
+
+pub fn check_types(
+    module: &Module,
+    resolutions: &Resolutions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> TypeckResults {
+    let mut items = Items::default();
+    items.visit_module(module);
+    items.collect_methods(resolutions);
+    let mut checker = Checker {
+        items,
+        resolutions,
+        lengths: consts::array_lengths(module, resolutions),
+        results: TypeckResults::default(),
+        diagnostics,
+        infer: Infer::default(),
+        recorded: vec![],
+        patterns: vec![],
+        generic_args: vec![],
+        expanding: vec![],
+        return_ty: None,
+        loops: vec![],
+        literals: vec![],
+        operations: vec![],
+        modules: vec![],
+    };
+    checker.visit_module(module);
+    checker.results
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VarKind {
+    General,
+    Integer,
+    Float,
+}
+
+/// The inference variables of one body, with what unification bound them to.
+#[derive(Default)]
+struct Infer {
+    vars: Vec<(VarKind, Option<Type>)>,
+}
+
+impl Infer {
+    fn fresh(&mut self, kind: VarKind) -> Type {
+        self.vars.push((kind, None));
+        Type::Var(self.vars.len() as u32 - 1)
+    }
+
+    /// `ty` with its outer variables followed.
+    fn shallow(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(var) = ty {
+            match self
+                .vars
+                .get(var as usize)
+                .and_then(|(_, bound)| bound.as_ref())
+            {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    fn kind(&self, var: u32) -> VarKind {
+        self.vars
+            .get(var as usize)
+            .map_or(VarKind::General, |(kind, _)| *kind)
+    }
+
+    fn occurs(&self, var: u32, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Var(other) => other == var,
+            Type::Tuple(tys) | Type::Adt(_, _, tys) | Type::Named(_, tys) => {
+                tys.iter().any(|ty| self.occurs(var, ty))
+            }
+            Type::Array(inner, _) | Type::Slice(inner) | Type::Ref(_, inner) => {
+                self.occurs(var, &inner)
+            }
+            Type::Fn(inputs, output) => {
+                inputs.iter().any(|ty| self.occurs(var, ty)) || self.occurs(var, &output)
+            }
+            _ => false,
+        }
+    }
+
+    fn bind(&mut self, var: u32, ty: Type) -> bool {
+        let kind = self.kind(var);
+        let fits = match (&ty, kind) {
+            (Type::Var(other), _) => {
+                let merged = match (kind, self.kind(*other)) {
+                    (VarKind::General, other) | (other, VarKind::General) => Some(other),
+                    (a, b) if a == b => Some(a),
+                    _ => None,
+                };
+                match merged {
+                    Some(merged) => {
+                        self.vars[*other as usize].0 = merged;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            (_, VarKind::General) => !self.occurs(var, &ty),
+            (Type::Int(_), VarKind::Integer) | (Type::Float(_), VarKind::Float) => true,
+            _ => false,
+        };
+        if fits {
+            self.vars[var as usize].1 = Some(ty);
+        }
+        fits
+    }
+
+    /// Makes `a` and `b` the same type, binding variables as needed.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.shallow(a), self.shallow(b));
+        match (&a, &b) {
+            (Type::Unknown | Type::Never, _) | (_, Type::Unknown | Type::Never) => true,
+            (Type::Var(x), Type::Var(y)) if x == y => true,
+            (Type::Var(x), _) => self.bind(*x, b),
+            (_, Type::Var(y)) => self.bind(*y, a),
+            (Type::Bool, Type::Bool) | (Type::Char, Type::Char) | (Type::Str, Type::Str) => true,
+            (Type::Int(x), Type::Int(y)) | (Type::Float(x), Type::Float(y)) => x == y,
+            (Type::Tuple(xs), Type::Tuple(ys)) => self.unify_all(xs, ys),
+            (Type::Array(x, n), Type::Array(y, m)) => {
+                self.unify(x, y) && (n.is_none() || m.is_none() || n == m)
+            }
+            (Type::Slice(x), Type::Slice(y)) => self.unify(x, y),
+            (Type::Ref(m, x), Type::Ref(n, y)) => m == n && self.unify(x, y),
+            (Type::Adt(x, _, xs), Type::Adt(y, _, ys)) => x == y && self.unify_all(xs, ys),
+            (Type::Named(x, xs), Type::Named(y, ys)) => x == y && self.unify_all(xs, ys),
+            (Type::Param(x, _), Type::Param(y, _)) => x == y,
+            (Type::Fn(xs, x), Type::Fn(ys, y)) => self.unify_all(xs, ys) && self.unify(x, y),
+            _ => false,
+        }
+    }
+
+    fn unify_all(&mut self, xs: &[Type], ys: &[Type]) -> bool {
+        xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.unify(x, y))
+    }
+
+    /// Whether a value of type `found` can be used where `expected` is : the same
+    /// type, or a reference that Rust converts on its own, `&String` to `&str`.
+    fn coerce(&mut self, found: &Type, expected: &Type) -> bool {
+        let (found, expected) = (self.shallow(found), self.shallow(expected));
+        let (Type::Ref(from_mut, from), Type::Ref(to_mut, to)) = (&found, &expected) else {
+            return self.unify(&found, &expected);
+        };
+        if *to_mut && !from_mut {
+            return false;
+        }
+        match (self.shallow(from), self.shallow(to)) {
+            (Type::Named(name, _), Type::Str) if name == "String" => true,
+            (Type::Named(name, args), Type::Slice(to)) if name == "Vec" && args.len() == 1 => {
+                self.unify(&args[0], &to)
+            }
+            (Type::Array(from, _), Type::Slice(to)) => self.unify(&from, &to),
+            (Type::Named(name, args), to) if name == "Box" && args.len() == 1 => {
+                self.unify(&args[0], &to) || self.unify(from, &to)
+            }
+            (from, to) => self.unify(&from, &to),
+        }
+    }
+
+    /// `ty` with every bound variable replaced, and the integer and float variables
+    /// left unbound given their default.
+    fn resolve(&self, ty: &Type) -> Type {
+        let all = |tys: &[Type]| tys.iter().map(|ty| self.resolve(ty)).collect();
+        match self.shallow(ty) {
+            Type::Var(var) => match self.kind(var) {
+                VarKind::General => Type::Unknown,
+                VarKind::Integer => Type::Int("i32"),
+                VarKind::Float => Type::Float("f64"),
+            },
+            Type::Tuple(tys) => Type::Tuple(all(&tys)),
+            Type::Array(inner, len) => Type::Array(Box::new(self.resolve(&inner)), len),
+            Type::Slice(inner) => Type::Slice(Box::new(self.resolve(&inner))),
+            Type::Ref(mutable, inner) => Type::Ref(mutable, Box::new(self.resolve(&inner))),
+            Type::Adt(id, name, tys) => Type::Adt(id, name, all(&tys)),
+            Type::Named(name, tys) => Type::Named(name, all(&tys)),
+            Type::Fn(inputs, output) => Type::Fn(all(&inputs), Box::new(self.resolve(&output))),
+            ty => ty,
+        }
+    }
+
+    /// The type as Oxydant code writes it, `_` for what is not known yet.
+    fn display(&self, ty: &Type) -> String {
+        let all = |tys: &[Type]| tys.iter().map(|ty| self.display(ty)).collect::<Vec<_>>();
+        let generic = |name: &str, tys: &[Type]| match tys {
+            [] => name.to_string(),
+            tys => format!("{name}<{}>", all(tys).join(", ")),
+        };
+        match self.shallow(ty) {
+            Type::Bool => "bool".to_string(),
+            Type::Char => "char".to_string(),
+            Type::Str => "str".to_string(),
+            Type::Int(name) | Type::Float(name) => name.to_string(),
+            Type::Tuple(tys) => tuple_text(all(&tys)),
+            Type::Array(inner, Some(len)) => format!("[{}; {len}]", self.display(&inner)),
+            Type::Array(inner, None) => format!("[{}; _]", self.display(&inner)),
+            Type::Slice(inner) => format!("[{}]", self.display(&inner)),
+            Type::Ref(true, inner) => format!("&mut {}", self.display(&inner)),
+            Type::Ref(false, inner) => format!("&{}", self.display(&inner)),
+            Type::Adt(_, name, tys) | Type::Named(name, tys) => generic(&name, &tys),
+            Type::Param(_, name) => name,
+            Type::Fn(inputs, output) => {
+                let output = match *output {
+                    Type::Tuple(ref tys) if tys.is_empty() => None,
+                    ref output => Some(self.display(output)),
+                };
+                fn_text(all(&inputs), output)
+            }
+            Type::Never => "!".to_string(),
+            Type::Var(var) => match self.kind(var) {
+                VarKind::General => "_".to_string(),
+                VarKind::Integer => "{integer}".to_string(),
+                VarKind::Float => "{float}".to_string(),
+            },
+            Type::Unknown => "_".to_string(),
+        }
+    }
+}
+
+/// The methods of the traits of the standard library a generic parameter is
+/// commonly bounded by. The others are left to rustc.
+const STD_METHODS: &[(&str, &[&str])] = &[
+    ("Clone", &["clone", "clone_from"]),
+    ("Copy", &[]),
+    ("Debug", &["fmt"]),
+    ("Default", &[]),
+    ("Display", &["fmt", "to_string"]),
+    ("Eq", &[]),
+    ("Hash", &["hash"]),
+    ("Ord", &["cmp", "max", "min", "clamp"]),
+    ("PartialEq", &["eq", "ne"]),
+    ("PartialOrd", &["partial_cmp", "lt", "le", "gt", "ge"]),
+    ("ToString", &["to_string"]),
+    ("Send", &[]),
+    ("Sync", &[]),
+    ("Sized", &[]),
+    ("Add", &["add"]),
+    ("Sub", &["sub"]),
+    ("Mul", &["mul"]),
+    ("Div", &["div"]),
+    ("Rem", &["rem"]),
+    ("Neg", &["neg"]),
+    ("Not", &["not"]),
+];
+
+/// The methods every type has, through the blanket impls of `From`.
+const ANY_TYPE_METHODS: &[&str] = &["into", "try_into"];
+
+fn std_methods(name: &str) -> Option<&'static [&'static str]> {
+    STD_METHODS
+        .iter()
+        .find(|(trait_name, _)| *trait_name == name)
+        .map(|(_, methods)| *methods)
+}
+
+/// The type the method `name` of the strings and sequences of the standard library
+/// returns, for those whose type does not depend on the elements.
+fn sequence_method(ty: &Type, name: &str) -> Option<Type> {
+    let sequence = match ty {
+        Type::Str | Type::Slice(_) | Type::Array(..) => true,
+        Type::Named(ty, _) => ty == "String" || ty == "Vec",
+        _ => false,
+    };
+    match name {
+        "len" if sequence => Some(Type::Int("usize")),
+        "is_empty" if sequence => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+/// A trait bounding a generic parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound<'ast> {
+    Crate(NodeId),
+    /// A trait of the standard library, by name.
+    Std(&'ast str),
+}
+
+/// What the crate declares, by id.
+#[derive(Default)]
+struct Items<'ast> {
+    /// The functions, with the impl of the methods.
+    functions: HashMap<NodeId, (&'ast Function, Option<&'ast Impl>)>,
+    structs: HashMap<NodeId, &'ast Struct>,
+    enums: HashMap<NodeId, &'ast Enum>,
+    consts: HashMap<NodeId, &'ast Const>,
+    aliases: HashMap<NodeId, &'ast TyAlias>,
+    impls: HashMap<NodeId, &'ast Impl>,
+    traits: HashMap<NodeId, &'ast Trait>,
+    generics: HashMap<NodeId, &'ast str>,
+    /// The bounds declared with each generic parameter.
+    bounds: HashMap<NodeId, &'ast [Path]>,
+    /// The `where` clauses, whose types are resolved when a parameter is looked up.
+    wheres: Vec<&'ast WherePredicate>,
+    /// The path from the root of the structs, the enums and the traits,
+    /// `crate::shapes::Circle`.
+    paths: HashMap<NodeId, String>,
+    /// The modules around the item being visited, by the id of their `mod` item.
+    modules: Vec<(NodeId, &'ast str)>,
+    /// The module declaring each struct, `None` for the root.
+    owners: HashMap<NodeId, Option<NodeId>>,
+    /// The methods of the inherent impls of each struct or enum.
+    methods: HashMap<(NodeId, &'ast str), NodeId>,
+    /// The trait impls, by trait and type.
+    trait_impls: Vec<(NodeId, SelfKey, &'ast Impl)>,
+    /// The impls of the traits of the operators, by trait and type.
+    operators: Vec<(&'ast str, NodeId, &'ast Impl)>,
+    /// The traits of the standard library each struct or enum derives or implements,
+    /// by name.
+    std_traits: HashMap<NodeId, Vec<&'ast str>>,
+}
+
+/// The type of an impl, as method calls look for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SelfKey {
+    Adt(NodeId),
+    /// A primitive type or a type of another crate, by name.
+    Named(String),
+}
+
+impl SelfKey {
+    fn of(ty: &Type) -> Option<SelfKey> {
+        Some(match ty {
+            Type::Adt(id, _, _) => SelfKey::Adt(*id),
+            Type::Named(name, _) => SelfKey::Named(name.clone()),
+            Type::Int(name) | Type::Float(name) => SelfKey::Named(name.to_string()),
+            Type::Bool => SelfKey::Named("bool".to_string()),
+            Type::Char => SelfKey::Named("char".to_string()),
+            Type::Str => SelfKey::Named("str".to_string()),
+            _ => return None,
+        })
+    }
+}
+
+impl<'ast> Visitor<'ast> for Items<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if let ItemKind::Struct(_) | ItemKind::Enum(_) = &item.kind {
+            let derives = item.attrs.iter().flat_map(Attribute::derives);
+            self.std_traits
+                .entry(item.id)
+                .or_default()
+                .extend(derives.map(|path| path.last().name.as_str()));
+        }
+        match &item.kind {
+            ItemKind::Function(function) => {
+                self.functions.insert(item.id, (function, None));
+            }
+            ItemKind::Struct(item_struct) => {
+                self.structs.insert(item.id, item_struct);
+                self.owners
+                    .insert(item.id, self.modules.last().map(|(id, _)| *id));
+                self.declare_path(item.id, &item_struct.name);
+            }
+            ItemKind::Enum(item_enum) => {
+                self.enums.insert(item.id, item_enum);
+                self.declare_path(item.id, &item_enum.name);
+            }
+            ItemKind::Trait(item_trait) => {
+                self.traits.insert(item.id, item_trait);
+                self.declare_path(item.id, &item_trait.name);
+            }
+            ItemKind::Mod(module) => {
+                self.modules.push((item.id, &module.name.name));
+                visit::walk_item(self, item);
+                self.modules.pop();
+                return;
+            }
+            ItemKind::Const(constant) | ItemKind::Static(constant) => {
+                self.consts.insert(item.id, constant);
+            }
+            ItemKind::TyAlias(alias) => {
+                self.aliases.insert(item.id, alias);
+            }
+            ItemKind::Impl(imp) => {
+                self.impls.insert(item.id, imp);
+                visit::walk_item(self, item);
+                for assoc in &imp.items {
+                    if let ItemKind::Function(function) = &assoc.kind {
+                        self.functions.insert(assoc.id, (function, Some(imp)));
+                    }
+                }
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_generics(&mut self, generics: &'ast Generics) {
+        for param in &generics.params {
+            self.generics.insert(param.id, &param.name.name);
+            self.bounds.insert(param.id, &param.bounds);
+        }
+        self.wheres.extend(&generics.where_clause);
+    }
+
+    // The bodies hold no items.
+    fn visit_block(&mut self, _block: &'ast Block) {}
+}
+
+impl<'ast> Items<'ast> {
+    fn declare_path(&mut self, id: NodeId, name: &Ident) {
+        let mut path = vec!["crate"];
+        path.extend(self.modules.iter().map(|(_, name)| name));
+        path.push(&name.name);
+        self.paths.insert(id, path.join("::"));
+    }
+
+    fn collect_methods(&mut self, resolutions: &Resolutions) {
+        let mut impls: Vec<_> = self.impls.iter().map(|(id, imp)| (*id, *imp)).collect();
+        impls.sort_by_key(|(id, _)| *id);
+        for (id, imp) in impls {
+            let key = match (resolutions.get(imp.self_ty.id), &imp.self_ty.kind) {
+                (Some(Res::Item(ty)), _) if self.adt_generics(ty).is_some() => SelfKey::Adt(ty),
+                (Some(Res::External), TyKind::Path(path)) => {
+                    SelfKey::Named(path.last().name.clone())
+                }
+                _ => continue,
+            };
+            if let Some(path) = &imp.of_trait {
+                if let SelfKey::Adt(ty) = key {
+                    self.operators.push((&path.last().name, ty, imp));
+                }
+                match (resolutions.get(id), key) {
+                    (Some(Res::Item(of_trait)), key) => self.trait_impls.push((of_trait, key, imp)),
+                    (Some(Res::External), SelfKey::Adt(ty)) => self
+                        .std_traits
+                        .entry(ty)
+                        .or_default()
+                        .push(&path.last().name),
+                    _ => {}
+                }
+                continue;
+            }
+            let SelfKey::Adt(ty) = key else {
+                continue;
+            };
+            for item in &imp.items {
+                if let ItemKind::Function(function) = &item.kind {
+                    self.methods
+                        .entry((ty, &function.name.name))
+                        .or_insert(item.id);
+                }
+            }
+        }
+    }
+
+    /// The function `name` of the trait `id`, with a body when `provided`.
+    fn trait_method(&self, id: NodeId, name: &str, provided: bool) -> Option<NodeId> {
+        self.traits
+            .get(&id)?
+            .items
+            .iter()
+            .find_map(|item| match &item.kind {
+                ItemKind::Function(function)
+                    if function.name.name == name && (!provided || function.body.is_some()) =>
+                {
+                    Some(item.id)
+                }
+                _ => None,
+            })
+    }
+
+    /// How the method `id` takes `self` : `Some(None)` by value, `Some(Some(true))`
+    /// by `&mut`, `None` for an associated function.
+    fn self_kind(&self, id: NodeId) -> Option<Option<bool>> {
+        let (function, _) = self.functions.get(&id)?;
+        let param = function.params.first().filter(|param| param.is_self())?;
+        match &param.ty.kind {
+            TyKind::Ref { mutable, .. } => Some(Some(*mutable)),
+            TyKind::Path(path) if path.last().name == "Self" => Some(None),
+            _ => None,
+        }
+    }
+
+    /// The fields of a struct, or of a variant with named fields.
+    fn fields(&self, res: Res) -> Option<(&'ast [FieldDef], NodeId)> {
+        match res {
+            Res::Item(id) => Some((&self.structs.get(&id)?.fields, id)),
+            Res::Variant(id, index) => match &self.enums.get(&id)?.variants[index].data {
+                VariantData::Struct(fields) => Some((fields, id)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn adt_generics(&self, id: NodeId) -> Option<&'ast Generics> {
+        match (self.structs.get(&id), self.enums.get(&id)) {
+            (Some(item), _) => Some(&item.generics),
+            (_, Some(item)) => Some(&item.generics),
+            _ => None,
+        }
+    }
+
+    fn adt_name(&self, id: NodeId) -> &'ast str {
+        match (self.structs.get(&id), self.enums.get(&id)) {
+            (Some(item), _) => &item.name.name,
+            (_, Some(item)) => &item.name.name,
+            _ => "_",
+        }
+    }
+}
+
+/// What a struct literal or a struct pattern names, for the messages.
+fn struct_kind(res: Option<Res>) -> &'static str {
+    match res {
+        Some(Res::Variant(..)) => "variant",
+        _ => "struct",
+    }
+}
+
+/// A method a method call may call.
+struct Candidate {
+    def: NodeId,
+    /// The trait of the method, `None` for an inherent one.
+    of_trait: Option<NodeId>,
+    /// The step of the autoderef of the receiver whose type the impl is for.
+    step: usize,
+    /// How the method takes `self` : `None` by value, `Some(true)` by `&mut`.
+    by_ref: Option<bool>,
+}
+
+impl Candidate {
+    /// The dereferences and the reference taken of the receiver for it to be the
+    /// `self` of the candidate, at the step `step`.
+    fn fits(&self, steps: &[Type], step: usize, autoref: Option<bool>) -> bool {
+        (self.step == step && self.by_ref == autoref)
+            || (autoref.is_none()
+                && self.step == step + 1
+                && matches!(steps[step], Type::Ref(mutable, _) if self.by_ref == Some(mutable)))
+    }
+}
+
+/// The candidate a method call calls, with the adjustments of its receiver.
+struct Pick<'c> {
+    candidate: &'c Candidate,
+    derefs: usize,
+    autoref: Option<bool>,
+}
+
+/// The number of dereferences method calls try on their receiver.
+const MAX_DEREFS: usize = 8;
+
+/// A signature with its generics replaced by fresh variables.
+struct Signature {
+    inputs: Vec<Type>,
+    output: Type,
+    /// The type of the impl of a method.
+    self_ty: Option<Type>,
+    /// The variables of the generic parameters, by parameter.
+    args: HashMap<NodeId, Type>,
+}
+
+struct Checker<'ast, 'r, 'd> {
+    items: Items<'ast>,
+    resolutions: &'r Resolutions,
+    results: TypeckResults,
+    diagnostics: &'d mut Vec<Diagnostic>,
+    /// The variables of the body being checked.
+    infer: Infer,
+    /// The types given in the body being checked, resolved at its end.
+    recorded: Vec<(NodeId, Type, bool)>,
+    /// The types the patterns of the body being checked match.
+    patterns: Vec<(NodeId, Type)>,
+    /// The generic arguments of the functions the body being checked uses.
+    generic_args: Vec<(NodeId, HashMap<NodeId, Type>)>,
+    return_ty: Option<Type>,
+    /// The array lengths `consts` evaluated, by the id of their expression.
+    lengths: HashMap<NodeId, u64>,
+    /// The type of the values the enclosing loops `break` with, `None` for `while`
+    /// and `for` which break without a value.
+    loops: Vec<Option<Type>>,
+    /// The number literals of the body being checked, with whether `-` applies to
+    /// them, checked against their type at its end.
+    literals: Vec<(&'ast Lit, bool, Span, Type)>,
+    /// The operators of the body applied to values of a type not known yet, checked
+    /// once it is : `-` must not turn out unsigned, and a struct or an enum must
+    /// implement the trait of the operator.
+    operations: Vec<(OverloadedOp, Span, Type)>,
+    /// The type aliases being lowered. One naming itself is a cycle, reported by
+    /// `consts`.
+    expanding: Vec<NodeId>,
+    /// The `mod` items around the item being checked, which see the private fields
+    /// of the structs they declare.
+    modules: Vec<NodeId>,
+}
+
+impl<'ast> Visitor<'ast> for Checker<'ast, '_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if let ItemKind::Mod(_) = &item.kind {
+            self.modules.push(item.id);
+            visit::walk_item(self, item);
+            self.modules.pop();
+            return;
+        }
+        visit::walk_item(self, item);
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        let Some(body) = &function.body else {
+            return;
+        };
+        let output = function
+            .output
+            .as_ref()
+            .map_or(Type::unit(), |ty| self.lower(ty));
+        self.return_ty = Some(output.clone());
+        self.results.outputs.insert(body.id, output.clone());
+        for param in &function.params {
+            let ty = self.lower(&param.ty);
+            self.check_pattern(&param.pat, &ty, false);
+        }
+        let found = self.check_block(body);
+        // A body reaching its end without a value is reported by `flow`.
+        if let Some(tail) = body.tail() {
+            if self.coerce(tail.span, &found, &output) {
+                self.sized(tail, &found);
+            }
+        }
+        self.finish();
+    }
+
+    // The initializers of constants are evaluated by `consts` first, against their
+    // type : what it reported is not reported again.
+    fn visit_const(&mut self, item: &'ast Const) {
+        let span = item.expr.span;
+        let evaluated: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span)
+            .filter(|found| found.start >= span.start && found.end <= span.end)
+            .collect();
+        let reported = self.diagnostics.len();
+        let expected = self.lower(&item.ty);
+        let found = self.check_expr(&item.expr);
+        self.coerce(span, &found, &expected);
+        self.literals.clear();
+        self.operations.clear();
+        self.finish();
+        let new = self.diagnostics.split_off(reported);
+        self.diagnostics
+            .extend(new.into_iter().filter(|diagnostic| {
+                !evaluated.iter().any(|found| {
+                    found.start < diagnostic.span.end && diagnostic.span.start < found.end
+                })
+            }));
+    }
+
+    // Array lengths are constants, checked by `consts`.
+    fn visit_ty(&mut self, _ty: &'ast Ty) {}
+}
+
+impl<'ast> Checker<'ast, '_, '_> {
+    /// Stores the types of the body just checked, and starts afresh.
+    fn finish(&mut self) {
+        self.check_operations();
+        self.check_literals();
+        for (id, ty, is_binding) in std::mem::take(&mut self.recorded) {
+            let ty = self.infer.resolve(&ty);
+            match is_binding {
+                true => self.results.bindings.insert(id, ty),
+                false => self.results.exprs.insert(id, ty),
+            };
+        }
+        for (id, ty) in std::mem::take(&mut self.patterns) {
+            let ty = self.infer.resolve(&ty);
+            self.results.patterns.insert(id, ty);
+        }
+        for (id, args) in std::mem::take(&mut self.generic_args) {
+            let args = args
+                .into_iter()
+                .map(|(param, ty)| (param, self.infer.resolve(&ty)))
+                .collect();
+            self.results.generic_args.insert(id, args);
+        }
+        self.infer = Infer::default();
+        self.return_ty = None;
+    }
+
+    /// The type written `ty`.
+    fn lower(&mut self, ty: &Ty) -> Type {
+        match &ty.kind {
+            TyKind::Path(path) => {
+                let args: Vec<_> = path.segments.last().map_or(vec![], |segment| {
+                    segment.args.iter().map(|arg| self.lower(arg)).collect()
+                });
+                self.lower_path(self.resolutions.get(ty.id), path, args)
+            }
+            TyKind::Ref { mutable, inner } => Type::Ref(*mutable, Box::new(self.lower(inner))),
+            TyKind::Tuple(tys) => Type::Tuple(tys.iter().map(|ty| self.lower(ty)).collect()),
+            TyKind::Array(inner, len) => {
+                let len = self.lengths.get(&len.id).copied();
+                Type::Array(Box::new(self.lower(inner)), len)
+            }
+            TyKind::Slice(inner) => Type::Slice(Box::new(self.lower(inner))),
+            TyKind::Fn { inputs, output } => Type::Fn(
+                inputs.iter().map(|ty| self.lower(ty)).collect(),
+                Box::new(output.as_ref().map_or(Type::unit(), |ty| self.lower(ty))),
+            ),
+        }
+    }
+
+    fn lower_path(&mut self, res: Option<Res>, path: &Path, args: Vec<Type>) -> Type {
+        match res {
+            Some(Res::Item(id)) => {
+                if let Some(generics) = self.items.adt_generics(id) {
+                    // Arguments left out are inferred.
+                    let args = (0..generics.params.len())
+                        .map(|i| {
+                            args.get(i)
+                                .cloned()
+                                .unwrap_or_else(|| self.infer.fresh(VarKind::General))
+                        })
+                        .collect();
+                    return Type::Adt(id, self.items.adt_name(id).to_string(), args);
+                }
+                match self
+                    .items
+                    .aliases
+                    .get(&id)
+                    .and_then(|alias| alias.ty.as_ref())
+                {
+                    Some(ty) if !self.expanding.contains(&id) => {
+                        self.expanding.push(id);
+                        let ty = self.lower(ty);
+                        self.expanding.pop();
+                        ty
+                    }
+                    _ => Type::Unknown,
+                }
+            }
+            Some(Res::Generic(id)) => {
+                Type::Param(id, self.items.generics.get(&id).unwrap_or(&"_").to_string())
+            }
+            Some(Res::SelfTy(id)) => match self.items.impls.get(&id) {
+                Some(imp) => self.lower(&imp.self_ty),
+                None => Type::Param(id, "Self".to_string()),
+            },
+            Some(Res::External) => {
+                let name = &path.last().name;
+                let mut args = args;
+                // `io::Result<T>` names its error type itself, which is left to rustc.
+                if name == "Result" && args.len() == 1 {
+                    args.push(Type::Unknown);
+                }
+                Type::primitive(name).unwrap_or_else(|| Type::Named(name.clone(), args))
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Fresh variables for the generic parameters of `generics`.
+    fn instantiate<'g>(
+        &mut self,
+        generics: impl IntoIterator<Item = &'g Generics>,
+    ) -> HashMap<NodeId, Type> {
+        generics
+            .into_iter()
+            .flat_map(|generics| &generics.params)
+            .map(|param| (param.id, self.infer.fresh(VarKind::General)))
+            .collect()
+    }
+
+    /// The signature of the function `id`, for one of its uses.
+    fn signature(&mut self, id: NodeId) -> Option<Signature> {
+        let (function, imp) = *self.items.functions.get(&id)?;
+        let args = self
+            .instantiate(std::iter::once(&function.generics).chain(imp.map(|imp| &imp.generics)));
+        let inputs = function
+            .params
+            .iter()
+            .map(|param| self.lower(&param.ty).subst(&args))
+            .collect();
+        let output = function
+            .output
+            .as_ref()
+            .map_or(Type::unit(), |ty| self.lower(ty))
+            .subst(&args);
+        let self_ty = imp.map(|imp| self.lower(&imp.self_ty).subst(&args));
+        Some(Signature {
+            inputs,
+            output,
+            self_ty,
+            args,
+        })
+    }
+
+    /// The enum `id` with fresh type arguments.
+    fn fresh_adt(&mut self, id: NodeId) -> (Type, HashMap<NodeId, Type>) {
+        let generics = self.items.adt_generics(id);
+        let args = self.instantiate(generics);
+        let tys = generics
+            .map(|generics| {
+                generics
+                    .params
+                    .iter()
+                    .map(|p| args[&p.id].clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        (
+            Type::Adt(id, self.items.adt_name(id).to_string(), tys),
+            args,
+        )
+    }
+
+    /// The arguments of the generics of an ADT, by parameter.
+    fn adt_args(&self, id: NodeId, tys: &[Type]) -> HashMap<NodeId, Type> {
+        self.items
+            .adt_generics(id)
+            .map(|generics| {
+                generics
+                    .params
+                    .iter()
+                    .zip(tys)
+                    .map(|(param, ty)| (param.id, ty.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn mismatch(&mut self, span: Span, expected: &Type, found: &Type) {
+        let note = format!(
+            "expected `{}`, found `{}`",
+            self.infer.display(expected),
+            self.infer.display(found)
+        );
+        self.diagnostics
+            .push(Diagnostic::error("mismatched types", span).with_note(note));
+    }
+
+    fn unify(&mut self, span: Span, found: &Type, expected: &Type) -> bool {
+        let unified = self.infer.unify(found, expected);
+        if !unified {
+            self.mismatch(span, expected, found);
+        }
+        unified
+    }
+
+    fn coerce(&mut self, span: Span, found: &Type, expected: &Type) -> bool {
+        let coerced = self.infer.coerce(found, expected);
+        if !coerced {
+            self.mismatch(span, expected, found);
+        }
+        coerced
+    }
+
+    /// The type behind any number of references.
+    fn peel_refs(&self, ty: &Type) -> Type {
+        match self.infer.shallow(ty) {
+            Type::Ref(_, inner) => self.peel_refs(&inner),
+            ty => ty,
+        }
+    }
+
+    fn check_block(&mut self, block: &'ast Block) -> Type {
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            let ty = match &stmt.kind {
+                StmtKind::Let(local) => {
+                    self.check_local(local);
+                    continue;
+                }
+                StmtKind::Expr(expr) | StmtKind::Semi(expr) => self.check_expr(expr),
+            };
+            diverges |= self.infer.shallow(&ty) == Type::Never;
+        }
+        match block.tail() {
+            Some(tail) => self.results_of(tail.id),
+            None if diverges => Type::Never,
+            None => Type::unit(),
+        }
+    }
+
+    /// The type recorded for `id` in the body being checked.
+    fn results_of(&self, id: NodeId) -> Type {
+        self.recorded
+            .iter()
+            .rev()
+            .find(|(recorded, _, is_binding)| *recorded == id && !is_binding)
+            .map_or(Type::Unknown, |(_, ty, _)| ty.clone())
+    }
+
+    fn check_local(&mut self, local: &'ast Local) {
+        let declared = match &local.ty {
+            Some(ty) => self.lower(ty),
+            None => self.infer.fresh(VarKind::General),
+        };
+        if let Some(init) = &local.init {
+            let found = self.check_expr(init);
+            if self.coerce(init.span, &found, &declared) {
+                self.sized(init, &found);
+            }
+        }
+        if let Some(els) = &local.els {
+            self.check_block(els);
+        }
+        self.check_pattern(&local.pat, &declared, false);
+    }
+
+    /// Gives their types to the bindings of `pattern`, matched against `expected`.
+    /// `by_ref` is set once a reference has been matched by a pattern that is not
+    /// one, `Some(x)` against `&Option<i32>` : the bindings are then references.
+    fn check_pattern(&mut self, pattern: &'ast Pattern, expected: &Type, by_ref: bool) {
+        let expected = self.infer.shallow(expected);
+        if let Type::Ref(_, inner) = &expected {
+            if matches!(
+                pattern.kind,
+                PatKind::Tuple(_)
+                    | PatKind::TupleStruct(..)
+                    | PatKind::Struct { .. }
+                    | PatKind::Path(_)
+            ) {
+                return self.check_pattern(pattern, inner, true);
+            }
+        }
+        self.patterns.push((pattern.id, expected.clone()));
+        let res = self.resolutions.get(pattern.id);
+        match &pattern.kind {
+            PatKind::Wild | PatKind::Rest | PatKind::Error => {}
+            PatKind::Paren(inner) => self.check_pattern(inner, &expected, by_ref),
+            PatKind::Binding { sub, .. } => {
+                let ty = match by_ref {
+                    true => Type::Ref(false, Box::new(expected.clone())),
+                    false => expected.clone(),
+                };
+                self.recorded.push((pattern.id, ty, true));
+                if let Some(sub) = sub {
+                    self.check_pattern(sub, &expected, by_ref);
+                }
+            }
+            PatKind::Lit(expr) => {
+                let found = self.check_expr(expr);
+                self.unify(pattern.span, &found, &expected);
+            }
+            PatKind::Range { lo, hi, .. } => {
+                for bound in [lo, hi].into_iter().flatten() {
+                    let found = self.check_expr(bound);
+                    self.unify(bound.span, &found, &expected);
+                }
+            }
+            PatKind::Tuple(patterns) => {
+                let rest = patterns.iter().position(|p| p.kind == PatKind::Rest);
+                let tys = match (&expected, rest) {
+                    (Type::Tuple(tys), _) => tys.clone(),
+                    (Type::Var(_), None) => {
+                        let tys: Vec<_> = patterns
+                            .iter()
+                            .map(|_| self.infer.fresh(VarKind::General))
+                            .collect();
+                        if !self.unify(pattern.span, &Type::Tuple(tys.clone()), &expected) {
+                            return;
+                        }
+                        tys
+                    }
+                    (Type::Unknown, _) => vec![Type::Unknown; patterns.len()],
+                    (Type::Var(var), _) if self.infer.kind(*var) == VarKind::General => {
+                        vec![Type::Unknown; patterns.len()]
+                    }
+                    _ => {
+                        let found = Type::Tuple(vec![Type::Unknown; patterns.len()]);
+                        return self.mismatch(pattern.span, &expected, &found);
+                    }
+                };
+                self.check_subpatterns(pattern, patterns, &tys, rest, "tuple", by_ref);
+            }
+            PatKind::TupleStruct(_, patterns) => {
+                let tys = match res {
+                    Some(Res::Variant(id, index)) => {
+                        let (adt, args) = self.fresh_adt(id);
+                        if !self.unify(pattern.span, &adt, &expected) {
+                            return;
+                        }
+                        match &self.items.enums[&id].variants[index].data {
+                            VariantData::Tuple(tys) => {
+                                tys.iter().map(|ty| self.lower(ty).subst(&args)).collect()
+                            }
+                            _ => return,
+                        }
+                    }
+                    Some(Res::External) => match self.prelude_variant(pattern, &expected) {
+                        Some(ty) => vec![ty],
+                        None => return,
+                    },
+                    _ => vec![Type::Unknown; patterns.len()],
+                };
+                let rest = patterns.iter().position(|p| p.kind == PatKind::Rest);
+                self.check_subpatterns(pattern, patterns, &tys, rest, "tuple variant", by_ref);
+            }
+            PatKind::Struct { fields, rest, path } => {
+                let Some((defs, args)) = self.struct_pattern(pattern, res, &expected) else {
+                    for field in fields {
+                        self.check_pattern(&field.pat, &Type::Unknown, by_ref);
+                    }
+                    return;
+                };
+                for field in fields {
+                    let ty = match defs.iter().find(|def| def.name.name == field.name.name) {
+                        Some(def) => {
+                            if let Some(Res::Item(id)) = res {
+                                self.check_privacy(id, def, field.name.span);
+                            }
+                            self.lower(&def.ty).subst(&args)
+                        }
+                        None => {
+                            self.diagnostics.push(Diagnostic::error(
+                                format!(
+                                    "{} `{}` does not have a field named `{}`",
+                                    struct_kind(res),
+                                    path.to_text(),
+                                    field.name.name
+                                ),
+                                field.name.span,
+                            ));
+                            Type::Unknown
+                        }
+                    };
+                    self.check_pattern(&field.pat, &ty, by_ref);
+                }
+                if !rest {
+                    for def in defs {
+                        if !fields.iter().any(|field| field.name.name == def.name.name) {
+                            self.diagnostics.push(
+                                Diagnostic::error(
+                                    format!("pattern does not mention field `{}`", def.name.name),
+                                    pattern.span,
+                                )
+                                .with_note("use `..` to ignore the other fields"),
+                            );
+                        }
+                    }
+                }
+            }
+            PatKind::Path(_) => {
+                let found = match res {
+                    Some(Res::Variant(id, _)) => self.fresh_adt(id).0,
+                    Some(Res::Item(id)) => match self.items.consts.get(&id) {
+                        Some(constant) => self.lower(&constant.ty),
+                        None if self.items.structs.contains_key(&id) => self.fresh_adt(id).0,
+                        None => Type::Unknown,
+                    },
+                    Some(Res::External) => {
+                        self.prelude_variant(pattern, &expected);
+                        return;
+                    }
+                    _ => Type::Unknown,
+                };
+                self.unify(pattern.span, &found, &expected);
+            }
+            PatKind::Or(alternatives) => {
+                for alternative in alternatives {
+                    self.check_pattern(alternative, &expected, by_ref);
+                }
+            }
+        }
+    }
+
+    /// Matches `patterns` against the types of the fields, around a `..`.
+    fn check_subpatterns(
+        &mut self,
+        pattern: &Pattern,
+        patterns: &'ast [Pattern],
+        tys: &[Type],
+        rest: Option<usize>,
+        what: &str,
+        by_ref: bool,
+    ) {
+        let count = patterns.len() - usize::from(rest.is_some());
+        if count > tys.len() || (rest.is_none() && count != tys.len()) {
+            let fields = |n: usize| if n == 1 { "field" } else { "fields" };
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "this pattern has {count} {}, but the corresponding {what} has {} {}",
+                    fields(count),
+                    tys.len(),
+                    fields(tys.len())
+                ),
+                pattern.span,
+            ));
+            return;
+        }
+        let after = rest.map_or(0, |rest| patterns.len() - rest - 1);
+        for (i, sub) in patterns.iter().enumerate() {
+            let ty = match rest {
+                Some(rest) if i == rest => continue,
+                Some(rest) if i > rest => &tys[tys.len() - after + (i - rest - 1)],
+                _ => &tys[i],
+            };
+            self.check_pattern(sub, ty, by_ref);
+        }
+    }
+
+    /// The type of the field of `Some(x)`, `Ok(x)` or `Err(x)`, and checks `None`.
+    fn prelude_variant(&mut self, pattern: &Pattern, expected: &Type) -> Option<Type> {
+        let path = match &pattern.kind {
+            PatKind::TupleStruct(path, _) | PatKind::Path(path) => path,
+            _ => return None,
+        };
+        let (found, field) = self.prelude_value(&path.last().name)?;
+        self.unify(pattern.span, &found, expected).then_some(field)
+    }
+
+    /// `Option<T>` or `Result<T, E>` with fresh variables, for one of their variants,
+    /// with the type of its field.
+    fn prelude_value(&mut self, name: &str) -> Option<(Type, Type)> {
+        let (ok, err) = (
+            self.infer.fresh(VarKind::General),
+            self.infer.fresh(VarKind::General),
+        );
+        Some(match name {
+            "Some" | "None" => (Type::Named("Option".to_string(), vec![ok.clone()]), ok),
+            "Ok" => (Type::Named("Result".to_string(), vec![ok.clone(), err]), ok),
+            "Err" => (
+                Type::Named("Result".to_string(), vec![ok, err.clone()]),
+                err,
+            ),
+            _ => return None,
+        })
+    }
+
+    /// The fields of the struct or the variant a struct pattern names.
+    fn struct_pattern(
+        &mut self,
+        pattern: &Pattern,
+        res: Option<Res>,
+        expected: &Type,
+    ) -> Option<(&'ast [FieldDef], HashMap<NodeId, Type>)> {
+        let res = match res? {
+            Res::SelfTy(id) => match self.lower_path(
+                Some(Res::SelfTy(id)),
+                &Path::from_ident(Ident {
+                    name: "Self".to_string(),
+                    span: pattern.span,
+                    written: None,
+                }),
+                vec![],
+            ) {
+                Type::Adt(id, ..) => Res::Item(id),
+                _ => return None,
+            },
+            res => res,
+        };
+        let (fields, id) = self.items.fields(res)?;
+        let (adt, args) = self.fresh_adt(id);
+        self.unify(pattern.span, &adt, expected)
+            .then_some((fields, args))
+    }
+
+    fn check_expr(&mut self, expr: &'ast Expr) -> Type {
+        let ty = self.expr_type(expr);
+        self.recorded.push((expr.id, ty.clone(), false));
+        ty
+    }
+
+    fn expr_type(&mut self, expr: &'ast Expr) -> Type {
+        match &expr.kind {
+            ExprKind::Lit(lit) => match &lit.kind {
+                LitKind::Int(_) | LitKind::Float(_) => {
+                    let kind = match lit.kind {
+                        LitKind::Int(_) => VarKind::Integer,
+                        _ => VarKind::Float,
+                    };
+                    let ty = self.infer.fresh(kind);
+                    self.literals.push((lit, false, expr.span, ty.clone()));
+                    ty
+                }
+                LitKind::Str(_) => Type::Ref(false, Box::new(Type::Str)),
+                LitKind::Char(_) => Type::Char,
+                LitKind::Bool(_) => Type::Bool,
+            },
+            ExprKind::Path(path) => self.path_type(expr.id, path),
+            ExprKind::Unary(op, operand) => self.unary(expr, *op, operand),
+            ExprKind::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs, expr.span),
+            ExprKind::AddrOf { mutable, expr } => {
+                Type::Ref(*mutable, Box::new(self.check_expr(expr)))
+            }
+            ExprKind::Assign(lhs, rhs) => {
+                let expected = self.check_expr(lhs);
+                let found = self.check_expr(rhs);
+                self.coerce(rhs.span, &found, &expected);
+                Type::unit()
+            }
+            ExprKind::Call(callee, args) => self.call(expr, callee, args),
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                generic_args,
+                args,
+                ..
+            } => self.method_call(expr.id, receiver, method, generic_args, args),
+            ExprKind::Field(base, name) => self.field(base, name),
+            ExprKind::Index(base, index) => self.index(base, index),
+            ExprKind::Try(inner) => {
+                let ty = self.check_expr(inner);
+                match self.infer.shallow(&ty) {
+                    Type::Named(name, args)
+                        if (name == "Option" || name == "Result") && !args.is_empty() =>
+                    {
+                        args[0].clone()
+                    }
+                    _ => Type::Unknown,
+                }
+            }
+            ExprKind::Tuple(exprs) => {
+                Type::Tuple(exprs.iter().map(|expr| self.check_expr(expr)).collect())
+            }
+            ExprKind::Array(exprs) => {
+                let elem = self.infer.fresh(VarKind::General);
+                for expr in exprs {
+                    let found = self.check_expr(expr);
+                    self.coerce(expr.span, &found, &elem);
+                }
+                Type::Array(Box::new(elem), Some(exprs.len() as u64))
+            }
+            ExprKind::Repeat(elem, len) => {
+                let elem = self.check_expr(elem);
+                // Array lengths are constants, checked by `consts`.
+                let found = self.check_expr(len);
+                self.infer.unify(&found, &Type::Int("usize"));
+                Type::Array(Box::new(elem), self.lengths.get(&len.id).copied())
+            }
+            ExprKind::Range { lo, hi, inclusive } => {
+                let elem = self.infer.fresh(VarKind::General);
+                for bound in [lo, hi].into_iter().flatten() {
+                    let found = self.check_expr(bound);
+                    self.unify(bound.span, &found, &elem);
+                }
+                let name = match (lo, hi, inclusive) {
+                    (None, None, _) => return Type::Named("RangeFull".to_string(), vec![]),
+                    (Some(_), None, _) => "RangeFrom",
+                    (None, Some(_), false) => "RangeTo",
+                    (None, Some(_), true) => "RangeToInclusive",
+                    (Some(_), Some(_), false) => "Range",
+                    (Some(_), Some(_), true) => "RangeInclusive",
+                };
+                Type::Named(name.to_string(), vec![elem])
+            }
+            ExprKind::StructLit { path, fields } => self.struct_lit(expr, path, fields),
+            ExprKind::Paren(inner) => self.check_expr(inner),
+            ExprKind::Block(block) => self.check_block(block),
+            ExprKind::If(cond, then, otherwise) => {
+                self.condition(cond);
+                let then_ty = self.check_block(then);
+                match otherwise {
+                    Some(otherwise) => {
+                        let else_ty = self.check_expr(otherwise);
+                        if !self.infer.unify(&then_ty, &else_ty) {
+                            self.incompatible(
+                                "`if` and `else` have incompatible types",
+                                otherwise.span,
+                                &then_ty,
+                                &else_ty,
+                            );
+                            return Type::Unknown;
+                        }
+                        match self.infer.shallow(&then_ty) {
+                            Type::Never => else_ty,
+                            _ => then_ty,
+                        }
+                    }
+                    None => {
+                        if !self.infer.unify(&then_ty, &Type::unit()) {
+                            let span = then.tail().map_or(then.span, |tail| tail.span);
+                            self.diagnostics.push(
+                                Diagnostic::error("`if` may be missing an `else` clause", span)
+                                    .with_note(format!(
+                                        "expected `()`, found `{}`",
+                                        self.infer.display(&then_ty)
+                                    )),
+                            );
+                        }
+                        Type::unit()
+                    }
+                }
+            }
+            ExprKind::While(cond, body) => {
+                self.condition(cond);
+                self.loops.push(None);
+                self.loop_body(body);
+                self.loops.pop();
+                Type::unit()
+            }
+            ExprKind::Loop(body) => {
+                self.loops.push(Some(Type::Never));
+                self.loop_body(body);
+                self.loops.pop().flatten().unwrap_or(Type::Never)
+            }
+            ExprKind::ForLoop { pat, iter, body } => {
+                let iter_ty = self.check_expr(iter);
+                let elem = self.iter_item(iter, &iter_ty);
+                self.check_pattern(pat, &elem, false);
+                self.loops.push(None);
+                self.loop_body(body);
+                self.loops.pop();
+                Type::unit()
+            }
+            ExprKind::Match(scrutinee, arms) => {
+                let scrutinee_ty = self.check_expr(scrutinee);
+                let mut result = Type::Never;
+                for arm in arms {
+                    self.check_pattern(&arm.pat, &scrutinee_ty, false);
+                    if let Some(guard) = &arm.guard {
+                        self.condition(guard);
+                    }
+                    let arm_ty = self.check_expr(&arm.body);
+                    if !self.infer.unify(&result, &arm_ty) {
+                        self.incompatible(
+                            "`match` arms have incompatible types",
+                            arm.body.span,
+                            &result,
+                            &arm_ty,
+                        );
+                        return Type::Unknown;
+                    }
+                    if self.infer.shallow(&result) == Type::Never {
+                        result = arm_ty;
+                    }
+                }
+                result
+            }
+            ExprKind::Closure(closure) => {
+                let inputs: Vec<_> = closure
+                    .params
+                    .iter()
+                    .map(|param| {
+                        let ty = match &param.ty {
+                            Some(ty) => self.lower(ty),
+                            None => self.infer.fresh(VarKind::General),
+                        };
+                        self.check_pattern(&param.pat, &ty, false);
+                        ty
+                    })
+                    .collect();
+                let output = match &closure.output {
+                    Some(ty) => self.lower(ty),
+                    None => self.infer.fresh(VarKind::General),
+                };
+                // `return` in the body leaves the closure, not the function.
+                let outer = self.return_ty.replace(output.clone());
+                let loops = std::mem::take(&mut self.loops);
+                let found = self.check_expr(&closure.body);
+                self.coerce(closure.body.span, &found, &output);
+                self.return_ty = outer;
+                self.loops = loops;
+                Type::Fn(inputs, Box::new(output))
+            }
+            ExprKind::Return(value) => {
+                let found = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::unit(),
+                };
+                if let Some(expected) = self.return_ty.clone() {
+                    let span = value.as_ref().map_or(expr.span, |value| value.span);
+                    if self.coerce(span, &found, &expected) {
+                        if let Some(value) = value {
+                            self.sized(value, &found);
+                        }
+                    }
+                }
+                Type::Never
+            }
+            ExprKind::Break(value) => {
+                let found = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::unit(),
+                };
+                let span = value.as_ref().map_or(expr.span, |value| value.span);
+                match self.loops.last().cloned() {
+                    Some(Some(Type::Never)) => {
+                        *self.loops.last_mut().unwrap() = Some(found);
+                    }
+                    Some(Some(expected)) => {
+                        self.unify(span, &found, &expected);
+                    }
+                    Some(None) if value.is_some() => self.diagnostics.push(
+                        Diagnostic::error(
+                            "`break` with value from a `while` or `for` loop",
+                            expr.span,
+                        )
+                        .with_note("only `loop` can give a value with `break`"),
+                    ),
+                    _ => {}
+                }
+                Type::Never
+            }
+            ExprKind::Continue => Type::Never,
+            ExprKind::Error => Type::Unknown,
+        }
+    }
+
+    fn incompatible(&mut self, message: &str, span: Span, expected: &Type, found: &Type) {
+        let note = format!(
+            "expected `{}`, found `{}`",
+            self.infer.display(expected),
+            self.infer.display(found)
+        );
+        self.diagnostics
+            .push(Diagnostic::error(message, span).with_note(note));
+    }
+
+    fn condition(&mut self, cond: &'ast Expr) {
+        let found = self.check_expr(cond);
+        self.unify(cond.span, &found, &Type::Bool);
+    }
+
+    fn loop_body(&mut self, body: &'ast Block) {
+        let found = self.check_block(body);
+        let span = body.tail().map_or(body.span, |tail| tail.span);
+        self.unify(span, &found, &Type::unit());
+    }
+
+    /// The type of the items a `for` loop takes from `iter`, of type `ty`.
+    fn iter_item(&mut self, iter: &Expr, ty: &Type) -> Type {
+        match self.infer.shallow(ty) {
+            Type::Array(elem, _) => *elem,
+            Type::Named(name, args)
+                if args.len() == 1 && (name.starts_with("Range") || name == "Vec") =>
+            {
+                args[0].clone()
+            }
+            Type::Ref(mutable, inner) => match self.infer.shallow(&inner) {
+                Type::Array(elem, _) | Type::Slice(elem) => Type::Ref(mutable, elem),
+                Type::Named(name, args) if name == "Vec" && args.len() == 1 => {
+                    Type::Ref(mutable, Box::new(args[0].clone()))
+                }
+                _ => Type::Unknown,
+            },
+            ty @ (Type::Bool | Type::Char | Type::Int(_) | Type::Float(_) | Type::Tuple(_)) => {
+                self.not_iterator(iter.span, &ty)
+            }
+            // An integer or a float whose type is not known yet is not one either.
+            ty @ Type::Var(var) if self.infer.kind(var) != VarKind::General => {
+                self.not_iterator(iter.span, &ty)
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    fn not_iterator(&mut self, span: Span, ty: &Type) -> Type {
+        let ty = self.infer.display(ty);
+        self.diagnostics.push(
+            Diagnostic::error(format!("`{ty}` is not an iterator"), span).with_note(format!(
+                "the trait `Iterator` is not implemented for `{ty}`"
+            )),
+        );
+        Type::Unknown
+    }
+
+    fn path_type(&mut self, id: NodeId, path: &Path) -> Type {
+        match self.resolutions.get(id) {
+            Some(Res::Local(binding)) => self
+                .recorded
+                .iter()
+                .rev()
+                .find(|(recorded, _, is_binding)| *recorded == binding && *is_binding)
+                .map_or(Type::Unknown, |(_, ty, _)| ty.clone()),
+            Some(Res::Item(item)) => {
+                if let Some(constant) = self.items.consts.get(&item) {
+                    return self.lower(&constant.ty);
+                }
+                if self.items.structs.contains_key(&item) {
+                    return self.fresh_adt(item).0;
+                }
+                match self.signature(item) {
+                    Some(signature) => {
+                        let written = path.segments.last().map_or(&[][..], |s| &s.args);
+                        self.turbofish(item, written, &signature.args);
+                        self.generic_args.push((id, signature.args));
+                        Type::Fn(signature.inputs, Box::new(signature.output))
+                    }
+                    None => Type::Unknown,
+                }
+            }
+            Some(Res::Variant(id, index)) => {
+                let (adt, args) = self.fresh_adt(id);
+                match &self.items.enums[&id].variants[index].data {
+                    VariantData::Tuple(tys) => {
+                        let inputs = tys.iter().map(|ty| self.lower(ty).subst(&args)).collect();
+                        Type::Fn(inputs, Box::new(adt))
+                    }
+                    _ => adt,
+                }
+            }
+            Some(Res::External) => match path.last().name.as_str() {
+                "None" => self
+                    .prelude_value("None")
+                    .map_or(Type::Unknown, |(ty, _)| ty),
+                name @ ("Some" | "Ok" | "Err") => match self.prelude_value(name) {
+                    Some((ty, field)) => Type::Fn(vec![field], Box::new(ty)),
+                    None => Type::Unknown,
+                },
+                _ => match &path.segments[..] {
+                    [ty, name] => self
+                        .constructor(&ty.ident.name, &name.ident.name)
+                        .unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                },
+            },
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Binds the generic parameters of the function `id` to the type arguments
+    /// `written` for them, `i32` in `id::<i32>` or `value.get::<i32>()`. Their number
+    /// is checked by `bounds`.
+    fn turbofish(&mut self, id: NodeId, written: &[Ty], args: &HashMap<NodeId, Type>) {
+        let Some(&(function, _)) = self.items.functions.get(&id) else {
+            return;
+        };
+        for (param, ty) in function.generics.params.iter().zip(written) {
+            let found = self.lower(ty);
+            self.unify(ty.span, &found, &args[&param.id]);
+        }
+    }
+
+    /// The type of the usual constructors of `String`, `Vec` and `Box`.
+    fn constructor(&mut self, ty: &str, name: &str) -> Option<Type> {
+        let elem = self.infer.fresh(VarKind::General);
+        let string = Type::Named("String".to_string(), vec![]);
+        let (inputs, output) = match (ty, name) {
+            ("String", "new") => (vec![], string),
+            ("String", "from") => (vec![Type::Unknown], string),
+            ("Vec", "new") => (vec![], Type::Named("Vec".to_string(), vec![elem])),
+            ("Vec", "with_capacity") => (
+                vec![Type::Int("usize")],
+                Type::Named("Vec".to_string(), vec![elem]),
+            ),
+            ("Box", "new") => (
+                vec![elem.clone()],
+                Type::Named("Box".to_string(), vec![elem]),
+            ),
+            _ => return None,
+        };
+        Some(Type::Fn(inputs, Box::new(output)))
+    }
+
+    fn call(&mut self, expr: &'ast Expr, callee: &'ast Expr, args: &'ast [Expr]) -> Type {
+        let callee_ty = self.check_expr(callee);
+        match self.infer.shallow(&callee_ty) {
+            Type::Fn(inputs, output) => {
+                self.check_args(expr.span, &inputs, args, "function");
+                *output
+            }
+            Type::Var(var) if self.infer.kind(var) == VarKind::General => {
+                let inputs: Vec<_> = args
+                    .iter()
+                    .map(|_| self.infer.fresh(VarKind::General))
+                    .collect();
+                let output = self.infer.fresh(VarKind::General);
+                self.infer.unify(
+                    &callee_ty,
+                    &Type::Fn(inputs.clone(), Box::new(output.clone())),
+                );
+                self.check_args(expr.span, &inputs, args, "function");
+                output
+            }
+            Type::Unknown | Type::Param(..) | Type::Named(..) | Type::Ref(..) | Type::Never => {
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::Unknown
+            }
+            found => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("expected function, found `{}`", self.infer.display(&found)),
+                    callee.span,
+                ));
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                Type::Unknown
+            }
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the callee.
+    fn check_args(&mut self, span: Span, inputs: &[Type], args: &'ast [Expr], what: &str) {
+        let found: Vec<_> = args.iter().map(|arg| self.check_expr(arg)).collect();
+        if inputs.len() != args.len() {
+            let plural = |n: usize, word: &str| {
+                if n == 1 {
+                    format!("{n} {word}")
+                } else {
+                    format!("{n} {word}s")
+                }
+            };
+            let supplied = if args.len() == 1 { "was" } else { "were" };
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "this {what} takes {} but {} {supplied} supplied",
+                    plural(inputs.len(), "argument"),
+                    plural(args.len(), "argument"),
+                ),
+                span,
+            ));
+            return;
+        }
+        for ((arg, found), expected) in args.iter().zip(&found).zip(inputs) {
+            if self.coerce(arg.span, found, expected) {
+                self.sized(arg, found);
+            }
+        }
+    }
+
+    /// Reports `expr` moved into a binding, an argument or the return value while
+    /// its size is not known, as `a[1..3]` of type `[i32]` : such values only go
+    /// behind a reference.
+    fn sized(&mut self, expr: &Expr, found: &Type) {
+        let ty = self.infer.shallow(found);
+        if !matches!(ty, Type::Slice(_) | Type::Str) {
+            return;
+        }
+        let start = Span::new(expr.span.start, expr.span.start).with_ctxt(expr.span.ctxt);
+        self.diagnostics.push(
+            Diagnostic::error(
+                format!(
+                    "the size for values of type `{}` cannot be known at compilation time",
+                    self.infer.display(&ty)
+                ),
+                expr.span,
+            )
+            .with_suggestion(start, "consider borrowing here", "&"),
+        );
+    }
+
+    /// Finds the method of the crate a method call calls, the way rustc does : the
+    /// receiver is dereferenced step by step, and at each step taken as it is, then
+    /// by `&`, then by `&mut`, until a method takes `self` as such. The inherent
+    /// methods come before those of the traits in scope. Methods of other crates are
+    /// left to rustc.
+    fn method_call(
+        &mut self,
+        id: NodeId,
+        receiver: &'ast Expr,
+        method: &Ident,
+        generic_args: &'ast [Ty],
+        args: &'ast [Expr],
+    ) -> Type {
+        let receiver_ty = self.check_expr(receiver);
+        let steps = self.autoderef(&receiver_ty);
+        if let Type::Var(var) = steps[0] {
+            if self.infer.kind(var) != VarKind::General {
+                self.ambiguous_numeric(receiver, method, var);
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                return Type::Unknown;
+            }
+        }
+        let in_scope = self.resolutions.traits_in_scope(id);
+        let candidates = self.candidates(&steps, &method.name, Some(in_scope));
+        let picked = match self.pick(&steps, &candidates) {
+            Ok(None) => {
+                self.out_of_scope(&steps, method);
+                self.not_bounded(&steps[0], method);
+                self.no_method(&steps, method, &candidates);
+                None
+            }
+            Ok(picked) => picked,
+            Err(ambiguous) => {
+                self.ambiguous(method, &ambiguous);
+                None
+            }
+        };
+        let Some(Pick {
+            candidate,
+            derefs,
+            autoref,
+        }) = picked
+        else {
+            for arg in args {
+                self.check_expr(arg);
+            }
+            return steps
+                .iter()
+                .find_map(|ty| sequence_method(ty, &method.name))
+                .unwrap_or(Type::Unknown);
+        };
+        let Some(mut signature) = self.signature(candidate.def) else {
+            return Type::Unknown;
+        };
+        let self_ty = steps[candidate.step].clone();
+        match (&signature.self_ty, candidate.of_trait) {
+            (Some(impl_ty), _) => {
+                self.infer.unify(impl_ty, &self_ty);
+            }
+            // A provided method of a trait, or one called on a generic parameter.
+            (None, Some(of_trait)) => {
+                let args = HashMap::from([(of_trait, self_ty.clone())]);
+                signature.inputs = signature.inputs.iter().map(|ty| ty.subst(&args)).collect();
+                signature.output = signature.output.subst(&args);
+            }
+            (None, None) => {}
+        }
+        let owner = candidate.of_trait.or(match self_ty {
+            Type::Adt(adt, _, _) => Some(adt),
+            _ => None,
+        });
+        let owner = owner.and_then(|owner| self.items.paths.get(&owner));
+        self.results.methods.insert(
+            id,
+            Callee {
+                def: candidate.def,
+                path: format!("{}::{}", owner.map_or("_", |path| path), method.name),
+                derefs,
+                autoref,
+                qualified: candidate.of_trait.is_some()
+                    && !matches!(self_ty, Type::Adt(..) | Type::Param(..)),
+            },
+        );
+        self.turbofish(candidate.def, generic_args, &signature.args);
+        self.generic_args.push((id, signature.args));
+        self.check_args(method.span, &signature.inputs[1..], args, "method");
+        signature.output
+    }
+
+    /// The types a method call tries its receiver as : the receiver, then what its
+    /// references and boxes point to.
+    fn autoderef(&self, ty: &Type) -> Vec<Type> {
+        let mut steps = vec![self.infer.shallow(ty)];
+        while steps.len() <= MAX_DEREFS {
+            let next = match steps.last() {
+                Some(Type::Ref(_, inner)) => inner,
+                Some(Type::Named(name, args)) if name == "Box" && args.len() == 1 => &args[0],
+                _ => break,
+            };
+            steps.push(self.infer.shallow(next));
+        }
+        steps
+    }
+
+    /// The methods `name` of the types of `steps` : those of their inherent impls
+    /// first, then those of the impls of the traits `in_scope`, or of every trait.
+    fn candidates(
+        &self,
+        steps: &[Type],
+        name: &str,
+        in_scope: Option<&[NodeId]>,
+    ) -> Vec<Candidate> {
+        let visible = |of_trait: &NodeId| in_scope.is_none_or(|traits| traits.contains(of_trait));
+        let mut inherent = vec![];
+        let mut traits = vec![];
+        for (step, ty) in steps.iter().enumerate() {
+            if let Type::Adt(adt, _, _) = ty {
+                if let Some(&def) = self.items.methods.get(&(*adt, name)) {
+                    inherent.push((def, None, step));
+                }
+            }
+            // The method is left to rustc when several bounds declare it.
+            if let Type::Param(id, _) = ty {
+                let declaring: Vec<_> = self
+                    .param_bounds(*id)
+                    .into_iter()
+                    .filter_map(|bound| match bound {
+                        Bound::Crate(of_trait) => {
+                            Some((self.items.trait_method(of_trait, name, false)?, of_trait))
+                        }
+                        Bound::Std(_) => None,
+                    })
+                    .collect();
+                if let [(def, of_trait)] = declaring[..] {
+                    traits.push((def, Some(of_trait), step));
+                }
+            }
+            let Some(key) = SelfKey::of(ty) else {
+                continue;
+            };
+            for (of_trait, impl_key, imp) in &self.items.trait_impls {
+                if *impl_key != key || !visible(of_trait) {
+                    continue;
+                }
+                let def = imp.items.iter().find_map(|item| match &item.kind {
+                    ItemKind::Function(function) if function.name.name == name => Some(item.id),
+                    _ => None,
+                });
+                if let Some(def) = def.or_else(|| self.items.trait_method(*of_trait, name, true)) {
+                    traits.push((def, Some(*of_trait), step));
+                }
+            }
+        }
+        inherent
+            .into_iter()
+            .chain(traits)
+            .filter_map(|(def, of_trait, step)| {
+                Some(Candidate {
+                    def,
+                    of_trait,
+                    step,
+                    by_ref: self.items.self_kind(def)?,
+                })
+            })
+            .collect()
+    }
+
+    /// The first candidate whose `self` the receiver can become, with the number of
+    /// dereferences and the reference it takes, or the methods of the traits which
+    /// would all do.
+    fn pick<'c>(
+        &self,
+        steps: &[Type],
+        candidates: &'c [Candidate],
+    ) -> Result<Option<Pick<'c>>, Vec<&'c Candidate>> {
+        for step in 0..steps.len() {
+            for autoref in [None, Some(false), Some(true)] {
+                let mut fitting = candidates
+                    .iter()
+                    .filter(|candidate| candidate.fits(steps, step, autoref));
+                let Some(first) = fitting.next() else {
+                    continue;
+                };
+                // `&self` methods are reached through the reference the receiver is.
+                let autoref = if first.step == step { autoref } else { None };
+                let pick = Pick {
+                    candidate: first,
+                    derefs: step,
+                    autoref,
+                };
+                if first.of_trait.is_none() {
+                    return Ok(Some(pick));
+                }
+                let others: Vec<_> = fitting.filter(|other| other.of_trait.is_some()).collect();
+                if others.is_empty() {
+                    return Ok(Some(pick));
+                }
+                return Err(std::iter::once(first).chain(others).collect());
+            }
+        }
+        Ok(None)
+    }
+
+    /// Methods are looked up in the type of the receiver, which an integer or a float
+    /// does not have before it is used.
+    fn ambiguous_numeric(&mut self, receiver: &Expr, method: &Ident, var: u32) {
+        let (ty, example) = match self.infer.kind(var) {
+            VarKind::Float => ("{float}", "f32"),
+            _ => ("{integer}", "i32"),
+        };
+        let note = match self.resolutions.get(receiver.id) {
+            Some(Res::Local(_)) => {
+                format!("you must specify a type for this binding, like `{example}`")
+            }
+            _ => {
+                format!("you must specify a concrete type for this numeric value, like `{example}`")
+            }
+        };
+        self.diagnostics.push(
+            Diagnostic::error(
+                format!(
+                    "can't call method `{}` on ambiguous numeric type `{ty}`",
+                    method.name
+                ),
+                method.span,
+            )
+            .with_note(note),
+        );
+    }
+
+    fn ambiguous(&mut self, method: &Ident, candidates: &[&Candidate]) {
+        let mut diagnostic = Diagnostic::error(
+            format!("multiple applicable items named `{}` in scope", method.name),
+            method.span,
+        );
+        for (i, candidate) in candidates.iter().enumerate() {
+            let path = candidate
+                .of_trait
+                .and_then(|of_trait| self.items.paths.get(&of_trait));
+            diagnostic = diagnostic.with_note(format!(
+                "candidate #{} is defined in the trait `{}`",
+                i + 1,
+                path.map_or("_", |path| path)
+            ));
+        }
+        diagnostic = diagnostic.with_note(format!(
+            "call it through its trait to say which one : `Trait::{}(receiver)`",
+            method.name
+        ));
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports a call to the method of a trait the crate implements for the receiver,
+    /// but which is not in scope.
+    fn out_of_scope(&mut self, steps: &[Type], method: &Ident) {
+        if steps.iter().any(|ty| matches!(ty, Type::Param(..))) {
+            return;
+        }
+        let candidates = self.candidates(steps, &method.name, None);
+        let found = match self.pick(steps, &candidates) {
+            Ok(Some(pick)) => vec![pick.candidate],
+            Ok(None) => return,
+            Err(ambiguous) => ambiguous,
+        };
+        let mut diagnostic = Diagnostic::error(
+            format!(
+                "no method named `{}` found for `{}` in the current scope",
+                method.name,
+                self.infer.display(&steps[0])
+            ),
+            method.span,
+        )
+        .with_note("items from traits can only be used if the trait is in scope");
+        for candidate in found {
+            let Some(path) = candidate
+                .of_trait
+                .and_then(|of_trait| self.items.paths.get(&of_trait))
+            else {
+                continue;
+            };
+            let trait_name = path.rsplit("::").next().unwrap_or(path);
+            diagnostic = diagnostic.with_note(format!(
+                "the trait `{trait_name}` which provides `{}` is implemented but not in scope; import it : `use {path};`",
+                method.name
+            ));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports a method called on a struct or an enum of the crate which has none by
+    /// that name : only its impls and the traits it derives or implements supply
+    /// methods. Those of the traits of the standard library not known, and of the
+    /// traits of the crate, which may have blanket impls, are left to rustc.
+    fn no_method(&mut self, steps: &[Type], method: &Ident, candidates: &[Candidate]) {
+        let Some(Type::Adt(id, adt, _)) = steps.last() else {
+            return;
+        };
+        let name = method.name.as_str();
+        let through_refs = steps[..steps.len() - 1]
+            .iter()
+            .all(|ty| matches!(ty, Type::Ref(..)));
+        // `Clone` is implemented for every reference.
+        let of_refs = steps.len() > 1 && matches!(name, "clone" | "to_owned");
+        let std_traits = self.items.std_traits.get(id).map_or(&[][..], Vec::as_slice);
+        let provides = |trait_name: &&str| {
+            std_methods(trait_name).is_none_or(|methods| methods.contains(&name))
+        };
+        let declared = self
+            .items
+            .traits
+            .keys()
+            .any(|&of_trait| self.items.trait_method(of_trait, name, false).is_some());
+        if !through_refs
+            || of_refs
+            || !candidates.is_empty()
+            || !self.candidates(steps, name, None).is_empty()
+            || ANY_TYPE_METHODS.contains(&name)
+            || std_traits.iter().any(provides)
+            || declared
+        {
+            return;
+        }
+        let kind = if self.items.structs.contains_key(id) {
+            "struct"
+        } else {
+            "enum"
+        };
+        self.diagnostics.push(Diagnostic::error(
+            format!("no method named `{name}` found for {kind} `{adt}` in the current scope"),
+            method.span,
+        ));
+    }
+
+    /// Reports a method called on a generic parameter none of whose bounds declare
+    /// it, unless a bound is a trait of the standard library whose methods are not
+    /// known.
+    fn not_bounded(&mut self, ty: &Type, method: &Ident) {
+        let Type::Param(id, param) = ty else {
+            return;
+        };
+        let bounds = self.param_bounds(*id);
+        let provides = |bound: &Bound| match bound {
+            Bound::Crate(of_trait) => self
+                .items
+                .trait_method(*of_trait, &method.name, false)
+                .is_some(),
+            Bound::Std(name) => {
+                std_methods(name).is_none_or(|methods| methods.contains(&method.name.as_str()))
+            }
+        };
+        if ANY_TYPE_METHODS.contains(&method.name.as_str()) || bounds.iter().any(provides) {
+            return;
+        }
+        self.diagnostics.push(
+            Diagnostic::error(
+                format!(
+                    "no method named `{}` found for type parameter `{param}` in the current scope",
+                    method.name
+                ),
+                method.span,
+            )
+            .with_note(
+                "items from traits can only be used if the type parameter is bounded by the trait",
+            ),
+        );
+    }
+
+    /// Reports an operator applied to a generic parameter which none of its bounds
+    /// provide, the trait `trait_name` of the standard library.
+    fn operator_bounded(&mut self, id: NodeId, trait_name: &str, message: String, span: Span) {
+        let bounds = self.param_bounds(id);
+        let provides = |bound: &Bound| match bound {
+            Bound::Crate(_) => false,
+            Bound::Std(name) => *name == trait_name || std_methods(name).is_none(),
+        };
+        if bounds.iter().any(provides) {
+            return;
+        }
+        let param = self.items.generics.get(&id).copied().unwrap_or("Self");
+        self.diagnostics
+            .push(Diagnostic::error(message, span).with_note(format!(
+                "consider restricting type parameter `{param}` with trait `{trait_name}`"
+            )));
+    }
+
+    /// The traits the generic parameter `id` is bounded by, with their supertraits.
+    /// `Self` in a trait is bounded by the trait.
+    fn param_bounds(&self, id: NodeId) -> Vec<Bound<'ast>> {
+        let mut paths: Vec<&'ast Path> = self
+            .items
+            .bounds
+            .get(&id)
+            .map_or(vec![], |bounds| bounds.iter().collect());
+        for predicate in &self.items.wheres {
+            if self.resolutions.get(predicate.ty.id) == Some(Res::Generic(id)) {
+                paths.extend(&predicate.bounds);
+            }
+        }
+        let mut work: Vec<_> = paths
+            .into_iter()
+            .filter_map(|path| self.bound(path))
+            .collect();
+        if self.items.traits.contains_key(&id) {
+            work.push(Bound::Crate(id));
+        }
+        let mut bounds = vec![];
+        while let Some(bound) = work.pop() {
+            if bounds.contains(&bound) {
+                continue;
+            }
+            match bound {
+                Bound::Crate(of_trait) => {
+                    if let Some(found) = self.items.traits.get(&of_trait) {
+                        work.extend(found.supertraits.iter().filter_map(|path| self.bound(path)));
+                    }
+                }
+                Bound::Std(name) => work.extend(
+                    STD_SUPERTRAITS
+                        .iter()
+                        .filter(|(sub, _)| *sub == name)
+                        .map(|(_, sup)| Bound::Std(sup)),
+                ),
+            }
+            bounds.push(bound);
+        }
+        bounds
+    }
+
+    fn bound(&self, path: &'ast Path) -> Option<Bound<'ast>> {
+        match self.resolutions.bound(path)? {
+            Res::Item(id) => Some(Bound::Crate(id)),
+            Res::External => Some(Bound::Std(&path.last().name)),
+            _ => None,
+        }
+    }
+
+    fn field(&mut self, base: &'ast Expr, name: &Ident) -> Type {
+        let base_ty = self.check_expr(base);
+        match self.peel_refs(&base_ty) {
+            Type::Adt(id, adt, args) => {
+                let Some(item) = self.items.structs.get(&id) else {
+                    return self.no_field(name, &adt);
+                };
+                match item
+                    .fields
+                    .iter()
+                    .find(|field| field.name.name == name.name)
+                {
+                    Some(field) => {
+                        self.check_privacy(id, field, name.span);
+                        let args = self.adt_args(id, &args);
+                        self.lower(&field.ty).subst(&args)
+                    }
+                    None => self.no_field(name, &adt),
+                }
+            }
+            Type::Tuple(tys) => match name.name.parse::<usize>().ok().and_then(|i| tys.get(i)) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let ty = self.infer.display(&Type::Tuple(tys));
+                    self.no_field(name, &ty)
+                }
+            },
+            ty @ (Type::Bool | Type::Char | Type::Int(_) | Type::Float(_)) => {
+                let ty = self.infer.display(&ty);
+                self.no_field(name, &ty)
+            }
+            // An integer or a float whose type is not known yet has no fields either.
+            ty @ Type::Var(var) if self.infer.kind(var) != VarKind::General => {
+                let ty = self.infer.display(&ty);
+                self.no_field(name, &ty)
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    /// Reports the private field `def` of the struct `id` used outside of the module
+    /// declaring the struct and its children. The fields of variants are public.
+    fn check_privacy(&mut self, id: NodeId, def: &FieldDef, span: Span) {
+        let (Some(item), Some(&owner)) = (self.items.structs.get(&id), self.items.owners.get(&id))
+        else {
+            return;
+        };
+        let visible = owner.is_none_or(|owner| self.modules.contains(&owner));
+        if def.vis == Visibility::Private && !visible {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    format!(
+                        "field `{}` of struct `{}` is private",
+                        def.name.name, item.name.name
+                    ),
+                    span,
+                )
+                .with_label(def.name.span, "the field is defined here"),
+            );
+        }
+    }
+
+    fn no_field(&mut self, name: &Ident, ty: &str) -> Type {
+        self.diagnostics.push(Diagnostic::error(
+            format!("no field `{}` on type `{ty}`", name.name),
+            name.span,
+        ));
+        Type::Unknown
+    }
+
+    fn index(&mut self, base: &'ast Expr, index: &'ast Expr) -> Type {
+        let base_ty = self.check_expr(base);
+        let index_ty = self.check_expr(index);
+        let range = matches!(self.infer.shallow(&index_ty), Type::Named(name, _) if name.starts_with("Range"));
+        let elem = match self.peel_refs(&base_ty) {
+            Type::Array(elem, _) | Type::Slice(elem) => *elem,
+            Type::Named(name, args) if name == "Vec" && args.len() == 1 => args[0].clone(),
+            Type::Str if range => return Type::Str,
+            ty @ (Type::Bool | Type::Char | Type::Int(_) | Type::Float(_) | Type::Tuple(_)) => {
+                return self.not_indexable(base.span, &ty);
+            }
+            // An integer or a float whose type is not known yet cannot be indexed either.
+            ty @ Type::Var(var) if self.infer.kind(var) != VarKind::General => {
+                return self.not_indexable(base.span, &ty);
+            }
+            _ => return Type::Unknown,
+        };
+        if range {
+            return Type::Slice(Box::new(elem));
+        }
+        self.unify(index.span, &index_ty, &Type::Int("usize"));
+        elem
+    }
+
+    fn not_indexable(&mut self, span: Span, ty: &Type) -> Type {
+        self.diagnostics.push(Diagnostic::error(
+            format!(
+                "cannot index into a value of type `{}`",
+                self.infer.display(ty)
+            ),
+            span,
+        ));
+        Type::Unknown
+    }
+
+    fn struct_lit(&mut self, expr: &'ast Expr, path: &Path, fields: &'ast [FieldInit]) -> Type {
+        let res = match self.resolutions.get(expr.id) {
+            Some(Res::SelfTy(id)) => match self.lower_path(Some(Res::SelfTy(id)), path, vec![]) {
+                Type::Adt(id, ..) => Some(Res::Item(id)),
+                _ => None,
+            },
+            res => res,
+        };
+        let Some((defs, id)) = res.and_then(|res| self.items.fields(res)) else {
+            for field in fields {
+                self.check_expr(&field.expr);
+            }
+            return Type::Unknown;
+        };
+        let (adt, args) = self.fresh_adt(id);
+        let name = path.to_text();
+        for field in fields {
+            let found = self.check_expr(&field.expr);
+            match defs.iter().find(|def| def.name.name == field.name.name) {
+                Some(def) => {
+                    self.check_privacy(id, def, field.name.span);
+                    let expected = self.lower(&def.ty).subst(&args);
+                    self.coerce(field.expr.span, &found, &expected);
+                }
+                None => self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "{} `{name}` has no field named `{}`",
+                        struct_kind(res),
+                        field.name.name
+                    ),
+                    field.name.span,
+                )),
+            }
+        }
+        let missing: Vec<_> = defs
+            .iter()
+            .filter(|def| !fields.iter().any(|field| field.name.name == def.name.name))
+            .map(|def| format!("`{}`", def.name.name))
+            .collect();
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            self.diagnostics.push(Diagnostic::error(
+                format!(
+                    "missing field{plural} {} in initializer of `{name}`",
+                    missing.join(", ")
+                ),
+                path.span,
+            ));
+        }
+        adt
+    }
+
+    fn unary(&mut self, expr: &'ast Expr, op: UnOp, operand: &'ast Expr) -> Type {
+        let ty = self.check_expr(operand);
+        let shallow = self.infer.shallow(&ty);
+        let fits = match (op, &shallow) {
+            (UnOp::Neg | UnOp::Not, Type::Param(id, _)) => {
+                let trait_name = if op == UnOp::Neg { "Neg" } else { "Not" };
+                let message = self.cannot_apply(op, &ty, expr.span).message;
+                self.operator_bounded(*id, trait_name, message, expr.span);
+                return Type::Unknown;
+            }
+            (_, Type::Unknown | Type::Param(..) | Type::Named(..)) => return Type::Unknown,
+            (UnOp::Deref, Type::Ref(_, inner)) => return *inner.clone(),
+            (UnOp::Deref, Type::Var(var)) if self.infer.kind(*var) == VarKind::General => {
+                return Type::Unknown;
+            }
+            (UnOp::Deref, found) => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!(
+                        "type `{}` cannot be dereferenced",
+                        self.infer.display(found)
+                    ),
+                    expr.span,
+                ));
+                return Type::Unknown;
+            }
+            // The impls of `Neg` and `Not` are checked by `operators`.
+            (_, Type::Adt(id, ..)) => {
+                let trait_name = if op == UnOp::Neg { "Neg" } else { "Not" };
+                return self.operator_output(trait_name, *id, &shallow, None);
+            }
+            (UnOp::Neg, Type::Int(name)) => name.starts_with('i'),
+            (UnOp::Neg, Type::Float(_)) => true,
+            (UnOp::Not, Type::Int(_) | Type::Bool) => true,
+            (UnOp::Neg, Type::Var(_)) => {
+                let op = OverloadedOp::Unary(op);
+                self.operations.push((op, expr.span, ty.clone()));
+                true
+            }
+            (UnOp::Not, Type::Var(var)) => self.infer.kind(*var) != VarKind::Float,
+            _ => false,
+        };
+        if !fits {
+            let diagnostic = self.cannot_apply(op, &ty, expr.span);
+            self.diagnostics.push(diagnostic);
+            return Type::Unknown;
+        }
+        // `-128` is the literal the range of `i8` is checked against.
+        if let (UnOp::Neg, ExprKind::Lit(lit)) = (op, &operand.kind) {
+            if let Some(literal) = self
+                .literals
+                .last_mut()
+                .filter(|literal| std::ptr::eq(literal.0, lit))
+            {
+                literal.1 = true;
+                literal.2 = expr.span;
+            }
+        }
+        ty
+    }
+
+    fn cannot_apply(&self, op: UnOp, ty: &Type, span: Span) -> Diagnostic {
+        let diagnostic = Diagnostic::error(
+            format!(
+                "cannot apply unary operator `{}` to type `{}`",
+                OverloadedOp::Unary(op).as_str(),
+                self.infer.display(ty)
+            ),
+            span,
+        );
+        match self.infer.shallow(ty) {
+            Type::Int(name) if op == UnOp::Neg && name.starts_with('u') => {
+                diagnostic.with_note("unsigned values cannot be negated")
+            }
+            _ => diagnostic,
+        }
+    }
+
+    /// Reports the operators applied to values whose type turned out not to have
+    /// them.
+    fn check_operations(&mut self) {
+        for (op, span, ty) in std::mem::take(&mut self.operations) {
+            let ty = self.infer.resolve(&ty);
+            match (op, &ty) {
+                (OverloadedOp::Unary(op), Type::Int(name)) if name.starts_with('u') => {
+                    let diagnostic = self.cannot_apply(op, &ty, span);
+                    self.diagnostics.push(diagnostic);
+                }
+                (_, Type::Adt(id, ..)) => {
+                    let Some((trait_name, _)) = op.trait_method() else {
+                        continue;
+                    };
+                    if self.has_operator(trait_name, *id) {
+                        continue;
+                    }
+                    let ty = self.infer.display(&ty);
+                    let message = match op {
+                        OverloadedOp::Binary(op) => format!(
+                            "binary operation `{}` cannot be applied to type `{ty}`",
+                            op.as_str()
+                        ),
+                        OverloadedOp::Unary(_) => format!(
+                            "cannot apply unary operator `{}` to type `{ty}`",
+                            op.as_str()
+                        ),
+                    };
+                    self.diagnostics
+                        .push(Diagnostic::error(message, span).with_note(format!(
+                            "an implementation of `{trait_name}` might be missing for `{ty}`"
+                        )));
+                }
+                (OverloadedOp::Binary(op), Type::Param(id, _)) => {
+                    if let Some((trait_name, _)) = OverloadedOp::Binary(op).trait_method() {
+                        let message = format!(
+                            "binary operation `{}` cannot be applied to type `{}`",
+                            op.as_str(),
+                            self.infer.display(&ty)
+                        );
+                        self.operator_bounded(*id, trait_name, message, span);
+                    }
+                }
+                (
+                    OverloadedOp::Binary(op),
+                    Type::Bool | Type::Char | Type::Tuple(_) | Type::Array(..) | Type::Fn(..),
+                ) => {
+                    let message = format!(
+                        "binary operation `{}` cannot be applied to type `{}`",
+                        op.as_str(),
+                        self.infer.display(&ty)
+                    );
+                    self.diagnostics.push(Diagnostic::error(message, span));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether an impl of the crate gives the trait of an operator to the struct or
+    /// the enum `id`, or to every type.
+    fn has_operator(&self, trait_name: &str, id: NodeId) -> bool {
+        let blanket = |imp: &&Impl| {
+            matches!(self.resolutions.get(imp.self_ty.id), Some(Res::Generic(_)))
+                && imp
+                    .of_trait
+                    .as_ref()
+                    .is_some_and(|path| path.last().name == trait_name)
+        };
+        self.items
+            .operators
+            .iter()
+            .any(|(name, ty, _)| *name == trait_name && *ty == id)
+            || self.items.impls.values().any(blanket)
+    }
+
+    /// Reports the literals that do not fit the type inferred for them.
+    fn check_literals(&mut self) {
+        for (lit, negated, span, ty) in std::mem::take(&mut self.literals) {
+            let written = match negated {
+                true => format!("-{}", lit.to_text()),
+                false => lit.to_text(),
+            };
+            match (&lit.kind, self.infer.resolve(&ty)) {
+                (LitKind::Int(value), Type::Int(name)) => {
+                    let Some(int) = IntTy::from_name(name) else {
+                        continue;
+                    };
+                    // Negated unsigned values are reported above.
+                    let fits = match negated {
+                        true => !int.signed || *value <= int.min().unsigned_abs(),
+                        false => name == "u128" || *value <= int.max() as u128,
+                    };
+                    if !fits {
+                        self.diagnostics.push(int.out_of_range(&written, span));
+                    }
+                }
+                (LitKind::Float(value), Type::Float("f32")) if (*value as f32).is_infinite() => {
+                    self.diagnostics.push(
+                        Diagnostic::error("literal out of range for `f32`", span).with_note(
+                            format!(
+                                "the literal `{written}` does not fit into the type `f32` and will be converted to `f32::INFINITY`"
+                            ),
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `lhs op rhs`, written at `span`.
+    fn binary(
+        &mut self,
+        op: BinOp,
+        lhs_expr: &'ast Expr,
+        rhs_expr: &'ast Expr,
+        span: Span,
+    ) -> Type {
+        let (lhs, rhs) = (lhs_expr, rhs_expr);
+        let lhs_ty = self.check_expr(lhs);
+        let rhs_ty = self.check_expr(rhs);
+        match op {
+            BinOp::And | BinOp::Or => {
+                self.unify(lhs.span, &lhs_ty, &Type::Bool);
+                self.unify(rhs.span, &rhs_ty, &Type::Bool);
+                return Type::Bool;
+            }
+            _ if op.is_comparison() => {
+                let shallow = self.infer.shallow(&lhs_ty);
+                if let Type::Param(id, _) = shallow {
+                    let trait_name = match op {
+                        BinOp::Eq | BinOp::Ne => "PartialEq",
+                        _ => "PartialOrd",
+                    };
+                    let message = format!(
+                        "binary operation `{}` cannot be applied to type `{}`",
+                        op.as_str(),
+                        self.infer.display(&lhs_ty)
+                    );
+                    self.operator_bounded(id, trait_name, message, span);
+                }
+                // `impl PartialEq<f64> for Meters` compares with another type.
+                let other_rhs =
+                    matches!(shallow, Type::Adt(..) | Type::Named(..) | Type::Param(..));
+                if !other_rhs {
+                    self.unify(rhs.span, &rhs_ty, &lhs_ty);
+                }
+                return Type::Bool;
+            }
+            _ => {}
+        }
+        let shallow = self.infer.shallow(&lhs_ty);
+        match &shallow {
+            Type::Int(_) | Type::Float(_) | Type::Var(_) => {
+                if !self.infer.unify(&lhs_ty, &rhs_ty) {
+                    let message = self.arithmetic_message(op, &lhs_ty, &rhs_ty);
+                    self.diagnostics.push(Diagnostic::error(message, span));
+                }
+                if let Type::Var(_) = shallow {
+                    let op = OverloadedOp::Binary(op);
+                    self.operations.push((op, span, lhs_ty.clone()));
+                }
+                lhs_ty
+            }
+            Type::Param(id, _) => {
+                if let Some((trait_name, _)) = OverloadedOp::Binary(op).trait_method() {
+                    let message = self.arithmetic_message(op, &lhs_ty, &rhs_ty);
+                    self.operator_bounded(*id, trait_name, message, span);
+                }
+                Type::Unknown
+            }
+            Type::Adt(id, ..) => {
+                let trait_name = OverloadedOp::Binary(op)
+                    .trait_method()
+                    .map_or("", |(name, _)| name);
+                self.operator_output(trait_name, *id, &shallow, Some((rhs, &rhs_ty)))
+            }
+            Type::Bool | Type::Char | Type::Tuple(_) | Type::Array(..) | Type::Fn(..) => {
+                let message = format!(
+                    "binary operation `{}` cannot be applied to type `{}`",
+                    op.as_str(),
+                    self.infer.display(&lhs_ty)
+                );
+                self.diagnostics.push(Diagnostic::error(message, lhs.span));
+                Type::Unknown
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    fn arithmetic_message(&self, op: BinOp, lhs: &Type, rhs: &Type) -> String {
+        let (lhs, rhs) = (self.infer.display(lhs), self.infer.display(rhs));
+        match op {
+            BinOp::Add => format!("cannot add `{rhs}` to `{lhs}`"),
+            BinOp::Sub => format!("cannot subtract `{rhs}` from `{lhs}`"),
+            BinOp::Mul => format!("cannot multiply `{lhs}` by `{rhs}`"),
+            BinOp::Div => format!("cannot divide `{lhs}` by `{rhs}`"),
+            _ => format!("cannot calculate the remainder of `{lhs}` divided by `{rhs}`"),
+        }
+    }
+
+    /// The `Output` of the impl of `trait_name` for the type `id`, once the right
+    /// operand is checked against the type argument of the trait.
+    fn operator_output(
+        &mut self,
+        trait_name: &str,
+        id: NodeId,
+        self_ty: &Type,
+        rhs: Option<(&Expr, &Type)>,
+    ) -> Type {
+        let impls: Vec<_> = self
+            .items
+            .operators
+            .iter()
+            .filter(|(name, ty, _)| *name == trait_name && *ty == id)
+            .map(|(_, _, imp)| *imp)
+            .collect();
+        // The impl whose operand has the type of `rhs`, the only one otherwise.
+        let mut chosen = None;
+        for imp in &impls {
+            let args = self.instantiate([&imp.generics]);
+            let imp_self = self.lower(&imp.self_ty).subst(&args);
+            let operand = match &imp.of_trait {
+                Some(path) => match path.segments.last().and_then(|s| s.args.first()) {
+                    Some(arg) => self.lower(arg).subst(&args),
+                    None => imp_self.clone(),
+                },
+                None => continue,
+            };
+            let fits = match rhs {
+                Some((_, rhs_ty)) => {
+                    let (rhs_ty, operand) =
+                        (self.infer.resolve(rhs_ty), self.infer.resolve(&operand));
+                    impls.len() == 1 || rhs_ty == operand
+                }
+                None => true,
+            };
+            if fits {
+                chosen = Some((imp, args, imp_self, operand));
+                break;
+            }
+        }
+        let Some((imp, args, imp_self, operand)) = chosen else {
+            return Type::Unknown;
+        };
+        self.infer.unify(&imp_self, self_ty);
+        if let Some((rhs, rhs_ty)) = rhs {
+            self.coerce(rhs.span, rhs_ty, &operand);
+        }
+        let output = imp.items.iter().find_map(|item| match &item.kind {
+            ItemKind::TyAlias(TyAlias { name, ty: Some(ty) }) if name.name == "Output" => Some(ty),
+            _ => None,
+        });
+        match output {
+            Some(ty) => self.lower(ty).subst(&args),
+            None => Type::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_types, Type};
+    use crate::semantic::resolve::resolve_names;
+    use crate::syntax::ast::*;
+    use crate::syntax::parse;
+
+    /// The messages, followed by their note when they have one.
+    fn messages(source: &str) -> Vec<String> {
+        let (module, errors) = parse(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        check_types(&module, &resolutions, &mut diagnostics);
+        diagnostics
+            .into_iter()
+            .map(|d| match d.notes.first() {
+                Some(note) => format!("{}: {note}", d.message),
+                None => d.message,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn infers_local_types() {
+        let (module, _) = parse(
+            "struct Point<T> { x: T, y: T }
+            fn f(flag: bool, k: u8) -> f64 {
+                let a = 1;
+                let b = Point { x: a, y: k };
+                let c = if flag { 1.5 } else { 2.0 };
+                let d = |x| x + 1;
+                let e: u64 = d(3);
+                c
+            }",
+        );
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        let types = check_types(&module, &resolutions, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let ItemKind::Function(function) = &module.items[1].kind else {
+            panic!("{:?}", module.items[1]);
+        };
+        let stmts = &function.body.as_ref().unwrap().stmts;
+        let binding = |i: usize| {
+            let StmtKind::Let(local) = &stmts[i].kind else {
+                panic!("{:?}", stmts[i]);
+            };
+            types.binding(local.pat.id).unwrap().clone()
+        };
+        assert_eq!(binding(0), Type::Int("u8"));
+        assert!(matches!(binding(1), Type::Adt(_, name, args)
+            if name == "Point" && args == [Type::Int("u8")]));
+        assert_eq!(binding(2), Type::Float("f64"));
+        assert_eq!(
+            binding(3),
+            Type::Fn(vec![Type::Int("u64")], Box::new(Type::Int("u64")))
+        );
+    }
+
+    #[test]
+    fn accepts_well_typed_code() {
+        let messages = messages(
+            "struct V { x: f64, y: f64 }
+            impl V {
+                fn len(&self) -> f64 { self.x * self.x + self.y * self.y }
+                fn scale(&mut self, k: f64) { self.x = self.x * k; }
+                operator +(self, o: V) -> V { V { x: self.x + o.x, y: self.y + o.y } }
+                operator *(self, k: f64) -> V { V { x: self.x * k, y: self.y * k } }
+            }
+            trait Source { type Out; fn get(&self) -> Self::Out; }
+            impl Source for V { type Out = f64; fn get(&self) -> Self::Out { self.x } }
+            enum Tree { Leaf(i32), Node(Box<Tree>, Box<Tree>) }
+            impl Tree {
+                fn sum(&self) -> i32 {
+                    match self { Tree::Leaf(n) => *n, Tree::Node(l, r) => l.sum() + r.sum() }
+                }
+            }
+            fn largest<T: PartialOrd + Copy>(xs: &[T]) -> T {
+                let mut best = xs[0];
+                for x in xs { if *x > best { best = *x; } }
+                best
+            }
+            fn apply(f: fn(i32) -> i32, x: i32) -> i32 { f(x) }
+            fn double(x: i32) -> i32 { x * 2 }
+            fn parse(s: &str) -> Result<i32, std::num::ParseIntError> { let n: i32 = s.parse()?; Ok(n) }
+            // A cycle, reported by `consts`.
+            type Loop = Loop;
+            fn endless(l: Loop) -> Loop { l }
+            fn main() {
+                let mut v = V { x: 1.0, y: 2.0 } + V { x: 0.5, y: 0.5 } * 2.0;
+                v.scale(2.0);
+                let l: f64 = v.len() + v.get();
+                let t = Tree::Node(Box::new(Tree::Leaf(1)), Box::new(Tree::Leaf(2)));
+                let big: i32 = largest(&[3, 1, 2]) + t.sum();
+                let s = String::from(\"text\");
+                let r: &str = &s;
+                let n = apply(double, 2);
+                let z = loop { break 5; };
+                let text = match parse(\"4\") { Ok(n) => n, Err(_) => return };
+                let (a, b) = &(1, 2.0);
+                let c: f64 = *b;
+            }",
+        );
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn reports_mismatches() {
+        let messages = messages(
+            "struct Point { x: i32, y: i32 }
+            fn double(x: i32) -> i32 { x * 2 }
+            fn f(p: Point) -> bool {
+                let a: i32 = \"text\";
+                let b = 1 + 2.0;
+                let c = p.z;
+                let d = Point { x: 1, z: 2 };
+                let e = if a > 0 { 1 } else { \"no\" };
+                double(1, 2);
+                let g = 5;
+                g();
+                let h = *a;
+                let Point { x, w } = p;
+                let i = id::<i32>(\"s\");
+                let j = 1;
+                let k = j.y;
+                let l: Point = Marker;
+                let m = p.get::<i32>(\"s\");
+                let n: [i32; 3] = [1, 2, 3];
+                let o = n[1..3];
+                double_all(n[..2]);
+                3
+            }
+            fn double_all<T>(t: T) {}
+            fn rest(s: &str) -> str { return s[1..]; }
+            impl Point { fn get<T>(&self, t: T) -> T { t } }
+            struct Marker;
+            fn id<T>(x: T) -> T { x }
+            const C: u64 = 3;
+            const N: bool = 1;
+            const S: &str = \"a\";
+            const V: i32 = S.len();
+            fn lengths() { let a = [0; C]; }
+            const L: usize = 4;
+            fn repeats() { let a = [0; L]; let b: [i32; 3] = a; }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "mismatched types: expected `i32`, found `&str`",
+                "cannot add `{float}` to `{integer}`",
+                "no field `z` on type `Point`",
+                "struct `Point` has no field named `z`",
+                "missing field `y` in initializer of `Point`",
+                "`if` and `else` have incompatible types: expected `{integer}`, found `&str`",
+                "this function takes 1 argument but 2 arguments were supplied",
+                "expected function, found `{integer}`",
+                "type `i32` cannot be dereferenced",
+                "struct `Point` does not have a field named `w`",
+                "pattern does not mention field `y`: use `..` to ignore the other fields",
+                "mismatched types: expected `i32`, found `&str`",
+                "no field `y` on type `{integer}`",
+                "mismatched types: expected `Point`, found `Marker`",
+                "mismatched types: expected `i32`, found `&str`",
+                "the size for values of type `[i32]` cannot be known at compilation time",
+                "the size for values of type `[i32]` cannot be known at compilation time",
+                "mismatched types: expected `bool`, found `{integer}`",
+                "the size for values of type `str` cannot be known at compilation time",
+                "mismatched types: expected `bool`, found `{integer}`",
+                "mismatched types: expected `i32`, found `usize`",
+                "mismatched types: expected `[i32; 3]`, found `[i32; 4]`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_generic_parameters_against_their_bounds() {
+        let messages = messages(
+            "trait Shape { fn area(&self) -> f64; fn twice(&self) -> f64 { self.area() * 2.0 } }
+            trait Named: Clone { fn name(&self) -> String; }
+            fn sum<T>(a: T, b: T) -> T { a + b }
+            fn copy<T>(a: T) -> T { a.clone() }
+            fn unknown<T: Clone>(a: T) -> T { a.frobnicate() }
+            fn equal<T>(a: T, b: T) -> bool { a == b }
+            fn negate<T>(a: T) -> T { -a }
+            fn largest<T: PartialOrd + Clone>(a: T, b: T) -> T { if a < b { b.clone() } else { a } }
+            fn area<T>(a: T) -> f64 where T: Shape { a.area() + a.twice() }
+            fn name<T: Named>(a: &T) -> String { let b = a.clone(); b.name() }
+            fn next<T: Iterator>(mut it: T) { it.next(); }
+            fn same<T: Ord>(a: T, b: T) -> bool { a == b }
+            fn convert<T>(a: T) -> T { a.into() }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot add `T` to `T`: consider restricting type parameter `T` with trait `Add`",
+                "no method named `clone` found for type parameter `T` in the current scope: items from traits can only be used if the type parameter is bounded by the trait",
+                "no method named `frobnicate` found for type parameter `T` in the current scope: items from traits can only be used if the type parameter is bounded by the trait",
+                "binary operation `==` cannot be applied to type `T`: consider restricting type parameter `T` with trait `PartialEq`",
+                "cannot apply unary operator `-` to type `T`: consider restricting type parameter `T` with trait `Neg`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_literals_against_their_type() {
+        let messages = messages(
+            "fn takes(x: u16) -> u16 { x }
+            fn f() {
+                let a: u8 = 255;
+                let b: u8 = 256;
+                let c: i8 = -128;
+                let d: i8 = -129;
+                let e: u32 = -1;
+                let g = -5;
+                let h: u64 = g;
+                let i = takes(70000);
+                let j = 2147483648;
+                let k: i64 = 2147483648;
+                let l: f64 = -0.5;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "cannot apply unary operator `-` to type `u32`: unsigned values cannot be negated",
+                "cannot apply unary operator `-` to type `u64`: unsigned values cannot be negated",
+                "literal out of range for `u8`: \
+                the literal `256` does not fit into the type `u8` whose range is `0..=255`",
+                "literal out of range for `i8`: \
+                the literal `-129` does not fit into the type `i8` whose range is `-128..=127`",
+                "literal out of range for `u16`: \
+                the literal `70000` does not fit into the type `u16` whose range is `0..=65535`",
+                "literal out of range for `i32`: \
+                the literal `2147483648` does not fit into the type `i32` whose range is \
+                `-2147483648..=2147483647`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_operators_once_their_operand_is_inferred() {
+        let messages = messages(
+            "struct V { x: i32 }
+            struct W { x: i32 }
+            impl std::ops::Sub for W {
+                type Output = W;
+                fn sub(self, other: W) -> W { W { x: self.x - other.x } }
+            }
+            fn f() {
+                let sub = |p| p - V { x: 1 };
+                let neg = |p| -p;
+                let v = neg(V { x: 2 });
+                let ok = |p| p - W { x: 1 };
+                let flag = |b| b + true;
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "binary operation `-` cannot be applied to type `V`: \
+                an implementation of `Sub` might be missing for `V`",
+                "cannot apply unary operator `-` to type `V`: \
+                an implementation of `Neg` might be missing for `V`",
+                "binary operation `+` cannot be applied to type `bool`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_values_of_literal_types() {
+        let messages = messages(
+            "fn f() {
+                let x = 1;
+                let f = 2.5;
+                let a = *x;
+                let b = f[0];
+                let (c, d) = x;
+                for (g, h) in [1, 2, 3] {}
+                for i in f {}
+                for j in 7 {}
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "type `{integer}` cannot be dereferenced",
+                "cannot index into a value of type `{float}`",
+                "mismatched types: expected `{integer}`, found `(_, _)`",
+                "mismatched types: expected `{integer}`, found `(_, _)`",
+                "`{float}` is not an iterator: the trait `Iterator` is not implemented for `{float}`",
+                "`{integer}` is not an iterator: the trait `Iterator` is not implemented for `{integer}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_methods_of_ambiguous_numeric_types() {
+        let messages = messages(
+            "trait Hi { fn hi(&self) -> bool; }
+            impl Hi for i32 { fn hi(&self) -> bool { true } }
+            fn f() {
+                let x = 3;
+                let y: i32 = x.hi();
+                2.5.nope();
+                let z: i32 = 4;
+                let w = z.hi();
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "can't call method `hi` on ambiguous numeric type `{integer}`: you must specify a type for this binding, like `i32`",
+                "can't call method `nope` on ambiguous numeric type `{float}`: you must specify a concrete type for this numeric value, like `f32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_methods_with_adjustments() {
+        let (module, _) = parse(
+            "mod shapes {
+                pub trait Shape { fn area(&self) -> f64; fn name(&self) -> i32 { 0 } }
+                pub struct Circle { pub r: f64 }
+                impl Circle { pub fn grow(&mut self) {} pub fn into_r(self) -> f64 { self.r } }
+                impl Shape for Circle { fn area(&self) -> f64 { self.r } }
+                impl Shape for String { fn area(&self) -> f64 { 0.0 } }
+            }
+            use shapes::{Circle, Shape};
+            fn f(mut c: Circle, r: &mut Circle, b: Box<Circle>, s: String) {
+                c.grow();
+                r.grow();
+                r.area();
+                b.area();
+                s.area();
+                c.name();
+                c.into_r();
+            }",
+        );
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        let types = check_types(&module, &resolutions, &mut diagnostics);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let ItemKind::Function(function) = &module.items[2].kind else {
+            panic!("{:?}", module.items[2]);
+        };
+        let callees: Vec<_> = function.body.as_ref().unwrap().stmts[..]
+            .iter()
+            .map(|stmt| {
+                let StmtKind::Semi(call) = &stmt.kind else {
+                    panic!("{stmt:?}");
+                };
+                let callee = types.callee(call.id).unwrap();
+                (
+                    callee.path.as_str(),
+                    callee.derefs,
+                    callee.autoref,
+                    callee.qualified,
+                )
+            })
+            .collect();
+        assert_eq!(
+            callees,
+            [
+                ("crate::shapes::Circle::grow", 0, Some(true), false),
+                ("crate::shapes::Circle::grow", 0, None, false),
+                ("crate::shapes::Shape::area", 1, Some(false), false),
+                ("crate::shapes::Shape::area", 1, Some(false), false),
+                ("crate::shapes::Shape::area", 0, Some(false), true),
+                ("crate::shapes::Shape::name", 0, Some(false), false),
+                ("crate::shapes::Circle::into_r", 0, None, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_ambiguous_and_out_of_scope_methods() {
+        let messages = messages(
+            "mod a {
+                pub trait Named { fn name(&self) -> i32; }
+                pub trait Titled { fn name(&self) -> i32; }
+                pub struct P { pub v: i32 }
+                impl Named for P { fn name(&self) -> i32 { 1 } }
+                impl Titled for P { fn name(&self) -> i32 { 2 } }
+                impl P { pub fn v(&self) -> i32 { self.v } }
+            }
+            mod b {
+                use crate::a::{Named, P, Titled};
+                fn both(p: &P) -> i32 { p.name() + p.v() }
+            }
+            mod c {
+                use crate::a::{Named, P};
+                fn one(p: &P) -> i32 { p.name() }
+            }
+            mod d {
+                use crate::a::P;
+                fn none(p: P) -> i32 { p.name() }
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "multiple applicable items named `name` in scope: \
+                candidate #1 is defined in the trait `crate::a::Named`",
+                "no method named `name` found for `P` in the current scope: \
+                items from traits can only be used if the trait is in scope",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_private_fields() {
+        let messages = messages(
+            "mod a {
+                pub struct S { f: i32, pub g: i32 }
+                pub enum E { V { f: i32 } }
+                fn inside(s: S) -> i32 { s.f }
+                mod b {
+                    fn child(s: super::S) -> i32 { s.f }
+                }
+            }
+            fn outside(s: a::S) -> i32 {
+                let t = a::S { f: 1, g: 2 };
+                let a::S { f, .. } = t;
+                let e = a::E::V { f: 1 };
+                s.f + s.g + f
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "field `f` of struct `S` is private",
+                "field `f` of struct `S` is private",
+                "field `f` of struct `S` is private",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_methods_of_the_crate_types() {
+        let messages = messages(
+            "#[derive(Clone, PartialEq)]
+            struct S { v: i32 }
+            impl S { fn get(&self) -> i32 { self.v } }
+            enum E { A }
+            impl std::fmt::Display for E {
+                fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+                    Ok(())
+                }
+            }
+            struct N;
+            fn f(s: S, r: &S, e: E, n: &N) -> i32 {
+                let t = s.clone();
+                let same = s.eq(&t) && r.ne(&t);
+                let text = e.to_string();
+                let m = n.clone();
+                s.frobnicate();
+                r.size();
+                e.len();
+                s.get() + r.get()
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "no method named `frobnicate` found for struct `S` in the current scope",
+                "no method named `size` found for struct `S` in the current scope",
+                "no method named `len` found for enum `E` in the current scope",
+            ]
+        );
+    }
+}
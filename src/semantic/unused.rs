@@ -0,0 +1,831 @@
+//! Warnings for what is declared and never used : bindings never read, `mut` on
+//! bindings never written to, private functions no live code calls, fields never
+//! read and imports no path goes through.
+//!
+//! Names starting with `_` are never reported, nor what an `#[allow(...)]` of the
+//! lint, or of the whole `unused` group, covers : the item, the field or the
+//! statement it is on, and everything inside. The generated crate allows the same
+//! lints of rustc, see [`LINTS`], so that they are reported once, against Oxydant.
+//!
+//! A function is live when `main`, a public item, a trait method, an operator, a
+//! test or an allowed function calls it, directly or not. The methods rustc alone
+//! resolves are taken to write to their receiver, and to be any method of the crate
+//! of that name. Imports of traits, which may be used for their methods only, are
+//! not reported. Nor are the imports `resolve` reported, or the bindings in scope
+//! of a statement the parser recovered from, which may have used them.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+use crate::syntax::ast::*;
+use crate::syntax::visit::{self, Visitor};
+
+use super::prelude;
+use super::resolve::{Res, Resolutions};
+use super::typeck::{Type, TypeckResults};
+
+/// The lints of rustc this pass reports in its place.
+pub const LINTS: &[&str] = &[
+    "dead_code",
+    "unused_imports",
+    "unused_mut",
+    "unused_variables",
+];
+
+/// The lint group holding every lint of [`LINTS`].
+const GROUP: &str = "unused";
+
+pub fn check_unused(
+    module: &Module,
+    resolutions: &Resolutions,
+    types: &TypeckResults,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut items = Items::default();
+    items.visit_module(module);
+    let mut collector = Collector {
+        items,
+        resolutions,
+        types,
+        allowed: vec![],
+        owner: None,
+        container: Container::Module,
+        depth: 0,
+        bindings: vec![],
+        aliases: HashMap::new(),
+        shorthands: HashSet::new(),
+        reads: HashSet::new(),
+        writes: HashSet::new(),
+        mutated: HashSet::new(),
+        functions: vec![],
+        roots: vec![],
+        calls: HashMap::new(),
+        fields: vec![],
+        read_fields: HashSet::new(),
+        read_names: HashSet::new(),
+        called_names: vec![],
+        imports: vec![],
+        blocks: vec![],
+        errors: vec![],
+    };
+    collector.visit_module(module);
+    let mut warnings = collector.report();
+    warnings.sort_by_key(|warning| warning.span.start);
+    diagnostics.extend(warnings);
+}
+
+/// The methods taking `&mut self`, and the names of the traits of the crate.
+#[derive(Default)]
+struct Items<'ast> {
+    mut_self: HashSet<NodeId>,
+    traits: HashSet<&'ast str>,
+}
+
+impl<'ast> Visitor<'ast> for Items<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match &item.kind {
+            ItemKind::Function(function) => {
+                let mut_self = function.params.first().is_some_and(|param| {
+                    param.is_self() && matches!(param.ty.kind, TyKind::Ref { mutable: true, .. })
+                });
+                if mut_self {
+                    self.mut_self.insert(item.id);
+                }
+            }
+            ItemKind::Trait(def) => {
+                self.traits.insert(&def.name.name);
+            }
+            _ => {}
+        }
+        visit::walk_item(self, item);
+    }
+}
+
+/// What holds the items being visited.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Module,
+    Impl,
+    /// A trait, or an impl of a trait.
+    Trait,
+}
+
+struct Binding<'ast> {
+    id: NodeId,
+    name: &'ast Ident,
+    mutable: bool,
+    span: Span,
+    /// `x` in `Point { x, .. }`.
+    shorthand: bool,
+    /// The block the binding is visible in.
+    scope: Span,
+    allow_unused: bool,
+    allow_mut: bool,
+}
+
+/// A private function, which no live code may call.
+struct Candidate<'ast> {
+    id: NodeId,
+    name: &'ast Ident,
+    kind: &'static str,
+}
+
+/// The private fields of a struct.
+struct Fields<'ast> {
+    id: NodeId,
+    name: &'ast Ident,
+    fields: Vec<&'ast Ident>,
+    derives: Vec<String>,
+}
+
+struct Collector<'ast, 'r> {
+    items: Items<'ast>,
+    resolutions: &'r Resolutions,
+    types: &'r TypeckResults,
+    /// The lints allowed where the visitor is.
+    allowed: Vec<&'ast str>,
+    /// The function whose body is visited.
+    owner: Option<NodeId>,
+    container: Container,
+    /// How deep in modules the visitor is, `0` for the root.
+    depth: usize,
+    bindings: Vec<Binding<'ast>>,
+    /// The bindings of the later alternatives of or-patterns, to the binding of the
+    /// same name in the first one.
+    aliases: HashMap<NodeId, NodeId>,
+    shorthands: HashSet<NodeId>,
+    reads: HashSet<NodeId>,
+    writes: HashSet<NodeId>,
+    /// The bindings written to, or borrowed mutably.
+    mutated: HashSet<NodeId>,
+    functions: Vec<Candidate<'ast>>,
+    /// The functions live whoever calls them.
+    roots: Vec<NodeId>,
+    /// The items each function names, `None` for what is named outside functions.
+    calls: HashMap<Option<NodeId>, Vec<NodeId>>,
+    fields: Vec<Fields<'ast>>,
+    read_fields: HashSet<(NodeId, &'ast str)>,
+    /// The fields read from a value of a type not known.
+    read_names: HashSet<&'ast str>,
+    /// The methods called on a value of a type not known, with the function calling
+    /// them. They may be any method of that name.
+    called_names: Vec<(Option<NodeId>, &'ast str)>,
+    /// The simple `use` trees, with the text of their path.
+    imports: Vec<(Span, String)>,
+    /// The blocks around the visitor, innermost last.
+    blocks: Vec<Span>,
+    /// What is left of the expressions the parser could not read.
+    errors: Vec<Span>,
+}
+
+/// The lints an `#[allow(...)]` allows.
+pub(super) fn allows(attrs: &[Attribute]) -> impl Iterator<Item = &str> {
+    attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::List(path, list) if path.to_text() == "allow" => Some(list),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|meta| match meta {
+            Meta::Word(path) => Some(path.last().name.as_str()),
+            _ => None,
+        })
+}
+
+/// The bindings of a pattern, with their names, those of the first alternative of
+/// an or-pattern.
+fn bindings<'ast>(pattern: &'ast Pattern, out: &mut Vec<(&'ast str, NodeId)>) {
+    match &pattern.kind {
+        PatKind::Binding { name, sub, .. } => {
+            out.push((&name.name, pattern.id));
+            if let Some(sub) = sub {
+                bindings(sub, out);
+            }
+        }
+        PatKind::Tuple(patterns) | PatKind::TupleStruct(_, patterns) => {
+            for pattern in patterns {
+                bindings(pattern, out);
+            }
+        }
+        PatKind::Paren(inner) => bindings(inner, out),
+        PatKind::Struct { fields, .. } => {
+            for field in fields {
+                bindings(&field.pat, out);
+            }
+        }
+        PatKind::Or(alternatives) => {
+            if let Some(first) = alternatives.first() {
+                bindings(first, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl<'ast> Visitor<'ast> for Collector<'ast, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let allowed = self.allowed.len();
+        self.allowed.extend(allows(&item.attrs));
+        let (owner, container, depth) = (self.owner, self.container, self.depth);
+        match &item.kind {
+            ItemKind::Function(function) => {
+                self.declare_function(item, function);
+                self.owner = Some(item.id);
+            }
+            ItemKind::Struct(def) => self.declare_fields(item, def),
+            ItemKind::Use(tree)
+                if item.vis == Visibility::Private
+                    && owner.is_none()
+                    && !self.is_allowed("unused_imports") =>
+            {
+                self.declare_imports(tree)
+            }
+            ItemKind::Impl(imp) => {
+                self.container = match imp.of_trait {
+                    Some(_) => Container::Trait,
+                    None => Container::Impl,
+                };
+            }
+            ItemKind::Trait(_) => self.container = Container::Trait,
+            ItemKind::Mod(_) => self.depth += 1,
+            _ => {}
+        }
+        visit::walk_item(self, item);
+        (self.owner, self.container, self.depth) = (owner, container, depth);
+        self.allowed.truncate(allowed);
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        // The parameters of a method without a body have nothing to be used by.
+        if let Some(body) = &function.body {
+            let container = std::mem::replace(&mut self.container, Container::Module);
+            self.blocks.push(body.span);
+            visit::walk_function(self, function);
+            self.blocks.pop();
+            self.container = container;
+        }
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.blocks.push(block.span);
+        visit::walk_block(self, block);
+        self.blocks.pop();
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        let allowed = self.allowed.len();
+        self.allowed.extend(allows(&stmt.attrs));
+        visit::walk_stmt(self, stmt);
+        self.allowed.truncate(allowed);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        match &pattern.kind {
+            PatKind::Binding { name, mutable, .. } => self.declare_binding(pattern, name, *mutable),
+            PatKind::Or(alternatives) => {
+                let mut first = vec![];
+                bindings(pattern, &mut first);
+                for alternative in alternatives.iter().skip(1) {
+                    let mut others = vec![];
+                    bindings(alternative, &mut others);
+                    for (name, id) in others {
+                        if let Some((_, canonical)) = first.iter().find(|(n, _)| *n == name) {
+                            self.aliases.insert(id, *canonical);
+                        }
+                    }
+                }
+            }
+            PatKind::Struct { fields, .. } => {
+                let owner = match self.resolutions.get(pattern.id) {
+                    Some(Res::Item(id)) => Some(id),
+                    _ => adt(self.types.pattern(pattern.id)),
+                };
+                for field in fields {
+                    self.read_field(owner, &field.name);
+                    if field.shorthand {
+                        self.shorthands.insert(field.pat.id);
+                    }
+                }
+            }
+            _ => {}
+        }
+        visit::walk_pattern(self, pattern);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match &expr.kind {
+            ExprKind::Path(_) => match self.resolutions.get(expr.id) {
+                Some(Res::Local(id)) => {
+                    self.reads.insert(self.canonical(id));
+                }
+                Some(Res::Item(id)) => self.call(id),
+                _ => {}
+            },
+            ExprKind::Error => self.errors.push(expr.span),
+            ExprKind::Assign(lhs, rhs) => {
+                if let Some(local) = self.root(lhs) {
+                    self.mutated.insert(local);
+                }
+                match &lhs.kind {
+                    ExprKind::Path(_) => {
+                        if let Some(Res::Local(id)) = self.resolutions.get(lhs.id) {
+                            self.writes.insert(self.canonical(id));
+                        }
+                    }
+                    // Writing a field does not read it.
+                    ExprKind::Field(base, _) => self.visit_expr(base),
+                    _ => self.visit_expr(lhs),
+                }
+                self.visit_expr(rhs);
+                return;
+            }
+            ExprKind::AddrOf {
+                mutable: true,
+                expr: place,
+            } => {
+                if let Some(local) = self.root(place) {
+                    self.mutated.insert(local);
+                }
+            }
+            ExprKind::MethodCall {
+                receiver, method, ..
+            } => {
+                match self.types.method(expr.id) {
+                    Some(function) => self.call(function),
+                    None => self.called_names.push((self.owner, &method.name)),
+                }
+                if self.may_mutate(expr.id, receiver) {
+                    if let Some(local) = self.root(receiver) {
+                        self.mutated.insert(local);
+                    }
+                }
+            }
+            // Calling a closure may need it to be `mut`.
+            ExprKind::Call(callee, _) => {
+                if let Some(Res::Local(id)) = self.resolutions.get(callee.id) {
+                    self.mutated.insert(self.canonical(id));
+                }
+            }
+            ExprKind::Field(base, name) => {
+                let owner = adt(self.types.expr(base.id));
+                self.read_field(owner, name);
+            }
+            _ => {}
+        }
+        visit::walk_expr(self, expr);
+    }
+}
+
+/// The struct or enum of the crate a value of type `ty` is, through references and
+/// boxes.
+pub(super) fn adt(ty: Option<&Type>) -> Option<NodeId> {
+    match ty? {
+        Type::Ref(_, inner) => adt(Some(inner)),
+        Type::Named(name, args) if name == "Box" => adt(args.first()),
+        Type::Adt(id, ..) => Some(*id),
+        _ => None,
+    }
+}
+
+impl<'ast> Collector<'ast, '_> {
+    fn is_allowed(&self, lint: &str) -> bool {
+        self.allowed
+            .iter()
+            .any(|allowed| *allowed == lint || *allowed == GROUP)
+    }
+
+    fn canonical(&self, id: NodeId) -> NodeId {
+        self.aliases.get(&id).copied().unwrap_or(id)
+    }
+
+    fn call(&mut self, id: NodeId) {
+        self.calls.entry(self.owner).or_default().push(id);
+    }
+
+    fn read_field(&mut self, owner: Option<NodeId>, name: &'ast Ident) {
+        match owner {
+            Some(owner) => self.read_fields.insert((owner, &name.name)),
+            None => self.read_names.insert(&name.name),
+        };
+    }
+
+    fn declare_binding(&mut self, pattern: &'ast Pattern, name: &'ast Ident, mutable: bool) {
+        if self.aliases.contains_key(&pattern.id)
+            || name.name == "self"
+            || name.name.starts_with('_')
+            || !pattern.span.ctxt.is_root()
+        {
+            return;
+        }
+        self.bindings.push(Binding {
+            id: pattern.id,
+            name,
+            mutable,
+            span: pattern.span,
+            shorthand: self.shorthands.contains(&pattern.id),
+            scope: self.blocks.last().copied().unwrap_or(pattern.span),
+            allow_unused: self.is_allowed("unused_variables"),
+            allow_mut: self.is_allowed("unused_mut"),
+        });
+    }
+
+    fn declare_function(&mut self, item: &'ast Item, function: &'ast Function) {
+        let name = &function.name;
+        let root = item.vis == Visibility::Public
+            || self.container == Container::Trait
+            || function.operator.is_some()
+            || (name.name == "main" && self.depth == 0 && self.owner.is_none())
+            || item.attrs.iter().any(|attr| attr.name() == "test")
+            || name.name.starts_with('_')
+            || self.is_allowed("dead_code");
+        if root {
+            self.roots.push(item.id);
+            return;
+        }
+        let kind = match self.container {
+            Container::Module | Container::Trait => "function",
+            Container::Impl if function.params.first().is_some_and(Param::is_self) => "method",
+            Container::Impl => "associated function",
+        };
+        self.functions.push(Candidate {
+            id: item.id,
+            name,
+            kind,
+        });
+    }
+
+    fn declare_fields(&mut self, item: &'ast Item, def: &'ast Struct) {
+        if self.is_allowed("dead_code") {
+            return;
+        }
+        let fields = def
+            .fields
+            .iter()
+            .filter(|field| {
+                field.vis == Visibility::Private
+                    && !field.name.name.starts_with('_')
+                    && !allows(&field.attrs).any(|lint| lint == "dead_code" || lint == GROUP)
+            })
+            .map(|field| &field.name)
+            .collect();
+        let derives = item
+            .attrs
+            .iter()
+            .flat_map(Attribute::derives)
+            .map(Path::to_text)
+            .collect();
+        self.fields.push(Fields {
+            id: item.id,
+            name: &def.name,
+            fields,
+            derives,
+        });
+    }
+
+    fn declare_imports(&mut self, tree: &'ast UseTree) {
+        match &tree.kind {
+            UseTreeKind::Simple(alias) => {
+                let mut segments = tree.prefix.segments.iter().rev();
+                let mut last = segments.next().map(|segment| &segment.ident);
+                if last.is_some_and(|last| last.name == "self") {
+                    last = segments.next().map(|segment| &segment.ident);
+                }
+                let Some(name) = alias.as_ref().or(last) else {
+                    return;
+                };
+                let imported = last.map_or("", |last| last.name.as_str());
+                if name.name.starts_with('_')
+                    || self.items.traits.contains(imported)
+                    || prelude::METHOD_TRAITS.contains(&imported)
+                {
+                    return;
+                }
+                let text = match alias {
+                    Some(alias) => format!("{} as {}", tree.prefix.to_text(), alias.name),
+                    None => tree.prefix.to_text(),
+                };
+                self.imports.push((tree.span, text));
+            }
+            UseTreeKind::Nested(trees) => {
+                for tree in trees {
+                    self.declare_imports(tree);
+                }
+            }
+            UseTreeKind::Glob => {}
+        }
+    }
+
+    /// The binding holding the place written to, unless it is behind a reference.
+    fn root(&self, place: &Expr) -> Option<NodeId> {
+        match &place.kind {
+            ExprKind::Paren(inner) => self.root(inner),
+            ExprKind::Path(_) => match self.resolutions.get(place.id) {
+                Some(Res::Local(id)) => Some(self.canonical(id)),
+                _ => None,
+            },
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => match self.types.expr(base.id) {
+                Some(Type::Ref(..)) => None,
+                _ => self.root(base),
+            },
+            ExprKind::Unary(UnOp::Deref, inner) => match self.types.expr(inner.id) {
+                Some(Type::Ref(..)) => None,
+                _ => self.root(inner),
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether a method call may borrow its receiver mutably.
+    fn may_mutate(&self, id: NodeId, receiver: &Expr) -> bool {
+        match self.types.expr(receiver.id) {
+            // A reference is reborrowed, the binding holding it is not written to.
+            Some(Type::Ref(..) | Type::Int(_) | Type::Float(_) | Type::Bool | Type::Char) => false,
+            _ => self
+                .types
+                .method(id)
+                .is_none_or(|function| self.items.mut_self.contains(&function)),
+        }
+    }
+
+    fn report(self) -> Vec<Diagnostic> {
+        let mut warnings = vec![];
+        for binding in &self.bindings {
+            let name = binding.name.display_name();
+            let recovered = self.errors.iter().any(|error| {
+                error.start >= binding.span.end
+                    && binding.scope.start <= error.start
+                    && error.end <= binding.scope.end
+            });
+            if recovered {
+                continue;
+            }
+            if !self.reads.contains(&binding.id) {
+                if binding.allow_unused {
+                    continue;
+                }
+                let warning = if self.writes.contains(&binding.id) {
+                    Diagnostic::warning(
+                        format!("variable `{name}` is assigned to, but never used"),
+                        binding.name.span,
+                    )
+                    .with_note(format!("consider using `_{name}` instead"))
+                } else if binding.shorthand {
+                    Diagnostic::warning(format!("unused variable: `{name}`"), binding.name.span)
+                        .with_suggestion(
+                            binding.name.span,
+                            "try ignoring the field",
+                            format!("{name}: _"),
+                        )
+                } else {
+                    Diagnostic::warning(format!("unused variable: `{name}`"), binding.name.span)
+                        .with_suggestion(
+                            binding.name.span,
+                            "if this is intentional, prefix it with an underscore",
+                            format!("_{name}"),
+                        )
+                };
+                warnings.push(warning);
+            } else if binding.mutable && !binding.allow_mut && !self.mutated.contains(&binding.id) {
+                let span = Span::new(binding.span.start, binding.name.span.end);
+                let keyword = Span::new(binding.span.start, binding.name.span.start);
+                warnings.push(
+                    Diagnostic::warning("variable does not need to be mutable", span)
+                        .with_suggestion(keyword, "remove this `mut`", ""),
+                );
+            }
+        }
+
+        let mut calls = self.calls.clone();
+        for (owner, name) in &self.called_names {
+            let methods = self
+                .functions
+                .iter()
+                .filter(|function| function.kind == "method" && function.name.name == *name);
+            calls
+                .entry(*owner)
+                .or_default()
+                .extend(methods.map(|function| function.id));
+        }
+        let mut live = HashSet::new();
+        let mut work = self.roots.clone();
+        work.extend(calls.get(&None).into_iter().flatten());
+        while let Some(id) = work.pop() {
+            if live.insert(id) {
+                work.extend(calls.get(&Some(id)).into_iter().flatten());
+            }
+        }
+        for function in &self.functions {
+            if !live.contains(&function.id) {
+                warnings.push(Diagnostic::warning(
+                    format!("{} `{}` is never used", function.kind, function.name.name),
+                    function.name.span,
+                ));
+            }
+        }
+
+        for def in &self.fields {
+            let unread: Vec<_> = def
+                .fields
+                .iter()
+                .filter(|field| {
+                    !self.read_fields.contains(&(def.id, &field.name))
+                        && !self.read_names.contains(field.name.as_str())
+                })
+                .collect();
+            let Some(first) = unread.first() else {
+                continue;
+            };
+            let names: Vec<_> = unread
+                .iter()
+                .map(|field| format!("`{}`", field.name))
+                .collect();
+            let (message, label) = match &names[..] {
+                [name] => (
+                    format!("field {name} is never read"),
+                    "field in this struct",
+                ),
+                [init @ .., last] => (
+                    format!("fields {} and {last} are never read", init.join(", ")),
+                    "fields in this struct",
+                ),
+                [] => continue,
+            };
+            let mut warning =
+                Diagnostic::warning(message, first.span).with_label(def.name.span, label);
+            if let Some((last, init)) = def.derives.split_last() {
+                let struct_name = &def.name.name;
+                warning = warning.with_note(match init {
+                    [] => format!(
+                        "`{struct_name}` has a derived impl for the trait `{last}`, but this is \
+                         intentionally ignored during dead code analysis"
+                    ),
+                    _ => format!(
+                        "`{struct_name}` has derived impls for the traits `{}` and `{last}`, but \
+                         these are intentionally ignored during dead code analysis",
+                        init.join("`, `")
+                    ),
+                });
+            }
+            warnings.push(warning);
+        }
+
+        for (span, text) in &self.imports {
+            if !self.resolutions.is_used_import(*span) && !self.resolutions.is_failed_import(*span)
+            {
+                warnings.push(Diagnostic::warning(
+                    format!("unused import: `{text}`"),
+                    *span,
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::check_unused;
+    use crate::diagnostics::Severity;
+    use crate::semantic;
+    use crate::semantic::resolve::resolve_names;
+    use crate::semantic::typeck::check_types;
+    use crate::syntax::parse;
+
+    fn messages(source: &str) -> Vec<String> {
+        semantic::test::messages(source, check_unused)
+    }
+
+    #[test]
+    fn accepts_used_code() {
+        let messages = messages(
+            "use std::collections::HashMap;
+            use std::fmt::Write;
+            #[derive(Clone)]
+            struct Point { x: i32, y: i32 }
+            impl Point {
+                fn new(x: i32) -> Point { Point { x, y: 0 } }
+                fn bump(&mut self) { self.x = self.x + 1; }
+                fn scale(&self, k: i32) -> i32 { self.x * k }
+            }
+            fn sum(p: &Point) -> i32 { let Point { x, y } = p; x + y }
+            fn main() {
+                let mut p = Point::new(1);
+                p.bump();
+                let mut q = p.clone();
+                q.bump();
+                let k = p.clone().scale(2);
+                let mut total = 0;
+                for n in 0..3 { total = total + n; }
+                let counts: HashMap<i32, i32> = HashMap::new();
+                let _ = (counts, total, sum(&p), k);
+            }",
+        );
+        assert_eq!(messages, Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_unused_bindings() {
+        let messages = messages(
+            "fn main() {
+                let a = 1;
+                let mut b = 2;
+                let mut c = 3;
+                c = 4;
+                let _d = 5;
+                match Some(b) { Some(e) | Some(e) => {} None => {} }
+            }",
+        );
+        assert_eq!(
+            messages,
+            [
+                "unused variable: `a`",
+                "variable does not need to be mutable",
+                "variable `c` is assigned to, but never used",
+                "unused variable: `e`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_dead_functions_fields_and_imports() {
+        let messages = messages(
+            "use std::collections::HashSet;
+            struct Point { x: i32, y: i32, z: i32 }
+            impl Point {
+                fn norm(&self) -> i32 { self.x }
+                fn origin() -> Point { Point { x: 0, y: 0, z: 0 } }
+            }
+            fn ping() -> i32 { pong() }
+            fn pong() -> i32 { ping() }
+            pub fn main() {}",
+        );
+        assert_eq!(
+            messages,
+            [
+                "unused import: `std::collections::HashSet`",
+                "fields `y` and `z` are never read",
+                "method `norm` is never used",
+                "associated function `origin` is never used",
+                "function `ping` is never used",
+                "function `pong` is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn honours_allow_attributes() {
+        let messages = messages(
+            "#[allow(unused_imports)]
+            use std::collections::HashSet;
+            struct Point { #[allow(dead_code)] x: i32 }
+            #[allow(dead_code)]
+            fn helper() -> i32 { inner() }
+            fn inner() -> i32 { 1 }
+            #[allow(unused)]
+            fn quiet(a: i32) { let mut b = 2; }
+            fn main() {
+                #[allow(unused_variables)]
+                let a = 1;
+                let _ = Point { x: 1 };
+            }",
+        );
+        assert_eq!(messages, Vec::<String>::new());
+    }
+
+    #[test]
+    fn skips_failed_imports_and_recovered_statements() {
+        let (module, errors) = parse(
+            "mod a { pub mod b { fn hidden() {} } }
+            use a::b::zz;
+            use a::b::hidden;
+            fn main() {
+                let c = 1;
+                let mut d = 2.0;
+                let e = c + d as i32;
+                let f = 3;
+            }
+            fn other() { let g = 4; }",
+        );
+        assert_eq!(errors.len(), 1);
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        let types = check_types(&module, &resolutions, &mut diagnostics);
+        check_unused(&module, &resolutions, &types, &mut diagnostics);
+        let warnings: Vec<_> = diagnostics
+            .into_iter()
+            .filter(|d| d.severity == Severity::Warning)
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "function `hidden` is never used",
+                "unused variable: `f`",
+                "function `other` is never used",
+                "unused variable: `g`",
+            ]
+        );
+    }
+}
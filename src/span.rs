@@ -0,0 +1,181 @@
+/// A range of bytes in the source, `start` inclusive and `end` exclusive. Code
+/// written by a macro keeps the positions of the macro definition, and `ctxt` tells
+/// which expansion it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub ctxt: SyntaxContext,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            ctxt: SyntaxContext::ROOT,
+        }
+    }
+
+    pub fn with_ctxt(self, ctxt: SyntaxContext) -> Self {
+        Self { ctxt, ..self }
+    }
+
+    /// A span covering both `self` and `other`, which come from the same context.
+    /// Spans of different expansions are joined by [`Expansions::join`].
+    pub fn to(self, other: Span) -> Span {
+        debug_assert_eq!(self.ctxt, other.ctxt, "spans of different contexts");
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            ctxt: self.ctxt,
+        }
+    }
+}
+
+/// Where a piece of code comes from : the source as written, or an expansion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SyntaxContext(u32);
+
+/// A macro call, which the code it expands to points back to.
+#[derive(Debug, Clone)]
+pub struct Expansion {
+    pub name: String,
+    /// The `name!(...)` call.
+    pub call_site: Span,
+    /// The `macro name` definition.
+    pub def_site: Span,
+}
+
+impl SyntaxContext {
+    pub const ROOT: Self = SyntaxContext(0);
+
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
+}
+
+/// Every expansion of a compilation, which the [`SyntaxContext`] of a span indexes
+/// so that spans stay `Copy`.
+#[derive(Debug, Clone, Default)]
+pub struct Expansions(Vec<Expansion>);
+
+impl Expansions {
+    /// A context for the code of a new expansion.
+    pub fn add(&mut self, expansion: Expansion) -> SyntaxContext {
+        self.0.push(expansion);
+        SyntaxContext(self.0.len() as u32)
+    }
+
+    pub fn get(&self, ctxt: SyntaxContext) -> Option<&Expansion> {
+        let index = (ctxt.0 as usize).checked_sub(1)?;
+        self.0.get(index)
+    }
+
+    fn call_site(&self, ctxt: SyntaxContext) -> Span {
+        self.get(ctxt)
+            .expect("only expanded code has a call site")
+            .call_site
+    }
+
+    /// The expansions `ctxt` is nested in, innermost first.
+    pub fn backtrace(&self, ctxt: SyntaxContext) -> Vec<&Expansion> {
+        let mut expansions = vec![];
+        let mut ctxt = ctxt;
+        while let Some(expansion) = self.get(ctxt) {
+            ctxt = expansion.call_site.ctxt;
+            expansions.push(expansion);
+        }
+        expansions
+    }
+
+    /// The span in the source as written : the outermost macro call for code
+    /// written by an expansion.
+    pub fn source_callsite(&self, span: Span) -> Span {
+        let mut span = span;
+        while let Some(expansion) = self.get(span.ctxt) {
+            span = expansion.call_site;
+        }
+        span
+    }
+
+    /// A span covering both `a` and `b`. When they come from different expansions,
+    /// the inner one is replaced by the macro call that produced it.
+    pub fn join(&self, a: Span, b: Span) -> Span {
+        let (mut a, mut b) = (a, b);
+        // A call is always older than the code it expands to, so the newest
+        // context is the innermost one.
+        while a.ctxt != b.ctxt {
+            if a.ctxt > b.ctxt {
+                a = self.call_site(a.ctxt);
+            } else {
+                b = self.call_site(b.ctxt);
+            }
+        }
+        a.to(b)
+    }
+}
+
+/// Every file read during a compilation. Each file gets its own range of positions,
+/// so a [`Span`] alone is enough to find the file it points into.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    /// The expansions of the macro calls of the files.
+    pub expansions: Expansions,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// Position of the first byte of the file.
+    pub base: usize,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file and returns the base to give to the lexer.
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> usize {
+        let base = self
+            .files
+            .last()
+            // Keeps one empty position after each file for "end of file" errors.
+            .map(|file| file.base + file.text.len() + 1)
+            .unwrap_or(0);
+        self.files.push(SourceFile {
+            name: name.into(),
+            text: text.into(),
+            base,
+        });
+        base
+    }
+
+    pub fn file(&self, position: usize) -> Option<&SourceFile> {
+        self.files
+            .iter()
+            .rev()
+            .find(|file| file.base <= position && position <= file.base + file.text.len())
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+}
+
+impl SourceFile {
+    /// The 1-based line and column of `position`, with the text of that line.
+    pub fn line_col(&self, position: usize) -> (usize, usize, &str) {
+        let offset = (position - self.base).min(self.text.len());
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |i| offset + i);
+        let line = self.text[..offset].matches('\n').count() + 1;
+        let column = self.text[line_start..offset].chars().count() + 1;
+        (line, column, &self.text[line_start..line_end])
+    }
+}
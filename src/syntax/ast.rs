@@ -0,0 +1,858 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::span::Span;
+
+/// Identifies a node of the tree, so later passes can attach information to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    /// Ids are unique for the whole compilation, across files.
+    pub fn next() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+    /// The name the source writes, when hygiene renamed the identifier.
+    pub written: Option<String>,
+}
+
+impl Ident {
+    /// The name as the source writes it, for the messages.
+    pub fn display_name(&self) -> &str {
+        self.written.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathSegment {
+    pub ident: Ident,
+    /// `<T, U>` in a type, `::<T, U>` in an expression, or the inputs of a closure
+    /// trait, `T, U` in `Fn(T, U) -> R`.
+    pub args: Vec<Ty>,
+    /// Whether the arguments are in parentheses, as those of `Fn`, `FnMut` and
+    /// `FnOnce`.
+    pub parenthesized: bool,
+    /// `R` in `Fn(T, U) -> R`.
+    pub output: Option<Box<Ty>>,
+}
+
+impl PathSegment {
+    pub fn new(ident: Ident) -> Self {
+        Self {
+            ident,
+            args: vec![],
+            parenthesized: false,
+            output: None,
+        }
+    }
+}
+
+impl Path {
+    /// A path of a single segment, e.g. the `x` in `Point { x }`.
+    pub fn from_ident(ident: Ident) -> Self {
+        Self {
+            span: ident.span,
+            segments: vec![PathSegment::new(ident)],
+        }
+    }
+
+    /// The last segment, e.g. `Circle` in `Shape::Circle`.
+    pub fn last(&self) -> &Ident {
+        &self.segments[self.segments.len() - 1].ident
+    }
+
+    /// `a::b::c` or `Vec<T>`, as written in a type.
+    pub fn to_text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let name = segment.ident.display_name();
+                let args: Vec<_> = segment.args.iter().map(Ty::to_text).collect();
+                if segment.parenthesized {
+                    let output = segment.output.as_ref();
+                    let output = output.map_or(String::new(), |ty| format!(" -> {}", ty.to_text()));
+                    return format!("{name}({}){output}", args.join(", "));
+                }
+                if args.is_empty() {
+                    return name.to_string();
+                }
+                format!("{name}<{}>", args.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+}
+
+/// The content of one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub items: Vec<Item>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: NodeId,
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub kind: ItemKind,
+    pub span: Span,
+}
+
+/// `#[derive(Debug, Clone)]`, on an item, a field, a variant or a statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub meta: Meta,
+    pub span: Span,
+}
+
+/// The content of an attribute, as in Rust.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Meta {
+    /// `inline`
+    Word(Path),
+    /// `derive(Debug, Clone)`
+    List(Path, Vec<Meta>),
+    /// `deprecated = "use g instead"`
+    NameValue(Path, Lit),
+    /// `"text"` inside a list.
+    Lit(Lit),
+}
+
+impl Attribute {
+    /// The name of the attribute, `derive` for `#[derive(Debug)]`.
+    pub fn name(&self) -> &str {
+        match &self.meta {
+            Meta::Word(path) | Meta::List(path, _) | Meta::NameValue(path, _) => &path.last().name,
+            Meta::Lit(_) => "",
+        }
+    }
+
+    /// The traits of a `#[derive(...)]`.
+    pub fn derives(&self) -> impl Iterator<Item = &Path> {
+        let list = match &self.meta {
+            Meta::List(path, list) if path.to_text() == "derive" => &list[..],
+            _ => &[],
+        };
+        list.iter().filter_map(|meta| match meta {
+            Meta::Word(path) => Some(path),
+            _ => None,
+        })
+    }
+}
+
+impl Meta {
+    /// The meta as written, e.g. `deprecated(note = "old")`.
+    pub fn to_text(&self) -> String {
+        match self {
+            Meta::Word(path) => path.to_text(),
+            Meta::List(path, list) => {
+                let list: Vec<_> = list.iter().map(Meta::to_text).collect();
+                format!("{}({})", path.to_text(), list.join(", "))
+            }
+            Meta::NameValue(path, lit) => format!("{} = {}", path.to_text(), lit.to_text()),
+            Meta::Lit(lit) => lit.to_text(),
+        }
+    }
+}
+
+/// Items and fields are private to their module, and its children, unless marked `pub`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Private,
+    Public,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind {
+    Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Mod(Mod),
+    Use(UseTree),
+    Trait(Trait),
+    Impl(Impl),
+    TyAlias(TyAlias),
+    Const(Const),
+    /// Written like a const, see [`Const`].
+    Static(Const),
+    MacroDef(MacroDef),
+    /// What is left of an item that could not be parsed.
+    Error,
+}
+
+/// `macro name(...) { ... }`. Its calls are expanded by the parser, see
+/// [`crate::syntax::expand`], so only the name is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroDef {
+    pub name: Ident,
+}
+
+/// `mod name;`, read from `name.oxy` or `name/mod.oxy`, or `mod name { ... }`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mod {
+    pub name: Ident,
+    pub inline: bool,
+    /// Filled by the driver for `mod name;`, stays empty if the file could not be read.
+    pub content: Option<Module>,
+}
+
+/// `use a::b::{c, d as e};` is the prefix `a::b` with two nested trees, `c` and `d as e`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UseTree {
+    pub prefix: Path,
+    pub kind: UseTreeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UseTreeKind {
+    /// `a::b`, or `a::b as c` with the new name.
+    Simple(Option<Ident>),
+    /// `a::{b, c}`
+    Nested(Vec<UseTree>),
+    /// `a::*`
+    Glob,
+}
+
+impl UseTree {
+    /// The name the tree brings in scope, for a simple tree.
+    pub fn binding(&self) -> Option<&Ident> {
+        match &self.kind {
+            UseTreeKind::Simple(Some(rename)) => Some(rename),
+            UseTreeKind::Simple(None) => self.prefix.segments.last().map(|segment| &segment.ident),
+            _ => None,
+        }
+    }
+}
+
+/// `<T: Ord + Clone, U>` and `where U: Default`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Generics {
+    pub params: Vec<GenericParam>,
+    pub where_clause: Vec<WherePredicate>,
+    pub span: Span,
+}
+
+impl Generics {
+    /// The bounds of `name`, from its declaration and from the `where` clause.
+    pub fn bounds_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Path> + 'a {
+        let declared = self
+            .params
+            .iter()
+            .filter(move |param| param.name.name == name)
+            .flat_map(|param| &param.bounds);
+        let clauses = self
+            .where_clause
+            .iter()
+            .filter(move |predicate| {
+                matches!(&predicate.ty.kind, TyKind::Path(path)
+                    if path.segments.len() == 1 && path.last().name == name)
+            })
+            .flat_map(|predicate| &predicate.bounds);
+        declared.chain(clauses)
+    }
+}
+
+/// `T: Ord + Clone`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericParam {
+    pub id: NodeId,
+    pub name: Ident,
+    pub bounds: Vec<Path>,
+    pub span: Span,
+}
+
+/// `Vec<T>: Debug` in a `where` clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WherePredicate {
+    pub ty: Ty,
+    pub bounds: Vec<Path>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: Ident,
+    pub generics: Generics,
+    pub params: Vec<Param>,
+    pub output: Option<Ty>,
+    /// Missing for the methods of a trait that have no default.
+    pub body: Option<Block>,
+    /// Set for `operator +` in an impl, whose name is then the method of the trait
+    /// behind the operator, `add`.
+    pub operator: Option<OverloadedOp>,
+    /// `const fn`, which constants can call, see [`crate::semantic::consts`].
+    pub is_const: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub id: NodeId,
+    /// `x`, `mut x` or `(a, b)`. `self` is a binding too.
+    pub pat: Pattern,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+impl Param {
+    /// The name of a parameter bound by a single name, `x` or `mut x`.
+    pub fn name(&self) -> Option<&Ident> {
+        match &self.pat.kind {
+            PatKind::Binding {
+                name, sub: None, ..
+            } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// `self`, `mut self`, `&self` or `&mut self`.
+    pub fn is_self(&self) -> bool {
+        self.name().is_some_and(|name| name.name == "self")
+    }
+}
+
+/// `trait Shape { type Unit; fn area(&self) -> f64; fn name(&self) -> f64 { 0.0 } }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trait {
+    pub name: Ident,
+    pub generics: Generics,
+    /// `trait Shape: Debug + Clone`
+    pub supertraits: Vec<Path>,
+    /// Functions and associated types.
+    pub items: Vec<Item>,
+}
+
+/// `impl Point { ... }` or `impl Shape for Point { ... }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impl {
+    pub generics: Generics,
+    pub of_trait: Option<Path>,
+    pub self_ty: Ty,
+    /// Functions and associated types.
+    pub items: Vec<Item>,
+}
+
+/// `type Unit;` in a trait, `type Unit = f64;` in an impl or a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TyAlias {
+    pub name: Ident,
+    pub ty: Option<Ty>,
+}
+
+/// `const MAX: u32 = 10 * 1024;` or `static NAME: &str = "oxy";`. The value is
+/// computed by [`crate::semantic::consts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Const {
+    pub name: Ident,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: Ident,
+    pub generics: Generics,
+    pub fields: Vec<FieldDef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDef {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub name: Ident,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: Ident,
+    pub generics: Generics,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub attrs: Vec<Attribute>,
+    pub name: Ident,
+    pub data: VariantData,
+    pub span: Span,
+}
+
+/// `A`, `A(i32, f64)` or `A { x: i32 }`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantData {
+    Unit,
+    Tuple(Vec<Ty>),
+    Struct(Vec<FieldDef>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ty {
+    pub id: NodeId,
+    pub kind: TyKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TyKind {
+    /// `i32`, `Shape`, `shapes::Shape`
+    Path(Path),
+    /// `&T` or `&mut T`
+    Ref { mutable: bool, inner: Box<Ty> },
+    /// `(i32, f64)`, `()` is the unit type.
+    Tuple(Vec<Ty>),
+    /// `[u8; 16]`, the length is evaluated by [`crate::semantic::consts`].
+    Array(Box<Ty>, Box<Expr>),
+    /// `[u8]`, behind a reference.
+    Slice(Box<Ty>),
+    /// `fn(i32, i32) -> bool`
+    Fn {
+        inputs: Vec<Ty>,
+        output: Option<Box<Ty>>,
+    },
+}
+
+impl Ty {
+    /// The type as written, e.g. `&mut Vec<T>`.
+    pub fn to_text(&self) -> String {
+        match &self.kind {
+            TyKind::Path(path) => path.to_text(),
+            TyKind::Ref {
+                mutable: true,
+                inner,
+            } => format!("&mut {}", inner.to_text()),
+            TyKind::Ref { inner, .. } => format!("&{}", inner.to_text()),
+            TyKind::Tuple(tys) => tuple_text(tys.iter().map(Ty::to_text).collect()),
+            TyKind::Array(inner, _) => format!("[{}; _]", inner.to_text()),
+            TyKind::Slice(inner) => format!("[{}]", inner.to_text()),
+            TyKind::Fn { inputs, output } => fn_text(
+                inputs.iter().map(Ty::to_text).collect(),
+                output.as_ref().map(|output| output.to_text()),
+            ),
+        }
+    }
+}
+
+/// `(a, b)`, with the trailing comma of a single element : `(a,)`.
+pub fn tuple_text(parts: Vec<String>) -> String {
+    match &parts[..] {
+        [single] => format!("({single},)"),
+        _ => format!("({})", parts.join(", ")),
+    }
+}
+
+/// `fn(a, b) -> c`, without the arrow for `()`.
+pub fn fn_text(inputs: Vec<String>, output: Option<String>) -> String {
+    let output = output
+        .map(|output| format!(" -> {output}"))
+        .unwrap_or_default();
+    format!("fn({}){output}", inputs.join(", "))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub id: NodeId,
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+impl Block {
+    /// The last expression of the block when it has no `;`, which is the block's value.
+    pub fn tail(&self) -> Option<&Expr> {
+        match self.stmts.last() {
+            Some(Stmt {
+                kind: StmtKind::Expr(expr),
+                ..
+            }) => Some(expr),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub attrs: Vec<Attribute>,
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let(Box<Local>),
+    /// An expression without a `;`. Only the last one gives its value to the block.
+    Expr(Expr),
+    /// An expression followed by a `;`.
+    Semi(Expr),
+}
+
+/// `let pattern: Ty = init;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub id: NodeId,
+    pub pat: Pattern,
+    pub ty: Option<Ty>,
+    pub init: Option<Expr>,
+    /// The `else` block of `let Some(x) = y else { return; };`, which must diverge.
+    pub els: Option<Block>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub id: NodeId,
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self {
+            id: NodeId::next(),
+            kind,
+            span,
+        }
+    }
+
+    /// Expressions ending with a block can be used as statements without `;`.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self.kind,
+            ExprKind::Block(_)
+                | ExprKind::If(..)
+                | ExprKind::While(..)
+                | ExprKind::ForLoop { .. }
+                | ExprKind::Loop(_)
+                | ExprKind::Match(..)
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Lit(Lit),
+    Path(Path),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// `&expr` or `&mut expr`
+    AddrOf {
+        mutable: bool,
+        expr: Box<Expr>,
+    },
+    Assign(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    MethodCall {
+        receiver: Box<Expr>,
+        method: Ident,
+        /// `T` in `value.method::<T>()`.
+        generic_args: Vec<Ty>,
+        args: Vec<Expr>,
+        /// The method called, `None` for those of other crates. Filled by
+        /// [`crate::semantic::typeck::record_callees`].
+        callee: Option<Callee>,
+    },
+    /// `point.x`, or `pair.0` with the index as the name.
+    Field(Box<Expr>, Ident),
+    /// `xs[i]`, or `xs[1..3]` for a slice.
+    Index(Box<Expr>, Box<Expr>),
+    /// `expr?`, see [`crate::semantic::propagation`].
+    Try(Box<Expr>),
+    /// `(a, b)`, `(a,)` or `()`
+    Tuple(Vec<Expr>),
+    /// `[1, 2, 3]`
+    Array(Vec<Expr>),
+    /// `[0; 16]`, the length is evaluated by [`crate::semantic::consts`].
+    Repeat(Box<Expr>, Box<Expr>),
+    /// `a..b`, `a..=b`, `a..`, `..b` or `..`
+    Range {
+        lo: Option<Box<Expr>>,
+        hi: Option<Box<Expr>>,
+        inclusive: bool,
+    },
+    /// `Point { x: 1, y }`
+    StructLit {
+        path: Path,
+        fields: Vec<FieldInit>,
+    },
+    Paren(Box<Expr>),
+    Block(Block),
+    If(Box<Expr>, Block, Option<Box<Expr>>),
+    While(Box<Expr>, Block),
+    Loop(Block),
+    /// `for pattern in iter { ... }`
+    ForLoop {
+        pat: Pattern,
+        iter: Box<Expr>,
+        body: Block,
+    },
+    Match(Box<Expr>, Vec<Arm>),
+    Closure(Box<Closure>),
+    Return(Option<Box<Expr>>),
+    Break(Option<Box<Expr>>),
+    Continue,
+    /// What is left of an expression or a statement that could not be parsed.
+    Error,
+}
+
+/// `|x, y| x + y`, `move |x: i32| -> i32 { x }` or `fun (x: i32) -> i32 { x }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub params: Vec<ClosureParam>,
+    pub output: Option<Ty>,
+    pub body: Box<Expr>,
+    pub capture: CaptureBy,
+    /// The locals of the enclosing functions used in the body, in order of first use.
+    /// Filled by [`crate::semantic::captures`].
+    pub captures: Vec<Ident>,
+}
+
+/// The method of the crate a method call calls, and how its receiver becomes `self`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callee {
+    /// The function, in an impl or in a trait.
+    pub def: NodeId,
+    /// `crate::shapes::Circle::area`, or `crate::shapes::Shape::area` for the method
+    /// of a trait.
+    pub path: String,
+    /// The number of `*` applied to the receiver.
+    pub derefs: usize,
+    /// The reference then taken of it, `Some(true)` for `&mut`.
+    pub autoref: Option<bool>,
+    /// Whether Rust calls it through [`Callee::path`] : a method of a trait on a type
+    /// of another crate, which rustc could find among the methods of the type first.
+    pub qualified: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureParam {
+    pub pat: Pattern,
+    pub ty: Option<Ty>,
+    pub span: Span,
+}
+
+/// How a closure holds what it captures : borrowed, or moved in with `move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBy {
+    Ref,
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit {
+    pub name: Ident,
+    pub expr: Expr,
+    /// `Point { x }` instead of `Point { x: x }`.
+    pub shorthand: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lit {
+    pub kind: LitKind,
+    pub span: Span,
+}
+
+impl Lit {
+    /// The literal as Rust writes it : `1.0`, `"a\"b"`, `'c'`.
+    pub fn to_text(&self) -> String {
+        match &self.kind {
+            LitKind::Int(value) => value.to_string(),
+            LitKind::Float(value) => format!("{value:?}"),
+            LitKind::Str(value) => format!("{value:?}"),
+            LitKind::Char(value) => format!("{value:?}"),
+            LitKind::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LitKind {
+    Int(u128),
+    Float(f64),
+    Str(String),
+    Char(char),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    /// `-x`
+    Neg,
+    /// `!x`
+    Not,
+    /// `*x`
+    Deref,
+}
+
+/// An operator that an `operator` declaration may overload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadedOp {
+    Binary(BinOp),
+    Unary(UnOp),
+}
+
+impl OverloadedOp {
+    /// The trait of `std::ops`, or `PartialEq`, behind the operator, and its method.
+    pub fn trait_method(&self) -> Option<(&'static str, &'static str)> {
+        Some(match self {
+            OverloadedOp::Binary(BinOp::Add) => ("Add", "add"),
+            OverloadedOp::Binary(BinOp::Sub) => ("Sub", "sub"),
+            OverloadedOp::Binary(BinOp::Mul) => ("Mul", "mul"),
+            OverloadedOp::Binary(BinOp::Div) => ("Div", "div"),
+            OverloadedOp::Binary(BinOp::Rem) => ("Rem", "rem"),
+            OverloadedOp::Binary(BinOp::Eq) => ("PartialEq", "eq"),
+            OverloadedOp::Unary(UnOp::Neg) => ("Neg", "neg"),
+            OverloadedOp::Unary(UnOp::Not) => ("Not", "not"),
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverloadedOp::Binary(op) => op.as_str(),
+            OverloadedOp::Unary(UnOp::Neg) => "-",
+            OverloadedOp::Unary(UnOp::Not) => "!",
+            OverloadedOp::Unary(UnOp::Deref) => "*",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinOp {
+    /// Binding power, higher binds tighter : `||` is 1, `*` is 5.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 3,
+            BinOp::Add | BinOp::Sub => 4,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 5,
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        self.precedence() == 3
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
+/// `pattern if guard => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub pat: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub id: NodeId,
+    pub kind: PatKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    pub fn new(kind: PatKind, span: Span) -> Self {
+        Self {
+            id: NodeId::next(),
+            kind,
+            span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatKind {
+    /// `_`
+    Wild,
+    /// `x`, `mut x` or `x @ subpattern`
+    Binding {
+        name: Ident,
+        mutable: bool,
+        sub: Option<Box<Pattern>>,
+    },
+    /// `1`, `-1`, `"text"`, `true`. Always a literal or a negated literal.
+    Lit(Box<Expr>),
+    /// `1..=5`, `'a'..='z'`, `..10`
+    Range {
+        lo: Option<Box<Expr>>,
+        hi: Option<Box<Expr>>,
+        inclusive: bool,
+    },
+    /// `(a, b)` or `(a,)`
+    Tuple(Vec<Pattern>),
+    /// `(a)`
+    Paren(Box<Pattern>),
+    /// `Shape::Circle(r)`
+    TupleStruct(Path, Vec<Pattern>),
+    /// `Shape::Rect { w, h: height, .. }`
+    Struct {
+        path: Path,
+        fields: Vec<FieldPat>,
+        rest: bool,
+    },
+    /// `Shape::Empty` or `None`
+    Path(Path),
+    /// `A | B`
+    Or(Vec<Pattern>),
+    /// `..` inside a tuple pattern.
+    Rest,
+    /// What is left of a pattern that could not be parsed.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPat {
+    pub name: Ident,
+    pub pat: Pattern,
+    /// `Point { x }` instead of `Point { x: x }`.
+    pub shorthand: bool,
+    pub span: Span,
+}
@@ -0,0 +1,545 @@
+//! Prints the tree, with spans, as an indented tree, an S-expression or JSON. The output
+//! only depends on the source, so it can be kept in snapshot tests.
+
+use std::fmt::Write;
+
+use super::ast::*;
+use crate::span::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Tree,
+    Sexpr,
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tree" => Some(Format::Tree),
+            "sexpr" => Some(Format::Sexpr),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+pub fn dump(module: &Module, format: Format) -> String {
+    let node = module_node(module);
+    let mut out = String::new();
+    match format {
+        Format::Tree => write_tree(&mut out, &node, 0),
+        Format::Sexpr => {
+            write_sexpr(&mut out, &node, 0);
+            out.push('\n');
+        }
+        Format::Json => {
+            write_json(&mut out, &node);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Every AST node is shown the same way : its kind, what it holds (a name, an
+/// operator, a literal) and its children.
+struct Node {
+    kind: &'static str,
+    value: Option<String>,
+    span: Span,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(kind: &'static str, span: Span) -> Self {
+        Self {
+            kind,
+            value: None,
+            span,
+            children: vec![],
+        }
+    }
+
+    fn value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    fn child(mut self, child: Node) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn children(mut self, children: impl IntoIterator<Item = Node>) -> Self {
+        self.children.extend(children);
+        self
+    }
+}
+
+fn mutable(mutable: bool, name: &str) -> String {
+    if mutable {
+        format!("mut {name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// The attributes come first among the children of their node.
+fn with_attributes(mut node: Node, attrs: &[Attribute]) -> Node {
+    let attrs = attrs
+        .iter()
+        .map(|attr| Node::new("Attribute", attr.span).value(attr.meta.to_text()));
+    node.children.splice(0..0, attrs);
+    node
+}
+
+fn module_node(module: &Module) -> Node {
+    Node::new("Module", module.span).children(module.items.iter().map(item_node))
+}
+
+fn item_node(item: &Item) -> Node {
+    let node = match &item.kind {
+        ItemKind::Function(function) => Node::new("Function", item.span)
+            .value(if function.is_const {
+                format!("const {}", function.name.name)
+            } else {
+                function.name.name.clone()
+            })
+            .children(generics_node(&function.generics))
+            .children(function.params.iter().map(|param| {
+                let node = Node::new("Param", param.span);
+                let node = match &param.pat.kind {
+                    PatKind::Binding {
+                        name,
+                        mutable: is_mut,
+                        sub: None,
+                    } => node.value(mutable(*is_mut, &name.name)),
+                    _ => node.child(pattern_node(&param.pat)),
+                };
+                node.child(ty_node(&param.ty))
+            }))
+            .children(
+                function
+                    .output
+                    .iter()
+                    .map(|ty| Node::new("Output", ty.span).child(ty_node(ty))),
+            )
+            .children(function.body.iter().map(block_node)),
+        ItemKind::Struct(item_struct) => Node::new("Struct", item.span)
+            .value(&item_struct.name.name)
+            .children(generics_node(&item_struct.generics))
+            .children(item_struct.fields.iter().map(field_def_node)),
+        ItemKind::Enum(item_enum) => Node::new("Enum", item.span)
+            .value(&item_enum.name.name)
+            .children(generics_node(&item_enum.generics))
+            .children(item_enum.variants.iter().map(|variant| {
+                let node = Node::new("Variant", variant.span).value(&variant.name.name);
+                let node = match &variant.data {
+                    VariantData::Unit => node,
+                    VariantData::Tuple(types) => node.children(types.iter().map(ty_node)),
+                    VariantData::Struct(fields) => node.children(fields.iter().map(field_def_node)),
+                };
+                with_attributes(node, &variant.attrs)
+            })),
+        ItemKind::Mod(item_mod) => Node::new("Mod", item.span)
+            .value(&item_mod.name.name)
+            .children(item_mod.content.iter().map(module_node)),
+        ItemKind::Use(tree) => Node::new("Use", item.span).child(use_tree_node(tree)),
+        ItemKind::Trait(item_trait) => Node::new("Trait", item.span)
+            .value(&item_trait.name.name)
+            .children(generics_node(&item_trait.generics))
+            .children(
+                item_trait
+                    .supertraits
+                    .iter()
+                    .map(|path| Node::new("Bound", path.span).value(path.to_text())),
+            )
+            .children(item_trait.items.iter().map(item_node)),
+        ItemKind::Impl(item_impl) => {
+            let node = Node::new("Impl", item.span);
+            let node = match &item_impl.of_trait {
+                Some(path) => node.value(path.to_text()),
+                None => node,
+            };
+            node.children(generics_node(&item_impl.generics))
+                .child(ty_node(&item_impl.self_ty))
+                .children(item_impl.items.iter().map(item_node))
+        }
+        ItemKind::TyAlias(alias) => Node::new("TyAlias", item.span)
+            .value(&alias.name.name)
+            .children(alias.ty.iter().map(ty_node)),
+        ItemKind::Const(item_const) => const_node("Const", item.span, item_const),
+        ItemKind::Static(item_static) => const_node("Static", item.span, item_static),
+        ItemKind::MacroDef(def) => Node::new("MacroDef", item.span).value(&def.name.name),
+        ItemKind::Error => Node::new("Error", item.span),
+    };
+    let node = with_attributes(node, &item.attrs);
+    match item.vis {
+        Visibility::Public => Node::new("Pub", item.span).child(node),
+        Visibility::Private => node,
+    }
+}
+
+fn const_node(kind: &'static str, span: Span, item: &Const) -> Node {
+    Node::new(kind, span)
+        .value(&item.name.name)
+        .child(ty_node(&item.ty))
+        .child(expr_node(&item.expr))
+}
+
+/// `Generics` with a child per parameter and per `where` predicate, when there are any.
+fn generics_node(generics: &Generics) -> Option<Node> {
+    if generics.params.is_empty() && generics.where_clause.is_empty() {
+        return None;
+    }
+    let bound = |path: &Path| Node::new("Bound", path.span).value(path.to_text());
+    let node = Node::new("Generics", generics.span)
+        .children(generics.params.iter().map(|param| {
+            Node::new("TypeParam", param.span)
+                .value(&param.name.name)
+                .children(param.bounds.iter().map(bound))
+        }))
+        .children(generics.where_clause.iter().map(|predicate| {
+            Node::new("Where", predicate.span)
+                .child(ty_node(&predicate.ty))
+                .children(predicate.bounds.iter().map(bound))
+        }));
+    Some(node)
+}
+
+fn use_tree_node(tree: &UseTree) -> Node {
+    let node = Node::new("UseTree", tree.span);
+    match &tree.kind {
+        UseTreeKind::Simple(None) => node.value(tree.prefix.to_text()),
+        UseTreeKind::Simple(Some(rename)) => {
+            node.value(format!("{} as {}", tree.prefix.to_text(), rename.name))
+        }
+        UseTreeKind::Nested(trees) => node
+            .value(format!("{}::{{}}", tree.prefix.to_text()))
+            .children(trees.iter().map(use_tree_node)),
+        UseTreeKind::Glob => node.value(format!("{}::*", tree.prefix.to_text())),
+    }
+}
+
+fn field_def_node(field: &FieldDef) -> Node {
+    let name = match field.vis {
+        Visibility::Public => format!("pub {}", field.name.name),
+        Visibility::Private => field.name.name.clone(),
+    };
+    let node = Node::new("Field", field.span)
+        .value(name)
+        .child(ty_node(&field.ty));
+    with_attributes(node, &field.attrs)
+}
+
+fn ty_node(ty: &Ty) -> Node {
+    match &ty.kind {
+        TyKind::Path(path) => Node::new("Type", ty.span).value(path.to_text()),
+        TyKind::Ref { mutable, inner } => Node::new("Ref", ty.span)
+            .value(if *mutable { "&mut" } else { "&" })
+            .child(ty_node(inner)),
+        TyKind::Tuple(tys) => Node::new("TupleType", ty.span).children(tys.iter().map(ty_node)),
+        TyKind::Array(inner, len) => Node::new("ArrayType", ty.span)
+            .child(ty_node(inner))
+            .child(expr_node(len)),
+        TyKind::Slice(inner) => Node::new("SliceType", ty.span).child(ty_node(inner)),
+        TyKind::Fn { inputs, output } => Node::new("FnType", ty.span)
+            .children(inputs.iter().map(ty_node))
+            .children(
+                output
+                    .iter()
+                    .map(|ty| Node::new("Output", ty.span).child(ty_node(ty))),
+            ),
+    }
+}
+
+fn block_node(block: &Block) -> Node {
+    Node::new("Block", block.span).children(block.stmts.iter().map(|stmt| {
+        let node = match &stmt.kind {
+            StmtKind::Let(local) => Node::new("Let", local.span)
+                .child(pattern_node(&local.pat))
+                .children(local.ty.iter().map(ty_node))
+                .children(local.init.iter().map(expr_node))
+                .children(
+                    local
+                        .els
+                        .iter()
+                        .map(|els| Node::new("Else", els.span).child(block_node(els))),
+                ),
+            StmtKind::Expr(expr) => expr_node(expr),
+            StmtKind::Semi(expr) => Node::new("Semi", stmt.span).child(expr_node(expr)),
+        };
+        with_attributes(node, &stmt.attrs)
+    }))
+}
+
+fn expr_node(expr: &Expr) -> Node {
+    let node = |kind| Node::new(kind, expr.span);
+    match &expr.kind {
+        ExprKind::Lit(lit) => node("Lit").value(lit.to_text()),
+        ExprKind::Path(path) => node("Path").value(path.to_text()),
+        ExprKind::Unary(op, operand) => node("Unary")
+            .value(match op {
+                UnOp::Neg => "-",
+                UnOp::Not => "!",
+                UnOp::Deref => "*",
+            })
+            .child(expr_node(operand)),
+        ExprKind::Binary(op, lhs, rhs) => node("Binary")
+            .value(op.as_str())
+            .child(expr_node(lhs))
+            .child(expr_node(rhs)),
+        ExprKind::AddrOf { mutable, expr } => node("AddrOf")
+            .value(if *mutable { "&mut" } else { "&" })
+            .child(expr_node(expr)),
+        ExprKind::Assign(lhs, rhs) => node("Assign").child(expr_node(lhs)).child(expr_node(rhs)),
+        ExprKind::Call(callee, args) => node("Call")
+            .child(expr_node(callee))
+            .children(args.iter().map(expr_node)),
+        ExprKind::MethodCall {
+            receiver,
+            method,
+            generic_args,
+            args,
+            ..
+        } => node("MethodCall")
+            .value(match &generic_args[..] {
+                [] => method.name.clone(),
+                tys => {
+                    let tys: Vec<_> = tys.iter().map(Ty::to_text).collect();
+                    format!("{}::<{}>", method.name, tys.join(", "))
+                }
+            })
+            .child(expr_node(receiver))
+            .children(args.iter().map(expr_node)),
+        ExprKind::Field(base, name) => node("Field").value(&name.name).child(expr_node(base)),
+        ExprKind::Index(base, index) => {
+            node("Index").child(expr_node(base)).child(expr_node(index))
+        }
+        ExprKind::Tuple(elements) => node("Tuple").children(elements.iter().map(expr_node)),
+        ExprKind::Array(elements) => node("Array").children(elements.iter().map(expr_node)),
+        ExprKind::Repeat(element, len) => node("Repeat")
+            .child(expr_node(element))
+            .child(expr_node(len)),
+        ExprKind::Range { lo, hi, inclusive } => node("Range")
+            .value(if *inclusive { "..=" } else { ".." })
+            .children(lo.iter().chain(hi).map(|bound| expr_node(bound))),
+        ExprKind::StructLit { path, fields } => {
+            node("StructLit")
+                .value(path.to_text())
+                .children(fields.iter().map(|field| {
+                    Node::new("FieldInit", field.span)
+                        .value(&field.name.name)
+                        .child(expr_node(&field.expr))
+                }))
+        }
+        ExprKind::Paren(inner) => node("Paren").child(expr_node(inner)),
+        ExprKind::Try(inner) => node("Try").child(expr_node(inner)),
+        ExprKind::Block(block) => block_node(block),
+        ExprKind::If(cond, then, otherwise) => node("If")
+            .child(expr_node(cond))
+            .child(block_node(then))
+            .children(otherwise.iter().map(|otherwise| expr_node(otherwise))),
+        ExprKind::While(cond, body) => node("While").child(expr_node(cond)).child(block_node(body)),
+        ExprKind::Loop(body) => node("Loop").child(block_node(body)),
+        ExprKind::ForLoop { pat, iter, body } => node("For")
+            .child(pattern_node(pat))
+            .child(expr_node(iter))
+            .child(block_node(body)),
+        ExprKind::Match(scrutinee, arms) => {
+            node("Match")
+                .child(expr_node(scrutinee))
+                .children(arms.iter().map(|arm| {
+                    Node::new("Arm", arm.span)
+                        .child(pattern_node(&arm.pat))
+                        .children(
+                            arm.guard.iter().map(|guard| {
+                                Node::new("Guard", guard.span).child(expr_node(guard))
+                            }),
+                        )
+                        .child(expr_node(&arm.body))
+                }))
+        }
+        ExprKind::Closure(closure) => {
+            let node = match closure.capture {
+                CaptureBy::Value => node("Closure").value("move"),
+                CaptureBy::Ref => node("Closure"),
+            };
+            node.children(closure.params.iter().map(|param| {
+                Node::new("Param", param.span)
+                    .child(pattern_node(&param.pat))
+                    .children(param.ty.iter().map(ty_node))
+            }))
+            .children(closure.output.iter().map(ty_node))
+            .child(expr_node(&closure.body))
+        }
+        ExprKind::Return(value) => {
+            node("Return").children(value.iter().map(|value| expr_node(value)))
+        }
+        ExprKind::Break(value) => {
+            node("Break").children(value.iter().map(|value| expr_node(value)))
+        }
+        ExprKind::Continue => node("Continue"),
+        ExprKind::Error => node("Error"),
+    }
+}
+
+fn pattern_node(pattern: &Pattern) -> Node {
+    let node = |kind| Node::new(kind, pattern.span);
+    match &pattern.kind {
+        PatKind::Wild => node("Wild"),
+        PatKind::Binding {
+            name,
+            mutable: is_mutable,
+            sub,
+        } => node("Binding")
+            .value(mutable(*is_mutable, &name.name))
+            .children(sub.iter().map(|sub| pattern_node(sub))),
+        PatKind::Lit(expr) => node("LitPat").child(expr_node(expr)),
+        PatKind::Range { lo, hi, inclusive } => node("RangePat")
+            .value(if *inclusive { "..=" } else { ".." })
+            .children(lo.iter().chain(hi).map(|bound| expr_node(bound))),
+        PatKind::Tuple(patterns) => node("TuplePat").children(patterns.iter().map(pattern_node)),
+        PatKind::Paren(inner) => node("ParenPat").child(pattern_node(inner)),
+        PatKind::TupleStruct(path, patterns) => node("TupleStructPat")
+            .value(path.to_text())
+            .children(patterns.iter().map(pattern_node)),
+        PatKind::Struct { path, fields, rest } => {
+            let value = if *rest {
+                format!("{} ..", path.to_text())
+            } else {
+                path.to_text()
+            };
+            node("StructPat")
+                .value(value)
+                .children(fields.iter().map(|field| {
+                    Node::new("FieldPat", field.span)
+                        .value(&field.name.name)
+                        .child(pattern_node(&field.pat))
+                }))
+        }
+        PatKind::Path(path) => node("PathPat").value(path.to_text()),
+        PatKind::Or(patterns) => node("OrPat").children(patterns.iter().map(pattern_node)),
+        PatKind::Rest => node("Rest"),
+        PatKind::Error => node("Error"),
+    }
+}
+
+fn write_tree(out: &mut String, node: &Node, depth: usize) {
+    let _ = write!(out, "{}{}", "  ".repeat(depth), node.kind);
+    if let Some(value) = &node.value {
+        let _ = write!(out, " {value}");
+    }
+    let _ = writeln!(out, " @{}..{}", node.span.start, node.span.end);
+    for child in &node.children {
+        write_tree(out, child, depth + 1);
+    }
+}
+
+/// `(Kind "value" @start..end children...)`, one node per line.
+fn write_sexpr(out: &mut String, node: &Node, depth: usize) {
+    if depth > 0 {
+        let _ = write!(out, "\n{}", "  ".repeat(depth));
+    }
+    let _ = write!(out, "({}", node.kind);
+    if let Some(value) = &node.value {
+        let _ = write!(out, " {value:?}");
+    }
+    let _ = write!(out, " @{}..{}", node.span.start, node.span.end);
+    for child in &node.children {
+        write_sexpr(out, child, depth + 1);
+    }
+    out.push(')');
+}
+
+fn write_json(out: &mut String, node: &Node) {
+    let _ = write!(out, "{{\"kind\":\"{}\"", node.kind);
+    if let Some(value) = &node.value {
+        let _ = write!(out, ",\"value\":{}", json_string(value));
+    }
+    let _ = write!(out, ",\"span\":[{},{}]", node.span.start, node.span.end);
+    if !node.children.is_empty() {
+        out.push_str(",\"children\":[");
+        for (id, child) in node.children.iter().enumerate() {
+            if id > 0 {
+                out.push(',');
+            }
+            write_json(out, child);
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dump, Format};
+    use crate::syntax::parse;
+
+    const INPUT: &str = "fn f(s: Shape) -> i32 { match s { Shape::A(x) if x > 1 => x, _ => 0 } }";
+
+    #[test]
+    fn tree() {
+        let (module, _) = parse(INPUT);
+        assert_eq!(
+            dump(&module, Format::Tree),
+            "Module @0..71
+  Function f @0..71
+    Param s @5..13
+      Type Shape @8..13
+    Output @18..21
+      Type i32 @18..21
+    Block @22..71
+      Match @24..69
+        Path s @30..31
+        Arm @34..59
+          TupleStructPat Shape::A @34..45
+            Binding x @43..44
+          Guard @49..54
+            Binary > @49..54
+              Path x @49..50
+              Lit 1 @53..54
+          Path x @58..59
+        Arm @61..67
+          Wild @61..62
+          Lit 0 @66..67
+"
+        );
+    }
+
+    #[test]
+    fn sexpr_and_json() {
+        let (module, _) = parse("struct P { x: i32 }");
+        assert_eq!(
+            dump(&module, Format::Sexpr),
+            "(Module @0..19
+  (Struct \"P\" @0..19
+    (Field \"x\" @11..17
+      (Type \"i32\" @14..17))))
+"
+        );
+        assert_eq!(
+            dump(&module, Format::Json),
+            "{\"kind\":\"Module\",\"span\":[0,19],\"children\":[{\"kind\":\"Struct\",\"value\":\"P\",\"span\":[0,19],\"children\":[{\"kind\":\"Field\",\"value\":\"x\",\"span\":[11,17],\"children\":[{\"kind\":\"Type\",\"value\":\"i32\",\"span\":[14,17]}]}]}]}\n"
+        );
+    }
+}
@@ -0,0 +1,656 @@
+//! `macro` definitions and their expansion. A macro is a list of rules, each a
+//! pattern over token trees and the tokens to write in place of the call :
+//!
+//! ```text
+//! macro square($x:expr) { $x * $x }
+//!
+//! macro max {
+//!     ($x:expr) => { $x },
+//!     ($x:expr, $($rest:expr),+) => { { let a = $x; let b = max!($($rest),+); if a > b { a } else { b } } },
+//! }
+//! ```
+//!
+//! The tokens of the definition keep their spans, marked with a fresh
+//! [`SyntaxContext`] for each call, so diagnostics can point at both the definition
+//! and the call, and [`super::hygiene`] can tell the bindings of the macro from
+//! those of the caller.
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexical::{flatten, Delimited, Lexeme, Token, TokenTree};
+use crate::span::{Expansion, Expansions, Span, SyntaxContext};
+
+use super::parser;
+
+/// The kind of syntax a `$name:kind` matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fragment {
+    Expr,
+    Ident,
+    Ty,
+    Pat,
+    Stmt,
+    Block,
+    Literal,
+    Path,
+    Item,
+    Tt,
+}
+
+impl Fragment {
+    const NAMES: &'static [(&'static str, Fragment)] = &[
+        ("expr", Fragment::Expr),
+        ("ident", Fragment::Ident),
+        ("ty", Fragment::Ty),
+        ("pat", Fragment::Pat),
+        ("stmt", Fragment::Stmt),
+        ("block", Fragment::Block),
+        ("literal", Fragment::Literal),
+        ("path", Fragment::Path),
+        ("item", Fragment::Item),
+        ("tt", Fragment::Tt),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, fragment)| *fragment)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repeat {
+    /// `*`
+    ZeroOrMore,
+    /// `+`
+    OneOrMore,
+    /// `?`
+    ZeroOrOne,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher<'input> {
+    Token(Lexeme<'input>),
+    Group {
+        open: &'input str,
+        matchers: Vec<Matcher<'input>>,
+    },
+    /// `$name:kind`
+    Fragment {
+        name: &'input str,
+        fragment: Fragment,
+    },
+    /// `$( ... ) sep? op`
+    Repeat {
+        matchers: Vec<Matcher<'input>>,
+        separator: Option<Lexeme<'input>>,
+        repeat: Repeat,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Rule<'input> {
+    matchers: Vec<Matcher<'input>>,
+    body: Vec<TokenTree<'input>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Macro<'input> {
+    pub name: &'input str,
+    rules: Vec<Rule<'input>>,
+    /// The whole definition.
+    pub span: Span,
+}
+
+/// What a fragment matched, one level of `Seq` per repetition around it.
+#[derive(Debug, Clone)]
+enum Matched<'input> {
+    Fragment(Fragment, Vec<TokenTree<'input>>),
+    Seq(Vec<Matched<'input>>),
+}
+
+type Bindings<'input> = HashMap<&'input str, Matched<'input>>;
+
+impl<'input> Macro<'input> {
+    /// Reads the definition from what follows `macro name` : either a single rule,
+    /// `(matcher) { body }`, or rules in braces, `{ (matcher) => { body }, ... }`.
+    pub fn parse(
+        name: &'input str,
+        trees: &[TokenTree<'input>],
+        span: Span,
+    ) -> Result<Self, Diagnostic> {
+        let malformed = |span| {
+            Diagnostic::error(format!("malformed definition of macro `{name}`"), span).with_note(
+                "the forms are `macro name(...) { ... }` and `macro name { (...) => { ... }, ... }`",
+            )
+        };
+        let rules = match trees {
+            [TokenTree::Delimited(matcher), TokenTree::Delimited(body)]
+                if matcher.open.text == "(" && body.open.text == "{" =>
+            {
+                vec![Rule::parse(matcher, body)?]
+            }
+            [TokenTree::Delimited(rules)] if rules.open.text == "{" => {
+                let mut parsed = vec![];
+                let mut rest = &rules.trees[..];
+                while !rest.is_empty() {
+                    let [TokenTree::Delimited(matcher), arrow, TokenTree::Delimited(body), tail @ ..] =
+                        rest
+                    else {
+                        return Err(malformed(rest[0].span()));
+                    };
+                    if !arrow.is("=>") {
+                        return Err(malformed(arrow.span()));
+                    }
+                    parsed.push(Rule::parse(matcher, body)?);
+                    rest = match tail {
+                        [separator, tail @ ..] if separator.is(",") || separator.is(";") => tail,
+                        [] => tail,
+                        [other, ..] => return Err(malformed(other.span())),
+                    };
+                }
+                parsed
+            }
+            _ => return Err(malformed(span)),
+        };
+        Ok(Self { name, rules, span })
+    }
+
+    /// The tokens written in place of the call `name!(args)`, whose span is
+    /// `call_site`. The first rule matching `args` is used.
+    pub fn expand(
+        &self,
+        args: &Delimited<'input>,
+        call_site: Span,
+        expansions: &mut Expansions,
+    ) -> Result<Vec<Lexeme<'input>>, Diagnostic> {
+        let found = self.rules.iter().find_map(|rule| {
+            let mut bindings = Bindings::new();
+            let matched = match_trees(&rule.matchers, &args.trees, &mut bindings);
+            (matched == Some(args.trees.len())).then_some((rule, bindings))
+        });
+        let Some((rule, bindings)) = found else {
+            return Err(Diagnostic::error(
+                format!("no rules of macro `{}` match this call", self.name),
+                call_site,
+            )
+            .with_label(self.span, "when calling this macro"));
+        };
+        let ctxt = expansions.add(Expansion {
+            name: self.name.to_string(),
+            call_site,
+            def_site: self.span,
+        });
+        let mut transcriber = Transcriber {
+            bindings: &bindings,
+            ctxt,
+            indices: vec![],
+        };
+        let trees = transcriber.transcribe(&rule.body)?;
+        Ok(flatten(&trees))
+    }
+}
+
+impl<'input> Rule<'input> {
+    fn parse(matcher: &Delimited<'input>, body: &Delimited<'input>) -> Result<Self, Diagnostic> {
+        Ok(Self {
+            matchers: parse_matchers(&matcher.trees)?,
+            body: body.trees.clone(),
+        })
+    }
+}
+
+fn parse_matchers<'input>(trees: &[TokenTree<'input>]) -> Result<Vec<Matcher<'input>>, Diagnostic> {
+    let mut matchers = vec![];
+    let mut rest = trees;
+    while let [tree, tail @ ..] = rest {
+        rest = tail;
+        let dollar = match tree {
+            TokenTree::Token(lexeme) if tree.is("$") => lexeme,
+            TokenTree::Token(lexeme) => {
+                matchers.push(Matcher::Token(lexeme.clone()));
+                continue;
+            }
+            TokenTree::Delimited(group) => {
+                matchers.push(Matcher::Group {
+                    open: group.open.text,
+                    matchers: parse_matchers(&group.trees)?,
+                });
+                continue;
+            }
+        };
+        match rest {
+            [TokenTree::Token(name), colon, TokenTree::Token(kind), tail @ ..] if colon.is(":") => {
+                let fragment = Fragment::from_name(kind.text).ok_or_else(|| {
+                    let names: Vec<_> = Fragment::NAMES.iter().map(|(name, _)| *name).collect();
+                    Diagnostic::error(
+                        format!("invalid fragment specifier `{}`", kind.text),
+                        kind.span,
+                    )
+                    .with_note(format!(
+                        "valid fragment specifiers are {}",
+                        names.join(", ")
+                    ))
+                })?;
+                matchers.push(Matcher::Fragment {
+                    name: name.text,
+                    fragment,
+                });
+                rest = tail;
+            }
+            [TokenTree::Token(name), ..] => {
+                return Err(Diagnostic::error(
+                    format!("missing fragment specifier for `${}`", name.text),
+                    dollar.span.to(name.span),
+                )
+                .with_note("write it as `$name:kind`, for example `$x:expr`"));
+            }
+            [TokenTree::Delimited(group), tail @ ..] if group.open.text == "(" => {
+                let (separator, repeat, tail) =
+                    parse_repeat(tail, dollar.span.to(group.close.span))?;
+                matchers.push(Matcher::Repeat {
+                    matchers: parse_matchers(&group.trees)?,
+                    separator,
+                    repeat,
+                });
+                rest = tail;
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    "expected a fragment `$name:kind` or a repetition `$( ... )*`",
+                    dollar.span,
+                ))
+            }
+        }
+    }
+    Ok(matchers)
+}
+
+/// The `sep? op` following `$( ... )`, and what comes after it.
+fn parse_repeat<'t, 'input>(
+    trees: &'t [TokenTree<'input>],
+    span: Span,
+) -> Result<(Option<Lexeme<'input>>, Repeat, &'t [TokenTree<'input>]), Diagnostic> {
+    let op = |tree: &TokenTree| {
+        [
+            ("*", Repeat::ZeroOrMore),
+            ("+", Repeat::OneOrMore),
+            ("?", Repeat::ZeroOrOne),
+        ]
+        .into_iter()
+        .find(|(text, _)| tree.is(text))
+        .map(|(_, repeat)| repeat)
+    };
+    match trees {
+        [tree, tail @ ..] if op(tree).is_some() => Ok((None, op(tree).unwrap(), tail)),
+        [TokenTree::Token(separator), tree, tail @ ..] if op(tree).is_some() => {
+            Ok((Some(separator.clone()), op(tree).unwrap(), tail))
+        }
+        _ => Err(Diagnostic::error("expected one of: `*`, `+`, or `?`", span)),
+    }
+}
+
+fn same_token(a: &Lexeme, b: &Lexeme) -> bool {
+    a.text == b.text && matches!(a.token, Token::Literal(_)) == matches!(b.token, Token::Literal(_))
+}
+
+/// Matches `matchers` against the start of `trees`, and returns how many trees they
+/// took. Repetitions are greedy.
+fn match_trees<'input>(
+    matchers: &[Matcher<'input>],
+    trees: &[TokenTree<'input>],
+    bindings: &mut Bindings<'input>,
+) -> Option<usize> {
+    let mut pos = 0;
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(expected) => match trees.get(pos) {
+                Some(TokenTree::Token(found)) if same_token(found, expected) => pos += 1,
+                _ => return None,
+            },
+            Matcher::Group { open, matchers } => match trees.get(pos) {
+                Some(TokenTree::Delimited(group)) if group.open.text == *open => {
+                    if match_trees(matchers, &group.trees, bindings)? != group.trees.len() {
+                        return None;
+                    }
+                    pos += 1;
+                }
+                _ => return None,
+            },
+            Matcher::Fragment { name, fragment } => {
+                let len = match_fragment(*fragment, &trees[pos..])?;
+                bindings.insert(
+                    name,
+                    Matched::Fragment(*fragment, trees[pos..pos + len].to_vec()),
+                );
+                pos += len;
+            }
+            Matcher::Repeat {
+                matchers,
+                separator,
+                repeat,
+            } => {
+                let mut iterations = vec![];
+                loop {
+                    let mut start = pos;
+                    if let (Some(separator), false) = (separator, iterations.is_empty()) {
+                        match trees.get(pos) {
+                            Some(TokenTree::Token(found)) if same_token(found, separator) => {
+                                start += 1
+                            }
+                            _ => break,
+                        }
+                    }
+                    let mut found = Bindings::new();
+                    let Some(len) = match_trees(matchers, &trees[start..], &mut found) else {
+                        break;
+                    };
+                    // A repetition that takes nothing would match forever.
+                    if start + len == pos {
+                        break;
+                    }
+                    pos = start + len;
+                    iterations.push(found);
+                    if *repeat == Repeat::ZeroOrOne {
+                        break;
+                    }
+                }
+                if *repeat == Repeat::OneOrMore && iterations.is_empty() {
+                    return None;
+                }
+                for name in fragment_names(matchers) {
+                    let seq = iterations
+                        .iter_mut()
+                        .filter_map(|found| found.remove(name))
+                        .collect();
+                    bindings.insert(name, Matched::Seq(seq));
+                }
+            }
+        }
+    }
+    Some(pos)
+}
+
+fn fragment_names<'input>(matchers: &[Matcher<'input>]) -> Vec<&'input str> {
+    let mut names = vec![];
+    for matcher in matchers {
+        match matcher {
+            Matcher::Token(_) => {}
+            Matcher::Fragment { name, .. } => names.push(*name),
+            Matcher::Group { matchers, .. } | Matcher::Repeat { matchers, .. } => {
+                names.extend(fragment_names(matchers))
+            }
+        }
+    }
+    names
+}
+
+/// How many trees at the start of `trees` make one `fragment`.
+fn match_fragment(fragment: Fragment, trees: &[TokenTree]) -> Option<usize> {
+    let first = trees.first()?;
+    match fragment {
+        Fragment::Tt => return Some(1),
+        Fragment::Ident => {
+            return matches!(
+                first,
+                TokenTree::Token(Lexeme {
+                    token: Token::Identifier(..),
+                    ..
+                })
+            )
+            .then_some(1)
+        }
+        Fragment::Literal => {
+            let literal = matches!(
+                first,
+                TokenTree::Token(Lexeme {
+                    token: Token::Literal(_),
+                    ..
+                })
+            ) || first.is("true")
+                || first.is("false");
+            return literal.then_some(1);
+        }
+        _ => {}
+    }
+    // The parser tells how many lexemes the fragment takes, which must end a tree.
+    let lexemes = parser::fragment_len(fragment, flatten(trees))?;
+    let mut taken = 0;
+    for (count, tree) in trees.iter().enumerate() {
+        if taken == lexemes {
+            return Some(count);
+        }
+        taken += flatten(std::slice::from_ref(tree)).len();
+        if taken > lexemes {
+            return None;
+        }
+    }
+    (taken == lexemes).then_some(trees.len())
+}
+
+struct Transcriber<'b, 'input> {
+    bindings: &'b Bindings<'input>,
+    ctxt: SyntaxContext,
+    /// The current iteration of each repetition being written, outermost first.
+    indices: Vec<usize>,
+}
+
+impl<'input> Transcriber<'_, 'input> {
+    /// A token of the definition, marked as written by this expansion.
+    fn mark(&self, lexeme: &Lexeme<'input>) -> Lexeme<'input> {
+        Lexeme {
+            span: lexeme.span.with_ctxt(self.ctxt),
+            ..lexeme.clone()
+        }
+    }
+
+    /// What `name` stands for in the current iteration.
+    fn lookup(&self, name: &str) -> Option<&Matched<'input>> {
+        let mut matched = self.bindings.get(name)?;
+        for &index in &self.indices {
+            match matched {
+                Matched::Seq(seq) => matched = seq.get(index)?,
+                Matched::Fragment(..) => break,
+            }
+        }
+        Some(matched)
+    }
+
+    fn transcribe(
+        &mut self,
+        body: &[TokenTree<'input>],
+    ) -> Result<Vec<TokenTree<'input>>, Diagnostic> {
+        let mut out = vec![];
+        let mut rest = body;
+        while let [tree, tail @ ..] = rest {
+            rest = tail;
+            let dollar = match tree {
+                TokenTree::Token(lexeme) if tree.is("$") => lexeme,
+                TokenTree::Token(lexeme) => {
+                    out.push(TokenTree::Token(self.mark(lexeme)));
+                    continue;
+                }
+                TokenTree::Delimited(group) => {
+                    out.push(TokenTree::Delimited(Delimited {
+                        open: self.mark(&group.open),
+                        trees: self.transcribe(&group.trees)?,
+                        close: self.mark(&group.close),
+                    }));
+                    continue;
+                }
+            };
+            match rest {
+                [TokenTree::Token(name), tail @ ..] => {
+                    rest = tail;
+                    let span = dollar.span.to(name.span);
+                    match self.lookup(name.text) {
+                        Some(Matched::Fragment(fragment, trees)) => {
+                            out.extend(substitute(*fragment, trees))
+                        }
+                        Some(Matched::Seq(_)) => {
+                            return Err(Diagnostic::error(
+                                format!(
+                                    "variable `{}` is still repeating at this depth",
+                                    name.text
+                                ),
+                                span,
+                            ))
+                        }
+                        None => {
+                            return Err(Diagnostic::error(
+                                format!("unknown macro variable `{}`", name.text),
+                                span,
+                            ))
+                        }
+                    }
+                }
+                [TokenTree::Delimited(group), tail @ ..] if group.open.text == "(" => {
+                    let span = dollar.span.to(group.close.span);
+                    let (separator, _, tail) = parse_repeat(tail, span)?;
+                    rest = tail;
+                    let count = self.repetitions(&group.trees, span)?;
+                    for index in 0..count {
+                        if let (Some(separator), true) = (&separator, index > 0) {
+                            out.push(TokenTree::Token(self.mark(separator)));
+                        }
+                        self.indices.push(index);
+                        let trees = self.transcribe(&group.trees);
+                        self.indices.pop();
+                        out.extend(trees?);
+                    }
+                }
+                _ => out.push(TokenTree::Token(self.mark(dollar))),
+            }
+        }
+        Ok(out)
+    }
+
+    /// How many times `$( body )` repeats : as many times as the variables it uses
+    /// that still repeat at this depth, which must agree.
+    fn repetitions(&self, body: &[TokenTree<'input>], span: Span) -> Result<usize, Diagnostic> {
+        let mut counts: Vec<(&str, usize)> = vec![];
+        for name in variables(body) {
+            if let Some(Matched::Seq(seq)) = self.lookup(name) {
+                counts.push((name, seq.len()));
+            }
+        }
+        let Some(&(first, count)) = counts.first() else {
+            return Err(Diagnostic::error(
+                "attempted to repeat an expression containing no syntax variables matched as repeating at this depth",
+                span,
+            ));
+        };
+        if let Some((other, other_count)) = counts.iter().find(|(_, n)| *n != count) {
+            return Err(Diagnostic::error(
+                format!(
+                    "meta-variable `{first}` repeats {count} times, but `{other}` repeats {other_count} times"
+                ),
+                span,
+            ));
+        }
+        Ok(count)
+    }
+}
+
+/// The `$name`s used in `body`.
+fn variables<'input>(body: &[TokenTree<'input>]) -> Vec<&'input str> {
+    let mut names = vec![];
+    for (i, tree) in body.iter().enumerate() {
+        match (tree, body.get(i + 1)) {
+            (dollar, Some(TokenTree::Token(name))) if dollar.is("$") => names.push(name.text),
+            (TokenTree::Delimited(group), _) => names.extend(variables(&group.trees)),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The trees a fragment is replaced with. An expression of several tokens is put
+/// in parentheses, so `$x * 2` keeps `$x` whole as it is in Rust. Their spans are
+/// empty, which tells the parser they were not written : they leave no `Paren`.
+fn substitute<'input>(fragment: Fragment, trees: &[TokenTree<'input>]) -> Vec<TokenTree<'input>> {
+    match (fragment, trees) {
+        (Fragment::Expr, [_, _, ..]) => {
+            let (first, last) = (trees[0].span(), trees[trees.len() - 1].span());
+            let paren = |text: &'static str, span: Span| Lexeme {
+                span,
+                ..crate::lexical::lex(text, 0)[0].clone()
+            };
+            vec![TokenTree::Delimited(Delimited {
+                open: paren(
+                    "(",
+                    Span::new(first.start, first.start).with_ctxt(first.ctxt),
+                ),
+                trees: trees.to_vec(),
+                close: paren(")", Span::new(last.end, last.end).with_ctxt(last.ctxt)),
+            })]
+        }
+        _ => trees.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::rust::module_source;
+    use crate::syntax::ast::*;
+    use crate::syntax::parse;
+    use crate::syntax::parser::Parser;
+
+    #[test]
+    fn expands_items_statements_and_expressions() {
+        let source = "macro square($x:expr) { $x * $x }
+            macro sum {
+                () => { 0 },
+                ($x:expr $(, $rest:expr)*) => { $x + sum!($($rest),*) },
+            }
+            macro unit_structs($($name:ident),+) { $(struct $name;)+ }
+            unit_structs!(A, B);
+            fn main() {
+                let a = 2 * square!(1 + 1);
+                let b = sum![1, 2, 3];
+            }";
+        let mut parser = Parser::new(source, 0);
+        let module = parser.parse_module();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let expansions = parser.take_expansions();
+        let text = module_source(&module);
+        assert!(text.contains("struct A;\n\nstruct B;"), "{text}");
+        assert!(text.contains("let a = 2 * ((1 + 1) * (1 + 1));"), "{text}");
+        assert!(text.contains("let b = 1 + (2 + (3 + 0));"), "{text}");
+        // Code written by the macro points into its definition, and back to the call.
+        let struct_a = &module.items[3];
+        assert!(matches!(struct_a.kind, ItemKind::Struct(_)), "{struct_a:?}");
+        let span = struct_a.span;
+        assert_eq!(&source[span.start..span.end], "struct $name;");
+        let expansion = expansions.get(span.ctxt).unwrap();
+        assert_eq!(expansion.name, "unit_structs");
+        let call = expansion.call_site;
+        assert_eq!(&source[call.start..call.end], "unit_structs!(A, B)");
+    }
+
+    #[test]
+    fn reports_bad_definitions_and_calls() {
+        let (_, errors) = parse(
+            "macro a($x:expression) { $x }
+            macro b($($x:expr),* ; $($y:expr),*) { $($x + $y),* }
+            macro c($x:expr) { $y }
+            fn main() {
+                b!(1, 2; 3);
+                c!(1);
+                a!(1);
+            }",
+        );
+        let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "invalid fragment specifier `expression`",
+                "meta-variable `x` repeats 2 times, but `y` repeats 1 times",
+                "unknown macro variable `y`",
+                "cannot find macro `a` in this scope",
+            ]
+        );
+    }
+}
@@ -0,0 +1,536 @@
+//! Rebuilds the tree by value. Each `fold_*` method rebuilds its node from the folded
+//! children through the matching `walk_*` function, so a pass only overrides the
+//! nodes it replaces, e.g. to desugar them.
+
+use super::ast::*;
+
+pub trait Fold: Sized {
+    fn fold_module(&mut self, module: Module) -> Module {
+        walk_module(self, module)
+    }
+    fn fold_item(&mut self, item: Item) -> Item {
+        walk_item(self, item)
+    }
+    fn fold_mod(&mut self, item: Mod) -> Mod {
+        walk_mod(self, item)
+    }
+    fn fold_use_tree(&mut self, tree: UseTree) -> UseTree {
+        walk_use_tree(self, tree)
+    }
+    fn fold_trait(&mut self, item: Trait) -> Trait {
+        walk_trait(self, item)
+    }
+    fn fold_impl(&mut self, item: Impl) -> Impl {
+        walk_impl(self, item)
+    }
+    fn fold_ty_alias(&mut self, item: TyAlias) -> TyAlias {
+        walk_ty_alias(self, item)
+    }
+    fn fold_const(&mut self, item: Const) -> Const {
+        walk_const(self, item)
+    }
+    fn fold_generics(&mut self, generics: Generics) -> Generics {
+        walk_generics(self, generics)
+    }
+    fn fold_function(&mut self, function: Function) -> Function {
+        walk_function(self, function)
+    }
+    fn fold_param(&mut self, param: Param) -> Param {
+        walk_param(self, param)
+    }
+    fn fold_struct(&mut self, item: Struct) -> Struct {
+        walk_struct(self, item)
+    }
+    fn fold_enum(&mut self, item: Enum) -> Enum {
+        walk_enum(self, item)
+    }
+    fn fold_variant(&mut self, variant: Variant) -> Variant {
+        walk_variant(self, variant)
+    }
+    fn fold_field_def(&mut self, field: FieldDef) -> FieldDef {
+        walk_field_def(self, field)
+    }
+    fn fold_ty(&mut self, ty: Ty) -> Ty {
+        walk_ty(self, ty)
+    }
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block(self, block)
+    }
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        walk_stmt(self, stmt)
+    }
+    fn fold_local(&mut self, local: Local) -> Local {
+        walk_local(self, local)
+    }
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        walk_expr(self, expr)
+    }
+    fn fold_closure(&mut self, closure: Closure) -> Closure {
+        walk_closure(self, closure)
+    }
+    fn fold_arm(&mut self, arm: Arm) -> Arm {
+        walk_arm(self, arm)
+    }
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        walk_pattern(self, pattern)
+    }
+    fn fold_path(&mut self, path: Path) -> Path {
+        walk_path(self, path)
+    }
+    fn fold_attribute(&mut self, attr: Attribute) -> Attribute {
+        attr
+    }
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        ident
+    }
+    fn fold_lit(&mut self, lit: Lit) -> Lit {
+        lit
+    }
+}
+
+fn fold_all<T>(nodes: Vec<T>, mut fold: impl FnMut(T) -> T) -> Vec<T> {
+    nodes.into_iter().map(&mut fold).collect()
+}
+
+fn fold_box<F: Fold>(folder: &mut F, expr: Expr) -> Box<Expr> {
+    Box::new(folder.fold_expr(expr))
+}
+
+pub fn walk_module<F: Fold>(folder: &mut F, module: Module) -> Module {
+    Module {
+        items: fold_all(module.items, |item| folder.fold_item(item)),
+        ..module
+    }
+}
+
+pub fn walk_item<F: Fold>(folder: &mut F, item: Item) -> Item {
+    let kind = match item.kind {
+        ItemKind::Function(function) => ItemKind::Function(folder.fold_function(function)),
+        ItemKind::Struct(item) => ItemKind::Struct(folder.fold_struct(item)),
+        ItemKind::Enum(item) => ItemKind::Enum(folder.fold_enum(item)),
+        ItemKind::Mod(item) => ItemKind::Mod(folder.fold_mod(item)),
+        ItemKind::Use(tree) => ItemKind::Use(folder.fold_use_tree(tree)),
+        ItemKind::Trait(item) => ItemKind::Trait(folder.fold_trait(item)),
+        ItemKind::Impl(item) => ItemKind::Impl(folder.fold_impl(item)),
+        ItemKind::TyAlias(item) => ItemKind::TyAlias(folder.fold_ty_alias(item)),
+        ItemKind::Const(item) => ItemKind::Const(folder.fold_const(item)),
+        ItemKind::Static(item) => ItemKind::Static(folder.fold_const(item)),
+        ItemKind::MacroDef(def) => ItemKind::MacroDef(MacroDef {
+            name: folder.fold_ident(def.name),
+        }),
+        ItemKind::Error => ItemKind::Error,
+    };
+    Item {
+        attrs: fold_all(item.attrs, |attr| folder.fold_attribute(attr)),
+        kind,
+        ..item
+    }
+}
+
+pub fn walk_trait<F: Fold>(folder: &mut F, item: Trait) -> Trait {
+    Trait {
+        name: folder.fold_ident(item.name),
+        generics: folder.fold_generics(item.generics),
+        supertraits: fold_all(item.supertraits, |path| folder.fold_path(path)),
+        items: fold_all(item.items, |item| folder.fold_item(item)),
+    }
+}
+
+pub fn walk_impl<F: Fold>(folder: &mut F, item: Impl) -> Impl {
+    Impl {
+        generics: folder.fold_generics(item.generics),
+        of_trait: item.of_trait.map(|path| folder.fold_path(path)),
+        self_ty: folder.fold_ty(item.self_ty),
+        items: fold_all(item.items, |item| folder.fold_item(item)),
+    }
+}
+
+pub fn walk_ty_alias<F: Fold>(folder: &mut F, item: TyAlias) -> TyAlias {
+    TyAlias {
+        name: folder.fold_ident(item.name),
+        ty: item.ty.map(|ty| folder.fold_ty(ty)),
+    }
+}
+
+pub fn walk_const<F: Fold>(folder: &mut F, item: Const) -> Const {
+    Const {
+        name: folder.fold_ident(item.name),
+        ty: folder.fold_ty(item.ty),
+        expr: folder.fold_expr(item.expr),
+    }
+}
+
+pub fn walk_mod<F: Fold>(folder: &mut F, item: Mod) -> Mod {
+    Mod {
+        name: folder.fold_ident(item.name),
+        content: item.content.map(|content| folder.fold_module(content)),
+        ..item
+    }
+}
+
+pub fn walk_use_tree<F: Fold>(folder: &mut F, tree: UseTree) -> UseTree {
+    let kind = match tree.kind {
+        UseTreeKind::Simple(rename) => {
+            UseTreeKind::Simple(rename.map(|rename| folder.fold_ident(rename)))
+        }
+        UseTreeKind::Nested(trees) => {
+            UseTreeKind::Nested(fold_all(trees, |tree| folder.fold_use_tree(tree)))
+        }
+        UseTreeKind::Glob => UseTreeKind::Glob,
+    };
+    UseTree {
+        prefix: folder.fold_path(tree.prefix),
+        kind,
+        ..tree
+    }
+}
+
+pub fn walk_generics<F: Fold>(folder: &mut F, generics: Generics) -> Generics {
+    let params = fold_all(generics.params, |param| GenericParam {
+        name: folder.fold_ident(param.name),
+        bounds: fold_all(param.bounds, |path| folder.fold_path(path)),
+        ..param
+    });
+    let where_clause = fold_all(generics.where_clause, |predicate| WherePredicate {
+        ty: folder.fold_ty(predicate.ty),
+        bounds: fold_all(predicate.bounds, |path| folder.fold_path(path)),
+        ..predicate
+    });
+    Generics {
+        params,
+        where_clause,
+        ..generics
+    }
+}
+
+pub fn walk_function<F: Fold>(folder: &mut F, function: Function) -> Function {
+    Function {
+        name: folder.fold_ident(function.name),
+        generics: folder.fold_generics(function.generics),
+        params: fold_all(function.params, |param| folder.fold_param(param)),
+        output: function.output.map(|ty| folder.fold_ty(ty)),
+        body: function.body.map(|body| folder.fold_block(body)),
+        operator: function.operator,
+        is_const: function.is_const,
+    }
+}
+
+pub fn walk_param<F: Fold>(folder: &mut F, param: Param) -> Param {
+    Param {
+        pat: folder.fold_pattern(param.pat),
+        ty: folder.fold_ty(param.ty),
+        ..param
+    }
+}
+
+pub fn walk_struct<F: Fold>(folder: &mut F, item: Struct) -> Struct {
+    Struct {
+        name: folder.fold_ident(item.name),
+        generics: folder.fold_generics(item.generics),
+        fields: fold_all(item.fields, |field| folder.fold_field_def(field)),
+    }
+}
+
+pub fn walk_enum<F: Fold>(folder: &mut F, item: Enum) -> Enum {
+    Enum {
+        name: folder.fold_ident(item.name),
+        generics: folder.fold_generics(item.generics),
+        variants: fold_all(item.variants, |variant| folder.fold_variant(variant)),
+    }
+}
+
+pub fn walk_variant<F: Fold>(folder: &mut F, variant: Variant) -> Variant {
+    let data = match variant.data {
+        VariantData::Unit => VariantData::Unit,
+        VariantData::Tuple(types) => VariantData::Tuple(fold_all(types, |ty| folder.fold_ty(ty))),
+        VariantData::Struct(fields) => {
+            VariantData::Struct(fold_all(fields, |field| folder.fold_field_def(field)))
+        }
+    };
+    Variant {
+        attrs: fold_all(variant.attrs, |attr| folder.fold_attribute(attr)),
+        name: folder.fold_ident(variant.name),
+        data,
+        ..variant
+    }
+}
+
+pub fn walk_field_def<F: Fold>(folder: &mut F, field: FieldDef) -> FieldDef {
+    FieldDef {
+        attrs: fold_all(field.attrs, |attr| folder.fold_attribute(attr)),
+        name: folder.fold_ident(field.name),
+        ty: folder.fold_ty(field.ty),
+        ..field
+    }
+}
+
+pub fn walk_ty<F: Fold>(folder: &mut F, ty: Ty) -> Ty {
+    let kind = match ty.kind {
+        TyKind::Path(path) => TyKind::Path(folder.fold_path(path)),
+        TyKind::Ref { mutable, inner } => TyKind::Ref {
+            mutable,
+            inner: Box::new(folder.fold_ty(*inner)),
+        },
+        TyKind::Tuple(tys) => TyKind::Tuple(fold_all(tys, |ty| folder.fold_ty(ty))),
+        TyKind::Array(inner, len) => {
+            TyKind::Array(Box::new(folder.fold_ty(*inner)), fold_box(folder, *len))
+        }
+        TyKind::Slice(inner) => TyKind::Slice(Box::new(folder.fold_ty(*inner))),
+        TyKind::Fn { inputs, output } => TyKind::Fn {
+            inputs: fold_all(inputs, |ty| folder.fold_ty(ty)),
+            output: output.map(|output| Box::new(folder.fold_ty(*output))),
+        },
+    };
+    Ty { kind, ..ty }
+}
+
+pub fn walk_block<F: Fold>(folder: &mut F, block: Block) -> Block {
+    Block {
+        stmts: fold_all(block.stmts, |stmt| folder.fold_stmt(stmt)),
+        ..block
+    }
+}
+
+pub fn walk_stmt<F: Fold>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let kind = match stmt.kind {
+        StmtKind::Let(local) => StmtKind::Let(Box::new(folder.fold_local(*local))),
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(expr)),
+        StmtKind::Semi(expr) => StmtKind::Semi(folder.fold_expr(expr)),
+    };
+    Stmt {
+        attrs: fold_all(stmt.attrs, |attr| folder.fold_attribute(attr)),
+        kind,
+        ..stmt
+    }
+}
+
+pub fn walk_local<F: Fold>(folder: &mut F, local: Local) -> Local {
+    let init = local.init.map(|init| folder.fold_expr(init));
+    let els = local.els.map(|els| folder.fold_block(els));
+    Local {
+        pat: folder.fold_pattern(local.pat),
+        ty: local.ty.map(|ty| folder.fold_ty(ty)),
+        init,
+        els,
+        ..local
+    }
+}
+
+pub fn walk_expr<F: Fold>(folder: &mut F, expr: Expr) -> Expr {
+    let kind = match expr.kind {
+        ExprKind::Lit(lit) => ExprKind::Lit(folder.fold_lit(lit)),
+        ExprKind::Path(path) => ExprKind::Path(folder.fold_path(path)),
+        ExprKind::Unary(op, operand) => ExprKind::Unary(op, fold_box(folder, *operand)),
+        ExprKind::Binary(op, lhs, rhs) => {
+            ExprKind::Binary(op, fold_box(folder, *lhs), fold_box(folder, *rhs))
+        }
+        ExprKind::AddrOf { mutable, expr } => ExprKind::AddrOf {
+            mutable,
+            expr: fold_box(folder, *expr),
+        },
+        ExprKind::Assign(lhs, rhs) => {
+            ExprKind::Assign(fold_box(folder, *lhs), fold_box(folder, *rhs))
+        }
+        ExprKind::Call(callee, args) => ExprKind::Call(
+            fold_box(folder, *callee),
+            fold_all(args, |arg| folder.fold_expr(arg)),
+        ),
+        ExprKind::MethodCall {
+            receiver,
+            method,
+            generic_args,
+            args,
+            callee,
+        } => ExprKind::MethodCall {
+            receiver: fold_box(folder, *receiver),
+            method: folder.fold_ident(method),
+            generic_args: fold_all(generic_args, |ty| folder.fold_ty(ty)),
+            args: fold_all(args, |arg| folder.fold_expr(arg)),
+            callee,
+        },
+        ExprKind::Field(base, name) => {
+            ExprKind::Field(fold_box(folder, *base), folder.fold_ident(name))
+        }
+        ExprKind::Index(base, index) => {
+            ExprKind::Index(fold_box(folder, *base), fold_box(folder, *index))
+        }
+        ExprKind::Tuple(elements) => {
+            ExprKind::Tuple(fold_all(elements, |element| folder.fold_expr(element)))
+        }
+        ExprKind::Array(elements) => {
+            ExprKind::Array(fold_all(elements, |element| folder.fold_expr(element)))
+        }
+        ExprKind::Repeat(element, len) => {
+            ExprKind::Repeat(fold_box(folder, *element), fold_box(folder, *len))
+        }
+        ExprKind::Range { lo, hi, inclusive } => ExprKind::Range {
+            lo: lo.map(|lo| fold_box(folder, *lo)),
+            hi: hi.map(|hi| fold_box(folder, *hi)),
+            inclusive,
+        },
+        ExprKind::StructLit { path, fields } => ExprKind::StructLit {
+            path: folder.fold_path(path),
+            fields: fold_all(fields, |field| FieldInit {
+                name: folder.fold_ident(field.name),
+                expr: folder.fold_expr(field.expr),
+                ..field
+            }),
+        },
+        ExprKind::Paren(inner) => ExprKind::Paren(fold_box(folder, *inner)),
+        ExprKind::Try(inner) => ExprKind::Try(fold_box(folder, *inner)),
+        ExprKind::Block(block) => ExprKind::Block(folder.fold_block(block)),
+        ExprKind::If(cond, then, otherwise) => ExprKind::If(
+            fold_box(folder, *cond),
+            folder.fold_block(then),
+            otherwise.map(|otherwise| fold_box(folder, *otherwise)),
+        ),
+        ExprKind::While(cond, body) => {
+            ExprKind::While(fold_box(folder, *cond), folder.fold_block(body))
+        }
+        ExprKind::Loop(body) => ExprKind::Loop(folder.fold_block(body)),
+        ExprKind::ForLoop { pat, iter, body } => {
+            let iter = fold_box(folder, *iter);
+            ExprKind::ForLoop {
+                pat: folder.fold_pattern(pat),
+                iter,
+                body: folder.fold_block(body),
+            }
+        }
+        ExprKind::Match(scrutinee, arms) => ExprKind::Match(
+            fold_box(folder, *scrutinee),
+            fold_all(arms, |arm| folder.fold_arm(arm)),
+        ),
+        ExprKind::Closure(closure) => ExprKind::Closure(Box::new(folder.fold_closure(*closure))),
+        ExprKind::Return(value) => ExprKind::Return(value.map(|value| fold_box(folder, *value))),
+        ExprKind::Break(value) => ExprKind::Break(value.map(|value| fold_box(folder, *value))),
+        ExprKind::Continue => ExprKind::Continue,
+        ExprKind::Error => ExprKind::Error,
+    };
+    Expr { kind, ..expr }
+}
+
+pub fn walk_closure<F: Fold>(folder: &mut F, closure: Closure) -> Closure {
+    let params = fold_all(closure.params, |param| ClosureParam {
+        pat: folder.fold_pattern(param.pat),
+        ty: param.ty.map(|ty| folder.fold_ty(ty)),
+        ..param
+    });
+    Closure {
+        params,
+        output: closure.output.map(|output| folder.fold_ty(output)),
+        body: fold_box(folder, *closure.body),
+        ..closure
+    }
+}
+
+pub fn walk_arm<F: Fold>(folder: &mut F, arm: Arm) -> Arm {
+    Arm {
+        pat: folder.fold_pattern(arm.pat),
+        guard: arm.guard.map(|guard| folder.fold_expr(guard)),
+        body: folder.fold_expr(arm.body),
+        ..arm
+    }
+}
+
+pub fn walk_pattern<F: Fold>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let kind = match pattern.kind {
+        PatKind::Binding { name, mutable, sub } => PatKind::Binding {
+            name: folder.fold_ident(name),
+            mutable,
+            sub: sub.map(|sub| Box::new(folder.fold_pattern(*sub))),
+        },
+        PatKind::Lit(expr) => PatKind::Lit(fold_box(folder, *expr)),
+        PatKind::Range { lo, hi, inclusive } => PatKind::Range {
+            lo: lo.map(|lo| fold_box(folder, *lo)),
+            hi: hi.map(|hi| fold_box(folder, *hi)),
+            inclusive,
+        },
+        PatKind::Tuple(patterns) => {
+            PatKind::Tuple(fold_all(patterns, |pattern| folder.fold_pattern(pattern)))
+        }
+        PatKind::Paren(inner) => PatKind::Paren(Box::new(folder.fold_pattern(*inner))),
+        PatKind::TupleStruct(path, patterns) => PatKind::TupleStruct(
+            folder.fold_path(path),
+            fold_all(patterns, |pattern| folder.fold_pattern(pattern)),
+        ),
+        PatKind::Struct { path, fields, rest } => PatKind::Struct {
+            path: folder.fold_path(path),
+            fields: fold_all(fields, |field| FieldPat {
+                name: folder.fold_ident(field.name),
+                pat: folder.fold_pattern(field.pat),
+                ..field
+            }),
+            rest,
+        },
+        PatKind::Path(path) => PatKind::Path(folder.fold_path(path)),
+        PatKind::Or(patterns) => {
+            PatKind::Or(fold_all(patterns, |pattern| folder.fold_pattern(pattern)))
+        }
+        kind @ (PatKind::Wild | PatKind::Rest | PatKind::Error) => kind,
+    };
+    Pattern { kind, ..pattern }
+}
+
+pub fn walk_path<F: Fold>(folder: &mut F, path: Path) -> Path {
+    Path {
+        segments: fold_all(path.segments, |segment| PathSegment {
+            ident: folder.fold_ident(segment.ident),
+            args: fold_all(segment.args, |ty| folder.fold_ty(ty)),
+            parenthesized: segment.parenthesized,
+            output: segment.output.map(|ty| Box::new(folder.fold_ty(*ty))),
+        }),
+        ..path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{walk_expr, Fold};
+    use crate::syntax::{ast::*, parse};
+
+    /// Replaces every `-x` by `0 - x`.
+    struct NoNeg;
+
+    impl Fold for NoNeg {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            let expr = walk_expr(self, expr);
+            match expr.kind {
+                ExprKind::Unary(UnOp::Neg, operand) => {
+                    let zero = Lit {
+                        kind: LitKind::Int(0),
+                        span: expr.span,
+                    };
+                    let zero = Expr::new(ExprKind::Lit(zero), expr.span);
+                    Expr {
+                        kind: ExprKind::Binary(BinOp::Sub, Box::new(zero), operand),
+                        ..expr
+                    }
+                }
+                kind => Expr { kind, ..expr },
+            }
+        }
+    }
+
+    #[test]
+    fn rebuilds_changed_nodes() {
+        let (module, _) = parse("fn main() { let a = -(-1); }");
+        let module = NoNeg.fold_module(module);
+        let ItemKind::Function(main) = &module.items[0].kind else {
+            panic!("expected a function");
+        };
+        let StmtKind::Let(local) = &main.body.as_ref().unwrap().stmts[0].kind else {
+            panic!("expected a let");
+        };
+        let Some(Expr {
+            kind: ExprKind::Binary(BinOp::Sub, _, inner),
+            ..
+        }) = &local.init
+        else {
+            panic!("expected a substraction");
+        };
+        let ExprKind::Paren(inner) = &inner.kind else {
+            panic!("expected parentheses");
+        };
+        assert!(matches!(inner.kind, ExprKind::Binary(BinOp::Sub, ..)));
+    }
+}
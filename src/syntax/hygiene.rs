@@ -0,0 +1,241 @@
+//! Hygiene : the local variables a macro introduces are renamed, so they can neither
+//! capture nor shadow the variables of the code calling the macro.
+//!
+//! A binding written by an expansion only binds the names written by the same
+//! expansion, told apart by the [`SyntaxContext`] of their spans. It gets a name
+//! used nowhere else in the crate, and so do its uses, which keep the written one
+//! for the messages. A name the expansion writes without binding it does not see
+//! the locals of the caller either : it gets a name nothing binds, and is reported
+//! as not found. Names starting with an uppercase letter are taken for unit
+//! variants and are left alone, as are items : a macro may define functions or
+//! types for its caller.
+
+use std::collections::HashSet;
+
+use super::ast::*;
+use super::visit::Visitor;
+use super::visit_mut::{self, VisitorMut};
+use crate::span::SyntaxContext;
+
+pub fn rename_bindings(module: &mut Module) {
+    let mut names = Names::default();
+    names.visit_module(module);
+    let mut renamer = Renamer {
+        used: names.0,
+        scopes: vec![vec![]],
+    };
+    renamer.visit_module(module);
+}
+
+/// Every name written in the crate.
+#[derive(Default)]
+struct Names(HashSet<String>);
+
+impl<'ast> Visitor<'ast> for Names {
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        self.0.insert(ident.name.clone());
+    }
+}
+
+struct Renamer {
+    used: HashSet<String>,
+    /// `(name, context, new name)` of each binding, innermost scope last. Those
+    /// written outside of expansions keep their name.
+    scopes: Vec<Vec<(String, SyntaxContext, String)>>,
+}
+
+impl Renamer {
+    fn scoped(&mut self, walk: impl FnOnce(&mut Self)) {
+        self.scopes.push(vec![]);
+        walk(self);
+        self.scopes.pop();
+    }
+
+    fn lookup(&self, ident: &Ident) -> Option<&str> {
+        self.scopes
+            .iter()
+            .rev()
+            .flatten()
+            .find_map(|(name, ctxt, new)| {
+                (*name == ident.name && *ctxt == ident.span.ctxt).then_some(new.as_str())
+            })
+    }
+
+    fn bind(&mut self, ident: &mut Ident) {
+        let ctxt = ident.span.ctxt;
+        if ident.name.starts_with(char::is_uppercase) {
+            return;
+        }
+        let scope = self.scopes.last().unwrap();
+        // Bound again in the same scope, by `let` shadowing or an or-pattern.
+        if let Some((_, _, new)) = scope
+            .iter()
+            .find(|(name, other, _)| *name == ident.name && *other == ctxt)
+        {
+            let new = new.clone();
+            rename(ident, new);
+            return;
+        }
+        let new = match ctxt.is_root() {
+            true => ident.name.clone(),
+            false => self.fresh(&ident.name),
+        };
+        let binding = (ident.name.clone(), ctxt, new.clone());
+        self.scopes.last_mut().unwrap().push(binding);
+        rename(ident, new);
+    }
+
+    /// A name used nowhere else in the crate.
+    fn fresh(&mut self, name: &str) -> String {
+        let new = (1..)
+            .map(|n| format!("{name}_{n}"))
+            .find(|new| !self.used.contains(new))
+            .unwrap();
+        self.used.insert(new.clone());
+        new
+    }
+
+    /// Whether `ident`, written by an expansion which does not bind it, would
+    /// otherwise name a local of another context.
+    fn is_hidden(&self, ident: &Ident) -> bool {
+        !ident.span.ctxt.is_root()
+            && self
+                .scopes
+                .iter()
+                .flatten()
+                .any(|(name, _, _)| *name == ident.name)
+    }
+}
+
+fn rename(ident: &mut Ident, new: String) {
+    if ident.name != new {
+        ident.written = Some(std::mem::replace(&mut ident.name, new));
+    }
+}
+
+impl VisitorMut for Renamer {
+    fn visit_function(&mut self, function: &mut Function) {
+        self.scoped(|this| visit_mut::walk_function(this, function));
+    }
+
+    fn visit_param(&mut self, param: &mut Param) {
+        if param.is_self() {
+            self.visit_ty(&mut param.ty);
+        } else {
+            visit_mut::walk_param(self, param);
+        }
+    }
+
+    fn visit_block(&mut self, block: &mut Block) {
+        self.scoped(|this| visit_mut::walk_block(this, block));
+    }
+
+    fn visit_closure(&mut self, closure: &mut Closure) {
+        self.scoped(|this| visit_mut::walk_closure(this, closure));
+    }
+
+    fn visit_arm(&mut self, arm: &mut Arm) {
+        self.scoped(|this| visit_mut::walk_arm(this, arm));
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut Pattern) {
+        if let PatKind::Binding { name, .. } = &mut pattern.kind {
+            self.bind(name);
+        }
+        visit_mut::walk_pattern(self, pattern);
+        // `Point { x }` binding `x_1` is now `Point { x: x_1 }`.
+        if let PatKind::Struct { fields, .. } = &mut pattern.kind {
+            for field in fields {
+                if let PatKind::Binding { name, .. } = &field.pat.kind {
+                    field.shorthand &= name.name == field.name.name;
+                }
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let ExprKind::Path(path) = &mut expr.kind {
+            if let [segment] = &mut path.segments[..] {
+                if let Some(new) = self.lookup(&segment.ident) {
+                    let new = new.to_string();
+                    rename(&mut segment.ident, new);
+                } else if self.is_hidden(&segment.ident) {
+                    let new = self.fresh(&segment.ident.name);
+                    rename(&mut segment.ident, new);
+                }
+            }
+        }
+        if let ExprKind::ForLoop { pat, iter, body } = &mut expr.kind {
+            self.visit_expr(iter);
+            self.scoped(|this| {
+                this.visit_pattern(pat);
+                this.visit_block(body);
+            });
+            return;
+        }
+        visit_mut::walk_expr(self, expr);
+        if let ExprKind::StructLit { fields, .. } = &mut expr.kind {
+            for field in fields {
+                if let ExprKind::Path(path) = &field.expr.kind {
+                    field.shorthand &= path.last().name == field.name.name;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codegen::rust::module_source;
+    use crate::semantic::{flow::check_flow, resolve::resolve_names};
+    use crate::syntax::parse;
+
+    #[test]
+    fn renames_bindings_of_expansions() {
+        let (module, errors) = parse(
+            "macro swap($a:ident, $b:ident) { let t = $a; $a = $b; $b = t; }
+            macro point($x:expr) { { let x = $x; Point { x } } }
+            fn main() {
+                let t = 1;
+                let mut u = 2;
+                swap!(t, u);
+                let p = point!(t + 1);
+                let x_1 = x;
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = module_source(&module);
+        assert!(
+            source.contains("let t_1 = t;\n    t = u;\n    u = t_1;"),
+            "{source}"
+        );
+        // `x_1` is taken by the caller.
+        assert!(source.contains("let x_2 = t + 1;"), "{source}");
+        assert!(source.contains("Point { x: x_2 }"), "{source}");
+        assert!(source.contains("let x_1 = x;"), "{source}");
+    }
+
+    #[test]
+    fn hides_the_locals_of_the_caller() {
+        let (module, errors) = parse(
+            "macro add($e:expr) { $e + x }
+            macro unset() { { let t: i32; t } }
+            fn main() {
+                let x = 1;
+                let y = add!(x) + unset!();
+            }",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut diagnostics = vec![];
+        let resolutions = resolve_names(&module, &mut diagnostics);
+        check_flow(&module, &resolutions, &mut diagnostics);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot find value `x` in this scope",
+                "used binding `t` isn't initialized",
+            ]
+        );
+    }
+}
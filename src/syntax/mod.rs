@@ -0,0 +1,10 @@
+pub mod ast;
+pub mod dump;
+pub mod expand;
+pub mod fold;
+pub mod hygiene;
+pub mod parser;
+pub mod visit;
+pub mod visit_mut;
+
+pub use parser::parse;
@@ -0,0 +1,692 @@
+use crate::lexical::{Keyword, Lexeme, Literal, Token};
+use crate::span::Span;
+
+use super::{PResult, Parser};
+use crate::diagnostics::Diagnostic;
+use crate::syntax::ast::*;
+
+/// Binding power of the binary operators, higher binds tighter.
+pub(super) fn binary_operator(text: &str) -> Option<(BinOp, u8)> {
+    let op = match text {
+        "||" => BinOp::Or,
+        "&&" => BinOp::And,
+        "==" => BinOp::Eq,
+        "!=" => BinOp::Ne,
+        "<" => BinOp::Lt,
+        "<=" => BinOp::Le,
+        ">" => BinOp::Gt,
+        ">=" => BinOp::Ge,
+        "+" => BinOp::Add,
+        "-" => BinOp::Sub,
+        "*" => BinOp::Mul,
+        "/" => BinOp::Div,
+        "%" => BinOp::Rem,
+        _ => return None,
+    };
+    Some((op, op.precedence()))
+}
+
+impl Parser<'_> {
+    pub(super) fn parse_block(&mut self) -> PResult<Block> {
+        let close = self.closers.get(&self.pos).copied();
+        let start = self.expect("{")?;
+        // A struct literal is fine again inside braces : `if x { Point { x: 1 } }`.
+        let no_struct_literal = std::mem::replace(&mut self.no_struct_literal, false);
+        let mut stmts = vec![];
+        while !self.at("}") {
+            if self.at_end() {
+                return Err(self.unexpected("`}`"));
+            }
+            if self.eat(";") {
+                continue;
+            }
+            if self.at_macro_stmt() {
+                stmts.extend(self.parse_macro_stmts());
+                continue;
+            }
+            stmts.push(self.parse_stmt_or_recover(close));
+        }
+        let end = self.expect("}")?;
+        self.no_struct_literal = no_struct_literal;
+        Ok(Block {
+            id: NodeId::next(),
+            stmts,
+            span: self.join(start, end),
+        })
+    }
+
+    pub(super) fn parse_stmt(&mut self) -> PResult<Stmt> {
+        let start = self.span();
+        let attrs = self.parse_attributes()?;
+        if self.at("let") {
+            let local = self.parse_local()?;
+            return Ok(Stmt {
+                attrs,
+                span: self.join(start, local.span),
+                kind: StmtKind::Let(Box::new(local)),
+            });
+        }
+        // As in Rust, `match x {} - 1` is a statement followed by `-1`.
+        let expr = if self.at_block_like() {
+            self.parse_primary()?
+        } else {
+            self.parse_expr()?
+        };
+        let kind = if self.eat(";") {
+            StmtKind::Semi(expr)
+        } else if self.at("}") || self.at_end() || expr.is_block_like() {
+            StmtKind::Expr(expr)
+        } else {
+            return Err(self.unexpected("`;` or `}`"));
+        };
+        Ok(Stmt {
+            attrs,
+            kind,
+            span: self.join(start, self.prev_span()),
+        })
+    }
+
+    fn at_block_like(&self) -> bool {
+        ["{", "if", "match", "while", "loop", "for"]
+            .iter()
+            .any(|start| self.at(start))
+    }
+
+    /// `let pattern: Ty = init;` or `let pattern = init else { ... };`
+    fn parse_local(&mut self) -> PResult<Local> {
+        let start = self.expect("let")?;
+        let pat = self.parse_pattern()?;
+        let ty = if self.eat(":") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        let init = if self.eat("=") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        let els = if init.is_some() && self.at("else") {
+            self.bump();
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+        let end = self.expect(";")?;
+        Ok(Local {
+            id: NodeId::next(),
+            pat,
+            ty,
+            init,
+            els,
+            span: self.join(start, end),
+        })
+    }
+
+    pub(super) fn parse_expr(&mut self) -> PResult<Expr> {
+        if self.at("..") || self.at("..=") {
+            return self.parse_range(None);
+        }
+        let lhs = self.parse_binary(0)?;
+        if self.at("..") || self.at("..=") {
+            return self.parse_range(Some(lhs));
+        }
+        if self.eat("=") {
+            let rhs = self.parse_expr()?;
+            let span = self.join(lhs.span, rhs.span);
+            return Ok(Expr::new(
+                ExprKind::Assign(Box::new(lhs), Box::new(rhs)),
+                span,
+            ));
+        }
+        Ok(lhs)
+    }
+
+    /// `lo..hi`, `lo..=hi`, `lo..`, `..hi` or `..`, once `lo` is read.
+    fn parse_range(&mut self, lo: Option<Expr>) -> PResult<Expr> {
+        let start = lo.as_ref().map_or(self.span(), |lo| lo.span);
+        let inclusive = self.at("..=");
+        self.bump();
+        let hi = if !self.at_range_end() {
+            Some(Box::new(self.parse_binary(0)?))
+        } else if inclusive {
+            return Err(self.unexpected("the end of the range"));
+        } else {
+            None
+        };
+        Ok(Expr::new(
+            ExprKind::Range {
+                lo: lo.map(Box::new),
+                hi,
+                inclusive,
+            },
+            self.join(start, self.prev_span()),
+        ))
+    }
+
+    /// Whether a range stops before the next token, as in `xs[1..]` or `for i in 0.. {`.
+    fn at_range_end(&self) -> bool {
+        self.at_end()
+            || [")", "]", "}", ",", ";", "=>"]
+                .iter()
+                .any(|end| self.at(end))
+            || (self.no_struct_literal && self.at("{"))
+    }
+
+    /// Reads an expression where `x {` starts a block, as in `if x { }`.
+    fn parse_expr_no_struct(&mut self) -> PResult<Expr> {
+        let previous = std::mem::replace(&mut self.no_struct_literal, true);
+        let expr = self.parse_expr();
+        self.no_struct_literal = previous;
+        expr
+    }
+
+    fn parse_binary(&mut self, min_power: u8) -> PResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some((op, power)) = self.peek().and_then(|l| binary_operator(l.text)) {
+            if power <= min_power {
+                break;
+            }
+            self.bump();
+            let rhs = self.parse_binary(power)?;
+            let span = self.join(lhs.span, rhs.span);
+            lhs = Expr::new(ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> PResult<Expr> {
+        let start = self.span();
+        let op = if self.eat("-") {
+            UnOp::Neg
+        } else if self.eat("!") {
+            UnOp::Not
+        } else if self.eat("*") {
+            UnOp::Deref
+        } else if self.at("&") || self.at("&&") {
+            // `&&` is lexed as one operator : `&&x` borrows twice, as `& &x`.
+            let twice = self.bump().text == "&&";
+            let inner_start = match twice {
+                true => Span {
+                    start: start.start + 1,
+                    ..start
+                },
+                false => start,
+            };
+            let mutable = self.eat("mut");
+            let expr = self.parse_unary()?;
+            let span = self.join(inner_start, expr.span);
+            let mut expr = Expr::new(
+                ExprKind::AddrOf {
+                    mutable,
+                    expr: Box::new(expr),
+                },
+                span,
+            );
+            if twice {
+                let span = self.join(start, expr.span);
+                expr = Expr::new(
+                    ExprKind::AddrOf {
+                        mutable: false,
+                        expr: Box::new(expr),
+                    },
+                    span,
+                );
+            }
+            return Ok(expr);
+        } else {
+            return self.parse_postfix();
+        };
+        let expr = self.parse_unary()?;
+        let span = self.join(start, expr.span);
+        Ok(Expr::new(ExprKind::Unary(op, Box::new(expr)), span))
+    }
+
+    fn parse_postfix(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.eat("(") {
+                let args = self.parse_comma_list(")", Self::parse_expr)?;
+                let span = self.join(expr.span, self.prev_span());
+                expr = Expr::new(ExprKind::Call(Box::new(expr), args), span);
+            } else if self.at("?") {
+                let question = self.bump().span;
+                let span = self.join(expr.span, question);
+                expr = Expr::new(ExprKind::Try(Box::new(expr)), span);
+            } else if self.eat("[") {
+                let index = self.parse_expr()?;
+                let end = self.expect("]")?;
+                let span = self.join(expr.span, end);
+                expr = Expr::new(ExprKind::Index(Box::new(expr), Box::new(index)), span);
+            } else if self.eat(".") {
+                if let Some(indices) = self.parse_tuple_indices()? {
+                    for index in indices {
+                        let span = self.join(expr.span, index.span);
+                        expr = Expr::new(ExprKind::Field(Box::new(expr), index), span);
+                    }
+                    continue;
+                }
+                let name = self.parse_ident()?;
+                let mut generic_args = vec![];
+                if self.at("::") && self.at_nth(1, "<") {
+                    self.bump();
+                    self.bump();
+                    generic_args = self.parse_comma_list(">", Self::parse_ty)?;
+                    if !self.at("(") {
+                        return Err(self.unexpected("`(`"));
+                    }
+                }
+                if self.eat("(") {
+                    let args = self.parse_comma_list(")", Self::parse_expr)?;
+                    let span = self.join(expr.span, self.prev_span());
+                    expr = Expr::new(
+                        ExprKind::MethodCall {
+                            receiver: Box::new(expr),
+                            method: name,
+                            generic_args,
+                            args,
+                            callee: None,
+                        },
+                        span,
+                    );
+                } else {
+                    let span = self.join(expr.span, name.span);
+                    expr = Expr::new(ExprKind::Field(Box::new(expr), name), span);
+                }
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// The `0` of `pair.0`. `pair.0.1` reads as a float after the `.`, it is split
+    /// into both indices.
+    fn parse_tuple_indices(&mut self) -> PResult<Option<Vec<Ident>>> {
+        let Some(Lexeme {
+            token: Token::Literal(literal @ (Literal::Integer(..) | Literal::Float(..))),
+            text,
+            span,
+        }) = self.peek()
+        else {
+            return Ok(None);
+        };
+        let mut indices = vec![];
+        let mut offset = span.start;
+        for part in text.split('.') {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Diagnostic::error(
+                    format!("invalid tuple index `{text}`"),
+                    *span,
+                ));
+            }
+            indices.push(Ident {
+                name: part.to_string(),
+                span: Span::new(offset, offset + part.len()),
+                written: None,
+            });
+            offset += part.len() + 1;
+        }
+        if matches!(literal, Literal::Float(..)) && indices.len() != 2 {
+            return Err(Diagnostic::error(
+                format!("invalid tuple index `{text}`"),
+                *span,
+            ));
+        }
+        self.bump();
+        Ok(Some(indices))
+    }
+
+    fn parse_primary(&mut self) -> PResult<Expr> {
+        let start = self.span();
+        if let Some(lit) = self.parse_lit()? {
+            return Ok(Expr::new(ExprKind::Lit(lit), start));
+        }
+        if self.at_macro_call() {
+            return Ok(self.parse_macro_expr());
+        }
+        if self.at_identifier() {
+            let path = self.parse_path()?;
+            if self.at("{") && !self.no_struct_literal {
+                return self.parse_struct_lit(path);
+            }
+            return Ok(Expr::new(ExprKind::Path(path.clone()), path.span));
+        }
+        if self.eat("(") {
+            // Empty parentheses are the invisible ones around an expanded `$x:expr`.
+            if start.start == start.end {
+                let inner = self.parse_expr()?;
+                self.expect(")")?;
+                return Ok(inner);
+            }
+            let mut elements = self.parse_comma_list(")", Self::parse_expr)?;
+            let span = self.join(start, self.prev_span());
+            if elements.len() == 1 && !self.after_trailing_comma() {
+                let inner = elements.pop().unwrap();
+                return Ok(Expr::new(ExprKind::Paren(Box::new(inner)), span));
+            }
+            return Ok(Expr::new(ExprKind::Tuple(elements), span));
+        }
+        if self.eat("[") {
+            return self.parse_array(start);
+        }
+        if self.at("{") {
+            let block = self.parse_block()?;
+            let span = block.span;
+            return Ok(Expr::new(ExprKind::Block(block), span));
+        }
+        if self.at("if") {
+            return self.parse_if();
+        }
+        if self.eat("match") {
+            return self.parse_match(start);
+        }
+        if self.eat("while") {
+            let cond = self.parse_expr_no_struct()?;
+            let body = self.parse_block()?;
+            let span = self.join(start, body.span);
+            return Ok(Expr::new(ExprKind::While(Box::new(cond), body), span));
+        }
+        if self.eat("loop") {
+            let body = self.parse_block()?;
+            let span = self.join(start, body.span);
+            return Ok(Expr::new(ExprKind::Loop(body), span));
+        }
+        if self.eat("for") {
+            let pat = self.parse_pattern()?;
+            self.expect("in")?;
+            let iter = self.parse_expr_no_struct()?;
+            let body = self.parse_block()?;
+            let span = self.join(start, body.span);
+            let kind = ExprKind::ForLoop {
+                pat,
+                iter: Box::new(iter),
+                body,
+            };
+            return Ok(Expr::new(kind, span));
+        }
+        if self.eat("return") {
+            let value = self.parse_optional_operand()?;
+            let kind = ExprKind::Return(value.map(Box::new));
+            return Ok(Expr::new(kind, self.join(start, self.prev_span())));
+        }
+        if self.eat("break") {
+            let value = self.parse_optional_operand()?;
+            let kind = ExprKind::Break(value.map(Box::new));
+            return Ok(Expr::new(kind, self.join(start, self.prev_span())));
+        }
+        if self.eat("continue") {
+            return Ok(Expr::new(ExprKind::Continue, start));
+        }
+        if self.at("|") || self.at("||") || self.at("move") {
+            return self.parse_closure();
+        }
+        if self.at_keyword(|k| matches!(k, Keyword::Function(..))) {
+            return self.parse_fun_closure();
+        }
+        Err(self.unexpected("an expression"))
+    }
+
+    /// `[1, 2, 3]` or `[0; 16]`, once the `[` is read.
+    fn parse_array(&mut self, start: Span) -> PResult<Expr> {
+        if self.eat("]") {
+            return Ok(Expr::new(
+                ExprKind::Array(vec![]),
+                self.join(start, self.prev_span()),
+            ));
+        }
+        let first = self.parse_expr()?;
+        if self.eat(";") {
+            let len = self.parse_expr()?;
+            let end = self.expect("]")?;
+            let kind = ExprKind::Repeat(Box::new(first), Box::new(len));
+            return Ok(Expr::new(kind, self.join(start, end)));
+        }
+        let mut elements = vec![first];
+        if self.eat(",") {
+            elements.extend(self.parse_comma_list("]", Self::parse_expr)?);
+        } else if !self.eat("]") {
+            return Err(self.unexpected("`,`, `;` or `]`"));
+        }
+        Ok(Expr::new(
+            ExprKind::Array(elements),
+            self.join(start, self.prev_span()),
+        ))
+    }
+
+    /// `|x, y| x + y`, `|| 1` or `move |x: i32| -> i32 { x }`
+    fn parse_closure(&mut self) -> PResult<Expr> {
+        let start = self.span();
+        let capture = if self.eat("move") {
+            CaptureBy::Value
+        } else {
+            CaptureBy::Ref
+        };
+        let params = if self.eat("||") {
+            vec![]
+        } else {
+            self.expect("|")?;
+            self.parse_comma_list("|", Self::parse_closure_param)?
+        };
+        let (output, body) = if self.eat("->") {
+            // As in Rust, a return type needs a block body.
+            let output = self.parse_ty()?;
+            let block = self.parse_block()?;
+            let span = block.span;
+            (Some(output), Expr::new(ExprKind::Block(block), span))
+        } else {
+            (None, self.parse_expr()?)
+        };
+        let span = self.join(start, body.span);
+        let closure = Closure {
+            params,
+            output,
+            body: Box::new(body),
+            capture,
+            captures: vec![],
+        };
+        Ok(Expr::new(ExprKind::Closure(Box::new(closure)), span))
+    }
+
+    /// `fun (x: i32) -> i32 { x + 1 }`, the block form of a closure.
+    fn parse_fun_closure(&mut self) -> PResult<Expr> {
+        let start = self.bump().span;
+        self.expect("(")?;
+        let params = self.parse_comma_list(")", Self::parse_closure_param)?;
+        let output = if self.eat("->") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        let block = self.parse_block()?;
+        let span = self.join(start, block.span);
+        let closure = Closure {
+            params,
+            output,
+            body: Box::new(Expr::new(ExprKind::Block(block), span)),
+            capture: CaptureBy::Ref,
+            captures: vec![],
+        };
+        Ok(Expr::new(ExprKind::Closure(Box::new(closure)), span))
+    }
+
+    /// `x`, `mut x: i32`. Or-patterns would be ambiguous with the closing `|`.
+    fn parse_closure_param(&mut self) -> PResult<ClosureParam> {
+        let pat = self.parse_single_pattern()?;
+        let ty = if self.eat(":") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        Ok(ClosureParam {
+            span: self.join(pat.span, self.prev_span()),
+            pat,
+            ty,
+        })
+    }
+
+    /// The value of `return` and `break`, when there is one.
+    fn parse_optional_operand(&mut self) -> PResult<Option<Expr>> {
+        if self.at_end() || [";", "}", ")", ","].iter().any(|end| self.at(end)) {
+            Ok(None)
+        } else {
+            self.parse_expr().map(Some)
+        }
+    }
+
+    fn parse_if(&mut self) -> PResult<Expr> {
+        let start = self.expect("if")?;
+        let cond = self.parse_expr_no_struct()?;
+        let then = self.parse_block()?;
+        let otherwise = if self.eat("else") {
+            if self.at("if") {
+                Some(Box::new(self.parse_if()?))
+            } else {
+                let block = self.parse_block()?;
+                let span = block.span;
+                Some(Box::new(Expr::new(ExprKind::Block(block), span)))
+            }
+        } else {
+            None
+        };
+        let span = self.join(start, self.prev_span());
+        Ok(Expr::new(
+            ExprKind::If(Box::new(cond), then, otherwise),
+            span,
+        ))
+    }
+
+    /// `match scrutinee { pattern if guard => body, ... }`
+    fn parse_match(&mut self, start: Span) -> PResult<Expr> {
+        let scrutinee = self.parse_expr_no_struct()?;
+        let close = self.closers.get(&self.pos).copied();
+        self.expect("{")?;
+        let mut arms = vec![];
+        while !self.at("}") {
+            if self.at_end() {
+                return Err(self.unexpected("`}`"));
+            }
+            arms.push(self.parse_arm_or_recover(close));
+        }
+        let end = self.expect("}")?;
+        Ok(Expr::new(
+            ExprKind::Match(Box::new(scrutinee), arms),
+            self.join(start, end),
+        ))
+    }
+
+    pub(super) fn parse_arm(&mut self) -> PResult<Arm> {
+        let pat = self.parse_pattern()?;
+        let guard = if self.eat("if") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        self.expect("=>")?;
+        let body = self.parse_expr()?;
+        let span = self.join(pat.span, body.span);
+        // The comma is optional after a block, and after the last arm.
+        if !self.eat(",") && !body.is_block_like() && !self.at("}") {
+            return Err(self.unexpected("`,` or `}`"));
+        }
+        Ok(Arm {
+            pat,
+            guard,
+            body,
+            span,
+        })
+    }
+
+    /// `Point { x: 1, y }`, once the path is read.
+    fn parse_struct_lit(&mut self, path: Path) -> PResult<Expr> {
+        self.expect("{")?;
+        let fields = self.parse_comma_list("}", |parser| {
+            let name = parser.parse_ident()?;
+            if parser.eat(":") {
+                let expr = parser.parse_expr()?;
+                Ok(FieldInit {
+                    span: parser.join(name.span, expr.span),
+                    name,
+                    expr,
+                    shorthand: false,
+                })
+            } else {
+                let path = Path::from_ident(name.clone());
+                Ok(FieldInit {
+                    span: name.span,
+                    expr: Expr::new(ExprKind::Path(path), name.span),
+                    name,
+                    shorthand: true,
+                })
+            }
+        })?;
+        let span = self.join(path.span, self.prev_span());
+        Ok(Expr::new(ExprKind::StructLit { path, fields }, span))
+    }
+
+    /// Reads a literal if the next token is one.
+    pub(super) fn parse_lit(&mut self) -> PResult<Option<Lit>> {
+        let kind = match self.peek() {
+            Some(Lexeme {
+                token: Token::Literal(literal),
+                text,
+                span,
+            }) => lit_kind(literal, text, *span)?,
+            _ if self.at("true") => LitKind::Bool(true),
+            _ if self.at("false") => LitKind::Bool(false),
+            _ => return Ok(None),
+        };
+        let span = self.bump().span;
+        Ok(Some(Lit { kind, span }))
+    }
+}
+
+fn lit_kind(literal: &Literal, text: &str, span: Span) -> PResult<LitKind> {
+    let invalid = |what: &str| Diagnostic::error(format!("invalid {what} literal `{text}`"), span);
+    Ok(match literal {
+        Literal::Integer(..) => LitKind::Int(
+            text.replace('_', "")
+                .parse()
+                .map_err(|_| invalid("integer"))?,
+        ),
+        Literal::Float(..) => LitKind::Float(
+            text.replace('_', "")
+                .parse()
+                .map_err(|_| invalid("float"))?,
+        ),
+        Literal::Str(..) => {
+            LitKind::Str(unescape(&text[1..text.len() - 1]).ok_or(invalid("string"))?)
+        }
+        Literal::Char(..) => {
+            let chars: Vec<char> = unescape(&text[1..text.len() - 1])
+                .ok_or(invalid("character"))?
+                .chars()
+                .collect();
+            match chars[..] {
+                [c] => LitKind::Char(c),
+                _ => return Err(invalid("character")),
+            }
+        }
+    })
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            _ => return None,
+        });
+    }
+    Some(out)
+}
@@ -0,0 +1,528 @@
+use crate::diagnostics::Diagnostic;
+use crate::lexical::Keyword;
+use crate::span::Span;
+
+use super::expr::binary_operator;
+use super::{PResult, Parser};
+use crate::syntax::ast::*;
+
+impl Parser<'_> {
+    pub(super) fn parse_item(&mut self) -> PResult<Item> {
+        let start = self.span();
+        let attrs = self.parse_attributes()?;
+        let vis = self.parse_visibility();
+        let kind = if self.at_function() {
+            let function = self.parse_function()?;
+            if function.body.is_none() {
+                self.errors.push(Diagnostic::error(
+                    "free function without a body",
+                    self.join(start, self.prev_span()),
+                ));
+            }
+            ItemKind::Function(function)
+        } else if self.at("trait") {
+            ItemKind::Trait(self.parse_trait()?)
+        } else if self.at("impl") {
+            ItemKind::Impl(self.parse_impl()?)
+        } else if self.at_keyword(|k| matches!(k, Keyword::Struct(..))) {
+            ItemKind::Struct(self.parse_struct()?)
+        } else if self.at("enum") {
+            ItemKind::Enum(self.parse_enum()?)
+        } else if self.at("type") {
+            let alias = self.parse_ty_alias()?;
+            if alias.ty.is_none() {
+                self.errors.push(Diagnostic::error(
+                    "free type alias without body",
+                    self.join(start, self.prev_span()),
+                ));
+            }
+            ItemKind::TyAlias(alias)
+        } else if self.at("const") {
+            ItemKind::Const(self.parse_const()?)
+        } else if self.at("static") {
+            ItemKind::Static(self.parse_const()?)
+        } else if self.at("macro") {
+            ItemKind::MacroDef(self.parse_macro_def()?)
+        } else if self.at("mod") {
+            ItemKind::Mod(self.parse_mod()?)
+        } else if self.eat("use") {
+            let tree = self.parse_use_tree()?;
+            self.expect(";")?;
+            ItemKind::Use(tree)
+        } else {
+            return Err(self.unexpected("an item"));
+        };
+        Ok(Item {
+            id: NodeId::next(),
+            attrs,
+            vis,
+            kind,
+            span: self.join(start, self.prev_span()),
+        })
+    }
+
+    /// A function or an associated type, in a trait or an impl. Only the functions of
+    /// a trait can leave out their body.
+    pub(super) fn parse_assoc_item(&mut self, in_trait: bool) -> PResult<Item> {
+        let start = self.span();
+        let attrs = self.parse_attributes()?;
+        let vis = self.parse_visibility();
+        let kind = if self.at_function() {
+            let function = self.parse_function()?;
+            if function.body.is_none() && !in_trait {
+                self.errors.push(Diagnostic::error(
+                    "associated function in `impl` without body",
+                    self.join(start, self.prev_span()),
+                ));
+            }
+            ItemKind::Function(function)
+        } else if !in_trait && self.at("operator") {
+            ItemKind::Function(self.parse_operator()?)
+        } else if self.at("type") {
+            ItemKind::TyAlias(self.parse_ty_alias()?)
+        } else if in_trait {
+            return Err(self.unexpected("`fn` or `type`"));
+        } else {
+            return Err(self.unexpected("`fn`, `operator` or `type`"));
+        };
+        Ok(Item {
+            id: NodeId::next(),
+            attrs,
+            vis,
+            kind,
+            span: self.join(start, self.prev_span()),
+        })
+    }
+
+    /// `trait Shape<T>: Debug + Clone where T: Ord { ... }`
+    fn parse_trait(&mut self) -> PResult<Trait> {
+        self.bump();
+        let name = self.parse_ident()?;
+        let mut generics = self.parse_generics()?;
+        let supertraits = if self.eat(":") {
+            self.parse_bounds()?
+        } else {
+            vec![]
+        };
+        self.parse_where_clause(&mut generics)?;
+        let items = self.parse_assoc_items(true)?;
+        Ok(Trait {
+            name,
+            generics,
+            supertraits,
+            items,
+        })
+    }
+
+    /// `impl<T> Point<T> { ... }` or `impl<T: Debug> Shape for Point<T> { ... }`
+    fn parse_impl(&mut self) -> PResult<Impl> {
+        self.bump();
+        let mut generics = self.parse_generics()?;
+        let ty = self.parse_ty()?;
+        let (of_trait, self_ty) = if self.eat("for") {
+            let TyKind::Path(path) = ty.kind else {
+                return Err(Diagnostic::error(
+                    "expected a trait, found a reference",
+                    ty.span,
+                ));
+            };
+            (Some(path), self.parse_ty()?)
+        } else {
+            (None, ty)
+        };
+        self.parse_where_clause(&mut generics)?;
+        let items = self.parse_assoc_items(false)?;
+        Ok(Impl {
+            generics,
+            of_trait,
+            self_ty,
+            items,
+        })
+    }
+
+    fn parse_assoc_items(&mut self, in_trait: bool) -> PResult<Vec<Item>> {
+        let close = self.closers.get(&self.pos).copied();
+        self.expect("{")?;
+        let mut items = vec![];
+        while !self.at("}") {
+            if self.at_end() {
+                return Err(self.unexpected("`}`"));
+            }
+            items.push(self.parse_assoc_item_or_recover(in_trait, close));
+        }
+        self.expect("}")?;
+        Ok(items)
+    }
+
+    /// `type Unit;` or `type Unit = f64;`
+    fn parse_ty_alias(&mut self) -> PResult<TyAlias> {
+        self.bump();
+        let name = self.parse_ident()?;
+        let ty = if self.eat("=") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        self.expect(";")?;
+        Ok(TyAlias { name, ty })
+    }
+
+    /// `const MAX: u32 = 1024;` or `static NAME: &str = "oxy";`, the type is required.
+    fn parse_const(&mut self) -> PResult<Const> {
+        self.bump();
+        let name = self.parse_ident()?;
+        self.expect(":")?;
+        let ty = self.parse_ty()?;
+        self.expect("=")?;
+        let expr = self.parse_expr()?;
+        self.expect(";")?;
+        Ok(Const { name, ty, expr })
+    }
+
+    fn parse_visibility(&mut self) -> Visibility {
+        if self.eat("pub") {
+            Visibility::Public
+        } else {
+            Visibility::Private
+        }
+    }
+
+    /// Any number of `#[meta]`.
+    pub(super) fn parse_attributes(&mut self) -> PResult<Vec<Attribute>> {
+        let mut attrs = vec![];
+        while self.at("#") {
+            let start = self.bump().span;
+            self.expect("[")?;
+            let meta = self.parse_meta()?;
+            let end = self.expect("]")?;
+            attrs.push(Attribute {
+                meta,
+                span: self.join(start, end),
+            });
+        }
+        Ok(attrs)
+    }
+
+    /// `inline`, `derive(Debug, Clone)`, `deprecated = "note"` or a literal.
+    fn parse_meta(&mut self) -> PResult<Meta> {
+        if let Some(lit) = self.parse_lit()? {
+            return Ok(Meta::Lit(lit));
+        }
+        let path = self.parse_path()?;
+        if self.eat("(") {
+            let list = self.parse_comma_list(")", Self::parse_meta)?;
+            Ok(Meta::List(path, list))
+        } else if self.eat("=") {
+            match self.parse_lit()? {
+                Some(lit) => Ok(Meta::NameValue(path, lit)),
+                None => Err(self.unexpected("a literal")),
+            }
+        } else {
+            Ok(Meta::Word(path))
+        }
+    }
+
+    /// `mod name;` or `mod name { items }`
+    fn parse_mod(&mut self) -> PResult<Mod> {
+        self.bump();
+        let name = self.parse_ident()?;
+        if self.eat(";") {
+            return Ok(Mod {
+                name,
+                inline: false,
+                content: None,
+            });
+        }
+        let start = self.expect("{")?;
+        let mut items = vec![];
+        while !self.at("}") {
+            if self.at_end() {
+                return Err(self.unexpected("`}`"));
+            }
+            if self.at_macro_call() {
+                items.extend(self.parse_macro_items());
+                continue;
+            }
+            items.push(self.parse_item_or_recover());
+        }
+        let end = self.expect("}")?;
+        Ok(Mod {
+            name,
+            inline: true,
+            content: Some(Module {
+                items,
+                span: self.join(start, end),
+            }),
+        })
+    }
+
+    /// `a::b`, `a::b as c`, `a::*` or `a::{b, c::d}`
+    fn parse_use_tree(&mut self) -> PResult<UseTree> {
+        let start = self.span();
+        let prefix = if self.at_identifier() {
+            self.parse_path()?
+        } else {
+            Path {
+                segments: vec![],
+                span: Span::new(start.start, start.start),
+            }
+        };
+        let nested = prefix.segments.is_empty() || self.eat("::");
+        let kind = if nested && self.eat("*") {
+            UseTreeKind::Glob
+        } else if nested && self.eat("{") {
+            UseTreeKind::Nested(self.parse_comma_list("}", Self::parse_use_tree)?)
+        } else if nested {
+            return Err(self.unexpected("an identifier, `*` or `{`"));
+        } else if self.eat("as") {
+            UseTreeKind::Simple(Some(self.parse_ident()?))
+        } else {
+            UseTreeKind::Simple(None)
+        };
+        Ok(UseTree {
+            prefix,
+            kind,
+            span: self.join(start, self.prev_span()),
+        })
+    }
+
+    /// `fn name<T: Ord>(a: T, mut b: i32) -> T where T: Clone { ... }`, or the same
+    /// after `const`.
+    fn parse_function(&mut self) -> PResult<Function> {
+        let is_const = self.eat("const");
+        self.bump();
+        let name = self.parse_ident()?;
+        let mut generics = self.parse_generics()?;
+        self.expect("(")?;
+        let params = self.parse_comma_list(")", Self::parse_param)?;
+        let output = if self.eat("->") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        self.parse_where_clause(&mut generics)?;
+        let body = if self.eat(";") {
+            None
+        } else {
+            Some(self.parse_block()?)
+        };
+        Ok(Function {
+            name,
+            generics,
+            params,
+            output,
+            body,
+            operator: None,
+            is_const,
+        })
+    }
+
+    /// `operator +(self, other: Vec2) -> Vec2 { ... }`, named after the method of the
+    /// trait behind the operator. `-` is `Neg` with `self` alone and `Sub` otherwise.
+    fn parse_operator(&mut self) -> PResult<Function> {
+        let start = self.bump().span;
+        if self.at_end() {
+            return Err(self.unexpected("an operator"));
+        }
+        let lexeme = self.bump();
+        self.expect("(")?;
+        let params = self.parse_comma_list(")", Self::parse_param)?;
+        let op = match (lexeme.text, params.len()) {
+            ("-", 1) => OverloadedOp::Unary(UnOp::Neg),
+            ("!", _) => OverloadedOp::Unary(UnOp::Not),
+            (text, _) => match binary_operator(text) {
+                Some((op, _)) => OverloadedOp::Binary(op),
+                None => {
+                    return Err(Diagnostic::error(
+                        format!("expected an operator, found `{}`", lexeme.text),
+                        lexeme.span,
+                    ))
+                }
+            },
+        };
+        let Some((trait_name, method)) = op.trait_method() else {
+            return Err(Diagnostic::error(
+                format!("`{}` cannot be overloaded with `operator`", op.as_str()),
+                lexeme.span,
+            )
+            .with_note(format!(
+                "only `+`, `-`, `*`, `/`, `%`, `==` and `!` can, implement `{}` for the others",
+                if op == OverloadedOp::Binary(BinOp::Ne) {
+                    "PartialEq"
+                } else {
+                    "PartialOrd"
+                }
+            )));
+        };
+        let arity = match op {
+            OverloadedOp::Binary(_) => 2,
+            OverloadedOp::Unary(_) => 1,
+        };
+        if params.len() != arity || !params[0].is_self() {
+            let expected = if arity == 2 {
+                "`self` and the right operand"
+            } else {
+                "only `self`"
+            };
+            self.errors.push(
+                Diagnostic::error(
+                    format!("`operator {}` takes {expected}", op.as_str()),
+                    self.join(start, self.prev_span()),
+                )
+                .with_note(format!("it stands for `{trait_name}::{method}`")),
+            );
+        }
+        let output = if self.eat("->") {
+            Some(self.parse_ty()?)
+        } else {
+            None
+        };
+        let body = Some(self.parse_block()?);
+        Ok(Function {
+            name: Ident {
+                name: method.to_string(),
+                span: lexeme.span,
+                written: None,
+            },
+            generics: Generics::default(),
+            params,
+            output,
+            body,
+            operator: Some(op),
+            is_const: false,
+        })
+    }
+
+    fn parse_param(&mut self) -> PResult<Param> {
+        let start = self.span();
+        if self.at("self") || self.at("&") || (self.at("mut") && self.at_nth(1, "self")) {
+            return self.parse_self_param();
+        }
+        let pat = self.parse_single_pattern()?;
+        self.expect(":")?;
+        let ty = self.parse_ty()?;
+        Ok(Param {
+            id: NodeId::next(),
+            pat,
+            span: self.join(start, ty.span),
+            ty,
+        })
+    }
+
+    /// `self`, `mut self`, `&self` or `&mut self`, typed as `Self`, `&Self` or `&mut Self`.
+    fn parse_self_param(&mut self) -> PResult<Param> {
+        let start = self.span();
+        let reference = self.eat("&");
+        let mutable = self.eat("mut");
+        let name = self.parse_ident()?;
+        if name.name != "self" {
+            return Err(Diagnostic::error(
+                format!("expected `self`, found `{}`", name.name),
+                name.span,
+            ));
+        }
+        let span = self.join(start, name.span);
+        let self_ty = Ty {
+            id: NodeId::next(),
+            kind: TyKind::Path(Path::from_ident(Ident {
+                name: "Self".to_string(),
+                span: name.span,
+                written: None,
+            })),
+            span: name.span,
+        };
+        let (mutable, ty) = if reference {
+            let ty = Ty {
+                id: NodeId::next(),
+                kind: TyKind::Ref {
+                    mutable,
+                    inner: Box::new(self_ty),
+                },
+                span,
+            };
+            (false, ty)
+        } else {
+            (mutable, self_ty)
+        };
+        let binding = PatKind::Binding {
+            name,
+            mutable,
+            sub: None,
+        };
+        Ok(Param {
+            id: NodeId::next(),
+            pat: Pattern::new(binding, span),
+            ty,
+            span,
+        })
+    }
+
+    /// `struct Point<T> { x: T, y: T }` or `struct Marker;`
+    fn parse_struct(&mut self) -> PResult<Struct> {
+        self.bump();
+        let name = self.parse_ident()?;
+        let mut generics = self.parse_generics()?;
+        self.parse_where_clause(&mut generics)?;
+        let fields = if self.eat(";") {
+            vec![]
+        } else {
+            self.expect("{")?;
+            self.parse_comma_list("}", Self::parse_field_def)?
+        };
+        Ok(Struct {
+            name,
+            generics,
+            fields,
+        })
+    }
+
+    fn parse_field_def(&mut self) -> PResult<FieldDef> {
+        let start = self.span();
+        let attrs = self.parse_attributes()?;
+        let vis = self.parse_visibility();
+        let name = self.parse_ident()?;
+        self.expect(":")?;
+        let ty = self.parse_ty()?;
+        Ok(FieldDef {
+            span: self.join(start, ty.span),
+            attrs,
+            vis,
+            name,
+            ty,
+        })
+    }
+
+    /// `enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }`
+    fn parse_enum(&mut self) -> PResult<Enum> {
+        self.bump();
+        let name = self.parse_ident()?;
+        let mut generics = self.parse_generics()?;
+        self.parse_where_clause(&mut generics)?;
+        self.expect("{")?;
+        let variants = self.parse_comma_list("}", Self::parse_variant)?;
+        Ok(Enum {
+            name,
+            generics,
+            variants,
+        })
+    }
+
+    fn parse_variant(&mut self) -> PResult<Variant> {
+        let start = self.span();
+        let attrs = self.parse_attributes()?;
+        let name = self.parse_ident()?;
+        let data = if self.eat("(") {
+            VariantData::Tuple(self.parse_comma_list(")", Self::parse_ty)?)
+        } else if self.eat("{") {
+            VariantData::Struct(self.parse_comma_list("}", Self::parse_field_def)?)
+        } else {
+            VariantData::Unit
+        };
+        Ok(Variant {
+            span: self.join(start, self.prev_span()),
+            attrs,
+            name,
+            data,
+        })
+    }
+}
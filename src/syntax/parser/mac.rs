@@ -0,0 +1,252 @@
+//! Macros in the parser. The definitions of the file are read before anything else,
+//! so a macro can be called above its definition. A call, `name!(...)`, is expanded
+//! where it is found, as items, statements or an expression, and the tokens it
+//! expands to are read by a parser of their own.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexical::{token_trees, Keyword, Lexeme, Token, TokenTree};
+use crate::syntax::expand::{Fragment, Macro};
+
+use super::{Macros, PResult, Parser};
+use crate::syntax::ast::*;
+
+/// How deep expansions may nest, so a macro calling itself forever is stopped.
+const RECURSION_LIMIT: usize = 64;
+
+/// Index of the last token of the definition starting with the `macro` at `at`.
+fn definition_end(lexemes: &[Lexeme], closers: &HashMap<usize, usize>, at: usize) -> Option<usize> {
+    let first = at + 2;
+    let close = *closers.get(&first)?;
+    match lexemes[first].text {
+        "(" if lexemes.get(close + 1)?.text == "{" => closers.get(&(close + 1)).copied(),
+        "{" => Some(close),
+        _ => None,
+    }
+}
+
+/// Reads every `macro` definition of the file.
+pub(super) fn collect_macros<'input>(
+    lexemes: &[Lexeme<'input>],
+    closers: &HashMap<usize, usize>,
+) -> (Macros<'input>, Vec<Diagnostic>) {
+    let mut macros: Macros = HashMap::new();
+    let mut errors = vec![];
+    for (at, lexeme) in lexemes.iter().enumerate() {
+        let (Token::Keyword(Keyword::Macro(..)), Some(name)) = (&lexeme.token, lexemes.get(at + 1))
+        else {
+            continue;
+        };
+        // Malformed definitions are reported by the parser.
+        let (Token::Identifier(..), Some(end)) =
+            (&name.token, definition_end(lexemes, closers, at))
+        else {
+            continue;
+        };
+        let span = lexeme.span.to(lexemes[end].span);
+        let trees = token_trees(&lexemes[at + 2..=end]);
+        match Macro::parse(name.text, &trees, span) {
+            Ok(found) => {
+                if let Some(first) = macros.get(name.text) {
+                    errors.push(
+                        Diagnostic::error(
+                            format!("the name `{}` is defined multiple times", name.text),
+                            name.span,
+                        )
+                        .with_label(first.span, "previous definition of the macro here"),
+                    );
+                } else {
+                    macros.insert(name.text, found);
+                }
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+    (macros, errors)
+}
+
+/// How many of `lexemes` make one `fragment`, when they start with one. Macro
+/// calls inside are not expanded.
+pub(crate) fn fragment_len(fragment: Fragment, lexemes: Vec<Lexeme>) -> Option<usize> {
+    let end = lexemes.last().map_or(0, |lexeme| lexeme.span.end);
+    let mut parser = Parser::from_lexemes(lexemes, end);
+    parser.skim = true;
+    let parsed = match fragment {
+        Fragment::Expr => parser.parse_expr().is_ok(),
+        Fragment::Ty => parser.parse_ty().is_ok(),
+        Fragment::Pat => parser.parse_pattern().is_ok(),
+        Fragment::Stmt => parser.parse_stmt().is_ok(),
+        Fragment::Block => parser.parse_block().is_ok(),
+        Fragment::Path => parser.parse_path().is_ok(),
+        Fragment::Item => parser.parse_item().is_ok(),
+        // Single tokens are matched without the parser.
+        Fragment::Ident | Fragment::Literal | Fragment::Tt => return Some(1),
+    };
+    (parsed && parser.errors.is_empty()).then_some(parser.pos)
+}
+
+impl<'input> Parser<'input> {
+    /// `name!(...)`, `name![...]` or `name!{...}`
+    pub(super) fn at_macro_call(&self) -> bool {
+        self.at_identifier()
+            && self.at_nth(1, "!")
+            && (self.at_nth(2, "(") || self.at_nth(2, "[") || self.at_nth(2, "{"))
+    }
+
+    /// A call standing for statements rather than for an expression : one followed
+    /// by `;` or ending the block.
+    pub(super) fn at_macro_stmt(&self) -> bool {
+        if !self.at_macro_call() {
+            return false;
+        }
+        let Some(&close) = self.closers.get(&(self.pos + 2)) else {
+            return false;
+        };
+        match self.lexemes.get(close + 1) {
+            Some(next) => next.text == ";" || next.text == "}",
+            None => true,
+        }
+    }
+
+    /// `macro name(...) { ... }` or `macro name { ... }`, which were read by
+    /// [`collect_macros`] and are only skipped here.
+    pub(super) fn parse_macro_def(&mut self) -> PResult<MacroDef> {
+        let at = self.pos;
+        self.bump();
+        let name = self.parse_ident()?;
+        let Some(end) = definition_end(&self.lexemes, &self.closers, at) else {
+            return Err(self.unexpected("`(` or `{`"));
+        };
+        self.pos = end + 1;
+        Ok(MacroDef { name })
+    }
+
+    /// Expands the call at the current token and returns a parser for the tokens
+    /// it expands to. Errors are reported and give `None`.
+    fn expand_macro_call(&mut self) -> Option<Parser<'input>> {
+        let name = self.bump();
+        self.bump();
+        let open = self.pos;
+        let Some(&close) = self.closers.get(&open) else {
+            self.errors.push(self.unexpected("a closed macro call"));
+            self.pos = self.lexemes.len();
+            return None;
+        };
+        self.pos = close + 1;
+        let call_site = self.join(name.span, self.prev_span());
+        if self.skim {
+            return None;
+        }
+        if self.expansion_depth >= RECURSION_LIMIT {
+            self.errors.push(
+                Diagnostic::error(
+                    format!("recursion limit reached while expanding `{}!`", name.text),
+                    call_site,
+                )
+                .with_note(format!(
+                    "expansions can nest at most {RECURSION_LIMIT} deep"
+                )),
+            );
+            return None;
+        }
+        let Some(found) = self.macros.get(name.text) else {
+            self.errors.push(Diagnostic::error(
+                format!("cannot find macro `{}` in this scope", name.text),
+                name.span,
+            ));
+            return None;
+        };
+        let trees = token_trees(&self.lexemes[open..=close]);
+        let [TokenTree::Delimited(args)] = &trees[..] else {
+            unreachable!("the call is bracketed")
+        };
+        let expanded = found.expand(args, call_site, &mut self.expansions.borrow_mut());
+        match expanded {
+            Ok(lexemes) => {
+                self.expanded = true;
+                let mut parser = Parser::from_lexemes(lexemes, self.prev_span().start);
+                parser.macros = Rc::clone(&self.macros);
+                parser.expansions = Rc::clone(&self.expansions);
+                parser.expansion_depth = self.expansion_depth + 1;
+                Some(parser)
+            }
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    /// Everything the parser left unread is an error, as in `let x = m!();` with
+    /// `m` expanding to `1; 2`.
+    fn finish_expansion(&mut self, mut parser: Parser<'input>) {
+        if let Some(lexeme) = parser.peek() {
+            let error = Diagnostic::error(
+                format!(
+                    "macro expansion ignores token `{}` and any following",
+                    lexeme.text
+                ),
+                lexeme.span,
+            );
+            parser.errors.push(error);
+        }
+        self.errors.extend(parser.errors);
+    }
+
+    pub(super) fn parse_macro_items(&mut self) -> Vec<Item> {
+        let Some(mut parser) = self.expand_macro_call() else {
+            self.eat(";");
+            return vec![];
+        };
+        let mut items = vec![];
+        while !parser.at_end() {
+            items.push(parser.parse_item_or_recover());
+        }
+        self.finish_expansion(parser);
+        self.eat(";");
+        items
+    }
+
+    /// A call in a block followed by `;` or `}`. When followed by `;`, a last
+    /// expression is a statement like the others.
+    pub(super) fn parse_macro_stmts(&mut self) -> Vec<Stmt> {
+        let expansion = self.expand_macro_call();
+        let semi = self.eat(";");
+        let Some(mut parser) = expansion else {
+            return vec![];
+        };
+        let mut stmts = vec![];
+        while !parser.at_end() {
+            if parser.eat(";") {
+                continue;
+            }
+            stmts.push(parser.parse_stmt_or_recover(None));
+        }
+        self.finish_expansion(parser);
+        if let (true, Some(stmt)) = (semi, stmts.last_mut()) {
+            if let StmtKind::Expr(expr) = &stmt.kind {
+                stmt.kind = StmtKind::Semi(expr.clone());
+            }
+        }
+        stmts
+    }
+
+    pub(super) fn parse_macro_expr(&mut self) -> Expr {
+        let start = self.span();
+        let Some(mut parser) = self.expand_macro_call() else {
+            return Expr::new(ExprKind::Error, self.join(start, self.prev_span()));
+        };
+        let expr = match parser.parse_expr() {
+            Ok(expr) => expr,
+            Err(error) => {
+                parser.errors.push(error);
+                parser.pos = parser.lexemes.len();
+                Expr::new(ExprKind::Error, self.join(start, self.prev_span()))
+            }
+        };
+        self.finish_expansion(parser);
+        expr
+    }
+}
@@ -0,0 +1,974 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexical::{lex, Keyword, Lexeme, Scope, Token};
+use crate::span::{Expansions, Span};
+
+use super::ast::*;
+use super::expand::Macro;
+use super::hygiene;
+
+mod expr;
+mod item;
+mod mac;
+mod pattern;
+mod recovery;
+
+pub(crate) use mac::fragment_len;
+
+/// An error ends the current statement or item, which is then skipped by the
+/// recovery so the parsing can go on, see [`recovery`].
+pub type PResult<T> = Result<T, Diagnostic>;
+
+pub struct Parser<'input> {
+    lexemes: Vec<Lexeme<'input>>,
+    pos: usize,
+    /// Index of the matching `)`, `]` or `}` of each opening one.
+    closers: HashMap<usize, usize>,
+    errors: Vec<Diagnostic>,
+    /// Where the input ends, to point at something when the file ends too early.
+    end: usize,
+    /// Set while reading the condition of an `if`, `while` or `match`, where `x {`
+    /// opens the block and is not a struct literal.
+    no_struct_literal: bool,
+    /// The macros defined in the file, see [`mac`].
+    macros: Rc<Macros<'input>>,
+    /// The expansions of the compilation, which the parsers of expanded tokens
+    /// share.
+    expansions: Rc<RefCell<Expansions>>,
+    /// How many expansions the tokens being read come from.
+    expansion_depth: usize,
+    /// Set when only the extent of the code matters : macro calls are skipped
+    /// rather than expanded.
+    skim: bool,
+    /// Set once a macro call has been expanded, for [`hygiene`].
+    expanded: bool,
+}
+
+type Macros<'input> = HashMap<&'input str, Macro<'input>>;
+
+impl<'input> Parser<'input> {
+    /// `base` is added to every span, see [`lex`].
+    pub fn new(input: &'input str, base: usize) -> Self {
+        let mut parser = Self::from_lexemes(lex(input, base), base + input.len());
+        let (macros, errors) = mac::collect_macros(&parser.lexemes, &parser.closers);
+        parser.macros = Rc::new(macros);
+        parser.errors = errors;
+        parser
+    }
+
+    /// A parser for tokens which do not come straight from a file. `end` is where
+    /// they end, for errors about a missing token.
+    fn from_lexemes(lexemes: Vec<Lexeme<'input>>, end: usize) -> Self {
+        Self {
+            closers: match_scopes(&lexemes),
+            lexemes,
+            pos: 0,
+            errors: vec![],
+            end,
+            no_struct_literal: false,
+            macros: Rc::default(),
+            expansions: Rc::default(),
+            expansion_depth: 0,
+            skim: false,
+            expanded: false,
+        }
+    }
+
+    /// Reads every item of the file. Broken items are replaced by [`ItemKind::Error`],
+    /// see [`Parser::errors`] for what went wrong.
+    pub fn parse_module(&mut self) -> Module {
+        let start = self.span();
+        let mut items = vec![];
+        while !self.at_end() {
+            if self.at_macro_call() {
+                items.extend(self.parse_macro_items());
+                continue;
+            }
+            items.push(self.parse_item_or_recover());
+        }
+        let mut module = Module {
+            items,
+            span: self.join(start, self.prev_span()),
+        };
+        if self.expanded {
+            hygiene::rename_bindings(&mut module);
+        }
+        module
+    }
+
+    /// Numbers the expansions of the file after `expansions`, those of the files
+    /// read before.
+    pub fn with_expansions(self, expansions: Expansions) -> Self {
+        *self.expansions.borrow_mut() = expansions;
+        self
+    }
+
+    /// The expansions given to [`Parser::with_expansions`], with those of the file.
+    pub fn take_expansions(&mut self) -> Expansions {
+        self.expansions.take()
+    }
+
+    /// A span covering both `a` and `b`, see [`Expansions::join`]. Only the extent
+    /// of the code matters while skimming, and `a` stands for both.
+    fn join(&self, a: Span, b: Span) -> Span {
+        if self.skim {
+            return a;
+        }
+        self.expansions.borrow().join(a, b)
+    }
+
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<Diagnostic> {
+        self.errors
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.lexemes.len()
+    }
+
+    fn peek(&self) -> Option<&Lexeme<'input>> {
+        self.lexemes.get(self.pos)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Lexeme<'input>> {
+        self.lexemes.get(self.pos + n)
+    }
+
+    fn bump(&mut self) -> Lexeme<'input> {
+        let lexeme = self.lexemes[self.pos].clone();
+        self.pos += 1;
+        lexeme
+    }
+
+    /// The span of the next token, or an empty span at the end of the input.
+    fn span(&self) -> Span {
+        self.peek()
+            .map(|lexeme| lexeme.span)
+            .unwrap_or(Span::new(self.end, self.end))
+    }
+
+    fn prev_span(&self) -> Span {
+        match self.pos {
+            0 => Span::new(0, 0),
+            pos => self.lexemes[pos - 1].span,
+        }
+    }
+
+    /// Compares the next token's text. Identifiers and literals can never be
+    /// written like punctuation, so the text is enough to know the token.
+    fn at(&self, text: &str) -> bool {
+        self.at_nth(0, text)
+    }
+
+    fn at_nth(&self, n: usize, text: &str) -> bool {
+        self.peek_nth(n)
+            .is_some_and(|lexeme| lexeme.text == text && !matches!(lexeme.token, Token::Literal(_)))
+    }
+
+    fn at_keyword(&self, is: fn(&Keyword) -> bool) -> bool {
+        matches!(self.peek(), Some(Lexeme { token: Token::Keyword(keyword), .. }) if is(keyword))
+    }
+
+    /// At `fn`, or at `const fn`.
+    fn at_function(&self) -> bool {
+        let is_function = |lexeme: Option<&Lexeme>| {
+            matches!(
+                lexeme,
+                Some(Lexeme {
+                    token: Token::Keyword(Keyword::Function(..)),
+                    ..
+                })
+            )
+        };
+        is_function(self.peek()) || (self.at("const") && is_function(self.peek_nth(1)))
+    }
+
+    fn at_identifier(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Lexeme {
+                token: Token::Identifier(..),
+                ..
+            })
+        )
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        if self.at(text) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> PResult<Span> {
+        if self.at(text) {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&format!("`{text}`")))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let found = match self.peek() {
+            Some(lexeme) => format!("`{}`", lexeme.text),
+            None => "end of file".to_string(),
+        };
+        Diagnostic::error(format!("expected {expected}, found {found}"), self.span())
+    }
+
+    fn parse_ident(&mut self) -> PResult<Ident> {
+        if self.at_identifier() {
+            let lexeme = self.bump();
+            Ok(Ident {
+                name: lexeme.text.to_string(),
+                span: lexeme.span,
+                written: None,
+            })
+        } else {
+            Err(self.unexpected("an identifier"))
+        }
+    }
+
+    /// `a::b::c`, or `max::<T>` with type arguments as in an expression.
+    fn parse_path(&mut self) -> PResult<Path> {
+        self.parse_path_with(true)
+    }
+
+    /// `a::b::C<T>`, the path of a type or of a bound, or `Fn(T) -> U`.
+    fn parse_ty_path(&mut self) -> PResult<Path> {
+        self.parse_path_with(false)
+    }
+
+    /// In expressions, the type arguments follow a `::`, otherwise `a < b` would be
+    /// ambiguous. In types they come directly after the name.
+    fn parse_path_with(&mut self, turbofish: bool) -> PResult<Path> {
+        let first = self.parse_ident()?;
+        let mut span = first.span;
+        let mut segments = vec![PathSegment::new(first)];
+        loop {
+            let args_start = if turbofish {
+                self.at("::") && self.at_nth(1, "<")
+            } else {
+                self.at("<")
+            };
+            let last = segments.last().map(|segment| segment.ident.name.as_str());
+            if !turbofish && matches!(last, Some("Fn" | "FnMut" | "FnOnce")) && self.at("(") {
+                self.bump();
+                let args = self.parse_comma_list(")", Self::parse_ty)?;
+                let output = if self.eat("->") {
+                    Some(Box::new(self.parse_ty()?))
+                } else {
+                    None
+                };
+                span = self.join(span, self.prev_span());
+                if let Some(segment) = segments.last_mut() {
+                    segment.args = args;
+                    segment.parenthesized = true;
+                    segment.output = output;
+                }
+                return Ok(Path { segments, span });
+            }
+            if args_start {
+                if turbofish {
+                    self.bump();
+                }
+                self.bump();
+                let args = self.parse_comma_list(">", Self::parse_ty)?;
+                span = self.join(span, self.prev_span());
+                if let Some(segment) = segments.last_mut() {
+                    segment.args = args;
+                }
+            } else if self.at("::")
+                && self
+                    .peek_nth(1)
+                    .is_some_and(|l| matches!(l.token, Token::Identifier(..)))
+            {
+                self.bump();
+                let ident = self.parse_ident()?;
+                span = self.join(span, ident.span);
+                segments.push(PathSegment::new(ident));
+            } else {
+                return Ok(Path { segments, span });
+            }
+        }
+    }
+
+    /// `<T: Ord + Clone, U>`, when there is one.
+    fn parse_generics(&mut self) -> PResult<Generics> {
+        let start = self.span();
+        if !self.eat("<") {
+            return Ok(Generics {
+                span: Span::new(start.start, start.start),
+                ..Generics::default()
+            });
+        }
+        let params = self.parse_comma_list(">", |parser| {
+            let name = parser.parse_ident()?;
+            let bounds = if parser.eat(":") {
+                parser.parse_bounds()?
+            } else {
+                vec![]
+            };
+            Ok(GenericParam {
+                id: NodeId::next(),
+                span: parser.join(name.span, parser.prev_span()),
+                name,
+                bounds,
+            })
+        })?;
+        Ok(Generics {
+            params,
+            where_clause: vec![],
+            span: self.join(start, self.prev_span()),
+        })
+    }
+
+    /// `where T: Ord, Vec<T>: Debug`, up to the `{` or the `;` ending it.
+    fn parse_where_clause(&mut self, generics: &mut Generics) -> PResult<()> {
+        if !self.eat("where") {
+            return Ok(());
+        }
+        while !self.at("{") && !self.at(";") && !self.at_end() {
+            let ty = self.parse_ty()?;
+            self.expect(":")?;
+            let bounds = self.parse_bounds()?;
+            generics.where_clause.push(WherePredicate {
+                span: self.join(ty.span, self.prev_span()),
+                ty,
+                bounds,
+            });
+            if !self.eat(",") {
+                break;
+            }
+        }
+        generics.span = self.join(generics.span, self.prev_span());
+        Ok(())
+    }
+
+    /// `Ord + Into<String>`
+    fn parse_bounds(&mut self) -> PResult<Vec<Path>> {
+        let mut bounds = vec![self.parse_ty_path()?];
+        while self.eat("+") {
+            bounds.push(self.parse_ty_path()?);
+        }
+        Ok(bounds)
+    }
+
+    fn parse_ty(&mut self) -> PResult<Ty> {
+        let start = self.span();
+        if self.at("&") || self.at("&&") {
+            // `&&T` is lexed with one operator, and is `& &T`.
+            let twice = self.bump().text == "&&";
+            let inner_start = match twice {
+                true => Span {
+                    start: start.start + 1,
+                    ..start
+                },
+                false => start,
+            };
+            let mutable = self.eat("mut");
+            let inner = self.parse_ty()?;
+            let mut ty = Ty {
+                id: NodeId::next(),
+                span: self.join(inner_start, inner.span),
+                kind: TyKind::Ref {
+                    mutable,
+                    inner: Box::new(inner),
+                },
+            };
+            if twice {
+                ty = Ty {
+                    id: NodeId::next(),
+                    span: self.join(start, ty.span),
+                    kind: TyKind::Ref {
+                        mutable: false,
+                        inner: Box::new(ty),
+                    },
+                };
+            }
+            return Ok(ty);
+        }
+        if self.eat("(") {
+            let mut tys = self.parse_comma_list(")", Self::parse_ty)?;
+            // `(T)` is `T`, `(T,)` a tuple of one.
+            if tys.len() == 1 && !self.after_trailing_comma() {
+                return Ok(tys.pop().unwrap());
+            }
+            return Ok(Ty {
+                id: NodeId::next(),
+                span: self.join(start, self.prev_span()),
+                kind: TyKind::Tuple(tys),
+            });
+        }
+        if self.eat("[") {
+            let inner = Box::new(self.parse_ty()?);
+            let kind = if self.eat(";") {
+                TyKind::Array(inner, Box::new(self.parse_expr()?))
+            } else {
+                TyKind::Slice(inner)
+            };
+            let end = self.expect("]")?;
+            return Ok(Ty {
+                id: NodeId::next(),
+                span: self.join(start, end),
+                kind,
+            });
+        }
+        if self.at_keyword(|k| matches!(k, Keyword::Function(..))) {
+            self.bump();
+            self.expect("(")?;
+            let inputs = self.parse_comma_list(")", Self::parse_ty)?;
+            let output = if self.eat("->") {
+                Some(Box::new(self.parse_ty()?))
+            } else {
+                None
+            };
+            return Ok(Ty {
+                id: NodeId::next(),
+                span: self.join(start, self.prev_span()),
+                kind: TyKind::Fn { inputs, output },
+            });
+        }
+        if !self.at_identifier() {
+            return Err(self.unexpected("a type"));
+        }
+        let path = self.parse_ty_path()?;
+        Ok(Ty {
+            id: NodeId::next(),
+            span: path.span,
+            kind: TyKind::Path(path),
+        })
+    }
+
+    /// Whether the list [`Parser::parse_comma_list`] just read ends with a comma.
+    fn after_trailing_comma(&self) -> bool {
+        self.pos >= 2 && self.lexemes[self.pos - 2].text == ","
+    }
+
+    /// Parses `item (, item)* ,?` up to `close`, which is consumed.
+    fn parse_comma_list<T>(
+        &mut self,
+        close: &str,
+        mut parse: impl FnMut(&mut Self) -> PResult<T>,
+    ) -> PResult<Vec<T>> {
+        let mut list = vec![];
+        while !self.at(close) {
+            list.push(parse(self)?);
+            if !self.eat(",") {
+                if !self.at(close) {
+                    return Err(self.unexpected(&format!("`,` or `{close}`")));
+                }
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(list)
+    }
+}
+
+/// Pairs the brackets of the input, using the `Scope` tokens. `<` and `>` are left
+/// out, they are also comparisons.
+fn match_scopes(lexemes: &[Lexeme]) -> HashMap<usize, usize> {
+    let mut closers = HashMap::new();
+    let mut open: Vec<(usize, &Scope)> = vec![];
+    for (id, lexeme) in lexemes.iter().enumerate() {
+        let Token::Scope(scope) = &lexeme.token else {
+            continue;
+        };
+        match scope {
+            Scope::ParenthesisOpen(..) | Scope::SquareBracketOpen(..) | Scope::BraceOpen(..) => {
+                open.push((id, scope))
+            }
+            Scope::ParenthesisClose(..) | Scope::SquareBracketClose(..) | Scope::BraceClose(..) => {
+                let matches = |opener: &Scope| {
+                    matches!(
+                        (opener, scope),
+                        (Scope::ParenthesisOpen(..), Scope::ParenthesisClose(..))
+                            | (Scope::SquareBracketOpen(..), Scope::SquareBracketClose(..))
+                            | (Scope::BraceOpen(..), Scope::BraceClose(..))
+                    )
+                };
+                // A stray closer is left alone, the parser reports it.
+                if open.last().is_some_and(|(_, opener)| matches(opener)) {
+                    let (opener, _) = open.pop().unwrap();
+                    closers.insert(opener, id);
+                }
+            }
+            Scope::ChevronOpen(..) | Scope::ChevronClose(..) => {}
+        }
+    }
+    closers
+}
+
+/// Parses a whole file, with every syntax error found on the way.
+pub fn parse(input: &str) -> (Module, Vec<Diagnostic>) {
+    let mut parser = Parser::new(input, 0);
+    let module = parser.parse_module();
+    (module, parser.into_errors())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+    use crate::syntax::ast::*;
+
+    #[test]
+    fn enum_with_payloads() {
+        let module = parse_ok("enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty }");
+        let ItemKind::Enum(shape) = &module.items[0].kind else {
+            panic!("expected an enum");
+        };
+        assert_eq!(shape.name.name, "Shape");
+        assert!(matches!(&shape.variants[0].data, VariantData::Tuple(types) if types.len() == 1));
+        assert!(
+            matches!(&shape.variants[1].data, VariantData::Struct(fields) if fields.len() == 2)
+        );
+        assert!(matches!(shape.variants[2].data, VariantData::Unit));
+    }
+
+    #[test]
+    fn match_patterns() {
+        let input = "fn area(s: Shape) -> f64 {
+            match s {
+                Shape::Circle(r) if r > 0.0 => 3.14 * r * r,
+                Shape::Rect { w, h: height } => w * height,
+                Shape::Empty | Shape::Point(_) => 0.0,
+                _ => { 1.0 }
+            }
+        }
+        fn digit(c: char) -> i32 {
+            match c {
+                '0'..='9' => 1,
+                n @ 10..20 => n,
+                -1 => 2,
+                (a, ..) => a,
+                (b,) => b,
+                (Some(c)) => c,
+                x => 0,
+            }
+        }";
+        let module = parse_ok(input);
+        let ItemKind::Function(area) = &module.items[0].kind else {
+            panic!("expected a function");
+        };
+        let Some(Expr {
+            kind: ExprKind::Match(_, arms),
+            ..
+        }) = area.body.as_ref().and_then(Block::tail)
+        else {
+            panic!("expected a match");
+        };
+        assert!(matches!(arms[0].pat.kind, PatKind::TupleStruct(..)));
+        assert!(arms[0].guard.is_some());
+        assert!(
+            matches!(&arms[1].pat.kind, PatKind::Struct { fields, rest: false, .. } if fields.len() == 2)
+        );
+        assert!(matches!(&arms[2].pat.kind, PatKind::Or(alternatives) if alternatives.len() == 2));
+        assert!(matches!(arms[3].pat.kind, PatKind::Wild));
+
+        let ItemKind::Function(digit) = &module.items[1].kind else {
+            panic!("expected a function");
+        };
+        let Some(Expr {
+            kind: ExprKind::Match(_, arms),
+            ..
+        }) = digit.body.as_ref().and_then(Block::tail)
+        else {
+            panic!("expected a match");
+        };
+        let kinds: Vec<_> = arms.iter().map(|arm| &arm.pat.kind).collect();
+        assert!(matches!(
+            kinds[0],
+            PatKind::Range {
+                inclusive: true,
+                ..
+            }
+        ));
+        assert!(matches!(kinds[1], PatKind::Binding { sub: Some(_), .. }));
+        assert!(matches!(kinds[2], PatKind::Lit(_)));
+        assert!(matches!(kinds[3], PatKind::Tuple(_)));
+        assert!(matches!(kinds[4], PatKind::Tuple(patterns) if patterns.len() == 1));
+        assert!(matches!(kinds[5], PatKind::Paren(_)));
+        assert!(matches!(kinds[6], PatKind::Binding { sub: None, .. }));
+    }
+
+    #[test]
+    fn modules_and_uses() {
+        let module = parse_ok(
+            "mod shapes;
+            pub mod util { pub use super::shapes::{circle, Rect as R}; }
+            use shapes::*;",
+        );
+        let ItemKind::Mod(shapes) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        assert!(!shapes.inline && shapes.content.is_none());
+        assert_eq!(module.items[1].vis, Visibility::Public);
+        let ItemKind::Mod(util) = &module.items[1].kind else {
+            panic!("{:?}", module.items[1])
+        };
+        let content = util.content.as_ref().unwrap();
+        let ItemKind::Use(tree) = &content.items[0].kind else {
+            panic!("{:?}", content.items[0])
+        };
+        assert_eq!(tree.prefix.to_text(), "super::shapes");
+        let UseTreeKind::Nested(trees) = &tree.kind else {
+            panic!("{tree:?}")
+        };
+        let names: Vec<_> = trees.iter().map(|t| &t.binding().unwrap().name).collect();
+        assert_eq!(names, ["circle", "R"]);
+        let ItemKind::Use(glob) = &module.items[2].kind else {
+            panic!("{:?}", module.items[2])
+        };
+        assert_eq!(glob.kind, UseTreeKind::Glob);
+    }
+
+    #[test]
+    fn traits_and_impls() {
+        let module = parse_ok(
+            "trait Shape { type Unit; fn area(&self) -> f64; }
+            impl fmt::Display for Point { fn fmt(&mut self, mut f: Formatter) {} }",
+        );
+        let ItemKind::Trait(shape) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let ItemKind::Function(area) = &shape.items[1].kind else {
+            panic!("{:?}", shape.items[1])
+        };
+        assert!(area.body.is_none() && area.params[0].is_self());
+        let ItemKind::Impl(display) = &module.items[1].kind else {
+            panic!("{:?}", module.items[1])
+        };
+        assert_eq!(display.of_trait.as_ref().unwrap().to_text(), "fmt::Display");
+        let ItemKind::Function(fmt) = &display.items[0].kind else {
+            panic!("{:?}", display.items[0])
+        };
+        let TyKind::Ref { mutable: true, .. } = fmt.params[0].ty.kind else {
+            panic!("{:?}", fmt.params[0])
+        };
+        assert!(matches!(
+            fmt.params[1].pat.kind,
+            PatKind::Binding { mutable: true, .. }
+        ));
+
+        let (_, errors) = parse("fn f(); impl Point { fn g(self); }");
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "free function without a body",
+                "associated function in `impl` without body"
+            ]
+        );
+    }
+
+    #[test]
+    fn operator_declarations() {
+        let module = parse_ok(
+            "impl Vec2 {
+                operator -(self, other: Vec2) -> Vec2 { other }
+                operator -(self) -> Vec2 { self }
+                operator ==(&self, other: &Vec2) -> bool { true }
+            }",
+        );
+        let ItemKind::Impl(imp) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let operators: Vec<_> = imp
+            .items
+            .iter()
+            .map(|item| match &item.kind {
+                ItemKind::Function(f) => (f.name.name.as_str(), f.operator),
+                kind => panic!("{kind:?}"),
+            })
+            .collect();
+        assert_eq!(
+            operators,
+            [
+                ("sub", Some(OverloadedOp::Binary(BinOp::Sub))),
+                ("neg", Some(OverloadedOp::Unary(UnOp::Neg))),
+                ("eq", Some(OverloadedOp::Binary(BinOp::Eq))),
+            ]
+        );
+
+        let (_, errors) = parse(
+            "impl Vec2 { operator +(other: Vec2) -> Vec2 { other } }
+            impl Vec2 { operator <(self, other: Vec2) -> bool { true } }",
+        );
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "`operator +` takes `self` and the right operand",
+                "`<` cannot be overloaded with `operator`"
+            ]
+        );
+    }
+
+    #[test]
+    fn generics_and_where_clauses() {
+        let module = parse_ok(
+            "fn max<T: Ord + Clone, U>(a: T, b: Vec<Option<U>>) -> T where U: Into<String> {
+                if a < b { max::<T, U>(a, b) } else { a }
+            }",
+        );
+        let ItemKind::Function(max) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let bounds: Vec<_> = max.generics.bounds_of("T").map(Path::to_text).collect();
+        assert_eq!(bounds, ["Ord", "Clone"]);
+        let bounds: Vec<_> = max.generics.bounds_of("U").map(Path::to_text).collect();
+        assert_eq!(bounds, ["Into<String>"]);
+        assert_eq!(max.params[1].ty.to_text(), "Vec<Option<U>>");
+        // `a < b` stays a comparison, `::<` starts type arguments.
+        let Some(Expr {
+            kind: ExprKind::If(cond, then, _),
+            ..
+        }) = max.body.as_ref().and_then(Block::tail)
+        else {
+            panic!("{:?}", max.body)
+        };
+        assert!(matches!(cond.kind, ExprKind::Binary(BinOp::Lt, ..)));
+        let Some(Expr {
+            kind: ExprKind::Call(callee, _),
+            ..
+        }) = then.tail()
+        else {
+            panic!("{then:?}")
+        };
+        let ExprKind::Path(path) = &callee.kind else {
+            panic!("{callee:?}")
+        };
+        assert_eq!(path.segments[0].args.len(), 2);
+        let module = parse_ok("fn f() { s.parse::<i32>().unwrap(); }");
+        let ItemKind::Function(f) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let StmtKind::Semi(Expr {
+            kind: ExprKind::MethodCall { receiver, .. },
+            ..
+        }) = &f.body.as_ref().unwrap().stmts[0].kind
+        else {
+            panic!("{:?}", f.body)
+        };
+        let ExprKind::MethodCall {
+            method,
+            generic_args,
+            ..
+        } = &receiver.kind
+        else {
+            panic!("{receiver:?}")
+        };
+        assert_eq!(method.name, "parse");
+        assert_eq!(generic_args[0].to_text(), "i32");
+    }
+
+    #[test]
+    fn closure_trait_bounds() {
+        let module = parse_ok(
+            "fn apply<F: Fn(i32, &str) -> i32, G>(f: F, g: G) where G: FnMut() + Clone {}",
+        );
+        let ItemKind::Function(apply) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let bounds: Vec<_> = apply.generics.bounds_of("F").map(Path::to_text).collect();
+        assert_eq!(bounds, ["Fn(i32, &str) -> i32"]);
+        let bounds: Vec<_> = apply.generics.bounds_of("G").map(Path::to_text).collect();
+        assert_eq!(bounds, ["FnMut()", "Clone"]);
+    }
+
+    #[test]
+    fn attributes() {
+        let module = parse_ok(
+            "#[derive(Debug, Clone)]
+            pub struct Point { #[serde(rename = \"X\")] x: i32 }
+            enum E { #[default] A }
+            #[deprecated(since = \"0.2\", note = \"old\")] #[inline]
+            fn f() { #[allow(unused)] let x = 1; }",
+        );
+        let item = &module.items[0];
+        let derives: Vec<_> = item.attrs[0].derives().map(Path::to_text).collect();
+        assert_eq!(derives, ["Debug", "Clone"]);
+        // The span of the item starts at its attributes.
+        assert_eq!(item.span.start, 0);
+        let ItemKind::Struct(point) = &item.kind else {
+            panic!("{item:?}")
+        };
+        assert_eq!(
+            point.fields[0].attrs[0].meta.to_text(),
+            "serde(rename = \"X\")"
+        );
+        let ItemKind::Enum(e) = &module.items[1].kind else {
+            panic!("{:?}", module.items[1])
+        };
+        assert_eq!(e.variants[0].attrs[0].name(), "default");
+        let f = &module.items[2];
+        let names: Vec<_> = f.attrs.iter().map(Attribute::name).collect();
+        assert_eq!(names, ["deprecated", "inline"]);
+        let ItemKind::Function(f) = &f.kind else {
+            panic!("{f:?}")
+        };
+        let stmt = &f.body.as_ref().unwrap().stmts[0];
+        assert_eq!(stmt.attrs[0].meta.to_text(), "allow(unused)");
+    }
+
+    #[test]
+    fn double_borrows() {
+        let module = parse_ok(
+            "fn f(q: &&str) -> bool {
+                let r = &&mut q;
+                a && &b
+            }",
+        );
+        let ItemKind::Function(f) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let TyKind::Ref { mutable, inner } = &f.params[0].ty.kind else {
+            panic!("{:?}", f.params[0].ty)
+        };
+        assert!(!mutable);
+        assert_eq!(inner.to_text(), "&str");
+        let body = f.body.as_ref().unwrap();
+        let StmtKind::Let(local) = &body.stmts[0].kind else {
+            panic!("{:?}", body.stmts[0])
+        };
+        let Some(Expr {
+            kind: ExprKind::AddrOf { mutable, expr },
+            ..
+        }) = &local.init
+        else {
+            panic!("{local:?}")
+        };
+        assert!(!mutable);
+        assert!(matches!(
+            &expr.kind,
+            ExprKind::AddrOf { mutable: true, expr } if matches!(expr.kind, ExprKind::Path(_))
+        ));
+        // Between operands, `&&` stays the boolean operator.
+        assert!(matches!(
+            body.tail().map(|tail| &tail.kind),
+            Some(ExprKind::Binary(BinOp::And, ..))
+        ));
+    }
+
+    #[test]
+    fn tuples_arrays_and_ranges() {
+        let module = parse_ok(
+            "fn f(pair: (i32, (bool,)), buf: &[u8], grid: [[u8; 4]; N]) {
+                let unit: () = ();
+                let p = (1).max(2);
+                let xs = [(1, 2), (3, 4,)];
+                let c = pair.1.0 + xs[0].1;
+                for i in 0..=n { buf[i..]; }
+                for x in .. {}
+            }",
+        );
+        let ItemKind::Function(f) = &module.items[0].kind else {
+            panic!("{:?}", module.items[0])
+        };
+        let types: Vec<_> = f.params.iter().map(|param| param.ty.to_text()).collect();
+        assert_eq!(types, ["(i32, (bool,))", "&[u8]", "[[u8; _]; _]"]);
+        let stmts = &f.body.as_ref().unwrap().stmts;
+        let init = |stmt: &Stmt| match &stmt.kind {
+            StmtKind::Let(local) => local.init.clone().unwrap().kind,
+            kind => panic!("{kind:?}"),
+        };
+        assert!(matches!(init(&stmts[0]), ExprKind::Tuple(elements) if elements.is_empty()));
+        let ExprKind::MethodCall { receiver, .. } = init(&stmts[1]) else {
+            panic!("{:?}", stmts[1])
+        };
+        assert!(matches!(receiver.kind, ExprKind::Paren(_)));
+        let ExprKind::Array(elements) = init(&stmts[2]) else {
+            panic!("{:?}", stmts[2])
+        };
+        assert!(elements
+            .iter()
+            .all(|element| matches!(&element.kind, ExprKind::Tuple(pair) if pair.len() == 2)));
+        let ExprKind::Binary(_, lhs, rhs) = init(&stmts[3]) else {
+            panic!("{:?}", stmts[3])
+        };
+        let ExprKind::Field(inner, index) = &lhs.kind else {
+            panic!("{lhs:?}")
+        };
+        assert_eq!(index.name, "0");
+        assert!(matches!(&inner.kind, ExprKind::Field(_, index) if index.name == "1"));
+        assert!(
+            matches!(&rhs.kind, ExprKind::Field(base, _) if matches!(base.kind, ExprKind::Index(..)))
+        );
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::ForLoop { iter, .. },
+            ..
+        }) = &stmts[4].kind
+        else {
+            panic!("{:?}", stmts[4])
+        };
+        assert!(matches!(
+            iter.kind,
+            ExprKind::Range {
+                lo: Some(_),
+                hi: Some(_),
+                inclusive: true
+            }
+        ));
+        // The `{` after `..` opens the body.
+        assert!(matches!(
+            &stmts[5].kind,
+            StmtKind::Expr(Expr {
+                kind: ExprKind::ForLoop { iter, .. },
+                ..
+            }) if matches!(iter.kind, ExprKind::Range { lo: None, hi: None, .. })
+        ));
+    }
+
+    fn parse_ok(input: &str) -> Module {
+        let (module, errors) = parse(input);
+        assert!(errors.is_empty(), "{errors:?}");
+        module
+    }
+
+    #[test]
+    fn recovers_from_independent_errors() {
+        let input = "fn one() {
+            let x = 1
+            let = 2;
+            foo(1 2);
+            let y = 3;
+        }
+        struct Broken { a: }
+        fn two() -> i32 {
+            match x {
+                1 => ),
+                _ => 2,
+            }
+        }";
+        let (module, errors) = parse(input);
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "expected `;`, found `let`",
+                "expected a pattern, found `=`",
+                "expected `,` or `)`, found `2`",
+                "expected a type, found `}`",
+                "expected an expression, found `)`",
+            ]
+        );
+        assert_eq!(module.items.len(), 3);
+        assert!(matches!(module.items[1].kind, ItemKind::Error));
+        let ItemKind::Function(one) = &module.items[0].kind else {
+            panic!("expected a function");
+        };
+        // The statements after the errors are still there.
+        let body = one.body.as_ref().unwrap();
+        assert_eq!(body.stmts.len(), 4);
+        assert!(matches!(body.stmts[3].kind, StmtKind::Let(_)));
+    }
+}
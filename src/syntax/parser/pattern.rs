@@ -0,0 +1,200 @@
+use crate::lexical::{Lexeme, Token};
+use crate::span::Span;
+
+use super::{PResult, Parser};
+use crate::syntax::ast::*;
+
+impl Parser<'_> {
+    /// `A | B | C`, a leading `|` is allowed.
+    pub(super) fn parse_pattern(&mut self) -> PResult<Pattern> {
+        let start = self.span();
+        self.eat("|");
+        let first = self.parse_single_pattern()?;
+        if !self.at("|") {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.eat("|") {
+            alternatives.push(self.parse_single_pattern()?);
+        }
+        Ok(Pattern::new(
+            PatKind::Or(alternatives),
+            self.join(start, self.prev_span()),
+        ))
+    }
+
+    pub(super) fn parse_single_pattern(&mut self) -> PResult<Pattern> {
+        let start = self.span();
+        if self.eat("..=") {
+            let hi = self.parse_range_bound()?;
+            let span = self.join(start, hi.span);
+            return Ok(Pattern::new(
+                PatKind::Range {
+                    lo: None,
+                    hi: Some(Box::new(hi)),
+                    inclusive: true,
+                },
+                span,
+            ));
+        }
+        if self.eat("..") {
+            return Ok(Pattern::new(PatKind::Rest, start));
+        }
+        if self.at("-") || self.at_lit() {
+            let lo = self.parse_range_bound()?;
+            return self.parse_range_rest(lo);
+        }
+        if self.eat("(") {
+            let mut patterns = self.parse_comma_list(")", Self::parse_pattern)?;
+            let span = self.join(start, self.prev_span());
+            if patterns.len() == 1 && !self.after_trailing_comma() {
+                let inner = patterns.pop().unwrap();
+                return Ok(Pattern::new(PatKind::Paren(Box::new(inner)), span));
+            }
+            return Ok(Pattern::new(PatKind::Tuple(patterns), span));
+        }
+        if self.at("_") {
+            self.bump();
+            return Ok(Pattern::new(PatKind::Wild, start));
+        }
+        if self.eat("mut") {
+            return self.parse_binding(true, start);
+        }
+        if !self.at_identifier() {
+            return Err(self.unexpected("a pattern"));
+        }
+        // A lone lowercase name is a new binding, anything else names a variant,
+        // a struct or a constant : `x` binds, `None` and `Shape::Empty` compare.
+        let is_binding = !self.at_nth(1, "::")
+            && !self.at_nth(1, "(")
+            && !self.at_nth(1, "{")
+            && self
+                .peek()
+                .is_some_and(|l| !l.text.starts_with(char::is_uppercase));
+        if is_binding {
+            return self.parse_binding(false, start);
+        }
+        let path = self.parse_path()?;
+        if self.eat("(") {
+            let patterns = self.parse_comma_list(")", Self::parse_pattern)?;
+            let span = self.join(start, self.prev_span());
+            return Ok(Pattern::new(PatKind::TupleStruct(path, patterns), span));
+        }
+        if self.eat("{") {
+            return self.parse_struct_pattern(path);
+        }
+        Ok(Pattern::new(PatKind::Path(path.clone()), path.span))
+    }
+
+    /// `x`, `mut x`, `x @ 1..=5`
+    fn parse_binding(&mut self, mutable: bool, start: Span) -> PResult<Pattern> {
+        let name = self.parse_ident()?;
+        let sub = if self.eat("@") {
+            Some(Box::new(self.parse_single_pattern()?))
+        } else {
+            None
+        };
+        Ok(Pattern::new(
+            PatKind::Binding { name, mutable, sub },
+            self.join(start, self.prev_span()),
+        ))
+    }
+
+    /// `Rect { w, h: height, .. }`, once the path and the `{` are read.
+    fn parse_struct_pattern(&mut self, path: Path) -> PResult<Pattern> {
+        let mut fields = vec![];
+        let mut rest = false;
+        while !self.at("}") {
+            if self.eat("..") {
+                rest = true;
+                break;
+            }
+            let start = self.span();
+            let mutable = self.eat("mut");
+            let name = self.parse_ident()?;
+            let field = if !mutable && self.eat(":") {
+                let pat = self.parse_pattern()?;
+                FieldPat {
+                    span: self.join(start, pat.span),
+                    name,
+                    pat,
+                    shorthand: false,
+                }
+            } else {
+                let span = self.join(start, name.span);
+                let binding = PatKind::Binding {
+                    name: name.clone(),
+                    mutable,
+                    sub: None,
+                };
+                FieldPat {
+                    name,
+                    pat: Pattern::new(binding, span),
+                    shorthand: true,
+                    span,
+                }
+            };
+            fields.push(field);
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect("}")?;
+        let span = self.join(path.span, self.prev_span());
+        Ok(Pattern::new(PatKind::Struct { path, fields, rest }, span))
+    }
+
+    fn at_lit(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Lexeme {
+                token: Token::Literal(_),
+                ..
+            })
+        ) || self.at("true")
+            || self.at("false")
+    }
+
+    /// A literal, possibly negated, as a bound or the whole pattern.
+    fn parse_range_bound(&mut self) -> PResult<Expr> {
+        let start = self.span();
+        let negated = self.eat("-");
+        let lit = match self.parse_lit()? {
+            Some(lit) => lit,
+            None => return Err(self.unexpected("a literal")),
+        };
+        let expr = Expr::new(ExprKind::Lit(lit), self.prev_span());
+        if negated {
+            let span = self.join(start, expr.span);
+            return Ok(Expr::new(ExprKind::Unary(UnOp::Neg, Box::new(expr)), span));
+        }
+        Ok(expr)
+    }
+
+    /// What may follow the first literal : `..=hi`, `..hi`, `..` or nothing.
+    fn parse_range_rest(&mut self, lo: Expr) -> PResult<Pattern> {
+        let start = lo.span;
+        let inclusive = if self.eat("..=") {
+            true
+        } else if self.eat("..") {
+            false
+        } else {
+            return Ok(Pattern::new(PatKind::Lit(Box::new(lo)), start));
+        };
+        let hi = if self.at("-") || self.at_lit() {
+            Some(Box::new(self.parse_range_bound()?))
+        } else if inclusive {
+            return Err(self.unexpected("the end of the range"));
+        } else {
+            None
+        };
+        Ok(Pattern::new(
+            PatKind::Range {
+                lo: Some(Box::new(lo)),
+                hi,
+                inclusive,
+            },
+            self.join(start, self.prev_span()),
+        ))
+    }
+}
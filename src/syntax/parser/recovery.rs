@@ -0,0 +1,138 @@
+//! Panic-mode recovery : after an error the parser skips tokens up to a point where
+//! it can start again, the next item or statement, and leaves an `Error` node
+//! behind. Bracketed groups are skipped whole, using the pairs found by
+//! [`super::match_scopes`], so a `;` or a `fn` inside them does not stop the skip.
+
+use crate::lexical::Keyword;
+use crate::span::Span;
+
+use super::Parser;
+use crate::syntax::ast::*;
+
+impl Parser<'_> {
+    pub(super) fn parse_item_or_recover(&mut self) -> Item {
+        let start = self.pos;
+        match self.parse_item() {
+            Ok(item) => item,
+            Err(error) => {
+                self.errors.push(error);
+                self.skip_until(start, None, |parser| parser.at_item_start());
+                Item {
+                    id: NodeId::next(),
+                    attrs: vec![],
+                    vis: Visibility::Private,
+                    kind: ItemKind::Error,
+                    span: self.skipped_span(start),
+                }
+            }
+        }
+    }
+
+    /// `close` is the index of the `}` ending the trait or the impl.
+    pub(super) fn parse_assoc_item_or_recover(
+        &mut self,
+        in_trait: bool,
+        close: Option<usize>,
+    ) -> Item {
+        let start = self.pos;
+        match self.parse_assoc_item(in_trait) {
+            Ok(item) => item,
+            Err(error) => {
+                self.errors.push(error);
+                self.skip_until(start, close, |parser| parser.at_item_start());
+                Item {
+                    id: NodeId::next(),
+                    attrs: vec![],
+                    vis: Visibility::Private,
+                    kind: ItemKind::Error,
+                    span: self.skipped_span(start),
+                }
+            }
+        }
+    }
+
+    /// `close` is the index of the `}` ending the current block.
+    pub(super) fn parse_stmt_or_recover(&mut self, close: Option<usize>) -> Stmt {
+        let start = self.pos;
+        match self.parse_stmt() {
+            Ok(stmt) => stmt,
+            Err(error) => {
+                self.errors.push(error);
+                self.skip_until(start, close, |parser| {
+                    parser.eat(";") || parser.at("let") || parser.at_item_start()
+                });
+                let span = self.skipped_span(start);
+                Stmt {
+                    attrs: vec![],
+                    kind: StmtKind::Semi(Expr::new(ExprKind::Error, span)),
+                    span,
+                }
+            }
+        }
+    }
+
+    /// `close` is the index of the `}` ending the `match`.
+    pub(super) fn parse_arm_or_recover(&mut self, close: Option<usize>) -> Arm {
+        let start = self.pos;
+        match self.parse_arm() {
+            Ok(arm) => arm,
+            Err(error) => {
+                self.errors.push(error);
+                self.skip_until(start, close, |parser| parser.eat(","));
+                let span = self.skipped_span(start);
+                Arm {
+                    pat: Pattern::new(PatKind::Error, span),
+                    guard: None,
+                    body: Expr::new(ExprKind::Error, span),
+                    span,
+                }
+            }
+        }
+    }
+
+    fn at_item_start(&self) -> bool {
+        self.at("#")
+            || self.at_keyword(|k| {
+                matches!(
+                    k,
+                    Keyword::Function(..)
+                        | Keyword::Struct(..)
+                        | Keyword::Enum(..)
+                        | Keyword::Mod(..)
+                        | Keyword::Use(..)
+                        | Keyword::Pub(..)
+                        | Keyword::Trait(..)
+                        | Keyword::Impl(..)
+                        | Keyword::Type(..)
+                        | Keyword::Const(..)
+                        | Keyword::Static(..)
+                        | Keyword::Macro(..)
+                )
+            })
+    }
+
+    /// Skips tokens until `stop` says so, or up to the closer at index `close`.
+    /// The error is found on the token that could not be read, which may already be
+    /// where to stop (`let` after a forgotten `;`), as long as the parser moved since
+    /// `start`. Otherwise at least one token is skipped.
+    fn skip_until(&mut self, start: usize, close: Option<usize>, stop: impl Fn(&mut Self) -> bool) {
+        while !self.at_end() && Some(self.pos) != close {
+            if self.pos > start && stop(self) {
+                return;
+            }
+            match self.closers.get(&self.pos) {
+                Some(&closer) => self.pos = closer + 1,
+                None => self.pos += 1,
+            }
+        }
+    }
+
+    fn skipped_span(&self, start: usize) -> Span {
+        let first = self
+            .lexemes
+            .get(start)
+            .map(|lexeme| lexeme.span)
+            .unwrap_or(self.span());
+        self.join(first, self.prev_span())
+    }
+}
@@ -0,0 +1,497 @@
+//! Walks the tree by reference. Each `visit_*` method goes into the children of its
+//! node through the matching `walk_*` function, so a pass overrides the nodes it
+//! cares about and calls `walk_*` when it still wants the children visited.
+
+use super::ast::*;
+
+pub trait Visitor<'ast>: Sized {
+    fn visit_module(&mut self, module: &'ast Module) {
+        walk_module(self, module)
+    }
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item)
+    }
+    fn visit_mod(&mut self, item: &'ast Mod) {
+        walk_mod(self, item)
+    }
+    fn visit_use_tree(&mut self, tree: &'ast UseTree) {
+        walk_use_tree(self, tree)
+    }
+    fn visit_trait(&mut self, item: &'ast Trait) {
+        walk_trait(self, item)
+    }
+    fn visit_impl(&mut self, item: &'ast Impl) {
+        walk_impl(self, item)
+    }
+    fn visit_ty_alias(&mut self, item: &'ast TyAlias) {
+        walk_ty_alias(self, item)
+    }
+    fn visit_const(&mut self, item: &'ast Const) {
+        walk_const(self, item)
+    }
+    fn visit_generics(&mut self, generics: &'ast Generics) {
+        walk_generics(self, generics)
+    }
+    fn visit_function(&mut self, function: &'ast Function) {
+        walk_function(self, function)
+    }
+    fn visit_param(&mut self, param: &'ast Param) {
+        walk_param(self, param)
+    }
+    fn visit_struct(&mut self, item: &'ast Struct) {
+        walk_struct(self, item)
+    }
+    fn visit_enum(&mut self, item: &'ast Enum) {
+        walk_enum(self, item)
+    }
+    fn visit_variant(&mut self, variant: &'ast Variant) {
+        walk_variant(self, variant)
+    }
+    fn visit_field_def(&mut self, field: &'ast FieldDef) {
+        walk_field_def(self, field)
+    }
+    fn visit_ty(&mut self, ty: &'ast Ty) {
+        walk_ty(self, ty)
+    }
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block)
+    }
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        walk_stmt(self, stmt)
+    }
+    fn visit_local(&mut self, local: &'ast Local) {
+        walk_local(self, local)
+    }
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr)
+    }
+    fn visit_closure(&mut self, closure: &'ast Closure) {
+        walk_closure(self, closure)
+    }
+    fn visit_arm(&mut self, arm: &'ast Arm) {
+        walk_arm(self, arm)
+    }
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        walk_pattern(self, pattern)
+    }
+    fn visit_path(&mut self, path: &'ast Path) {
+        walk_path(self, path)
+    }
+    fn visit_attribute(&mut self, _attr: &'ast Attribute) {}
+    fn visit_ident(&mut self, _ident: &'ast Ident) {}
+    fn visit_lit(&mut self, _lit: &'ast Lit) {}
+}
+
+pub fn walk_module<'ast, V: Visitor<'ast>>(visitor: &mut V, module: &'ast Module) {
+    for item in &module.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Item) {
+    for attr in &item.attrs {
+        visitor.visit_attribute(attr);
+    }
+    match &item.kind {
+        ItemKind::Function(function) => visitor.visit_function(function),
+        ItemKind::Struct(item) => visitor.visit_struct(item),
+        ItemKind::Enum(item) => visitor.visit_enum(item),
+        ItemKind::Mod(item) => visitor.visit_mod(item),
+        ItemKind::Use(tree) => visitor.visit_use_tree(tree),
+        ItemKind::Trait(item) => visitor.visit_trait(item),
+        ItemKind::Impl(item) => visitor.visit_impl(item),
+        ItemKind::TyAlias(item) => visitor.visit_ty_alias(item),
+        ItemKind::Const(item) | ItemKind::Static(item) => visitor.visit_const(item),
+        ItemKind::MacroDef(def) => visitor.visit_ident(&def.name),
+        ItemKind::Error => {}
+    }
+}
+
+pub fn walk_trait<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Trait) {
+    visitor.visit_ident(&item.name);
+    visitor.visit_generics(&item.generics);
+    for bound in &item.supertraits {
+        visitor.visit_path(bound);
+    }
+    for item in &item.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_impl<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Impl) {
+    visitor.visit_generics(&item.generics);
+    if let Some(path) = &item.of_trait {
+        visitor.visit_path(path);
+    }
+    visitor.visit_ty(&item.self_ty);
+    for item in &item.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_ty_alias<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast TyAlias) {
+    visitor.visit_ident(&item.name);
+    if let Some(ty) = &item.ty {
+        visitor.visit_ty(ty);
+    }
+}
+
+pub fn walk_const<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Const) {
+    visitor.visit_ident(&item.name);
+    visitor.visit_ty(&item.ty);
+    visitor.visit_expr(&item.expr);
+}
+
+pub fn walk_mod<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Mod) {
+    visitor.visit_ident(&item.name);
+    if let Some(content) = &item.content {
+        visitor.visit_module(content);
+    }
+}
+
+pub fn walk_use_tree<'ast, V: Visitor<'ast>>(visitor: &mut V, tree: &'ast UseTree) {
+    visitor.visit_path(&tree.prefix);
+    match &tree.kind {
+        UseTreeKind::Simple(rename) => {
+            if let Some(rename) = rename {
+                visitor.visit_ident(rename);
+            }
+        }
+        UseTreeKind::Nested(trees) => {
+            for tree in trees {
+                visitor.visit_use_tree(tree);
+            }
+        }
+        UseTreeKind::Glob => {}
+    }
+}
+
+pub fn walk_generics<'ast, V: Visitor<'ast>>(visitor: &mut V, generics: &'ast Generics) {
+    for param in &generics.params {
+        visitor.visit_ident(&param.name);
+        for bound in &param.bounds {
+            visitor.visit_path(bound);
+        }
+    }
+    for predicate in &generics.where_clause {
+        visitor.visit_ty(&predicate.ty);
+        for bound in &predicate.bounds {
+            visitor.visit_path(bound);
+        }
+    }
+}
+
+pub fn walk_function<'ast, V: Visitor<'ast>>(visitor: &mut V, function: &'ast Function) {
+    visitor.visit_ident(&function.name);
+    visitor.visit_generics(&function.generics);
+    for param in &function.params {
+        visitor.visit_param(param);
+    }
+    if let Some(output) = &function.output {
+        visitor.visit_ty(output);
+    }
+    if let Some(body) = &function.body {
+        visitor.visit_block(body);
+    }
+}
+
+pub fn walk_param<'ast, V: Visitor<'ast>>(visitor: &mut V, param: &'ast Param) {
+    visitor.visit_pattern(&param.pat);
+    visitor.visit_ty(&param.ty);
+}
+
+pub fn walk_struct<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Struct) {
+    visitor.visit_ident(&item.name);
+    visitor.visit_generics(&item.generics);
+    for field in &item.fields {
+        visitor.visit_field_def(field);
+    }
+}
+
+pub fn walk_enum<'ast, V: Visitor<'ast>>(visitor: &mut V, item: &'ast Enum) {
+    visitor.visit_ident(&item.name);
+    visitor.visit_generics(&item.generics);
+    for variant in &item.variants {
+        visitor.visit_variant(variant);
+    }
+}
+
+pub fn walk_variant<'ast, V: Visitor<'ast>>(visitor: &mut V, variant: &'ast Variant) {
+    for attr in &variant.attrs {
+        visitor.visit_attribute(attr);
+    }
+    visitor.visit_ident(&variant.name);
+    match &variant.data {
+        VariantData::Unit => {}
+        VariantData::Tuple(types) => {
+            for ty in types {
+                visitor.visit_ty(ty);
+            }
+        }
+        VariantData::Struct(fields) => {
+            for field in fields {
+                visitor.visit_field_def(field);
+            }
+        }
+    }
+}
+
+pub fn walk_field_def<'ast, V: Visitor<'ast>>(visitor: &mut V, field: &'ast FieldDef) {
+    for attr in &field.attrs {
+        visitor.visit_attribute(attr);
+    }
+    visitor.visit_ident(&field.name);
+    visitor.visit_ty(&field.ty);
+}
+
+pub fn walk_ty<'ast, V: Visitor<'ast>>(visitor: &mut V, ty: &'ast Ty) {
+    match &ty.kind {
+        TyKind::Path(path) => visitor.visit_path(path),
+        TyKind::Ref { inner, .. } | TyKind::Slice(inner) => visitor.visit_ty(inner),
+        TyKind::Tuple(tys) => {
+            for ty in tys {
+                visitor.visit_ty(ty);
+            }
+        }
+        TyKind::Fn { inputs, output } => {
+            for ty in inputs.iter().chain(output.as_deref()) {
+                visitor.visit_ty(ty);
+            }
+        }
+        TyKind::Array(inner, len) => {
+            visitor.visit_ty(inner);
+            visitor.visit_expr(len);
+        }
+    }
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast>>(visitor: &mut V, block: &'ast Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<'ast, V: Visitor<'ast>>(visitor: &mut V, stmt: &'ast Stmt) {
+    for attr in &stmt.attrs {
+        visitor.visit_attribute(attr);
+    }
+    match &stmt.kind {
+        StmtKind::Let(local) => visitor.visit_local(local),
+        StmtKind::Expr(expr) | StmtKind::Semi(expr) => visitor.visit_expr(expr),
+    }
+}
+
+pub fn walk_local<'ast, V: Visitor<'ast>>(visitor: &mut V, local: &'ast Local) {
+    // The initializer and the `else` block are visited first : they cannot see the
+    // bindings of the pattern.
+    if let Some(init) = &local.init {
+        visitor.visit_expr(init);
+    }
+    if let Some(els) = &local.els {
+        visitor.visit_block(els);
+    }
+    visitor.visit_pattern(&local.pat);
+    if let Some(ty) = &local.ty {
+        visitor.visit_ty(ty);
+    }
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast>>(visitor: &mut V, expr: &'ast Expr) {
+    match &expr.kind {
+        ExprKind::Lit(lit) => visitor.visit_lit(lit),
+        ExprKind::Path(path) => visitor.visit_path(path),
+        ExprKind::Unary(_, operand) => visitor.visit_expr(operand),
+        ExprKind::Binary(_, lhs, rhs) | ExprKind::Assign(lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        ExprKind::AddrOf { expr, .. } | ExprKind::Paren(expr) | ExprKind::Try(expr) => {
+            visitor.visit_expr(expr)
+        }
+        ExprKind::Call(callee, args) => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::MethodCall {
+            receiver,
+            method,
+            generic_args,
+            args,
+            ..
+        } => {
+            visitor.visit_expr(receiver);
+            visitor.visit_ident(method);
+            for ty in generic_args {
+                visitor.visit_ty(ty);
+            }
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Field(base, name) => {
+            visitor.visit_expr(base);
+            visitor.visit_ident(name);
+        }
+        ExprKind::Index(base, index) | ExprKind::Repeat(base, index) => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        ExprKind::Tuple(elements) | ExprKind::Array(elements) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        ExprKind::Range { lo, hi, .. } => {
+            if let Some(lo) = lo {
+                visitor.visit_expr(lo);
+            }
+            if let Some(hi) = hi {
+                visitor.visit_expr(hi);
+            }
+        }
+        ExprKind::StructLit { path, fields } => {
+            visitor.visit_path(path);
+            for field in fields {
+                visitor.visit_ident(&field.name);
+                visitor.visit_expr(&field.expr);
+            }
+        }
+        ExprKind::Block(block) | ExprKind::Loop(block) => visitor.visit_block(block),
+        ExprKind::If(cond, then, otherwise) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expr(otherwise);
+            }
+        }
+        ExprKind::While(cond, body) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(body);
+        }
+        ExprKind::ForLoop { pat, iter, body } => {
+            visitor.visit_expr(iter);
+            visitor.visit_pattern(pat);
+            visitor.visit_block(body);
+        }
+        ExprKind::Match(scrutinee, arms) => {
+            visitor.visit_expr(scrutinee);
+            for arm in arms {
+                visitor.visit_arm(arm);
+            }
+        }
+        ExprKind::Closure(closure) => visitor.visit_closure(closure),
+        ExprKind::Return(value) | ExprKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Continue | ExprKind::Error => {}
+    }
+}
+
+pub fn walk_closure<'ast, V: Visitor<'ast>>(visitor: &mut V, closure: &'ast Closure) {
+    for param in &closure.params {
+        visitor.visit_pattern(&param.pat);
+        if let Some(ty) = &param.ty {
+            visitor.visit_ty(ty);
+        }
+    }
+    if let Some(output) = &closure.output {
+        visitor.visit_ty(output);
+    }
+    visitor.visit_expr(&closure.body);
+}
+
+pub fn walk_arm<'ast, V: Visitor<'ast>>(visitor: &mut V, arm: &'ast Arm) {
+    visitor.visit_pattern(&arm.pat);
+    if let Some(guard) = &arm.guard {
+        visitor.visit_expr(guard);
+    }
+    visitor.visit_expr(&arm.body);
+}
+
+pub fn walk_pattern<'ast, V: Visitor<'ast>>(visitor: &mut V, pattern: &'ast Pattern) {
+    match &pattern.kind {
+        PatKind::Wild | PatKind::Rest | PatKind::Error => {}
+        PatKind::Binding { name, sub, .. } => {
+            visitor.visit_ident(name);
+            if let Some(sub) = sub {
+                visitor.visit_pattern(sub);
+            }
+        }
+        PatKind::Paren(inner) => visitor.visit_pattern(inner),
+        PatKind::Lit(expr) => visitor.visit_expr(expr),
+        PatKind::Range { lo, hi, .. } => {
+            for bound in [lo, hi].into_iter().flatten() {
+                visitor.visit_expr(bound);
+            }
+        }
+        PatKind::Tuple(patterns) | PatKind::Or(patterns) => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        PatKind::TupleStruct(path, patterns) => {
+            visitor.visit_path(path);
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        PatKind::Struct { path, fields, .. } => {
+            visitor.visit_path(path);
+            for field in fields {
+                visitor.visit_ident(&field.name);
+                visitor.visit_pattern(&field.pat);
+            }
+        }
+        PatKind::Path(path) => visitor.visit_path(path),
+    }
+}
+
+pub fn walk_path<'ast, V: Visitor<'ast>>(visitor: &mut V, path: &'ast Path) {
+    for segment in &path.segments {
+        visitor.visit_ident(&segment.ident);
+        for arg in &segment.args {
+            visitor.visit_ty(arg);
+        }
+        if let Some(output) = &segment.output {
+            visitor.visit_ty(output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{walk_expr, Visitor};
+    use crate::syntax::{ast::*, parse};
+
+    /// Only overrides expressions, and still reaches the ones nested in patterns and arms.
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl<'ast> Visitor<'ast> for Calls {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let ExprKind::Call(callee, _) = &expr.kind {
+                if let ExprKind::Path(path) = &callee.kind {
+                    self.0.push(path.to_text());
+                }
+            }
+            walk_expr(self, expr)
+        }
+    }
+
+    #[test]
+    fn visits_nested_nodes() {
+        let (module, _) = parse(
+            "fn main() {
+                let a = first(second(1));
+                match a { x if third(x) => { fourth() } _ => 0 }
+            }",
+        );
+        let mut calls = Calls::default();
+        calls.visit_module(&module);
+        assert_eq!(calls.0, ["first", "second", "third", "fourth"]);
+    }
+}
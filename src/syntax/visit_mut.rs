@@ -0,0 +1,462 @@
+//! Walks the tree by mutable reference, to change nodes in place. Works like
+//! [`super::visit`], with the same `visit_*` and `walk_*` pairs.
+
+use super::ast::*;
+
+pub trait VisitorMut: Sized {
+    fn visit_module(&mut self, module: &mut Module) {
+        walk_module(self, module)
+    }
+    fn visit_item(&mut self, item: &mut Item) {
+        walk_item(self, item)
+    }
+    fn visit_mod(&mut self, item: &mut Mod) {
+        walk_mod(self, item)
+    }
+    fn visit_use_tree(&mut self, tree: &mut UseTree) {
+        walk_use_tree(self, tree)
+    }
+    fn visit_trait(&mut self, item: &mut Trait) {
+        walk_trait(self, item)
+    }
+    fn visit_impl(&mut self, item: &mut Impl) {
+        walk_impl(self, item)
+    }
+    fn visit_ty_alias(&mut self, item: &mut TyAlias) {
+        walk_ty_alias(self, item)
+    }
+    fn visit_const(&mut self, item: &mut Const) {
+        walk_const(self, item)
+    }
+    fn visit_generics(&mut self, generics: &mut Generics) {
+        walk_generics(self, generics)
+    }
+    fn visit_function(&mut self, function: &mut Function) {
+        walk_function(self, function)
+    }
+    fn visit_param(&mut self, param: &mut Param) {
+        walk_param(self, param)
+    }
+    fn visit_struct(&mut self, item: &mut Struct) {
+        walk_struct(self, item)
+    }
+    fn visit_enum(&mut self, item: &mut Enum) {
+        walk_enum(self, item)
+    }
+    fn visit_variant(&mut self, variant: &mut Variant) {
+        walk_variant(self, variant)
+    }
+    fn visit_field_def(&mut self, field: &mut FieldDef) {
+        walk_field_def(self, field)
+    }
+    fn visit_ty(&mut self, ty: &mut Ty) {
+        walk_ty(self, ty)
+    }
+    fn visit_block(&mut self, block: &mut Block) {
+        walk_block(self, block)
+    }
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        walk_stmt(self, stmt)
+    }
+    fn visit_local(&mut self, local: &mut Local) {
+        walk_local(self, local)
+    }
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr(self, expr)
+    }
+    fn visit_closure(&mut self, closure: &mut Closure) {
+        walk_closure(self, closure)
+    }
+    fn visit_arm(&mut self, arm: &mut Arm) {
+        walk_arm(self, arm)
+    }
+    fn visit_pattern(&mut self, pattern: &mut Pattern) {
+        walk_pattern(self, pattern)
+    }
+    fn visit_path(&mut self, path: &mut Path) {
+        walk_path(self, path)
+    }
+    fn visit_attribute(&mut self, _attr: &mut Attribute) {}
+    fn visit_ident(&mut self, _ident: &mut Ident) {}
+    fn visit_lit(&mut self, _lit: &mut Lit) {}
+}
+
+pub fn walk_module<V: VisitorMut>(visitor: &mut V, module: &mut Module) {
+    for item in &mut module.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: VisitorMut>(visitor: &mut V, item: &mut Item) {
+    for attr in &mut item.attrs {
+        visitor.visit_attribute(attr);
+    }
+    match &mut item.kind {
+        ItemKind::Function(function) => visitor.visit_function(function),
+        ItemKind::Struct(item) => visitor.visit_struct(item),
+        ItemKind::Enum(item) => visitor.visit_enum(item),
+        ItemKind::Mod(item) => visitor.visit_mod(item),
+        ItemKind::Use(tree) => visitor.visit_use_tree(tree),
+        ItemKind::Trait(item) => visitor.visit_trait(item),
+        ItemKind::Impl(item) => visitor.visit_impl(item),
+        ItemKind::TyAlias(item) => visitor.visit_ty_alias(item),
+        ItemKind::Const(item) | ItemKind::Static(item) => visitor.visit_const(item),
+        ItemKind::MacroDef(def) => visitor.visit_ident(&mut def.name),
+        ItemKind::Error => {}
+    }
+}
+
+pub fn walk_trait<V: VisitorMut>(visitor: &mut V, item: &mut Trait) {
+    visitor.visit_ident(&mut item.name);
+    visitor.visit_generics(&mut item.generics);
+    for bound in &mut item.supertraits {
+        visitor.visit_path(bound);
+    }
+    for item in &mut item.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_impl<V: VisitorMut>(visitor: &mut V, item: &mut Impl) {
+    visitor.visit_generics(&mut item.generics);
+    if let Some(path) = &mut item.of_trait {
+        visitor.visit_path(path);
+    }
+    visitor.visit_ty(&mut item.self_ty);
+    for item in &mut item.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_ty_alias<V: VisitorMut>(visitor: &mut V, item: &mut TyAlias) {
+    visitor.visit_ident(&mut item.name);
+    if let Some(ty) = &mut item.ty {
+        visitor.visit_ty(ty);
+    }
+}
+
+pub fn walk_const<V: VisitorMut>(visitor: &mut V, item: &mut Const) {
+    visitor.visit_ident(&mut item.name);
+    visitor.visit_ty(&mut item.ty);
+    visitor.visit_expr(&mut item.expr);
+}
+
+pub fn walk_mod<V: VisitorMut>(visitor: &mut V, item: &mut Mod) {
+    visitor.visit_ident(&mut item.name);
+    if let Some(content) = &mut item.content {
+        visitor.visit_module(content);
+    }
+}
+
+pub fn walk_use_tree<V: VisitorMut>(visitor: &mut V, tree: &mut UseTree) {
+    visitor.visit_path(&mut tree.prefix);
+    match &mut tree.kind {
+        UseTreeKind::Simple(rename) => {
+            if let Some(rename) = rename {
+                visitor.visit_ident(rename);
+            }
+        }
+        UseTreeKind::Nested(trees) => {
+            for tree in trees {
+                visitor.visit_use_tree(tree);
+            }
+        }
+        UseTreeKind::Glob => {}
+    }
+}
+
+pub fn walk_generics<V: VisitorMut>(visitor: &mut V, generics: &mut Generics) {
+    for param in &mut generics.params {
+        visitor.visit_ident(&mut param.name);
+        for bound in &mut param.bounds {
+            visitor.visit_path(bound);
+        }
+    }
+    for predicate in &mut generics.where_clause {
+        visitor.visit_ty(&mut predicate.ty);
+        for bound in &mut predicate.bounds {
+            visitor.visit_path(bound);
+        }
+    }
+}
+
+pub fn walk_function<V: VisitorMut>(visitor: &mut V, function: &mut Function) {
+    visitor.visit_ident(&mut function.name);
+    visitor.visit_generics(&mut function.generics);
+    for param in &mut function.params {
+        visitor.visit_param(param);
+    }
+    if let Some(output) = &mut function.output {
+        visitor.visit_ty(output);
+    }
+    if let Some(body) = &mut function.body {
+        visitor.visit_block(body);
+    }
+}
+
+pub fn walk_param<V: VisitorMut>(visitor: &mut V, param: &mut Param) {
+    visitor.visit_pattern(&mut param.pat);
+    visitor.visit_ty(&mut param.ty);
+}
+
+pub fn walk_struct<V: VisitorMut>(visitor: &mut V, item: &mut Struct) {
+    visitor.visit_ident(&mut item.name);
+    visitor.visit_generics(&mut item.generics);
+    for field in &mut item.fields {
+        visitor.visit_field_def(field);
+    }
+}
+
+pub fn walk_enum<V: VisitorMut>(visitor: &mut V, item: &mut Enum) {
+    visitor.visit_ident(&mut item.name);
+    visitor.visit_generics(&mut item.generics);
+    for variant in &mut item.variants {
+        visitor.visit_variant(variant);
+    }
+}
+
+pub fn walk_variant<V: VisitorMut>(visitor: &mut V, variant: &mut Variant) {
+    for attr in &mut variant.attrs {
+        visitor.visit_attribute(attr);
+    }
+    visitor.visit_ident(&mut variant.name);
+    match &mut variant.data {
+        VariantData::Unit => {}
+        VariantData::Tuple(types) => {
+            for ty in types {
+                visitor.visit_ty(ty);
+            }
+        }
+        VariantData::Struct(fields) => {
+            for field in fields {
+                visitor.visit_field_def(field);
+            }
+        }
+    }
+}
+
+pub fn walk_field_def<V: VisitorMut>(visitor: &mut V, field: &mut FieldDef) {
+    for attr in &mut field.attrs {
+        visitor.visit_attribute(attr);
+    }
+    visitor.visit_ident(&mut field.name);
+    visitor.visit_ty(&mut field.ty);
+}
+
+pub fn walk_ty<V: VisitorMut>(visitor: &mut V, ty: &mut Ty) {
+    match &mut ty.kind {
+        TyKind::Path(path) => visitor.visit_path(path),
+        TyKind::Ref { inner, .. } | TyKind::Slice(inner) => visitor.visit_ty(inner),
+        TyKind::Tuple(tys) => {
+            for ty in tys {
+                visitor.visit_ty(ty);
+            }
+        }
+        TyKind::Fn { inputs, output } => {
+            for ty in inputs.iter_mut().chain(output.as_deref_mut()) {
+                visitor.visit_ty(ty);
+            }
+        }
+        TyKind::Array(inner, len) => {
+            visitor.visit_ty(inner);
+            visitor.visit_expr(len);
+        }
+    }
+}
+
+pub fn walk_block<V: VisitorMut>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: VisitorMut>(visitor: &mut V, stmt: &mut Stmt) {
+    for attr in &mut stmt.attrs {
+        visitor.visit_attribute(attr);
+    }
+    match &mut stmt.kind {
+        StmtKind::Let(local) => visitor.visit_local(local),
+        StmtKind::Expr(expr) | StmtKind::Semi(expr) => visitor.visit_expr(expr),
+    }
+}
+
+pub fn walk_local<V: VisitorMut>(visitor: &mut V, local: &mut Local) {
+    // The initializer and the `else` block are visited first : they cannot see the
+    // bindings of the pattern.
+    if let Some(init) = &mut local.init {
+        visitor.visit_expr(init);
+    }
+    if let Some(els) = &mut local.els {
+        visitor.visit_block(els);
+    }
+    visitor.visit_pattern(&mut local.pat);
+    if let Some(ty) = &mut local.ty {
+        visitor.visit_ty(ty);
+    }
+}
+
+pub fn walk_expr<V: VisitorMut>(visitor: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Lit(lit) => visitor.visit_lit(lit),
+        ExprKind::Path(path) => visitor.visit_path(path),
+        ExprKind::Unary(_, operand) => visitor.visit_expr(operand),
+        ExprKind::Binary(_, lhs, rhs) | ExprKind::Assign(lhs, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        ExprKind::AddrOf { expr, .. } | ExprKind::Paren(expr) | ExprKind::Try(expr) => {
+            visitor.visit_expr(expr)
+        }
+        ExprKind::Call(callee, args) => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::MethodCall {
+            receiver,
+            method,
+            generic_args,
+            args,
+            ..
+        } => {
+            visitor.visit_expr(receiver);
+            visitor.visit_ident(method);
+            for ty in generic_args {
+                visitor.visit_ty(ty);
+            }
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Field(base, name) => {
+            visitor.visit_expr(base);
+            visitor.visit_ident(name);
+        }
+        ExprKind::Index(base, index) | ExprKind::Repeat(base, index) => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        ExprKind::Tuple(elements) | ExprKind::Array(elements) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        ExprKind::Range { lo, hi, .. } => {
+            if let Some(lo) = lo {
+                visitor.visit_expr(lo);
+            }
+            if let Some(hi) = hi {
+                visitor.visit_expr(hi);
+            }
+        }
+        ExprKind::StructLit { path, fields } => {
+            visitor.visit_path(path);
+            for field in fields {
+                visitor.visit_ident(&mut field.name);
+                visitor.visit_expr(&mut field.expr);
+            }
+        }
+        ExprKind::Block(block) | ExprKind::Loop(block) => visitor.visit_block(block),
+        ExprKind::If(cond, then, otherwise) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_expr(otherwise);
+            }
+        }
+        ExprKind::While(cond, body) => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(body);
+        }
+        ExprKind::ForLoop { pat, iter, body } => {
+            visitor.visit_expr(iter);
+            visitor.visit_pattern(pat);
+            visitor.visit_block(body);
+        }
+        ExprKind::Match(scrutinee, arms) => {
+            visitor.visit_expr(scrutinee);
+            for arm in arms {
+                visitor.visit_arm(arm);
+            }
+        }
+        ExprKind::Closure(closure) => visitor.visit_closure(closure),
+        ExprKind::Return(value) | ExprKind::Break(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprKind::Continue | ExprKind::Error => {}
+    }
+}
+
+pub fn walk_closure<V: VisitorMut>(visitor: &mut V, closure: &mut Closure) {
+    for param in &mut closure.params {
+        visitor.visit_pattern(&mut param.pat);
+        if let Some(ty) = &mut param.ty {
+            visitor.visit_ty(ty);
+        }
+    }
+    if let Some(output) = &mut closure.output {
+        visitor.visit_ty(output);
+    }
+    visitor.visit_expr(&mut closure.body);
+}
+
+pub fn walk_arm<V: VisitorMut>(visitor: &mut V, arm: &mut Arm) {
+    visitor.visit_pattern(&mut arm.pat);
+    if let Some(guard) = &mut arm.guard {
+        visitor.visit_expr(guard);
+    }
+    visitor.visit_expr(&mut arm.body);
+}
+
+pub fn walk_pattern<V: VisitorMut>(visitor: &mut V, pattern: &mut Pattern) {
+    match &mut pattern.kind {
+        PatKind::Wild | PatKind::Rest | PatKind::Error => {}
+        PatKind::Binding { name, sub, .. } => {
+            visitor.visit_ident(name);
+            if let Some(sub) = sub {
+                visitor.visit_pattern(sub);
+            }
+        }
+        PatKind::Paren(inner) => visitor.visit_pattern(inner),
+        PatKind::Lit(expr) => visitor.visit_expr(expr),
+        PatKind::Range { lo, hi, .. } => {
+            for bound in [lo, hi].into_iter().flatten() {
+                visitor.visit_expr(bound);
+            }
+        }
+        PatKind::Tuple(patterns) | PatKind::Or(patterns) => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        PatKind::TupleStruct(path, patterns) => {
+            visitor.visit_path(path);
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        PatKind::Struct { path, fields, .. } => {
+            visitor.visit_path(path);
+            for field in fields {
+                visitor.visit_ident(&mut field.name);
+                visitor.visit_pattern(&mut field.pat);
+            }
+        }
+        PatKind::Path(path) => visitor.visit_path(path),
+    }
+}
+
+pub fn walk_path<V: VisitorMut>(visitor: &mut V, path: &mut Path) {
+    for segment in &mut path.segments {
+        visitor.visit_ident(&mut segment.ident);
+        for arg in &mut segment.args {
+            visitor.visit_ty(arg);
+        }
+        if let Some(output) = &mut segment.output {
+            visitor.visit_ty(output);
+        }
+    }
+}